@@ -1,11 +1,14 @@
 use crate::config::Config;
-use crate::config::CrateName;
 use crate::crate_index::BuildScriptId;
+use crate::crate_index::CrateSel;
 use crate::problem::DisallowedBuildInstruction;
 use crate::problem::Problem;
 use crate::problem::ProblemList;
 use crate::proxy::rpc::BuildScriptOutput;
 use anyhow::Result;
+use std::path::Component;
+use std::path::Path;
+use std::path::PathBuf;
 
 pub(crate) fn check(outputs: &BuildScriptOutput, config: &Config) -> Result<ProblemList> {
     let build_script_id = &outputs.build_script_id;
@@ -18,7 +21,7 @@ pub(crate) fn check(outputs: &BuildScriptOutput, config: &Config) -> Result<Prob
             .into(),
         );
     }
-    let crate_name = CrateName::from(build_script_id);
+    let crate_name = config.crate_name_for(&CrateSel::BuildScript(build_script_id.clone()));
     let allow_build_instructions = config
         .packages
         .get(&crate_name)
@@ -31,27 +34,59 @@ pub(crate) fn check(outputs: &BuildScriptOutput, config: &Config) -> Result<Prob
         ))
         .into());
     };
+    let allowed_link_search = allowed_link_search_paths(config, &outputs.out_dir);
+    let emitted = outputs.instructions();
     let mut problems = ProblemList::default();
     for line in stdout.lines() {
-        if line.starts_with("cargo:") {
+        if line.starts_with(INSTRUCTION_PREFIX) {
             problems.merge(check_directive(
                 line,
                 build_script_id,
                 allow_build_instructions,
+                &allowed_link_search,
+                &emitted,
             ));
         }
     }
     Ok(problems)
 }
 
+const INSTRUCTION_PREFIX: &str = "cargo:";
+
+/// A `cargo:` instruction emitted by a build script, split into the kind of instruction (e.g.
+/// `rustc-link-lib`) and its value.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub(crate) struct BuildInstruction {
+    pub(crate) kind: String,
+    pub(crate) value: String,
+}
+
+impl BuildInstruction {
+    pub(crate) fn parse(line: &str) -> Option<Self> {
+        let rest = line.strip_prefix(INSTRUCTION_PREFIX)?;
+        let (kind, value) = rest.split_once('=').unwrap_or((rest, ""));
+        Some(Self {
+            kind: kind.to_owned(),
+            value: value.to_owned(),
+        })
+    }
+}
+
 /// Cargo instructions that should be harmless, so would just add noise if we were required to
 /// explicitly allow them.
 const ALWAYS_PERMITTED: &[&str] = &["cargo:rerun-if-", "cargo:warning", "cargo:rustc-cfg="];
 
+const LINK_SEARCH_PREFIX: &str = "cargo:rustc-link-search=";
+
+/// Link search kinds that may precede the path in a `rustc-link-search` instruction.
+const LINK_SEARCH_KINDS: &[&str] = &["dependency", "crate", "native", "framework", "all"];
+
 fn check_directive(
     instruction: &str,
     build_script_id: &BuildScriptId,
     allow_build_instructions: &[String],
+    allowed_link_search: &[PathBuf],
+    emitted: &[BuildInstruction],
 ) -> ProblemList {
     if ALWAYS_PERMITTED
         .iter()
@@ -59,20 +94,70 @@ fn check_directive(
     {
         return ProblemList::default();
     }
-    if allow_build_instructions
-        .iter()
-        .any(|i| matches(instruction, i))
-    {
+    let link_search_path = link_search_path(instruction);
+    if let Some(path) = link_search_path {
+        // A path containing `..` could escape from an allowed prefix, so it can only be permitted
+        // by an exact rule.
+        let has_parent_dir = path
+            .components()
+            .any(|component| component == Component::ParentDir);
+        if !has_parent_dir
+            && allowed_link_search
+                .iter()
+                .any(|prefix| path.starts_with(prefix))
+        {
+            return ProblemList::default();
+        }
+    }
+    // If link search paths are restricted, then wildcard rules don't permit link search paths
+    // outside of the allowed prefixes. Only an exact match does.
+    let restrict_wildcards = link_search_path.is_some() && !allowed_link_search.is_empty();
+    if allow_build_instructions.iter().any(|i| {
+        if restrict_wildcards {
+            without_prefix(instruction) == without_prefix(i)
+        } else {
+            matches(instruction, i)
+        }
+    }) {
         return ProblemList::default();
     }
     Problem::DisallowedBuildInstruction(DisallowedBuildInstruction {
         build_script_id: build_script_id.clone(),
         instruction: instruction.to_owned(),
+        emitted: emitted.to_owned(),
     })
     .into()
 }
 
+/// Returns the path from `instruction` if it's a `rustc-link-search` instruction.
+fn link_search_path(instruction: &str) -> Option<&Path> {
+    let value = instruction.strip_prefix(LINK_SEARCH_PREFIX)?;
+    let path = match value.split_once('=') {
+        Some((kind, path)) if LINK_SEARCH_KINDS.contains(&kind) => path,
+        _ => value,
+    };
+    Some(Path::new(path))
+}
+
+/// Returns the path prefixes that `rustc-link-search` instructions are permitted to reference, with
+/// any `$OUT_DIR` placeholder replaced by `out_dir`.
+fn allowed_link_search_paths(config: &Config, out_dir: &Path) -> Vec<PathBuf> {
+    config
+        .common
+        .allow_link_search
+        .iter()
+        .map(|prefix| match prefix.strip_prefix("$OUT_DIR") {
+            Some(rest) => out_dir.join(rest.trim_start_matches('/')),
+            None => PathBuf::from(prefix),
+        })
+        .collect()
+}
+
+/// Returns whether `rule` permits `instruction`. Rules may omit the `cargo:` prefix, so
+/// `rustc-cfg=*` permits all `rustc-cfg` instructions.
 fn matches(instruction: &str, rule: &str) -> bool {
+    let instruction = without_prefix(instruction);
+    let rule = without_prefix(rule);
     if let Some(prefix) = rule.strip_suffix('*') {
         instruction.starts_with(prefix)
     } else {
@@ -80,8 +165,15 @@ fn matches(instruction: &str, rule: &str) -> bool {
     }
 }
 
+fn without_prefix(instruction: &str) -> &str {
+    instruction
+        .strip_prefix(INSTRUCTION_PREFIX)
+        .unwrap_or(instruction)
+}
+
 #[cfg(test)]
 mod tests {
+    use super::BuildInstruction;
     use crate::config;
     use crate::config::SandboxConfig;
     use crate::crate_index::testing::build_script_id;
@@ -101,10 +193,22 @@ mod tests {
             build_script_id: build_script_id("my_pkg"),
             sandbox_config: SandboxConfig::default(),
             build_script: PathBuf::new(),
+            out_dir: PathBuf::from("/target/out"),
+            timed_out_after: None,
         };
         super::check(&outputs, &config).unwrap()
     }
 
+    /// Returns the problem expected for a build script that emitted only `instruction`.
+    fn disallowed(instruction: &str) -> ProblemList {
+        Problem::DisallowedBuildInstruction(DisallowedBuildInstruction {
+            build_script_id: build_script_id("my_pkg"),
+            instruction: instruction.to_owned(),
+            emitted: vec![BuildInstruction::parse(instruction).unwrap()],
+        })
+        .into()
+    }
+
     #[test]
     fn test_empty() {
         assert_eq!(check("", ""), ProblemList::default());
@@ -122,11 +226,7 @@ mod tests {
     fn test_link_directive() {
         assert_eq!(
             check("cargo:rustc-link-search=some_directory", ""),
-            Problem::DisallowedBuildInstruction(DisallowedBuildInstruction {
-                build_script_id: build_script_id("my_pkg"),
-                instruction: "cargo:rustc-link-search=some_directory".to_owned(),
-            })
-            .into()
+            disallowed("cargo:rustc-link-search=some_directory")
         );
         assert_eq!(
             check(
@@ -149,4 +249,95 @@ mod tests {
             ProblemList::default()
         );
     }
+
+    #[test]
+    fn test_allow_link_search() {
+        let config = r#"
+            allow_link_search = ["/usr/lib", "$OUT_DIR"]
+
+            [pkg.my_pkg.build]
+            allow_build_instructions = [ "cargo:rustc-link-*" ]
+            "#;
+        assert_eq!(
+            check("cargo:rustc-link-search=native=/usr/lib/x86_64", config),
+            ProblemList::default()
+        );
+        assert_eq!(
+            check("cargo:rustc-link-search=/target/out/lib", config),
+            ProblemList::default()
+        );
+        assert_eq!(
+            check("cargo:rustc-link-search=/usr/lib-evil", config),
+            disallowed("cargo:rustc-link-search=/usr/lib-evil")
+        );
+        assert_eq!(
+            check("cargo:rustc-link-search=native=/attacker/path", config),
+            disallowed("cargo:rustc-link-search=native=/attacker/path")
+        );
+        assert_eq!(
+            check(
+                "cargo:rustc-link-search=/usr/lib/../../home/attacker",
+                config
+            ),
+            disallowed("cargo:rustc-link-search=/usr/lib/../../home/attacker")
+        );
+        assert_eq!(
+            check(
+                "cargo:rustc-link-search=native=/target/out/../../evil",
+                config
+            ),
+            disallowed("cargo:rustc-link-search=native=/target/out/../../evil")
+        );
+        // Other link instructions are still permitted by the wildcard.
+        assert_eq!(
+            check("cargo:rustc-link-lib=foo", config),
+            ProblemList::default()
+        );
+    }
+
+    #[test]
+    fn test_rule_without_prefix() {
+        let config = r#"
+            [pkg.my_pkg.build]
+            allow_build_instructions = [ "rustc-env=*", "rustc-link-lib=foo" ]
+            "#;
+        assert_eq!(
+            check("cargo:rustc-env=A=1\ncargo:rustc-link-lib=foo", config),
+            ProblemList::default()
+        );
+        assert_eq!(
+            check("cargo:rustc-link-lib=bar", config),
+            disallowed("cargo:rustc-link-lib=bar")
+        );
+    }
+
+    #[test]
+    fn test_emitted_instructions_shown_grouped() {
+        let problems = check(
+            "cargo:rustc-link-lib=foo\n\
+             cargo:rustc-cfg=has_foo\n\
+             cargo:rustc-link-lib=bar\n\
+             cargo:rustc-env=FOO_DIR=/opt/foo\n\
+             not an instruction",
+            r#"
+            [pkg.my_pkg.build]
+            allow_build_instructions = [ "rustc-link-*" ]
+            "#,
+        );
+        let problem = problems.into_iter().next().unwrap();
+        assert_eq!(
+            format!("{problem:#}"),
+            indoc::indoc! {"
+                my_pkg's build script emitted disallowed instruction `cargo:rustc-env=FOO_DIR=/opt/foo`
+                All instructions emitted by the build script:
+                  rustc-cfg
+                    has_foo
+                  rustc-env
+                    FOO_DIR=/opt/foo
+                  rustc-link-lib
+                    foo
+                    bar
+            "}
+        );
+    }
 }
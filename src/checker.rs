@@ -1,21 +1,43 @@
 use crate::build_script_checker;
+use crate::config::ApiPath;
 use crate::config::Config;
 use crate::config::CrateName;
 use crate::config::PermissionName;
+use crate::config::SandboxKind;
 use crate::crate_index::BuildScriptId;
 use crate::crate_index::CrateIndex;
 use crate::crate_index::CrateSel;
+use crate::crate_index::FeatureOptions;
+use crate::crate_index::PackageId;
+use crate::env_vars;
+use crate::env_vars::EnvVar;
+use crate::inventory::Inventory;
 use crate::link_info::LinkInfo;
 use crate::location::SourceLocation;
 use crate::names::Name;
 use crate::names::SymbolOrDebugName;
 use crate::problem::ApiUsages;
+use crate::problem::BuildScriptFailed;
+use crate::problem::DisallowedLicense;
+use crate::problem::IncludedAndExcluded;
 use crate::problem::PossibleExportedApi;
 use crate::problem::Problem;
 use crate::problem::ProblemList;
+use crate::problem::Severity;
+use crate::problem::ShellInvocation;
 use crate::problem::UnusedAllowApi;
+use crate::problem::UnusedAllowSymbols;
+use crate::problem::UnusedExclude;
+use crate::problem::UnusedSuppression;
 use crate::proxy::rpc;
 use crate::proxy::rpc::UnsafeUsage;
+use crate::proxy::target::BuildTargets;
+use crate::reexports;
+use crate::resume::ResumeLog;
+use crate::resume::ResumeState;
+use crate::suppressions;
+use crate::suppressions::Suppression;
+use crate::symbol::Symbol;
 use crate::symbol_graph::NameSource;
 use crate::symbol_graph::UsageDebugData;
 use crate::timing::TimingCollector;
@@ -24,22 +46,51 @@ use crate::CheckState;
 use anyhow::anyhow;
 use anyhow::Context;
 use anyhow::Result;
+use clap::ValueEnum;
 use fxhash::FxHashMap;
 use fxhash::FxHashSet;
 use log::info;
+use log::warn;
+use serde::Deserialize;
+use serde::Serialize;
 use std::borrow::Cow;
+use std::collections::BTreeSet;
 use std::path::Path;
 use std::path::PathBuf;
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::Ordering;
 use std::sync::Arc;
 use tempfile::TempDir;
 
 mod api_map;
 
+use api_map::GlobPath;
+
 pub(crate) struct Checker {
     /// For each name, the set of permissions active for that name and all names that have this name
     /// as a prefix.
     permissions_by_prefix: api_map::ApiMap,
+    /// For each name, the permissions that are excluded by paths without globs. Only consulted
+    /// when there are glob includes, since those mustn't override a more specific exclude.
+    excluded_by_prefix: api_map::ApiMap,
+    /// Include and exclude paths that contain globs. Each exclude is paired with its index in
+    /// `excludes`.
+    glob_includes: Vec<GlobPath>,
+    glob_excludes: Vec<(GlobPath, usize)>,
+    /// Each `exclude` entry from the current config. The `ApiMap`s and `glob_excludes` refer to
+    /// these by index.
+    excludes: Vec<ConfiguredExclude>,
+    /// `exclude` entries from API definitions that haven't yet been found to exclude anything.
+    unused_excludes: BTreeSet<(PermissionName, ApiPath)>,
+    /// Source paths from `common.ignore_paths`, whose code we don't analyse.
+    ignored_paths: Vec<glob::Pattern>,
+    /// Packages that we don't report problems for because they're only dev or build dependencies
+    /// and the config says to ignore those.
+    ignored_packages: FxHashSet<PackageId>,
     pub(crate) crate_infos: FxHashMap<CrateName, CrateInfo>,
+    /// Permissions from `common.allow_all`, together with the components of any composite APIs
+    /// listed there.
+    allowed_for_all: FxHashSet<PermissionName>,
     config_path: PathBuf,
     pub(crate) config: Arc<Config>,
     target_dir: PathBuf,
@@ -52,11 +103,66 @@ pub(crate) struct Checker {
     /// source path.
     path_to_crate: FxHashMap<PathBuf, Vec<CrateSel>>,
 
+    /// For each path that is a `pub use` re-export, the paths at which the re-exported items are
+    /// defined.
+    reexports: FxHashMap<String, BTreeSet<String>>,
+
+    /// Set when we've found re-exports that aren't yet reflected in `permissions_by_prefix`. Rather
+    /// than rebuilding the map after each crate is compiled, we rebuild it when it's next needed.
+    api_map_stale: bool,
+
+    /// For each source path, the inline annotations found in it that suppress API usages.
+    suppressions: FxHashMap<PathBuf, Vec<Suppression>>,
+
+    /// Suppressions that haven't yet been found to suppress anything.
+    unused_suppressions: BTreeSet<Suppression>,
+
+    env_var_finder: env_vars::EnvVarFinder,
+
+    /// For each kind of sandbox that build scripts are configured to use, an error if it can't be
+    /// used on this system. Checking can mean running a binary, so we only check each kind once.
+    sandbox_errors: FxHashMap<SandboxKind, Option<String>>,
+
+    /// What we're building for. Only populated if the config has target-specific parts.
+    build_targets: Option<BuildTargets>,
+
+    /// The version reported by a subprocess if it differs from our own version.
+    pub(crate) subprocess_version_mismatch: Option<String>,
+
+    /// Output from subprocesses that were run again after failing. Only populated for the crate
+    /// selected with --rerun-failed.
+    pub(crate) reruns: Vec<rpc::RerunOutput>,
+
+    /// For each package name, names under that package that are candidates for API prefixes. Only
+    /// populated if requested via --print-exports.
+    exports: FxHashMap<String, BTreeSet<String>>,
+
+    /// Packages for which we've reported at least one problem and a description of each of those
+    /// problems.
+    pub(crate) package_problems: FxHashMap<PackageId, BTreeSet<String>>,
+
     pub(crate) timings: TimingCollector,
+
+    /// Where we record what we learn, so that if we're interrupted, the next run can resume.
+    resume_log: Option<ResumeLog>,
+
+    /// All API usages, including allowed ones. Only populated if requested via --inventory.
+    inventory: Option<Inventory>,
+
+    /// Whether we've checked for yanked versions. Whether a version is yanked won't change during
+    /// a run, so we only report yanked versions once, not each time the config is reloaded.
+    checked_yanked: bool,
 }
 
-#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq)]
-pub(crate) struct PermId(usize);
+/// An `exclude` entry from an API definition.
+struct ConfiguredExclude {
+    /// The API whose definition contains the exclude.
+    api: PermissionName,
+    path: ApiPath,
+    /// Set once a name that we looked up would have matched some API, but for this exclude. Names
+    /// are looked up while object files are processed in parallel, so this is atomic.
+    used: AtomicBool,
+}
 
 #[derive(Default, Debug)]
 pub(crate) struct CrateInfo {
@@ -66,6 +172,29 @@ pub(crate) struct CrateInfo {
     /// Permissions that are allowed for this crate according to cackle.toml,
     /// but haven't yet been found to be used by the crate.
     unused_allowed_perms: FxHashSet<PermissionName>,
+
+    /// Permissions that are allowed because they're components of a composite API in
+    /// `allowed_perms`.
+    implied_perms: FxHashSet<PermissionName>,
+
+    /// Permissions from `common.allow_all` that this crate isn't permitted to use, including the
+    /// components of any composite APIs that it denies.
+    denied_perms: FxHashSet<PermissionName>,
+
+    /// Permissions from `common.allow_all`, not otherwise allowed for this crate, that it has been
+    /// found to use.
+    used_common_perms: FxHashSet<PermissionName>,
+
+    /// Symbols that this crate may use, even if they're part of an API that it isn't allowed to
+    /// use.
+    allowed_symbols: FxHashSet<String>,
+
+    /// Allowed symbols that haven't yet been found to be used by the crate.
+    unused_allowed_symbols: FxHashSet<String>,
+
+    /// Environment variables that this crate may read, even if it isn't allowed the API that covers
+    /// reading them.
+    allowed_env_vars: FxHashSet<String>,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -76,6 +205,49 @@ pub(crate) struct ApiUsage {
     pub(crate) to_name: Name,
     pub(crate) to_source: NameSource<'static>,
     pub(crate) debug_data: Option<UsageDebugData>,
+    /// The functions in the same crate through which `from` is reached, nearest first. Only
+    /// populated if requested via --show-backtrace.
+    pub(crate) backtrace: Vec<Symbol<'static>>,
+    /// If the usage reads an environment variable, which one. Only populated for usages that are
+    /// reported.
+    pub(crate) env_var: Option<EnvVar>,
+}
+
+/// A category of checks that can be selected with `--only`.
+#[derive(ValueEnum, Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Category {
+    /// Use of unsafe code.
+    Unsafe,
+    /// Use of APIs, including those invoked via a shell.
+    ApiUsage,
+    /// Whether packages have build scripts and what instructions those build scripts emit.
+    BuildScripts,
+}
+
+impl Args {
+    pub(crate) fn should_check(&self, category: Category) -> bool {
+        self.only.is_empty() || self.only.contains(&category)
+    }
+
+    /// Returns the config files that should be merged into the main config, in order.
+    pub(crate) fn config_overrides(&self) -> &[PathBuf] {
+        self.cackle_path.get(1..).unwrap_or_default()
+    }
+
+    /// Returns the feature options to run cargo with. Features specified on the command line
+    /// replace `config_features`, which are those from the config.
+    pub(crate) fn feature_options(&self, config_features: &[String]) -> FeatureOptions {
+        let features = if self.features.is_empty() {
+            config_features
+        } else {
+            &self.features
+        };
+        FeatureOptions {
+            features: features.to_owned(),
+            all_features: self.all_features,
+            no_default_features: self.no_default_features,
+        }
+    }
 }
 
 impl Checker {
@@ -87,9 +259,18 @@ impl Checker {
         config_path: PathBuf,
     ) -> Self {
         let timings = TimingCollector::new(args.print_timing);
+        let inventory = args.inventory.as_ref().map(|_| Inventory::default());
         Self {
             permissions_by_prefix: Default::default(),
+            excluded_by_prefix: Default::default(),
+            glob_includes: Vec::new(),
+            glob_excludes: Vec::new(),
+            excludes: Vec::new(),
+            unused_excludes: Default::default(),
+            ignored_paths: Vec::new(),
+            ignored_packages: Default::default(),
             crate_infos: Default::default(),
+            allowed_for_all: Default::default(),
             config_path,
             config: Default::default(),
             target_dir,
@@ -97,13 +278,54 @@ impl Checker {
             args,
             crate_index,
             path_to_crate: Default::default(),
+            reexports: Default::default(),
+            api_map_stale: false,
+            suppressions: Default::default(),
+            unused_suppressions: Default::default(),
+            env_var_finder: Default::default(),
+            sandbox_errors: Default::default(),
+            build_targets: None,
+            subprocess_version_mismatch: None,
+            reruns: Vec::new(),
+            exports: Default::default(),
+            checked_yanked: false,
+            package_problems: Default::default(),
             timings,
+            resume_log: None,
+            inventory,
         }
     }
 
     /// Load (or reload) config. Note in the case of reloading, permissions are only ever additive.
     pub(crate) fn load_config(&mut self) -> Result<()> {
-        let config = crate::config::parse_file(&self.config_path, &self.crate_index)?;
+        let mut config = crate::config::parse_files(
+            &self.config_path,
+            self.args.config_overrides(),
+            &self.crate_index,
+        )?;
+        if config.has_target_config() {
+            let mut selected = (*config).clone();
+            let targets = self.build_targets()?.clone();
+            // The index was built before we had the config, so if the config selects features,
+            // then we need to resolve them again.
+            let feature_options = self.args.feature_options(&config.common.features);
+            let reindexed;
+            let crate_index = if feature_options == self.crate_index.feature_options {
+                &self.crate_index
+            } else {
+                let dir = self
+                    .crate_index
+                    .manifest_path
+                    .parent()
+                    .unwrap_or(Path::new(""));
+                reindexed = CrateIndex::new(dir, self.args.offline, false, &feature_options)?;
+                &reindexed
+            };
+            selected.select_targets(&targets, |crate_name| {
+                crate_index.enabled_features(crate_name)
+            })?;
+            config = Arc::new(selected);
+        }
         // Every time we reload our configuration, we rewrite the flattened configuration. The
         // flattened configuration is used by subprocesses rather than using the original
         // configuration since using the original would require each subprocess to run `cargo
@@ -119,42 +341,82 @@ impl Checker {
         crate::fs::write_atomic(&flattened_path, &config.flattened_toml()?)?;
 
         self.update_config(config);
+        if let Some(resume_log) = &mut self.resume_log {
+            if !resume_log.config_loaded(&self.config)? {
+                info!("Config changed, run will no longer be resumable");
+                self.resume_log = None;
+            }
+        }
         info!("Config (re)loaded");
         Ok(())
     }
 
-    pub(crate) fn print_timing(&self) {
-        println!("{}", self.timings);
+    /// Returns the targets that we're building for, querying rustc the first time we're called.
+    fn build_targets(&mut self) -> Result<&BuildTargets> {
+        if self.build_targets.is_none() {
+            self.build_targets = Some(BuildTargets::query(self.args.target.as_deref())?);
+        }
+        Ok(self.build_targets.as_ref().unwrap())
     }
 
-    fn update_config(&mut self, config: Arc<Config>) {
-        self.permissions_by_prefix.clear();
-        for api in config.apis.values() {
-            for path in api.include.iter().chain(api.exclude.iter()) {
-                self.permissions_by_prefix
-                    .create_entry(crate::names::split_simple(&path.prefix).parts())
-            }
+    /// Applies what we learned during a previous, interrupted run, then starts recording what we
+    /// learn during this run. Should be called after the config has been loaded.
+    pub(crate) fn resume(&mut self, log_path: &Path, state: ResumeState) -> Result<()> {
+        for output in &state.crate_outputs {
+            self.record_crate_paths(output)?;
         }
-        for (perm_name, api) in &config.apis {
-            for path in &api.include {
-                let name = &crate::names::split_simple(&path.prefix);
-                self.permissions_by_prefix
-                    .mut_tree(name.parts())
-                    .update_subtree(&|permissions| {
-                        permissions.insert(perm_name.clone());
-                    });
-            }
+        for (crate_name, permission) in &state.used_permissions {
+            self.record_permission_used(crate_name, permission);
         }
-        for (perm_name, api) in &config.apis {
-            for path in &api.exclude {
-                let name = &crate::names::split_simple(&path.prefix);
-                self.permissions_by_prefix
-                    .mut_tree(name.parts())
-                    .update_subtree(&|permissions| {
-                        permissions.remove(perm_name);
-                    });
+        for (crate_name, symbol) in &state.used_symbols {
+            if let Some(crate_info) = self.crate_infos.get_mut(crate_name) {
+                crate_info.unused_allowed_symbols.remove(symbol);
             }
         }
+        for suppression in &state.used_suppressions {
+            self.unused_suppressions.remove(suppression);
+        }
+        for exclude in &state.used_excludes {
+            self.unused_excludes.remove(exclude);
+        }
+        self.resume_log = Some(ResumeLog::create(
+            log_path,
+            &self.config,
+            &self.args.only,
+            self.args.package.as_deref(),
+            &state,
+        )?);
+        Ok(())
+    }
+
+    /// Called once the check has completed successfully. The next run will then start from a clean
+    /// build.
+    pub(crate) fn finish_resume_log(&mut self) -> Result<()> {
+        if let Some(resume_log) = self.resume_log.take() {
+            resume_log.delete()?;
+        }
+        Ok(())
+    }
+
+    pub(crate) fn print_timing(&self) {
+        println!("{}", self.timings);
+    }
+
+    fn update_config(&mut self, config: Arc<Config>) {
+        // Usage flags are reset when we rebuild the API map, so we need to collect them first.
+        self.record_used_excludes();
+        self.update_api_map(&config);
+        self.update_unused_excludes(&config);
+        self.update_ignored_paths(&config);
+        self.ignored_packages = self.crate_index.packages_only_reachable_via(
+            config.common.ignore_dev_dependencies,
+            config.common.ignore_build_dependencies,
+        );
+        self.allowed_for_all = components_of(&config, &config.common.allow_all);
+        for crate_info in self.crate_infos.values_mut() {
+            crate_info.implied_perms.clear();
+            crate_info.denied_perms.clear();
+        }
         for (crate_name, crate_config) in &config.packages {
             let crate_info = self
                 .crate_infos
@@ -165,33 +427,402 @@ impl Checker {
                     crate_info.unused_allowed_perms.insert(perm.clone());
                 }
             }
+            crate_info.implied_perms = components_of(&config, &crate_info.allowed_perms);
+            crate_info.denied_perms = components_of(&config, &crate_config.deny_apis);
+            for symbol in &crate_config.allow_symbols {
+                if crate_info.allowed_symbols.insert(symbol.clone()) {
+                    crate_info.unused_allowed_symbols.insert(symbol.clone());
+                }
+            }
+            crate_info
+                .allowed_env_vars
+                .extend(crate_config.allow_env_vars.iter().cloned());
         }
         self.config = config;
     }
 
+    /// Rebuilds `ignored_paths` from `common.ignore_paths` in `config`, making relative patterns
+    /// relative to the workspace root.
+    fn update_ignored_paths(&mut self, config: &Config) {
+        let root = self
+            .crate_index
+            .manifest_path
+            .parent()
+            .unwrap_or(Path::new(""));
+        self.ignored_paths = config
+            .common
+            .ignore_paths
+            .iter()
+            .filter_map(|pattern| {
+                let pattern = if Path::new(pattern).is_relative() {
+                    format!(
+                        "{}/{pattern}",
+                        glob::Pattern::escape(&root.to_string_lossy())
+                    )
+                } else {
+                    pattern.clone()
+                };
+                // Invalid patterns are reported when the config is validated.
+                glob::Pattern::new(&pattern).ok()
+            })
+            .collect();
+    }
+
+    /// Returns whether we report problems in the package `pkg_id`. We don't if it's only a dev or
+    /// build dependency that we're ignoring, or if a different package was selected with
+    /// --package.
+    pub(crate) fn is_reported_package(&self, pkg_id: &PackageId) -> bool {
+        !self.ignored_packages.contains(pkg_id) && self.is_selected_package(pkg_id.name())
+    }
+
+    /// Returns whether `pkg_name` was selected with --package, or true if no package was selected.
+    fn is_selected_package(&self, pkg_name: &str) -> bool {
+        self.args
+            .package
+            .as_deref()
+            .map_or(true, |package| package == pkg_name)
+    }
+
+    /// Returns whether code from the source file `path` should be ignored.
+    pub(crate) fn is_ignored_path(&self, path: &Path) -> bool {
+        if self.ignored_paths.is_empty() {
+            return false;
+        }
+        let options = glob::MatchOptions {
+            require_literal_separator: true,
+            ..Default::default()
+        };
+        // Paths from debug info can contain `..`, e.g. if a module is included via `#[path]`.
+        let mut normalised = PathBuf::new();
+        for component in path.components() {
+            if component == std::path::Component::ParentDir {
+                normalised.pop();
+            } else {
+                normalised.push(component);
+            }
+        }
+        self.ignored_paths
+            .iter()
+            .any(|pattern| pattern.matches_path_with(&normalised, options))
+    }
+
+    /// Rebuilds `permissions_by_prefix` from the APIs in `config`. API paths that go via a
+    /// re-export are also added under the path where the item is actually defined, since that's
+    /// the name that we'll see in symbols and debug info. Paths containing globs are kept separately
+    /// in `glob_includes` and `glob_excludes`.
+    fn update_api_map(&mut self, config: &Config) {
+        self.api_map_stale = false;
+        self.permissions_by_prefix.clear();
+        self.excluded_by_prefix.clear();
+        self.glob_includes.clear();
+        self.glob_excludes.clear();
+        self.excludes.clear();
+        for api in config.apis.values() {
+            for path in api.include.iter().chain(api.exclude.iter()) {
+                if path.is_glob() {
+                    continue;
+                }
+                for prefix in self.reexport_targets(&path.prefix) {
+                    let name = crate::names::split_simple(&prefix);
+                    self.permissions_by_prefix.create_entry(name.parts());
+                    self.excluded_by_prefix.create_entry(name.parts());
+                }
+            }
+        }
+        let resolved: Vec<_> = config
+            .apis
+            .keys()
+            .map(|perm_name| (perm_name, config.resolved_perm_paths(perm_name)))
+            .collect();
+        for (perm_name, (include, _)) in &resolved {
+            for path in include {
+                if path.is_glob() {
+                    self.glob_includes.push(GlobPath::new(
+                        crate::names::split_simple(&path.prefix).parts(),
+                        (*perm_name).clone(),
+                    ));
+                    continue;
+                }
+                for prefix in self.reexport_targets(&path.prefix) {
+                    let name = &crate::names::split_simple(&prefix);
+                    self.permissions_by_prefix
+                        .mut_tree(name.parts())
+                        .update_subtree(&|permissions| {
+                            permissions.insert((*perm_name).clone());
+                        });
+                }
+            }
+        }
+        let mut exclude_indexes = FxHashMap::default();
+        for (perm_name, (_, exclude)) in &resolved {
+            for (exclude_api, path) in exclude {
+                let exclude_index =
+                    *exclude_indexes
+                        .entry((*exclude_api, *path))
+                        .or_insert_with(|| {
+                            self.excludes.push(ConfiguredExclude {
+                                api: (*exclude_api).clone(),
+                                path: (*path).clone(),
+                                used: AtomicBool::new(false),
+                            });
+                            self.excludes.len() - 1
+                        });
+                if path.is_glob() {
+                    self.glob_excludes.push((
+                        GlobPath::new(
+                            crate::names::split_simple(&path.prefix).parts(),
+                            (*perm_name).clone(),
+                        ),
+                        exclude_index,
+                    ));
+                    continue;
+                }
+                for prefix in self.reexport_targets(&path.prefix) {
+                    let name = &crate::names::split_simple(&prefix);
+                    self.permissions_by_prefix
+                        .mut_tree(name.parts())
+                        .exclude_from_subtree(perm_name, exclude_index);
+                    self.excluded_by_prefix
+                        .mut_tree(name.parts())
+                        .add_exclusion_to_subtree(perm_name, exclude_index);
+                }
+            }
+        }
+    }
+
+    /// Starts tracking any `exclude` entries in `config` that we haven't seen before and stops
+    /// tracking any that are no longer present. Excludes that come from built-in APIs aren't
+    /// tracked, since the user didn't write them.
+    fn update_unused_excludes(&mut self, config: &Config) {
+        let built_ins = crate::config::built_in::get_built_ins();
+        let mut current = BTreeSet::new();
+        for (api, api_config) in &config.apis {
+            for path in &api_config.exclude {
+                if built_ins
+                    .get(api)
+                    .is_some_and(|built_in| built_in.exclude.contains(path))
+                {
+                    continue;
+                }
+                current.insert((api.clone(), path.clone()));
+            }
+        }
+        for exclude in &current {
+            if !self
+                .config
+                .apis
+                .get(&exclude.0)
+                .is_some_and(|api_config| api_config.exclude.contains(&exclude.1))
+            {
+                self.unused_excludes.insert(exclude.clone());
+            }
+        }
+        self.unused_excludes
+            .retain(|exclude| current.contains(exclude));
+    }
+
+    /// Moves any `exclude` entries that have now excluded something out of `unused_excludes`.
+    pub(crate) fn record_used_excludes(&mut self) {
+        for exclude in &self.excludes {
+            if !exclude.used.load(Ordering::Relaxed) {
+                continue;
+            }
+            let key = (exclude.api.clone(), exclude.path.clone());
+            if self.unused_excludes.remove(&key) {
+                if let Some(resume_log) = &mut self.resume_log {
+                    if let Err(error) = resume_log.exclude_used(&exclude.api, &exclude.path) {
+                        warn!("{error:#}");
+                    }
+                }
+            }
+        }
+    }
+
+    fn exclude_used(&self, exclude_index: usize) {
+        let used = &self.excludes[exclude_index].used;
+        // Avoid writing, and thus contention between threads, in the common case where we already
+        // know that the exclude is used.
+        if !used.load(Ordering::Relaxed) {
+            used.store(true, Ordering::Relaxed);
+        }
+    }
+
+    /// Returns `prefix` together with the paths that it refers to via re-exports, following
+    /// chains of re-exports. Each re-export is followed at most once per chain, otherwise a glob
+    /// re-export of a submodule, e.g. `pub use self::imp::*` in `foo`, would give us `foo::imp::x`,
+    /// then `foo::imp::imp::x` and so on forever.
+    fn reexport_targets(&self, prefix: &str) -> BTreeSet<String> {
+        let mut targets = BTreeSet::new();
+        let mut pending: Vec<(String, Vec<&str>)> = vec![(prefix.to_owned(), Vec::new())];
+        while let Some((path, followed)) = pending.pop() {
+            for (alias, alias_targets) in &self.reexports {
+                if followed.contains(&alias.as_str()) {
+                    continue;
+                }
+                let Some(rest) = path.strip_prefix(alias.as_str()) else {
+                    continue;
+                };
+                if !rest.is_empty() && !rest.starts_with("::") {
+                    continue;
+                }
+                for target in alias_targets {
+                    let mut followed = followed.clone();
+                    followed.push(alias);
+                    pending.push((format!("{target}{rest}"), followed));
+                }
+            }
+            targets.insert(path);
+        }
+        targets
+    }
+
     fn base_problems(&self) -> ProblemList {
         let mut problems = ProblemList::default();
+        if !self.args.only.is_empty() {
+            // Checks that don't fall into any of the selectable categories are skipped when only
+            // some categories were requested.
+            return problems;
+        }
+        for (api, api_config) in &self.config.apis {
+            // When a path is both included and excluded, the exclude wins, since we apply all
+            // includes before any excludes. This is almost certainly a mistake though.
+            for path in &api_config.include {
+                if api_config.exclude.contains(path) {
+                    problems.push(Problem::IncludedAndExcluded(IncludedAndExcluded {
+                        api: api.clone(),
+                        path: path.clone(),
+                    }));
+                }
+            }
+        }
         for pkg_id in self.crate_index.proc_macros() {
             if !self
                 .config
                 .packages
-                .get(&pkg_id.into())
+                .get(
+                    &self
+                        .config
+                        .crate_name_for(&CrateSel::Primary(pkg_id.clone())),
+                )
                 .map(|pkg_config| pkg_config.allow_proc_macro)
                 .unwrap_or(false)
             {
                 problems.push(Problem::IsProcMacro(pkg_id.clone()));
             }
         }
+        self.license_problems(&mut problems);
         problems
     }
 
+    fn yanked_problems(
+        &mut self,
+        registry_cache: &crate::yanked::RegistryCache,
+        problems: &mut ProblemList,
+    ) {
+        if self.checked_yanked {
+            return;
+        }
+        self.checked_yanked = true;
+        let mut yanked: Vec<&PackageId> = self
+            .crate_index
+            .package_infos
+            .iter()
+            .filter(|(pkg_id, info)| {
+                let crate_name = self
+                    .config
+                    .crate_name_for(&CrateSel::Primary((*pkg_id).clone()));
+                info.from_crates_io
+                    && !self.config.yanked_permitted_for_crate(&crate_name)
+                    && registry_cache.is_yanked(pkg_id.name(), pkg_id.version())
+            })
+            .map(|(pkg_id, _)| pkg_id)
+            .collect();
+        yanked.sort_by_key(|pkg_id| pkg_id.to_string());
+        for pkg_id in yanked {
+            problems.push(Problem::YankedVersion(pkg_id.clone()));
+        }
+    }
+
+    fn license_problems(&self, problems: &mut ProblemList) {
+        let common = &self.config.common;
+        let allow_unknown = common.allow_unknown_license.unwrap_or(true);
+        if common.allowed_licenses.is_empty() && allow_unknown {
+            return;
+        }
+        let mut disallowed: Vec<DisallowedLicense> = self
+            .crate_index
+            .package_infos
+            .iter()
+            // Workspace members are the user's own packages, so we only check dependencies.
+            .filter(|(pkg_id, _)| !self.crate_index.roots.contains(pkg_id))
+            .filter(|(_, info)| match &info.license {
+                Some(license) => {
+                    !common.allowed_licenses.is_empty()
+                        && !crate::licenses::is_allowed(license, &common.allowed_licenses)
+                }
+                None => !allow_unknown,
+            })
+            .map(|(pkg_id, info)| DisallowedLicense {
+                pkg_id: pkg_id.clone(),
+                license: info.license.clone(),
+            })
+            .collect();
+        disallowed.sort_by_key(|d| d.pkg_id.to_string());
+        for d in disallowed {
+            problems.push(Problem::DisallowedLicense(d));
+        }
+    }
+
     pub(crate) fn problems(
         &mut self,
         request: &Option<rpc::Request>,
         check_state: &mut CheckState,
+    ) -> Result<ProblemList> {
+        let problems = self.problems_for_request(request, check_state)?;
+        Ok(self.filter_and_record_problems(problems))
+    }
+
+    /// Checks API usage in a binary that we didn't see get linked, as done by `cackle inspect`.
+    /// Without the object files, we scan the binary itself as if it were an object file. That finds
+    /// references via relocations that the binary kept, which is all of them if it was linked with
+    /// `--emit-relocs`, together with references recorded in its debug info, i.e. those from
+    /// inlined functions.
+    pub(crate) fn check_prebuilt_binary(
+        &mut self,
+        bin_path: &Path,
+        check_state: &mut CheckState,
+    ) -> Result<ProblemList> {
+        let problems = self.check_object_paths(&[bin_path.to_owned()], bin_path, check_state)?;
+        Ok(self.filter_and_record_problems(problems))
+    }
+
+    fn filter_and_record_problems(&mut self, mut problems: ProblemList) -> ProblemList {
+        // We build all targets, so packages that are only dev or build dependencies get compiled
+        // and linked along with everything else. Similarly, the package selected with --package
+        // is linked with its dependencies. If we're not reporting problems for some packages, we
+        // filter them out here, once we know which package each problem belongs to.
+        if !self.ignored_packages.is_empty() || self.args.package.is_some() {
+            problems.retain(|problem| {
+                problem
+                    .pkg_id()
+                    .map_or(true, |pkg_id| self.is_reported_package(pkg_id))
+            });
+        }
+        self.record_problem_packages(&problems);
+        problems
+    }
+
+    fn problems_for_request(
+        &mut self,
+        request: &Option<rpc::Request>,
+        check_state: &mut CheckState,
     ) -> Result<ProblemList> {
         let Some(request) = request else {
-            return Ok(self.base_problems());
+            let mut problems = self.base_problems();
+            if self.args.only.is_empty() && self.config.common.check_yanked {
+                self.yanked_problems(&crate::yanked::RegistryCache::find(), &mut problems);
+            }
+            return Ok(problems);
         };
         match request {
             rpc::Request::CrateUsesUnsafe(usage) => Ok(self.crate_uses_unsafe(usage)),
@@ -201,10 +832,18 @@ impl Checker {
             rpc::Request::BuildScriptComplete(output) => self.check_build_script_output(output),
             rpc::Request::RustcComplete(info) => {
                 self.record_crate_paths(info)?;
+                if let Some(resume_log) = &mut self.resume_log {
+                    resume_log.crate_compiled(info)?;
+                }
+                Ok(ProblemList::default())
+            }
+            rpc::Request::RustcStarted(info) => {
+                info!("Rustc started compiling {}", info.crate_sel);
+                self.check_subprocess_version(&info.cackle_version);
                 Ok(ProblemList::default())
             }
-            rpc::Request::RustcStarted(crate_sel) => {
-                info!("Rustc started compiling {crate_sel}");
+            rpc::Request::RerunComplete(output) => {
+                self.reruns.push(output.clone());
                 Ok(ProblemList::default())
             }
         }
@@ -219,13 +858,30 @@ impl Checker {
         let mut problems = ProblemList::default();
         if let CrateSel::BuildScript(build_script_id) = &info.crate_sel {
             problems.merge(self.verify_build_script_permitted(build_script_id));
+            problems.merge(self.verify_sandbox_available(build_script_id, &info.output_file));
         }
-        problems.merge(self.check_object_paths(
-            &info.object_paths_under(&self.target_dir),
-            &info.output_file,
-            check_state,
-        )?);
-        let problems = problems.grouped_by_type_crate_and_api();
+        // Scanning the object files is by far the most expensive part of a check and is only
+        // needed to find API usages.
+        let object_paths = info.object_paths_under(&self.target_dir);
+        if self.args.should_check(Category::ApiUsage) {
+            problems.merge(self.check_object_paths(
+                &object_paths,
+                &info.output_file,
+                check_state,
+            )?);
+        }
+        if self.args.crate_sizes && !info.is_build_script() {
+            if let Some(inventory) = &mut self.inventory {
+                let start = std::time::Instant::now();
+                inventory.record_sizes(crate::symbol_graph::sizes::package_sizes(
+                    &object_paths,
+                    &self.crate_index,
+                )?);
+                self.timings.add_timing(start, "Compute crate sizes");
+            }
+        }
+        let problems =
+            self.add_pre_link_hook_problem(info, problems.grouped_by_type_crate_and_api());
         self.timings.add_timing(start, "Total object processing");
         info!(
             "Checking linker args for {} with {} objects. {} problems",
@@ -236,12 +892,71 @@ impl Checker {
         Ok(problems)
     }
 
+    /// Runs the pre-link hook if `problems` contains nothing that would stop the link. Warnings
+    /// don't stop the link, so the hook needs to see the objects even when there are some.
+    fn add_pre_link_hook_problem(&self, info: &LinkInfo, mut problems: ProblemList) -> ProblemList {
+        let has_errors = (&problems)
+            .into_iter()
+            .any(|problem| problem.severity() == Severity::Error);
+        if !has_errors {
+            if let Some(problem) = self.run_pre_link_hook(info) {
+                problems.push(problem);
+            }
+        }
+        problems
+    }
+
+    /// Runs `common.pre_link_hook`, if configured, passing it the paths of the objects being
+    /// linked. Returns a problem if the hook couldn't be run or exited with an error.
+    fn run_pre_link_hook(&self, info: &LinkInfo) -> Option<Problem> {
+        let hook = self.config.common.pre_link_hook.as_ref()?;
+        let hook = self
+            .config_path
+            .parent()
+            .unwrap_or(Path::new(""))
+            .join(hook);
+        let output = match std::process::Command::new(&hook)
+            .args(&info.object_paths)
+            .output()
+        {
+            Ok(output) => output,
+            Err(error) => {
+                return Some(Problem::Message(format!(
+                    "Failed to run pre-link hook `{}`: {error}",
+                    hook.display()
+                )))
+            }
+        };
+        if output.status.success() {
+            return None;
+        }
+        let mut message = format!(
+            "Pre-link hook `{}` rejected linking `{}` ({})",
+            hook.display(),
+            info.crate_sel,
+            output.status
+        );
+        for stream in [&output.stdout, &output.stderr] {
+            let text = String::from_utf8_lossy(stream);
+            if !text.trim().is_empty() {
+                message.push('\n');
+                message.push_str(text.trim_end());
+            }
+        }
+        Some(Problem::Message(message))
+    }
+
     pub(crate) fn check_object_paths(
         &mut self,
         paths: &[PathBuf],
         exe_path: &Path,
         check_state: &mut CheckState,
     ) -> Result<ProblemList> {
+        if self.api_map_stale {
+            // Usage flags are reset when we rebuild the API map, so we need to collect them first.
+            self.record_used_excludes();
+            self.update_api_map(&self.config.clone());
+        }
         if check_state.graph_outputs.is_none() {
             let graph_outputs = crate::symbol_graph::scan_objects(paths, exe_path, self)?;
             check_state.graph_outputs = Some(graph_outputs);
@@ -252,10 +967,18 @@ impl Checker {
     }
 
     fn check_build_script_output(&self, output: &rpc::BuildScriptOutput) -> Result<ProblemList> {
+        if !self.args.should_check(Category::BuildScripts) {
+            return Ok(ProblemList::default());
+        }
         build_script_checker::check(output, &self.config)
     }
 
     pub(crate) fn crate_uses_unsafe(&self, usage: &UnsafeUsage) -> ProblemList {
+        // We don't expect to be told about unsafe if we're not checking it, since the rustc
+        // wrapper doesn't look for it in that case.
+        if !self.args.should_check(Category::Unsafe) {
+            return ProblemList::default();
+        }
         Problem::DisallowedUnsafe(usage.clone()).into()
     }
 
@@ -263,22 +986,61 @@ impl Checker {
         &mut self,
         build_script_id: &BuildScriptId,
     ) -> ProblemList {
-        if !self.config.common.explicit_build_scripts {
+        if !self.config.common.explicit_build_scripts
+            || !self.args.should_check(Category::BuildScripts)
+        {
             return ProblemList::default();
         }
-        if self
-            .crate_infos
-            .contains_key(&CrateName::from(build_script_id))
-        {
+        if self.crate_infos.contains_key(
+            &self
+                .config
+                .crate_name_for(&CrateSel::BuildScript(build_script_id.clone())),
+        ) {
             return ProblemList::default();
         }
         Problem::UsesBuildScript(build_script_id.clone()).into()
     }
 
+    /// Checks that the sandbox that the build script will be run in can be used on this system.
+    /// This lets us report a clear problem rather than have the build script fail to run. The
+    /// problem is reported as a build script failure, so that the same fixes are offered.
+    fn verify_sandbox_available(
+        &mut self,
+        build_script_id: &BuildScriptId,
+        build_script: &Path,
+    ) -> ProblemList {
+        let sandbox_config = self.config.sandbox_config_for_build_script(build_script_id);
+        let error = self
+            .sandbox_errors
+            .entry(sandbox_config.kind)
+            .or_insert_with(|| {
+                crate::sandbox::verify_kind(sandbox_config.kind)
+                    .err()
+                    .map(|error| format!("{error:#}"))
+            });
+        let Some(error) = error else {
+            return ProblemList::default();
+        };
+        Problem::BuildScriptFailed(BuildScriptFailed {
+            build_script_id: build_script_id.clone(),
+            output: rpc::BuildScriptOutput {
+                exit_code: -1,
+                stdout: Vec::new(),
+                stderr: format!("Can't sandbox build script: {error}\n").into_bytes(),
+                build_script_id: build_script_id.clone(),
+                sandbox_config,
+                build_script: build_script.to_owned(),
+                out_dir: PathBuf::new(),
+                timed_out_after: None,
+            },
+        })
+        .into()
+    }
+
     pub(crate) fn crate_names_from_source_path(
         &self,
         source_path: &Path,
-    ) -> Result<Cow<Vec<CrateSel>>> {
+    ) -> Result<Cow<'_, [CrateSel]>> {
         self.opt_crate_names_from_source_path(source_path)
             .ok_or_else(|| anyhow!("Couldn't find crate name for {}", source_path.display(),))
     }
@@ -286,10 +1048,22 @@ impl Checker {
     pub(crate) fn opt_crate_names_from_source_path(
         &self,
         source_path: &Path,
-    ) -> Option<Cow<Vec<CrateSel>>> {
+    ) -> Option<Cow<'_, [CrateSel]>> {
         self.path_to_crate
             .get(source_path)
-            .map(Cow::Borrowed)
+            .map(|crate_sels| Cow::Borrowed(crate_sels.as_slice()))
+            .or_else(|| {
+                // Code without debug info is attributed to the package whose rlib it's in. This
+                // comes before looking for a package that contains the path, since the target
+                // directory is often inside the workspace's root package.
+                if source_path.extension().is_some_and(|ext| ext == "rlib") {
+                    return self
+                        .crate_index
+                        .package_id_for_output(source_path)
+                        .map(|pkg_id| Cow::Owned(vec![CrateSel::Primary(pkg_id.clone())]));
+                }
+                None
+            })
             .or_else(|| {
                 // If the source path is from the rust standard library, or from one of the
                 // precompiled crates that comes with the standard library, then report no crates.
@@ -305,61 +1079,517 @@ impl Checker {
     }
 
     /// Returns all permissions that are matched by `name`. e.g. The name `["std", "fs", "write"]`
-    /// might return the APIs `{"net"}`.
+    /// might return the APIs `{"net"}`. Also records which `exclude` entries stopped `name` from
+    /// matching a permission.
     pub(crate) fn apis_for_name_iterator<'a>(
         &self,
         key_it: impl Iterator<Item = &'a str>,
-    ) -> &FxHashSet<PermissionName> {
-        self.permissions_by_prefix.get(key_it)
-    }
-
-    pub(crate) fn permission_used(&mut self, api_usage: &ApiUsages, problems: &mut ProblemList) {
-        assert_eq!(api_usage.usages.keys().count(), 1);
-        let permission = api_usage.usages.keys().next().unwrap();
-        if let Some(crate_info) = self
-            .crate_infos
-            .get_mut(&CrateName::from(&api_usage.crate_sel))
-        {
-            if crate_info.allowed_perms.contains(permission) {
-                crate_info.unused_allowed_perms.remove(permission);
-                return;
+    ) -> Cow<'_, FxHashSet<PermissionName>> {
+        if self.glob_includes.is_empty() && self.glob_excludes.is_empty() {
+            let node = self.permissions_by_prefix.get_node(key_it);
+            for (_, exclude_index) in node.excluded_by() {
+                self.exclude_used(*exclude_index);
             }
+            return Cow::Borrowed(node.apis());
+        }
+        // As for paths without globs, a permission applies if any include matches and no exclude
+        // matches, regardless of which of those includes and excludes are globs.
+        let path: Vec<&str> = key_it.collect();
+        let literal_node = self.permissions_by_prefix.get_node(path.iter().cloned());
+        for (_, exclude_index) in literal_node.excluded_by() {
+            self.exclude_used(*exclude_index);
+        }
+        let literal = literal_node.apis();
+        let excluded_node = self.excluded_by_prefix.get_node(path.iter().cloned());
+        let included: Vec<&PermissionName> = self
+            .glob_includes
+            .iter()
+            .filter(|glob| !literal.contains(&glob.permission) && glob.matches_prefix_of(&path))
+            .filter(|glob| {
+                if !excluded_node.apis().contains(&glob.permission) {
+                    return true;
+                }
+                for (permission, exclude_index) in excluded_node.excluded_by() {
+                    if permission == &glob.permission {
+                        self.exclude_used(*exclude_index);
+                    }
+                }
+                false
+            })
+            .map(|glob| &glob.permission)
+            .collect();
+        let excluded: Vec<&PermissionName> = self
+            .glob_excludes
+            .iter()
+            .filter(|(glob, _)| {
+                (literal.contains(&glob.permission) || included.contains(&&glob.permission))
+                    && glob.matches_prefix_of(&path)
+            })
+            .map(|(glob, exclude_index)| {
+                self.exclude_used(*exclude_index);
+                &glob.permission
+            })
+            .collect();
+        if included.is_empty() && excluded.is_empty() {
+            return Cow::Borrowed(literal);
+        }
+        let mut apis = literal.clone();
+        apis.extend(included.into_iter().cloned());
+        for permission in excluded {
+            apis.remove(permission);
         }
-        problems.push(Problem::DisallowedApiUsage(api_usage.clone()));
+        Cow::Owned(apis)
     }
 
-    pub(crate) fn check_unused(&self) -> ProblemList {
-        let mut problems = ProblemList::default();
-        let crate_names_in_index: FxHashSet<_> = self.crate_index.crate_names().collect();
-        for (crate_name, crate_info) in &self.crate_infos {
-            if !crate_names_in_index.contains(crate_name) {
-                problems.push(Problem::UnusedPackageConfig(crate_name.clone()));
-            }
-            if !crate_info.unused_allowed_perms.is_empty() {
-                problems.push(Problem::UnusedAllowApi(UnusedAllowApi {
-                    crate_name: crate_name.clone(),
-                    permissions: crate_info.unused_allowed_perms.iter().cloned().collect(),
-                }));
+    /// Returns whether the config allows `crate_name` to use `permission`. Crates that aren't
+    /// mentioned in the config are only allowed to use permissions from `common.allow_all`.
+    pub(crate) fn is_allowed(&self, crate_name: &CrateName, permission: &PermissionName) -> bool {
+        let crate_info = self.crate_infos.get(crate_name);
+        crate_info.is_some_and(|crate_info| {
+            crate_info.allowed_perms.contains(permission)
+                || crate_info.implied_perms.contains(permission)
+        }) || (self.allowed_for_all.contains(permission)
+            && !crate_info.is_some_and(|crate_info| crate_info.denied_perms.contains(permission)))
+    }
+
+    /// Returns the permissions that the config allows `crate_name` to use, either via its own
+    /// config or via `common.allow_all`, sorted by name.
+    pub(crate) fn allowed_permissions(&self, crate_name: &CrateName) -> Vec<&PermissionName> {
+        let crate_info = self.crate_infos.get(crate_name);
+        let mut permissions: Vec<_> = self
+            .allowed_for_all
+            .iter()
+            .filter(|perm| !crate_info.is_some_and(|info| info.denied_perms.contains(*perm)))
+            .chain(crate_info.into_iter().flat_map(|info| &info.allowed_perms))
+            .collect();
+        permissions.sort();
+        permissions.dedup();
+        permissions
+    }
+
+    /// Returns the allowed permissions that `crate_name` has so far been found to use, sorted by
+    /// name.
+    pub(crate) fn used_permissions(&self, crate_name: &CrateName) -> Vec<&PermissionName> {
+        let Some(crate_info) = self.crate_infos.get(crate_name) else {
+            return Vec::new();
+        };
+        let mut permissions: Vec<_> = crate_info
+            .allowed_perms
+            .iter()
+            .filter(|perm| !crate_info.unused_allowed_perms.contains(*perm))
+            .chain(&crate_info.used_common_perms)
+            .collect();
+        permissions.sort();
+        permissions
+    }
+
+    /// Records that `crate_name` used `permission`, which it's allowed to use. Returns whether this
+    /// is the first use of a permission that's listed in the crate's config or in
+    /// `common.allow_all`.
+    fn record_permission_used(
+        &mut self,
+        crate_name: &CrateName,
+        permission: &PermissionName,
+    ) -> bool {
+        if self.allowed_for_all.contains(permission) {
+            let crate_info = self.crate_infos.entry(crate_name.clone()).or_default();
+            if !crate_info.allowed_perms.contains(permission) {
+                return crate_info.used_common_perms.insert(permission.clone());
             }
         }
-        problems
+        self.crate_infos
+            .get_mut(crate_name)
+            .is_some_and(|crate_info| crate_info.unused_allowed_perms.remove(permission))
     }
 
-    fn record_crate_paths(&mut self, info: &rpc::RustcOutput) -> Result<()> {
-        for path in &info.source_paths {
-            self.path_to_crate
-                .entry(path.to_owned())
-                .or_default()
-                .push(info.crate_sel.clone());
+    /// Returns whether code from `crate_sel` runs on the build machine while building, rather than
+    /// as part of the resulting program.
+    fn is_build_time(&self, crate_sel: &CrateSel) -> bool {
+        match crate_sel {
+            CrateSel::BuildScript(_) => true,
+            CrateSel::Primary(pkg_id) => self.crate_index.is_proc_macro(pkg_id),
         }
-        Ok(())
     }
 
-    pub(crate) fn print_path_to_crate_map(&self) {
-        for (path, crates) in &self.path_to_crate {
-            for c in crates {
-                println!("{c} -> {}", path.display());
+    pub(crate) fn permission_used(&mut self, api_usage: &ApiUsages, problems: &mut ProblemList) {
+        assert_eq!(api_usage.usages.keys().count(), 1);
+        let permission = api_usage.usages.keys().next().unwrap();
+        let crate_name = self.config.crate_name_for(&api_usage.crate_sel);
+        if let Some(inventory) = &mut self.inventory {
+            inventory.record(api_usage);
+        }
+        // This comes before we ignore runtime usage of build-time-only APIs, so that an allowed
+        // permission that's only used at runtime doesn't get reported as unused.
+        if self.is_allowed(&crate_name, permission) {
+            if self.record_permission_used(&crate_name, permission) {
+                if let Some(resume_log) = &mut self.resume_log {
+                    if let Err(error) = resume_log.permission_used(&crate_name, permission) {
+                        warn!("{error:#}");
+                    }
+                }
             }
+            return;
+        }
+        if !self.is_build_time(&api_usage.crate_sel)
+            && self
+                .config
+                .apis
+                .get(permission)
+                .is_some_and(|api| api.build_time_only == Some(true))
+        {
+            return;
+        }
+        let had_usages = api_usage.usages.values().any(|usages| !usages.is_empty());
+        let mut api_usage = api_usage.clone();
+        self.remove_allowed_symbols(&crate_name, &mut api_usage);
+        self.remove_allowed_env_vars(&crate_name, &mut api_usage);
+        if self
+            .config
+            .unknown_locations_permitted_for_crate(&crate_name)
+        {
+            for usages in api_usage.usages.values_mut() {
+                usages.retain(|usage| !usage.source_location.is_unknown());
+            }
+        }
+        // If every usage was individually allowed, there's nothing to report.
+        if had_usages && api_usage.usages.values().all(Vec::is_empty) {
+            return;
+        }
+        api_usage.dependency_path = self
+            .crate_index
+            .dependency_path(api_usage.crate_sel.pkg_id());
+        api_usage.build_time = matches!(api_usage.crate_sel, CrateSel::Primary(_))
+            && self.crate_index.is_proc_macro(api_usage.crate_sel.pkg_id());
+        let api = self.config.apis.get(permission);
+        // A negative cap, e.g. -1, means no cap.
+        if let Some(cap) = api
+            .and_then(|api| api.report_cap)
+            .or(self.args.usage_report_cap)
+            .and_then(|cap| usize::try_from(cap).ok())
+        {
+            api_usage.report_caps.insert(permission.clone(), cap);
+        }
+        if let Some(api) = api {
+            if let Some(description) = &api.description {
+                api_usage
+                    .descriptions
+                    .insert(permission.clone(), description.clone());
+            }
+            if api.severity == Some(Severity::Warning) {
+                api_usage.warning_apis.insert(permission.clone());
+            }
+        }
+        problems.push(Problem::DisallowedApiUsage(api_usage));
+    }
+
+    /// Records which environment variable each usage in `api_usage` reads, if any, then removes
+    /// those that read variables that `crate_name` is allowed to read.
+    fn remove_allowed_env_vars(&mut self, crate_name: &CrateName, api_usage: &mut ApiUsages) {
+        let allowed_env_vars = self
+            .crate_infos
+            .get(crate_name)
+            .map(|crate_info| &crate_info.allowed_env_vars);
+        let env_var_finder = &mut self.env_var_finder;
+        for usages in api_usage.usages.values_mut() {
+            usages.retain_mut(|usage| {
+                if !env_vars::is_env_read(&usage.to_name) {
+                    return true;
+                }
+                let env_var = env_var_finder.var_read_at(&usage.source_location);
+                let allowed = match &env_var {
+                    EnvVar::Named(name) => allowed_env_vars.is_some_and(|vars| vars.contains(name)),
+                    EnvVar::Unknown => false,
+                };
+                usage.env_var = Some(env_var);
+                !allowed
+            });
+        }
+    }
+
+    /// Removes from `api_usage` any usages of symbols that `crate_name` is allowed to use, recording
+    /// that those symbols were used.
+    fn remove_allowed_symbols(&mut self, crate_name: &CrateName, api_usage: &mut ApiUsages) {
+        let Some(crate_info) = self.crate_infos.get_mut(crate_name) else {
+            return;
+        };
+        if crate_info.allowed_symbols.is_empty() {
+            return;
+        }
+        let mut newly_used = Vec::new();
+        for usages in api_usage.usages.values_mut() {
+            usages.retain(|usage| {
+                let Some(symbol) = crate_info
+                    .allowed_symbols
+                    .iter()
+                    .find(|symbol| name_starts_with(&usage.to_name, symbol))
+                else {
+                    return true;
+                };
+                if crate_info.unused_allowed_symbols.remove(symbol) {
+                    newly_used.push(symbol.clone());
+                }
+                false
+            });
+        }
+        if let Some(resume_log) = &mut self.resume_log {
+            for symbol in newly_used {
+                if let Err(error) = resume_log.symbol_used(crate_name, &symbol) {
+                    warn!("{error:#}");
+                }
+            }
+        }
+    }
+
+    /// Returns the inline annotation, if any, that suppresses usages of `permission` at `location`.
+    pub(crate) fn suppression_for(
+        &self,
+        location: &SourceLocation,
+        permission: &PermissionName,
+    ) -> Option<&Suppression> {
+        self.suppressions
+            .get(location.filename())?
+            .iter()
+            .find(|suppression| suppression.applies_to(location, permission))
+    }
+
+    /// Records that `suppressions` were found to suppress at least one API usage.
+    pub(crate) fn suppressions_used(&mut self, suppressions: &[Suppression]) {
+        for suppression in suppressions {
+            if self.unused_suppressions.remove(suppression) {
+                if let Some(resume_log) = &mut self.resume_log {
+                    if let Err(error) = resume_log.suppression_used(suppression) {
+                        warn!("{error:#}");
+                    }
+                }
+            }
+        }
+    }
+
+    /// Returns problems with the structure of the config that we can find without building, such
+    /// as APIs that don't include anything and config for packages that aren't in the dependency
+    /// tree. Used by `cackle config-check`.
+    pub(crate) fn config_problems(&self) -> ProblemList {
+        let mut problems = ProblemList::default();
+        for (perm_name, perm_config) in &self.config.apis {
+            let has_target_includes = perm_config
+                .target
+                .values()
+                .any(|target| !target.include.is_empty());
+            if perm_config.include.is_empty()
+                && perm_config.includes_perms.is_empty()
+                && !has_target_includes
+            {
+                problems.push(Problem::Message(format!(
+                    "API `{perm_name}` doesn't include any paths, so nothing will use it"
+                )));
+            }
+        }
+        let crate_names_in_index: FxHashSet<_> = self.crate_index.crate_names().collect();
+        for crate_name in self.config.packages.keys() {
+            if !crate_names_in_index.contains(crate_name) {
+                problems.push(Problem::UnusedPackageConfig(crate_name.clone()));
+            }
+        }
+        problems
+    }
+
+    pub(crate) fn check_unused(&self) -> ProblemList {
+        let mut problems = ProblemList::default();
+        let crate_names_in_index: FxHashSet<_> = self.crate_index.crate_names().collect();
+        for (crate_name, crate_info) in &self.crate_infos {
+            if !crate_names_in_index.contains(crate_name) {
+                problems.push(Problem::UnusedPackageConfig(crate_name.clone()));
+            }
+            // If only one package was built, other packages may use APIs from binaries that we
+            // didn't build.
+            if !self.is_selected_package(crate_name.pkg_name()) {
+                continue;
+            }
+            // If we didn't look for API usages, then all allowed APIs will appear to be unused.
+            if !crate_info.unused_allowed_perms.is_empty()
+                && self.args.should_check(Category::ApiUsage)
+            {
+                problems.push(Problem::UnusedAllowApi(UnusedAllowApi {
+                    crate_name: crate_name.clone(),
+                    permissions: crate_info.unused_allowed_perms.iter().cloned().collect(),
+                }));
+            }
+            if !crate_info.unused_allowed_symbols.is_empty()
+                && self.args.should_check(Category::ApiUsage)
+            {
+                let mut symbols: Vec<String> =
+                    crate_info.unused_allowed_symbols.iter().cloned().collect();
+                symbols.sort();
+                problems.push(Problem::UnusedAllowSymbols(UnusedAllowSymbols {
+                    crate_name: crate_name.clone(),
+                    symbols,
+                }));
+            }
+        }
+        // Likewise, suppressions and excludes may only be used by packages that we didn't build.
+        if self.args.should_check(Category::ApiUsage) && self.args.package.is_none() {
+            for suppression in &self.unused_suppressions {
+                problems.push(Problem::UnusedSuppression(UnusedSuppression {
+                    suppression: suppression.clone(),
+                }));
+            }
+            // Excludes used since we last called `record_used_excludes` are still only flagged in
+            // `excludes`.
+            let used_excludes: BTreeSet<(&PermissionName, &ApiPath)> = self
+                .excludes
+                .iter()
+                .filter(|exclude| exclude.used.load(Ordering::Relaxed))
+                .map(|exclude| (&exclude.api, &exclude.path))
+                .collect();
+            for (api, path) in &self.unused_excludes {
+                if !used_excludes.contains(&(api, path)) {
+                    problems.push(Problem::UnusedExclude(UnusedExclude {
+                        api: api.clone(),
+                        path: path.clone(),
+                    }));
+                }
+            }
+        }
+        if self.config.common.require_explicit_decisions {
+            let mut undecided: Vec<PackageId> = self
+                .package_problems
+                .keys()
+                .filter(|pkg_id| !self.has_package_config(pkg_id))
+                .cloned()
+                .collect();
+            if !undecided.is_empty() {
+                undecided.sort_by_key(|pkg_id| pkg_id.to_string());
+                problems.push(Problem::UndecidedPackages(undecided));
+            }
+        }
+        problems
+    }
+
+    fn check_subprocess_version(&mut self, version: &str) {
+        if version != rpc::CACKLE_VERSION && self.subprocess_version_mismatch.is_none() {
+            warn!(
+                "Subprocess reported version {version}, but we're version {}",
+                rpc::CACKLE_VERSION
+            );
+            self.subprocess_version_mismatch = Some(version.to_owned());
+        }
+    }
+
+    fn record_problem_packages(&mut self, problems: &ProblemList) {
+        for problem in problems {
+            if let Some(pkg_id) = problem.pkg_id() {
+                self.package_problems
+                    .entry(pkg_id.clone())
+                    .or_default()
+                    .insert(problem.to_string());
+            }
+        }
+    }
+
+    /// Returns whether the config has an entry for either `pkg_id` or its build script.
+    fn has_package_config(&self, pkg_id: &PackageId) -> bool {
+        [
+            CrateSel::Primary(pkg_id.clone()),
+            CrateSel::BuildScript(BuildScriptId {
+                pkg_id: pkg_id.clone(),
+            }),
+        ]
+        .iter()
+        .any(|crate_sel| {
+            self.config
+                .packages
+                .contains_key(&self.config.crate_name_for(crate_sel))
+        })
+    }
+
+    fn record_crate_paths(&mut self, info: &rpc::RustcOutput) -> Result<()> {
+        for path in &info.source_paths {
+            self.path_to_crate
+                .entry(path.to_owned())
+                .or_default()
+                .push(info.crate_sel.clone());
+            // Annotations in dependencies from registries or git are ignored, otherwise a
+            // dependency could grant itself whatever permissions it liked.
+            if !self.suppressions.contains_key(path)
+                && self.crate_index.is_local(info.crate_sel.pkg_id())
+                && path.extension().is_some_and(|ext| ext == "rs")
+                && !self.is_ignored_path(path)
+            {
+                // Not being able to read a file shouldn't fail the whole check. We just won't see
+                // any annotations that it contains.
+                let suppressions = suppressions::scan_path(path).unwrap_or_else(|error| {
+                    warn!("{error:#}");
+                    Vec::new()
+                });
+                self.unused_suppressions
+                    .extend(suppressions.iter().cloned());
+                self.suppressions.insert(path.to_owned(), suppressions);
+            }
+        }
+        if let CrateSel::Primary(pkg_id) = &info.crate_sel {
+            let mut changed = false;
+            for reexport in reexports::scan_crate(&pkg_id.crate_name(), &info.source_paths) {
+                changed |= self
+                    .reexports
+                    .entry(reexport.alias)
+                    .or_default()
+                    .insert(reexport.target);
+            }
+            self.api_map_stale |= changed;
+        }
+        Ok(())
+    }
+
+    pub(crate) fn print_path_to_crate_map(&self) {
+        for (path, crates) in &self.path_to_crate {
+            for c in crates {
+                println!("{c} -> {}", path.display());
+            }
+        }
+    }
+
+    pub(crate) fn record_export(&mut self, pkg_id: &PackageId, prefix: String) {
+        self.exports
+            .entry(pkg_id.name().to_owned())
+            .or_default()
+            .insert(prefix);
+    }
+
+    pub(crate) fn print_exports(&self, pkg_name: &str) {
+        print!("{}", self.exports_text(pkg_name));
+    }
+
+    fn exports_text(&self, pkg_name: &str) -> String {
+        let Some(exports) = self.exports.get(pkg_name) else {
+            return format!("No exports found for package `{pkg_name}`\n");
+        };
+        exports.iter().map(|prefix| format!("{prefix}\n")).collect()
+    }
+
+    /// Writes all API usages that we've seen to `path`. Does nothing if --inventory wasn't
+    /// specified.
+    pub(crate) fn write_inventory(&self, path: &Path) -> Result<()> {
+        match &self.inventory {
+            Some(inventory) => inventory.write(path),
+            None => Ok(()),
+        }
+    }
+
+    pub(crate) fn print_permissions(&self, crate_name: &str) {
+        let crate_name = CrateName::from(crate_name);
+        let allowed = self.allowed_permissions(&crate_name);
+        if allowed.is_empty() {
+            println!("Crate `{crate_name}` isn't allowed any APIs");
+            return;
+        }
+        let used = self.used_permissions(&crate_name);
+        for permission in allowed {
+            let status = if used.contains(&permission) {
+                "used"
+            } else {
+                "unused"
+            };
+            println!("{permission} ({status})");
         }
     }
 
@@ -370,7 +1600,10 @@ impl Checker {
     ) {
         for p in possible_exported_apis {
             let crate_name = CrateName::from(&p.pkg_id);
-            if let Some(pkg_config) = self.config.packages.get(&crate_name) {
+            let config_name = self
+                .config
+                .crate_name_for(&CrateSel::Primary(p.pkg_id.clone()));
+            if let Some(pkg_config) = self.config.packages.get(&config_name) {
                 // If we've imported any APIs, or ignored available APIs from the package, then we
                 // don't want to report a possible export.
                 if pkg_config.import.is_some() {
@@ -383,10 +1616,35 @@ impl Checker {
                 {
                     continue;
                 }
+                // An exclude of the module is used, even if nothing it excludes is referenced, since
+                // it's what tells us that the module isn't part of the API.
+                if let Some(exclude_index) = self
+                    .excludes
+                    .iter()
+                    .position(|exclude| exclude.api == p.api && exclude.path == p.api_path())
+                {
+                    self.exclude_used(exclude_index);
+                    continue;
+                }
             }
             problems.push(Problem::PossibleExportedApi(p.clone()));
         }
     }
+
+    pub(crate) fn shell_invocation_problems(
+        &self,
+        shell_invocations: &[ShellInvocation],
+        problems: &mut ProblemList,
+    ) {
+        for invocation in shell_invocations {
+            if !self
+                .config
+                .shell_permitted_for_crate(&self.config.crate_name_for(&invocation.crate_sel))
+            {
+                problems.push(Problem::ShellInvocation(invocation.clone()));
+            }
+        }
+    }
 }
 
 // Returns whether `source_path` is from the rust standard library or precompiled crates that are
@@ -395,29 +1653,41 @@ pub(crate) fn is_in_rust_std(source_path: &Path) -> bool {
     source_path.starts_with("/rustc/") || source_path.starts_with("/cargo/registry")
 }
 
+/// Returns whether the first parts of `name` are the `::`-separated parts of `prefix`. e.g.
+/// `std::fs::metadata` starts with `std::fs::metadata` and `std::fs`, but not `std::f`.
+fn name_starts_with(name: &Name, prefix: &str) -> bool {
+    let mut parts = name.parts();
+    prefix.split("::").all(|part| parts.next() == Some(part))
+}
+
+/// Returns `perms` together with the components of any composite APIs among them.
+fn components_of<'a>(
+    config: &Config,
+    perms: impl IntoIterator<Item = &'a PermissionName>,
+) -> FxHashSet<PermissionName> {
+    perms
+        .into_iter()
+        .flat_map(|perm| config.perm_components(perm))
+        .cloned()
+        .collect()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::config::testing::parse;
     use crate::symbol::Symbol;
     use std::collections::BTreeMap;
-    use std::fmt::Debug;
-    use std::fmt::Display;
 
-    // Wraps a type T and makes it implement Debug by deferring to the Display implementation of T.
-    struct DebugAsDisplay<T: Display>(T);
-
-    impl<T: Display> Debug for DebugAsDisplay<T> {
-        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-            write!(f, "{}", self.0)
-        }
+    fn checker_for_testing() -> Checker {
+        checker_with_args(Args::default())
     }
 
-    fn checker_for_testing() -> Checker {
+    fn checker_with_args(args: Args) -> Checker {
         Checker::new(
             Arc::new(TempDir::new().unwrap()),
             PathBuf::default(),
-            Arc::new(Args::default()),
+            Arc::new(args),
             Arc::new(CrateIndex::default()),
             PathBuf::default(),
         )
@@ -456,61 +1726,1492 @@ mod tests {
     }
 
     #[test]
-    fn reload_config() {
-        let config = parse(
-            r#"
-            [api.fs]
-            include = [
-                "std::fs",
-            ]
-            [pkg.foo]
-            allow_apis = [
-                "fs",
-            ]
-        "#,
+    fn test_apis_for_glob_path() {
+        let config = r#"
+                [api.spawn]
+                include = ["std::*::spawn", "tokio::**::spawn_blocking"]
+                exclude = ["std::process::spawn"]
+
+                [api.net]
+                include = ["std::net"]
+                exclude = ["std::net::**::local_addr"]
+                "#;
+        assert_perms(config, &["std", "thread", "spawn"], &["spawn"]);
+        assert_perms(config, &["std", "process", "spawn"], &[]);
+        assert_perms(config, &["std", "spawn"], &[]);
+        assert_perms(
+            config,
+            &["tokio", "runtime", "Handle", "spawn_blocking"],
+            &["spawn"],
+        );
+        assert_perms(config, &["std", "net", "TcpStream", "connect"], &["net"]);
+        assert_perms(config, &["std", "net", "TcpStream", "local_addr"], &[]);
+    }
+
+    #[test]
+    fn ignored_paths() {
+        let mut checker = Checker::new(
+            Arc::new(TempDir::new().unwrap()),
+            PathBuf::default(),
+            Arc::new(Args::default()),
+            Arc::new({
+                let mut crate_index = CrateIndex::default();
+                crate_index.manifest_path = PathBuf::from("/ws/Cargo.toml");
+                crate_index
+            }),
+            PathBuf::default(),
+        );
+        checker.update_config(
+            parse(r#"ignore_paths = ["vendor/**", "generated/*.c", "/usr/include/**"]"#).unwrap(),
+        );
+        let ignored = |path: &str| checker.is_ignored_path(Path::new(path));
+        assert!(ignored("/ws/vendor/bindings/net.rs"));
+        assert!(ignored("/ws/src/../vendor/net.rs"));
+        assert!(ignored("/ws/generated/net.c"));
+        assert!(!ignored("/ws/generated/sub/net.c"));
+        assert!(!ignored("/ws/src/lib.rs"));
+        assert!(!ignored("/other/vendor/net.rs"));
+        assert!(ignored("/usr/include/sys/socket.h"));
+    }
+
+    #[test]
+    fn test_apis_for_reexported_path() {
+        let mut checker = checker_for_testing();
+        for (alias, target) in [("foo::api", "foo::imp"), ("foo::imp::f", "foo::hidden::f")] {
+            checker
+                .reexports
+                .entry(alias.to_owned())
+                .or_default()
+                .insert(target.to_owned());
+        }
+        checker.update_config(
+            parse(
+                r#"
+                [api.secret]
+                include = ["foo::api::f"]
+                "#,
+            )
+            .unwrap(),
+        );
+        let apis = |path: &[&str]| -> Vec<String> {
+            let apis = checker.apis_for_name_iterator(path.iter().cloned());
+            apis.iter().map(|api| api.to_string()).collect()
+        };
+        assert_eq!(apis(&["foo", "api", "f"]), vec!["secret"]);
+        assert_eq!(apis(&["foo", "imp", "f"]), vec!["secret"]);
+        assert_eq!(apis(&["foo", "hidden", "f"]), vec!["secret"]);
+        assert!(apis(&["foo", "imp", "g"]).is_empty());
+    }
+
+    #[test]
+    fn prebuilt_binary_usage_reported() {
+        let dir = tempfile::tempdir().unwrap();
+        let source_path = dir.path().join("main.rs");
+        std::fs::write(
+            &source_path,
+            "fn main() {\n    println!(\"{}\", std::fs::read(\"x\").is_ok());\n}\n",
         )
         .unwrap();
-        let mut checker = Checker {
-            crate_index: crate::crate_index::testing::index_with_package_names(&["foo"]),
-            ..checker_for_testing()
+        let bin_path = dir.path().join("main");
+        let status = std::process::Command::new("rustc")
+            .arg("-Cdebuginfo=2")
+            .arg("-Clink-arg=-Wl,--emit-relocs")
+            .arg("-o")
+            .arg(&bin_path)
+            .arg(&source_path)
+            .status()
+            .unwrap();
+        assert!(status.success());
+
+        let mut checker = checker_for_testing();
+        checker.update_config(
+            parse(
+                r#"
+                [api.fs]
+                include = ["std::fs"]
+                "#,
+            )
+            .unwrap(),
+        );
+        checker.path_to_crate.insert(
+            source_path,
+            vec![CrateSel::Primary(crate::crate_index::testing::pkg_id(
+                "crab1",
+            ))],
+        );
+        let problems = checker
+            .check_prebuilt_binary(&bin_path, &mut crate::CheckState::default())
+            .unwrap();
+        let problems: Vec<_> = (&problems).into_iter().collect();
+        assert_eq!(problems.len(), 1, "{problems:?}");
+        let Problem::DisallowedApiUsage(usages) = problems[0] else {
+            panic!("Unexpected problem: {}", problems[0]);
         };
-        checker.update_config(config.clone());
-        let mut problems = ProblemList::default();
+        assert_eq!(usages.crate_sel.pkg_id().name(), "crab1");
+        assert!(usages.usages.contains_key(&PermissionName::from("fs")));
+    }
 
-        let crate_sel = CrateSel::Primary(crate::crate_index::testing::pkg_id("foo"));
-        let permissions = checker
-            .apis_for_name_iterator(["std", "fs", "read_to_string"].into_iter())
-            .clone();
-        assert_eq!(permissions.len(), 1);
+    #[test]
+    fn self_referential_glob_reexport() {
+        let mut checker = checker_for_testing();
+        // `pub use self::imp::*` in `foo`.
+        checker
+            .reexports
+            .entry("foo".to_owned())
+            .or_default()
+            .insert("foo::imp".to_owned());
         assert_eq!(
-            permissions.iter().next().unwrap(),
-            &PermissionName::from("fs")
+            checker.reexport_targets("foo::f"),
+            BTreeSet::from(["foo::f".to_owned(), "foo::imp::f".to_owned()])
         );
-        for api in permissions {
-            let mut usages = BTreeMap::new();
-            usages.insert(
-                api,
-                vec![ApiUsage {
-                    source_location: SourceLocation::new(Path::new("lib.rs"), 1, None),
-                    from: SymbolOrDebugName::Symbol(Symbol::borrowed(&[])),
-                    to_name: crate::names::split_simple("foo::bar"),
-                    to: SymbolOrDebugName::Symbol(Symbol::borrowed(&[])),
-                    to_source: NameSource::Symbol(Symbol::borrowed(b"foo::bar")),
-                    debug_data: None,
-                }],
-            );
-            let api_usage = ApiUsages {
-                crate_sel: crate_sel.clone(),
-                usages,
-            };
-            checker.permission_used(&api_usage, &mut problems);
+    }
+
+    #[test]
+    fn builtin_perms() {
+        let config = r#"
+                use_builtin_perms = ["fs", "clock"]
+                "#;
+        assert_perms(config, &["std", "fs", "File", "open"], &["fs"]);
+        assert_perms(config, &["std", "time", "Instant", "now"], &["clock"]);
+        assert_perms(config, &["std", "env", "var"], &[]);
+
+        let config = r#"
+                import_std = ["fs", "platform_specific"]
+                "#;
+        assert_perms(
+            config,
+            &["std", "os", "unix", "fs", "symlink"],
+            &["fs", "platform_specific"],
+        );
+        assert_perms(config, &["std", "fs", "File", "open"], &["fs"]);
+
+        // Built-in definitions can be extended.
+        let config = r#"
+                use_builtin_perms = ["fs"]
+
+                [api.fs]
+                include = ["crab1::fs"]
+                "#;
+        assert_perms(config, &["std", "fs", "File", "open"], &["fs"]);
+        assert_perms(config, &["crab1", "fs", "read"], &["fs"]);
+
+        assert!(parse(r#"use_builtin_perms = ["not_an_api"]"#).is_err());
+    }
+
+    #[test]
+    fn builtin_panic_perm() {
+        let config = r#"
+                import_std = ["panic"]
+                "#;
+        assert_perms(config, &["core", "panicking", "panic_fmt"], &["panic"]);
+        assert_perms(config, &["core", "option", "Option", "unwrap"], &["panic"]);
+        assert_perms(config, &["core", "result", "Result", "expect"], &["panic"]);
+        assert_perms(config, &["core", "option", "Option", "unwrap_or"], &[]);
+
+        // Generic arguments are stripped from symbol names, so a monomorphised `unwrap` matches.
+        let mut checker = checker_for_testing();
+        checker.update_config(parse(config).unwrap());
+        let symbol =
+            Symbol::borrowed(b"_ZN4core6result19Result$LT$T$C$E$GT$6unwrap17h0123456789abcdefE");
+        let mut names = symbol.names_without_std_generic_args().unwrap();
+        let mut apis = Vec::new();
+        while let Some((parts, _)) = names.next_name().unwrap() {
+            apis.extend(checker.apis_for_name_iterator(parts).iter().cloned());
         }
+        assert_eq!(apis, vec![PermissionName::from("panic")]);
+    }
+
+    #[test]
+    fn builtin_nondeterministic_perm() {
+        let config = r#"
+                import_std = ["nondeterministic"]
+                "#;
+        assert_perms(
+            config,
+            &["std", "time", "SystemTime", "now"],
+            &["nondeterministic"],
+        );
+        assert_perms(config, &["getrandom", "getrandom"], &["nondeterministic"]);
+        assert_perms(config, &["std", "time", "SystemTime", "elapsed"], &[]);
 
+        // Only usage from build scripts is reported, unless the config says otherwise.
+        let mut checker = checker_for_testing();
+        checker.update_config(parse(config).unwrap());
+        let runtime = CrateSel::Primary(crate::crate_index::testing::pkg_id("foo"));
+        let build_script =
+            CrateSel::BuildScript(crate::crate_index::testing::build_script_id("foo"));
+        let mut problems = ProblemList::default();
+        checker.permission_used(
+            &api_usages(&runtime, "nondeterministic".into()),
+            &mut problems,
+        );
         assert!(problems.is_empty());
-        assert!(checker.check_unused().is_empty());
+        checker.permission_used(
+            &api_usages(&build_script, "nondeterministic".into()),
+            &mut problems,
+        );
+        assert_eq!(problems.len(), 1);
 
-        // Now reload the config and make that we still don't report any unused configuration.
-        checker.update_config(config);
+        // Runtime usage still counts as using a permission that's been allowed.
+        let mut checker = checker_for_testing();
+        checker.update_config(
+            parse(
+                r#"
+                import_std = ["nondeterministic"]
+
+                [pkg.foo]
+                allow_apis = ["nondeterministic"]
+                "#,
+            )
+            .unwrap(),
+        );
+        checker.permission_used(
+            &api_usages(&runtime, "nondeterministic".into()),
+            &mut problems,
+        );
+        assert_eq!(
+            checker.used_permissions(&CrateName::from("foo")),
+            vec![&PermissionName::from("nondeterministic")]
+        );
+
+        let mut checker = checker_for_testing();
+        checker.update_config(
+            parse(
+                r#"
+                import_std = ["nondeterministic"]
+
+                [api.nondeterministic]
+                build_time_only = false
+                "#,
+            )
+            .unwrap(),
+        );
+        let mut problems = ProblemList::default();
+        checker.permission_used(
+            &api_usages(&runtime, "nondeterministic".into()),
+            &mut problems,
+        );
+        assert_eq!(problems.len(), 1);
+    }
+
+    #[test]
+    fn builtin_dynamic_loading_perm() {
+        let config = r#"
+                import_std = ["dynamic_loading"]
+                "#;
+        assert_perms(
+            config,
+            &["libloading", "Library", "new"],
+            &["dynamic_loading"],
+        );
+        assert_perms(
+            config,
+            &["libloading", "os", "unix", "Library", "open"],
+            &["dynamic_loading"],
+        );
+        assert_perms(config, &["libc", "dlopen"], &["dynamic_loading"]);
+        assert_perms(config, &["libc", "dlclose"], &[]);
+        assert_perms(config, &["libloading", "Error"], &[]);
+
+        // Wrappers around dynamic loading can be added to the built-in API.
+        let config = r#"
+                import_std = ["dynamic_loading"]
+
+                [api.dynamic_loading]
+                include = ["plugins::load"]
+                "#;
+        assert_perms(config, &["plugins", "load"], &["dynamic_loading"]);
+        assert_perms(config, &["libc", "dlopen"], &["dynamic_loading"]);
+    }
+
+    #[test]
+    fn builtin_process_perm() {
+        let config = r#"
+                import_std = ["process", "terminate"]
+                "#;
+        assert_perms(config, &["std", "process", "Command", "new"], &["process"]);
+        assert_perms(
+            config,
+            &["std", "os", "unix", "process", "CommandExt", "exec"],
+            &["process"],
+        );
+        assert_perms(config, &["std", "process", "abort"], &["terminate"]);
+
+        let mut checker = Checker {
+            crate_index: crate::crate_index::testing::index_with_package_names(&["foo"]),
+            ..checker_for_testing()
+        };
+        checker.update_config(parse(config).unwrap());
+        let apis: Vec<PermissionName> = checker
+            .apis_for_name_iterator(["std", "process", "Command", "new"].into_iter())
+            .iter()
+            .cloned()
+            .collect();
+        let crate_sel = CrateSel::Primary(crate::crate_index::testing::pkg_id("foo"));
+        let mut problems = ProblemList::default();
+        for api in apis {
+            checker.permission_used(&api_usages(&crate_sel, api), &mut problems);
+        }
+        assert_eq!(problems.len(), 1);
+        assert!(matches!(problems[0], Problem::DisallowedApiUsage(..)));
+    }
+
+    #[test]
+    fn include_and_exclude_same_path() {
+        let config = r#"
+                [api.env]
+                include = ["std::env", "std::env::var"]
+                exclude = ["std::env::var"]
+                "#;
+        // The exclude takes precedence, including for paths below it.
+        assert_perms(config, &["std", "env", "var"], &[]);
+        assert_perms(config, &["std", "env", "var", "inner"], &[]);
+        assert_perms(config, &["std", "env", "args"], &["env"]);
+
+        let mut checker = checker_for_testing();
+        checker.update_config(parse(config).unwrap());
+        assert_eq!(
+            checker.base_problems(),
+            Problem::IncludedAndExcluded(IncludedAndExcluded {
+                api: PermissionName::from("env"),
+                path: crate::config::ApiPath::from_str("std::env::var"),
+            })
+            .into()
+        );
+    }
+
+    #[test]
+    fn includes_perms() {
+        let config = r#"
+                [api.net_tcp]
+                include = ["std::net::TcpStream"]
+
+                [api.net_udp]
+                include = ["std::net::UdpSocket"]
+
+                [api.net]
+                include = ["std::net::IpAddr"]
+                includes_perms = ["net_tcp", "net_udp"]
+                "#;
+        assert_perms(
+            config,
+            &["std", "net", "TcpStream", "connect"],
+            &["net", "net_tcp"],
+        );
+        assert_perms(config, &["std", "net", "UdpSocket"], &["net", "net_udp"]);
+        assert_perms(config, &["std", "net", "IpAddr"], &["net"]);
+    }
+
+    #[test]
+    fn config_problems() {
+        let mut checker = checker_for_testing();
+        checker.update_config(
+            parse(
+                r#"
+                [api.fs]
+                include = ["std::fs"]
+
+                [api.empty]
+
+                [pkg.foo]
+                allow_apis = ["fs"]
+                "#,
+            )
+            .unwrap(),
+        );
+        let problems = checker.config_problems();
+        let messages: Vec<String> = problems
+            .into_iter()
+            .map(|problem| problem.to_string())
+            .collect();
+        assert_eq!(
+            messages,
+            vec![
+                "API `empty` doesn't include any paths, so nothing will use it",
+                "Config supplied for package `foo` not in dependency tree",
+            ]
+        );
+    }
+
+    #[test]
+    fn includes_perms_transitive() {
+        let mut checker = checker_for_testing();
+        checker.update_config(
+            parse(
+                r#"
+                [api.fs]
+                include = ["std::fs"]
+
+                [api.net_tcp]
+                include = ["std::net::TcpStream"]
+
+                [api.net]
+                includes_perms = ["net_tcp"]
+
+                [api.dangerous]
+                includes_perms = ["fs", "net"]
+
+                [pkg.foo]
+                allow_apis = ["dangerous"]
+                "#,
+            )
+            .unwrap(),
+        );
+        let apis = checker.apis_for_name_iterator(["std", "net", "TcpStream"].into_iter());
+        let mut api_names: Vec<_> = apis.iter().map(AsRef::as_ref).collect();
+        api_names.sort();
+        assert_eq!(api_names, &["dangerous", "net", "net_tcp"]);
+
+        // Allowing the composite permission implicitly allows each of its components.
+        let foo = CrateName::from("foo");
+        for perm in ["dangerous", "fs", "net", "net_tcp"] {
+            assert!(checker.is_allowed(&foo, &PermissionName::from(perm)));
+        }
+        assert!(!checker.is_allowed(&CrateName::from("bar"), &PermissionName::from("net_tcp")));
+
+        let mut problems = ProblemList::default();
+        let crate_sel = CrateSel::Primary(crate::crate_index::testing::pkg_id("foo"));
+        checker.permission_used(
+            &api_usages(&crate_sel, PermissionName::from("net_tcp")),
+            &mut problems,
+        );
+        assert!(problems.is_empty());
+    }
+
+    #[test]
+    fn disallowed_usage_includes_api_description() {
+        let mut checker = checker_for_testing();
+        checker.update_config(
+            parse(
+                r#"
+                [api.fs]
+                include = ["std::fs"]
+                description = "Filesystem access"
+                "#,
+            )
+            .unwrap(),
+        );
+        let mut usages = BTreeMap::new();
+        usages.insert(PermissionName::from("fs"), vec![]);
+        let mut problems = ProblemList::default();
+        checker.permission_used(
+            &crate::problem::testing::api_usages(
+                CrateSel::Primary(crate::crate_index::testing::pkg_id("foo")),
+                usages,
+            ),
+            &mut problems,
+        );
+        let Problem::DisallowedApiUsage(usage) = &problems[0] else {
+            panic!("Unexpected problem {:?}", problems[0]);
+        };
+        assert_eq!(
+            usage.descriptions.get(&PermissionName::from("fs")),
+            Some(&"Filesystem access".to_owned())
+        );
+    }
+
+    #[test]
+    fn warning_severity_api() {
+        let mut checker = checker_for_testing();
+        checker.update_config(
+            parse(
+                r#"
+                [api.fs]
+                include = ["std::fs"]
+                severity = "warning"
+
+                [api.net]
+                include = ["std::net"]
+                "#,
+            )
+            .unwrap(),
+        );
+        let crate_sel = CrateSel::Primary(crate::crate_index::testing::pkg_id("foo"));
+        let mut problems = ProblemList::default();
+        checker.permission_used(&api_usages(&crate_sel, "fs".into()), &mut problems);
+        assert_eq!(problems[0].severity(), Severity::Warning);
+        checker.permission_used(&api_usages(&crate_sel, "net".into()), &mut problems);
+        assert_eq!(problems[1].severity(), Severity::Error);
+        // Once grouped, the problem is only a warning if all the APIs used are.
+        let grouped = problems.grouped_by_type_and_crate();
+        assert_eq!(grouped.len(), 1);
+        assert_eq!(grouped[0].severity(), Severity::Error);
+    }
+
+    #[test]
+    fn usage_report_caps() {
+        let mut checker = checker_with_args(Args {
+            usage_report_cap: Some(5),
+            ..Args::default()
+        });
+        checker.update_config(
+            parse(
+                r#"
+                [api.fs]
+                include = ["std::fs"]
+                report_cap = 2
+
+                [api.net]
+                include = ["std::net"]
+                report_cap = -1
+
+                [api.process]
+                include = ["std::process"]
+                "#,
+            )
+            .unwrap(),
+        );
+        let crate_sel = CrateSel::Primary(crate::crate_index::testing::pkg_id("foo"));
+        let mut problems = ProblemList::default();
+        for api in ["fs", "net", "process"] {
+            checker.permission_used(&api_usages(&crate_sel, api.into()), &mut problems);
+        }
+        let caps: Vec<_> = problems
+            .into_iter()
+            .map(|problem| {
+                let Problem::DisallowedApiUsage(usage) = problem else {
+                    panic!("Unexpected problem {problem:?}");
+                };
+                usage.report_caps.clone()
+            })
+            .collect();
+        assert_eq!(caps[0].get(&PermissionName::from("fs")), Some(&2));
+        assert!(caps[1].is_empty());
+        assert_eq!(caps[2].get(&PermissionName::from("process")), Some(&5));
+    }
+
+    #[test]
+    fn version_qualified_permissions() {
+        use crate::crate_index::testing::pkg_id_with_version;
+
+        let mut checker = checker_for_testing();
+        checker.update_config(
+            parse(
+                r#"
+                [api.net]
+                include = ["std::net"]
+
+                [api.fs]
+                include = ["std::fs"]
+
+                [pkg.foo]
+                allow_apis = ["fs"]
+
+                [pkg."foo@2.0.0"]
+                allow_apis = ["net"]
+                "#,
+            )
+            .unwrap(),
+        );
+        let v1 = CrateSel::Primary(pkg_id_with_version("foo", 1));
+        let v2 = CrateSel::Primary(pkg_id_with_version("foo", 2));
+        let problem_count = |checker: &mut Checker, crate_sel: &CrateSel, api: &'static str| {
+            let mut problems = ProblemList::default();
+            checker.permission_used(&api_usages(crate_sel, api.into()), &mut problems);
+            problems.len()
+        };
+        // The unqualified entry applies to versions that don't have their own entry.
+        assert_eq!(problem_count(&mut checker, &v1, "fs"), 0);
+        assert_eq!(problem_count(&mut checker, &v1, "net"), 1);
+        // A version-qualified entry replaces the unqualified one for that version.
+        assert_eq!(problem_count(&mut checker, &v2, "net"), 0);
+        assert_eq!(problem_count(&mut checker, &v2, "fs"), 1);
+    }
+
+    #[test]
+    fn allowed_symbols() {
+        let mut checker = checker_for_testing();
+        checker.update_config(
+            parse(
+                r#"
+                [api.fs]
+                include = ["std::fs"]
+
+                [pkg.foo]
+                allow_symbols = ["foo::bar", "std::fs::metadata"]
+                "#,
+            )
+            .unwrap(),
+        );
+        let crate_sel = CrateSel::Primary(crate::crate_index::testing::pkg_id("foo"));
+        let mut problems = ProblemList::default();
+        // The usage from `api_usages` is of `foo::bar`, which is allowed.
+        checker.permission_used(&api_usages(&crate_sel, "fs".into()), &mut problems);
+        assert!(problems.is_empty());
+
+        let mut usages = api_usages(&crate_sel, "fs".into());
+        for usage in usages.usages.values_mut().flatten() {
+            usage.to_name = crate::names::split_simple("std::fs::read");
+        }
+        checker.permission_used(&usages, &mut problems);
+        assert_eq!(problems.len(), 1);
+
+        let unused = Problem::UnusedAllowSymbols(UnusedAllowSymbols {
+            crate_name: "foo".into(),
+            symbols: vec!["std::fs::metadata".to_owned()],
+        });
+        let problems = checker.check_unused();
+        assert!((&problems).into_iter().any(|problem| problem == &unused));
+    }
+
+    #[test]
+    fn allowed_env_vars() {
+        let dir = tempfile::tempdir().unwrap();
+        let source_path = dir.path().join("lib.rs");
+        std::fs::write(
+            &source_path,
+            indoc::indoc! {r#"
+                fn read(name: &str) {
+                    let _ = std::env::var("HOME");
+                    let _ = std::env::var("SECRET");
+                    let _ = std::env::var(name);
+                }
+            "#},
+        )
+        .unwrap();
+        let mut checker = checker_for_testing();
+        checker.update_config(
+            parse(
+                r#"
+                [api.env]
+                include = ["std::env::var"]
+
+                [pkg.foo]
+                allow_env_vars = ["HOME"]
+                "#,
+            )
+            .unwrap(),
+        );
+        let crate_sel = CrateSel::Primary(crate::crate_index::testing::pkg_id("foo"));
+        let mut usages = api_usages(&crate_sel, "env".into());
+        let template = usages.usages.values().flatten().next().unwrap().clone();
+        let env_usages = (2..=4)
+            .map(|line| ApiUsage {
+                source_location: SourceLocation::new(source_path.as_path(), line, Some(13)),
+                to_name: crate::names::split_simple("std::env::var"),
+                ..template.clone()
+            })
+            .collect();
+        usages.usages.insert("env".into(), env_usages);
+        let mut problems = ProblemList::default();
+        checker.permission_used(&usages, &mut problems);
+        let Problem::DisallowedApiUsage(usage) = &problems[0] else {
+            panic!("Unexpected problem {:?}", problems[0]);
+        };
+        let env_vars: Vec<_> = usage
+            .usages
+            .values()
+            .flatten()
+            .map(|usage| usage.env_var.clone())
+            .collect();
+        assert_eq!(
+            env_vars,
+            vec![
+                Some(EnvVar::Named("SECRET".to_owned())),
+                Some(EnvVar::Unknown)
+            ]
+        );
+    }
+
+    #[test]
+    fn name_prefixes() {
+        let name = crate::names::split_simple("std::fs::metadata");
+        assert!(name_starts_with(&name, "std::fs::metadata"));
+        assert!(name_starts_with(&name, "std::fs"));
+        assert!(!name_starts_with(&name, "std::f"));
+        assert!(!name_starts_with(&name, "std::fs::metadata::inner"));
+    }
+
+    #[test]
+    fn require_explicit_decisions() {
+        let config = parse(
+            r#"
+            require_explicit_decisions = true
+
+            [pkg.foo]
+            allow_unsafe = true
+        "#,
+        )
+        .unwrap();
+        let mut checker = Checker {
+            crate_index: crate::crate_index::testing::index_with_package_names(&[
+                "foo", "bar", "baz",
+            ]),
+            ..checker_for_testing()
+        };
+        checker.update_config(config);
+        assert!(checker.check_unused().is_empty());
+
+        let mut problems = ProblemList::default();
+        for name in ["foo", "bar"] {
+            problems.push(Problem::IsProcMacro(crate::crate_index::testing::pkg_id(
+                name,
+            )));
+        }
+        checker.record_problem_packages(&problems);
+        let unused = checker.check_unused();
+        assert_eq!(unused.len(), 1);
+        assert_eq!(
+            unused[0],
+            Problem::UndecidedPackages(vec![crate::crate_index::testing::pkg_id("bar")])
+        );
+    }
+
+    #[test]
+    fn disallowed_licenses() {
+        let checker_with_config = |config: &str| {
+            let mut checker = Checker {
+                crate_index: crate::crate_index::testing::index_with_licenses(&[
+                    ("mit", Some("MIT")),
+                    ("dual", Some("MIT OR Apache-2.0")),
+                    ("gpl", Some("GPL-3.0-only")),
+                    ("unknown", None),
+                ]),
+                ..checker_for_testing()
+            };
+            checker.update_config(parse(config).unwrap());
+            checker
+        };
+        let disallowed = |name: &str, license: Option<&str>| {
+            Problem::DisallowedLicense(DisallowedLicense {
+                pkg_id: crate::crate_index::testing::pkg_id(name),
+                license: license.map(str::to_owned),
+            })
+        };
+
+        // Licenses aren't checked by default.
+        assert!(checker_with_config("").base_problems().is_empty());
+
+        let mut expected = ProblemList::default();
+        expected.push(disallowed("gpl", Some("GPL-3.0-only")));
+        assert_eq!(
+            checker_with_config(r#"allowed_licenses = ["MIT"]"#).base_problems(),
+            expected
+        );
+
+        expected.push(disallowed("unknown", None));
+        assert_eq!(
+            checker_with_config(
+                r#"
+                allowed_licenses = ["MIT"]
+                allow_unknown_license = false
+                "#
+            )
+            .base_problems(),
+            expected
+        );
+    }
+
+    #[test]
+    fn yanked_versions_reported_once() {
+        use crate::crate_index::testing::pkg_id;
+
+        let cargo_home = tempfile::tempdir().unwrap();
+        for name in ["crab1", "crab2"] {
+            crate::yanked::testing::write_cache_entry(cargo_home.path(), name, &[("0.0.0", true)]);
+        }
+        let registry_cache = crate::yanked::RegistryCache::from_cargo_home(cargo_home.path());
+        let mut checker = Checker {
+            crate_index: crate::crate_index::testing::index_with_registry_packages(
+                &[],
+                &["crab1", "crab2"],
+            ),
+            ..checker_for_testing()
+        };
+        checker.update_config(
+            parse(
+                r#"
+                check_yanked = true
+
+                [pkg.crab2]
+                allow_yanked = true
+                "#,
+            )
+            .unwrap(),
+        );
+
+        let mut problems = ProblemList::default();
+        checker.yanked_problems(&registry_cache, &mut problems);
+        assert_eq!(problems, Problem::YankedVersion(pkg_id("crab1")).into());
+
+        // Reloading the config and checking again mustn't report the same versions again.
+        let mut problems = ProblemList::default();
+        checker.yanked_problems(&registry_cache, &mut problems);
+        assert!(problems.is_empty());
+    }
+
+    #[test]
+    fn pre_link_hook() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let hook_path = dir.path().join("verify.sh");
+        std::fs::write(
+            &hook_path,
+            "#!/bin/sh\nfor f in \"$@\"; do\n  case \"$f\" in *bad.o) echo \"unsigned: $f\"; exit 1;; esac\ndone\n",
+        )
+        .unwrap();
+        std::fs::set_permissions(&hook_path, std::fs::Permissions::from_mode(0o755)).unwrap();
+        let mut checker = Checker::new(
+            Arc::new(TempDir::new().unwrap()),
+            PathBuf::default(),
+            Arc::new(Args::default()),
+            Arc::new(CrateIndex::default()),
+            dir.path().join("cackle.toml"),
+        );
+        let link_info = |objects: &[&str]| LinkInfo {
+            crate_sel: CrateSel::Primary(crate::crate_index::testing::pkg_id("foo")),
+            object_paths: objects.iter().map(PathBuf::from).collect(),
+            output_file: PathBuf::from("foo"),
+        };
+
+        // Without a hook configured, nothing is run.
+        checker.update_config(parse("").unwrap());
+        assert_eq!(checker.run_pre_link_hook(&link_info(&["bad.o"])), None);
+
+        checker.update_config(parse(r#"pre_link_hook = "verify.sh""#).unwrap());
+        assert_eq!(checker.run_pre_link_hook(&link_info(&["a.o", "b.o"])), None);
+        let Some(Problem::Message(message)) =
+            checker.run_pre_link_hook(&link_info(&["a.o", "bad.o"]))
+        else {
+            panic!("Expected hook to reject link");
+        };
+        assert!(message.starts_with("Pre-link hook"));
+        assert!(message.ends_with("unsigned: bad.o"));
+
+        // Warnings don't stop the link, so the hook still runs, but errors do, so it doesn't.
+        let mut warnings = ProblemList::default();
+        warnings.push(Problem::UnusedPackageConfig(CrateName::from("bar")));
+        let problems = checker.add_pre_link_hook_problem(&link_info(&["bad.o"]), warnings);
+        assert_eq!(problems.len(), 2);
+        assert!(matches!(problems[1], Problem::Message(..)));
+        let mut errors = ProblemList::default();
+        errors.push(Problem::Message("error".to_owned()));
+        let problems = checker.add_pre_link_hook_problem(&link_info(&["bad.o"]), errors);
+        assert_eq!(problems.len(), 1);
+    }
+
+    #[test]
+    fn subprocess_version_mismatch() {
+        let mut checker = checker_for_testing();
+        let crate_sel = CrateSel::Primary(crate::crate_index::testing::pkg_id("foo"));
+        let mut request = Some(rpc::Request::RustcStarted(rpc::RustcStartInfo {
+            crate_sel,
+            cackle_version: rpc::CACKLE_VERSION.to_owned(),
+        }));
+        let mut check_state = CheckState::default();
+        checker.problems(&request, &mut check_state).unwrap();
+        assert_eq!(checker.subprocess_version_mismatch, None);
+
+        if let Some(rpc::Request::RustcStarted(info)) = &mut request {
+            info.cackle_version = "0.0.0-other".to_owned();
+        }
+        checker.problems(&request, &mut check_state).unwrap();
+        assert_eq!(
+            checker.subprocess_version_mismatch.as_deref(),
+            Some("0.0.0-other")
+        );
+    }
+
+    #[test]
+    fn rerun_recorded() {
+        let mut checker = checker_for_testing();
+        let rerun = rpc::RerunOutput {
+            crate_sel: CrateSel::BuildScript(crate::crate_index::testing::build_script_id("foo")),
+            command: "bwrap ... original-build-script".to_owned(),
+            exit_code: 1,
+            stdout: b"out".to_vec(),
+            stderr: b"err".to_vec(),
+        };
+        let problems = checker
+            .problems(
+                &Some(rpc::Request::RerunComplete(rerun.clone())),
+                &mut CheckState::default(),
+            )
+            .unwrap();
+        assert!(problems.is_empty());
+        assert_eq!(checker.reruns, vec![rerun]);
+    }
+
+    #[test]
+    fn unavailable_sandbox_is_build_script_failure() {
+        let mut checker = checker_for_testing();
+        checker.update_config(
+            parse(
+                r#"
+                [sandbox]
+                kind = "Bubblewrap"
+            "#,
+            )
+            .unwrap(),
+        );
+        checker.sandbox_errors.insert(
+            SandboxKind::Bubblewrap,
+            Some("Failed to run `bwrap`".to_owned()),
+        );
+        let build_script_id = crate::crate_index::testing::build_script_id("foo");
+        let problems =
+            checker.verify_sandbox_available(&build_script_id, Path::new("build-script-build"));
+        let problems: Vec<&Problem> = (&problems).into_iter().collect();
+        assert_eq!(problems.len(), 1);
+        let Problem::BuildScriptFailed(failure) = problems[0] else {
+            panic!("Unexpected problem: {}", problems[0]);
+        };
+        assert!(String::from_utf8_lossy(&failure.output.stderr).contains("bwrap"));
+        #[cfg(feature = "ui")]
+        {
+            let fixes: Vec<String> = crate::config_editor::fixes_for_problem(problems[0])
+                .iter()
+                .map(|edit| edit.title())
+                .collect();
+            assert_eq!(fixes.len(), 2, "{fixes:?}");
+        }
+    }
+
+    fn api_usages(crate_sel: &CrateSel, api: PermissionName) -> ApiUsages {
+        let usage = crate::problem::testing::api_usage(
+            "",
+            "foo::bar",
+            SourceLocation::new(Path::new("lib.rs"), 1, None),
+        );
+        crate::problem::testing::api_usages(crate_sel.clone(), BTreeMap::from([(api, vec![usage])]))
+    }
+
+    #[test]
+    fn reload_config() {
+        let config = parse(
+            r#"
+            [api.fs]
+            include = [
+                "std::fs",
+            ]
+            [pkg.foo]
+            allow_apis = [
+                "fs",
+            ]
+        "#,
+        )
+        .unwrap();
+        let mut checker = Checker {
+            crate_index: crate::crate_index::testing::index_with_package_names(&["foo"]),
+            ..checker_for_testing()
+        };
+        checker.update_config(config.clone());
+        let mut problems = ProblemList::default();
+
+        let crate_sel = CrateSel::Primary(crate::crate_index::testing::pkg_id("foo"));
+        let permissions = checker
+            .apis_for_name_iterator(["std", "fs", "read_to_string"].into_iter())
+            .into_owned();
+        assert_eq!(permissions.len(), 1);
+        assert_eq!(
+            permissions.iter().next().unwrap(),
+            &PermissionName::from("fs")
+        );
+        for api in permissions {
+            checker.permission_used(&api_usages(&crate_sel, api), &mut problems);
+        }
+
+        assert!(problems.is_empty());
+        assert!(checker.check_unused().is_empty());
+
+        // Now reload the config and make that we still don't report any unused configuration.
+        checker.update_config(config);
+        assert!(checker.check_unused().is_empty());
+    }
+
+    #[test]
+    fn allow_all_with_deny() {
+        let config = parse(
+            r#"
+            allow_all = ["clock"]
+
+            [api.clock]
+            include = ["std::time"]
+
+            [pkg.foo]
+            deny_apis = ["clock"]
+
+            [pkg.bar]
+            deny_apis = ["clock"]
+            allow_apis = ["clock"]
+        "#,
+        )
+        .unwrap();
+        let mut checker = Checker {
+            crate_index: crate::crate_index::testing::index_with_package_names(&[
+                "foo", "bar", "baz",
+            ]),
+            ..checker_for_testing()
+        };
+        checker.update_config(config);
+        let clock = PermissionName::from("clock");
+        let usage_problems = |checker: &mut Checker, pkg_name: &str| {
+            let crate_sel = CrateSel::Primary(crate::crate_index::testing::pkg_id(pkg_name));
+            let mut problems = ProblemList::default();
+            checker.permission_used(&api_usages(&crate_sel, clock.clone()), &mut problems);
+            problems
+        };
+
+        // `baz` isn't mentioned in the config, but is allowed via `allow_all`.
+        assert!(usage_problems(&mut checker, "baz").is_empty());
+        // `foo` denies the API, so gets an error.
+        assert_eq!(usage_problems(&mut checker, "foo").len(), 1);
+        // An explicit allow takes precedence over a deny.
+        assert!(usage_problems(&mut checker, "bar").is_empty());
+        assert!(checker.check_unused().is_empty());
+        assert!(!checker.is_allowed(&CrateName::from("baz"), &PermissionName::from("fs")));
+    }
+
+    #[test]
+    fn allow_all_composite_with_deny() {
+        let config = parse(
+            r#"
+            allow_all = ["time"]
+
+            [api.clock]
+            include = ["std::time::Instant"]
+
+            [api.time]
+            include = ["std::time::Duration"]
+            includes_perms = ["clock"]
+
+            [pkg.foo]
+            deny_apis = ["time"]
+        "#,
+        )
+        .unwrap();
+        let mut checker = checker_for_testing();
+        checker.update_config(config);
+        let clock = PermissionName::from("clock");
+        // Components of a composite API in `allow_all` are allowed for everyone...
+        assert!(checker.is_allowed(&CrateName::from("bar"), &clock));
+        // ... unless the composite is denied.
+        assert!(!checker.is_allowed(&CrateName::from("foo"), &clock));
+    }
+
+    #[test]
+    fn only_unsafe() {
+        let config = parse(
+            r#"
+            [api.fs]
+            include = ["std::fs"]
+
+            [pkg.foo]
+            allow_apis = ["fs"]
+        "#,
+        )
+        .unwrap();
+        let crate_sel = CrateSel::Primary(crate::crate_index::testing::pkg_id("foo"));
+        let link_request = Some(rpc::Request::LinkerInvoked(LinkInfo {
+            crate_sel: crate_sel.clone(),
+            object_paths: vec![],
+            output_file: PathBuf::from("/does/not/exist"),
+        }));
+        let unsafe_request = Some(rpc::Request::CrateUsesUnsafe(UnsafeUsage {
+            crate_sel,
+            locations: vec![],
+        }));
+
+        // With all checks enabled, we need to analyse the linked binary, which doesn't exist.
+        let mut checker = checker_for_testing();
+        checker.update_config(config.clone());
+        let mut check_state = CheckState::default();
+        assert!(checker.problems(&link_request, &mut check_state).is_err());
+
+        // When only checking unsafe, we skip the analysis that would find API usages, so don't
+        // report them or complain that the allowed API is unused.
+        let mut checker = Checker {
+            crate_index: crate::crate_index::testing::index_with_package_names(&["foo"]),
+            ..checker_with_args(Args {
+                only: vec![Category::Unsafe],
+                ..Args::default()
+            })
+        };
+        checker.update_config(config.clone());
+        let problems = checker.problems(&link_request, &mut check_state).unwrap();
+        assert!(!problems
+            .into_iter()
+            .any(|problem| matches!(problem, Problem::DisallowedApiUsage(..))));
+        assert!(checker.check_unused().is_empty());
+        assert_eq!(
+            checker
+                .problems(&unsafe_request, &mut check_state)
+                .unwrap()
+                .len(),
+            1
+        );
+
+        let mut checker = checker_with_args(Args {
+            only: vec![Category::ApiUsage],
+            ..Args::default()
+        });
+        checker.update_config(config);
+        assert!(checker
+            .problems(&unsafe_request, &mut check_state)
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn permission_queries() {
+        let config = parse(
+            r#"
+            [api.fs]
+            include = ["std::fs"]
+
+            [api.net]
+            include = ["std::net"]
+
+            [pkg.foo]
+            allow_apis = ["net", "fs"]
+        "#,
+        )
+        .unwrap();
+        let mut checker = checker_for_testing();
+        checker.update_config(config);
+        let foo = CrateName::from("foo");
+        let fs = PermissionName::from("fs");
+        let net = PermissionName::from("net");
+        assert!(checker.is_allowed(&foo, &fs));
+        assert!(checker.is_allowed(&foo, &net));
+        assert!(!checker.is_allowed(&foo, &PermissionName::from("process")));
+        assert_eq!(checker.allowed_permissions(&foo), vec![&fs, &net]);
+        assert!(checker.used_permissions(&foo).is_empty());
+
+        let mut problems = ProblemList::default();
+        let crate_sel = CrateSel::Primary(crate::crate_index::testing::pkg_id("foo"));
+        checker.permission_used(&api_usages(&crate_sel, fs.clone()), &mut problems);
+        assert!(problems.is_empty());
+        assert_eq!(checker.used_permissions(&foo), vec![&fs]);
+
+        // Crates not mentioned in the config aren't allowed anything.
+        let bar = CrateName::from("bar");
+        assert!(!checker.is_allowed(&bar, &fs));
+        assert!(checker.allowed_permissions(&bar).is_empty());
+        assert!(checker.used_permissions(&bar).is_empty());
+    }
+
+    #[test]
+    fn permission_queries_with_allow_all() {
+        let config = parse(
+            r#"
+            allow_all = ["fs", "net"]
+
+            [api.fs]
+            include = ["std::fs"]
+
+            [api.net]
+            include = ["std::net"]
+
+            [api.process]
+            include = ["std::process"]
+
+            [pkg.foo]
+            allow_apis = ["process"]
+            deny_apis = ["net"]
+        "#,
+        )
+        .unwrap();
+        let mut checker = checker_for_testing();
+        checker.update_config(config);
+        let foo = CrateName::from("foo");
+        let bar = CrateName::from("bar");
+        let fs = PermissionName::from("fs");
+        let net = PermissionName::from("net");
+        let process = PermissionName::from("process");
+        assert_eq!(checker.allowed_permissions(&foo), vec![&fs, &process]);
+        assert_eq!(checker.allowed_permissions(&bar), vec![&fs, &net]);
+
+        let mut problems = ProblemList::default();
+        let bar_sel = CrateSel::Primary(crate::crate_index::testing::pkg_id("bar"));
+        checker.permission_used(&api_usages(&bar_sel, net.clone()), &mut problems);
+        assert!(problems.is_empty());
+        assert_eq!(checker.used_permissions(&bar), vec![&net]);
+        assert!(checker.used_permissions(&foo).is_empty());
+    }
+
+    #[test]
+    fn unknown_locations() {
+        // Code without debug info is attributed to the package whose rlib it's in, by way of the
+        // deps file that rustc writes alongside the rlib.
+        let dir = tempfile::tempdir().unwrap();
+        let dir = dir.path().canonicalize().unwrap();
+        let pkg_dir = dir.join("foo");
+        let deps_dir = dir.join("target").join("deps");
+        std::fs::create_dir_all(pkg_dir.join("src")).unwrap();
+        std::fs::create_dir_all(&deps_dir).unwrap();
+        std::fs::write(pkg_dir.join("src").join("lib.rs"), "").unwrap();
+        std::fs::write(
+            deps_dir.join("foo-1234.d"),
+            format!("{}:\n", pkg_dir.join("src").join("lib.rs").display()),
+        )
+        .unwrap();
+        let mut checker = checker_for_testing();
+        checker.crate_index = crate::crate_index::testing::index_with_package_dir("foo", &pkg_dir);
+        let foo = CrateSel::Primary(crate::crate_index::testing::pkg_id("foo"));
+        let object_path = deps_dir.join("libfoo-1234.rlib");
+        assert_eq!(
+            checker
+                .opt_crate_names_from_source_path(&object_path)
+                .unwrap()
+                .as_ref(),
+            std::slice::from_ref(&foo)
+        );
+
+        let fs = PermissionName::from("fs");
+        let mut api_usage = api_usages(&foo, fs.clone());
+        let mut unknown_usage = api_usage.first_usage().unwrap().clone();
+        unknown_usage.source_location = SourceLocation::unknown(object_path.as_path());
+        api_usage.usages.get_mut(&fs).unwrap().push(unknown_usage);
+
+        let config = r#"
+            [api.fs]
+            include = ["foo::bar"]
+            "#;
+        checker.update_config(parse(config).unwrap());
+        let mut problems = ProblemList::default();
+        checker.permission_used(&api_usage, &mut problems);
+        let problems: Vec<&Problem> = (&problems).into_iter().collect();
+        let [Problem::DisallowedApiUsage(usage)] = problems.as_slice() else {
+            panic!("Unexpected problems: {problems:?}");
+        };
+        assert_eq!(usage.usages[&fs].len(), 2);
+        // Unknown locations aren't given to tools that expect to find a position in a file.
+        assert_eq!(problems[0].source_locations().len(), 1);
+
+        // Allowing unknown locations only stops those usages from being reported.
+        checker.update_config(
+            parse(&format!(
+                "{config}\n[pkg.foo]\nallow_unknown_locations = true\n"
+            ))
+            .unwrap(),
+        );
+        let mut problems = ProblemList::default();
+        checker.permission_used(&api_usage, &mut problems);
+        let problems: Vec<&Problem> = (&problems).into_iter().collect();
+        let [Problem::DisallowedApiUsage(usage)] = problems.as_slice() else {
+            panic!("Unexpected problems: {problems:?}");
+        };
+        assert_eq!(usage.usages[&fs].len(), 1);
+        assert!(!usage.usages[&fs][0].source_location.is_unknown());
+
+        api_usage.usages.get_mut(&fs).unwrap().remove(0);
+        let mut problems = ProblemList::default();
+        checker.permission_used(&api_usage, &mut problems);
+        assert!(problems.is_empty());
+    }
+
+    #[test]
+    fn inline_suppressions() {
+        let dir = tempfile::tempdir().unwrap();
+        let source_path = dir.path().join("lib.rs");
+        std::fs::write(
+            &source_path,
+            indoc::indoc! {r#"
+                fn connect() {
+                    // cackle:allow(net)
+                    std::net::TcpStream::connect("127.0.0.1:80");
+                }
+
+                fn read() {
+                    // cackle:allow(fs)
+                    foo();
+                }
+            "#},
+        )
+        .unwrap();
+        let mut checker = Checker {
+            crate_index: crate::crate_index::testing::index_with_package_names(&["foo"]),
+            ..checker_for_testing()
+        };
+        checker
+            .record_crate_paths(&rpc::RustcOutput {
+                crate_sel: CrateSel::Primary(crate::crate_index::testing::pkg_id("foo")),
+                source_paths: vec![source_path.clone()],
+            })
+            .unwrap();
+        let net = PermissionName::from("net");
+        let suppression = checker
+            .suppression_for(
+                &SourceLocation::new(source_path.as_path(), 3, Some(5)),
+                &net,
+            )
+            .cloned()
+            .unwrap();
+        assert_eq!(suppression.location.line(), 2);
+        assert!(checker
+            .suppression_for(
+                &SourceLocation::new(source_path.as_path(), 8, Some(5)),
+                &net
+            )
+            .is_none());
+        checker.suppressions_used(&[suppression]);
+
+        // The `fs` annotation didn't suppress anything, so should be reported.
+        let problems = checker.check_unused();
+        assert_eq!(problems.len(), 1);
+        let Problem::UnusedSuppression(unused) = &problems[0] else {
+            panic!("Unexpected problem {:?}", problems[0]);
+        };
+        assert_eq!(unused.suppression.permission, PermissionName::from("fs"));
+        assert_eq!(unused.suppression.location.line(), 7);
+        assert_eq!(problems[0].severity(), Severity::Warning);
+    }
+
+    #[test]
+    fn inline_suppressions_in_dependencies_ignored() {
+        let dir = tempfile::tempdir().unwrap();
+        let source_path = dir.path().join("lib.rs");
+        std::fs::write(
+            &source_path,
+            indoc::indoc! {r#"
+                fn connect() {
+                    // cackle:allow(net)
+                    std::net::TcpStream::connect("127.0.0.1:80");
+                }
+            "#},
+        )
+        .unwrap();
+        let mut checker = Checker {
+            crate_index: crate::crate_index::testing::index_with_registry_packages(&[], &["dep"]),
+            ..checker_for_testing()
+        };
+        checker
+            .record_crate_paths(&rpc::RustcOutput {
+                crate_sel: CrateSel::Primary(crate::crate_index::testing::pkg_id("dep")),
+                source_paths: vec![source_path.clone()],
+            })
+            .unwrap();
+        assert!(checker
+            .suppression_for(
+                &SourceLocation::new(source_path.as_path(), 3, Some(5)),
+                &PermissionName::from("net")
+            )
+            .is_none());
+        assert!(checker.check_unused().is_empty());
+    }
+
+    #[test]
+    fn exports_text() {
+        let mut checker = checker_for_testing();
+        let foo = crate::crate_index::testing::pkg_id("foo");
+        checker.record_export(&foo, "foo::net".to_owned());
+        checker.record_export(&foo, "foo::fs".to_owned());
+        checker.record_export(&foo, "foo::net".to_owned());
+        assert_eq!(checker.exports_text("foo"), "foo::fs\nfoo::net\n");
+        assert_eq!(
+            checker.exports_text("bar"),
+            "No exports found for package `bar`\n"
+        );
+    }
+
+    #[test]
+    fn ignored_packages_have_no_problems() {
+        let mut checker = checker_for_testing();
+        checker
+            .ignored_packages
+            .insert(crate::crate_index::testing::pkg_id("dev_only"));
+        let mut check_state = CheckState::default();
+        let mut problems_for = |name| {
+            let request = Some(rpc::Request::CrateUsesUnsafe(rpc::UnsafeUsage {
+                crate_sel: CrateSel::Primary(crate::crate_index::testing::pkg_id(name)),
+                locations: vec![SourceLocation::new(Path::new("lib.rs"), 1, None)],
+            }));
+            checker.problems(&request, &mut check_state).unwrap()
+        };
+        assert_eq!(problems_for("foo").len(), 1);
+        assert!(problems_for("dev_only").is_empty());
+    }
+
+    #[test]
+    fn selected_package() {
+        let mut checker = checker_with_args(Args {
+            package: Some("foo".to_owned()),
+            ..Args::default()
+        });
+        checker.update_config(
+            parse(
+                r#"
+                [api.net]
+                include = ["std::net"]
+
+                [pkg.foo]
+                allow_apis = ["net"]
+
+                [pkg.bar]
+                allow_apis = ["net"]
+                "#,
+            )
+            .unwrap(),
+        );
+        let mut check_state = CheckState::default();
+        let mut problems_for = |name| {
+            let request = Some(rpc::Request::CrateUsesUnsafe(rpc::UnsafeUsage {
+                crate_sel: CrateSel::Primary(crate::crate_index::testing::pkg_id(name)),
+                locations: vec![SourceLocation::new(Path::new("lib.rs"), 1, None)],
+            }));
+            checker.problems(&request, &mut check_state).unwrap()
+        };
+        assert_eq!(problems_for("foo").len(), 1);
+        assert!(problems_for("bar").is_empty());
+
+        // `bar` might use `net` from a binary that we didn't build, so we only report `foo`.
+        let problems = checker.check_unused();
+        let unused_allow_apis: Vec<&CrateName> = (&problems)
+            .into_iter()
+            .filter_map(|problem| match problem {
+                Problem::UnusedAllowApi(info) => Some(&info.crate_name),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(unused_allow_apis, vec![&CrateName::from("foo")]);
+    }
+
+    #[test]
+    fn unused_excludes() {
+        let mut checker = checker_for_testing();
+        checker.update_config(
+            parse(
+                r#"
+                import_std = ["process"]
+
+                [api.env]
+                include = ["std::env"]
+                exclude = ["std::env::var", "std::env::args"]
+
+                [api.spawn]
+                include = ["std::*::spawn"]
+                exclude = ["std::process::**::spawn", "std::thread::**::spawn"]
+                "#,
+            )
+            .unwrap(),
+        );
+        for path in [
+            "std::env::var::inner",
+            "std::env::home_dir",
+            "std::thread::spawn",
+        ] {
+            checker.apis_for_name_iterator(path.split("::"));
+        }
+        let problems = checker.check_unused();
+        let unused: Vec<String> = (&problems)
+            .into_iter()
+            .map(|problem| match problem {
+                Problem::UnusedExclude(info) => format!("{}: {}", info.api, info.path),
+                other => panic!("Unexpected problem {other:?}"),
+            })
+            .collect();
+        // Excludes from built-in APIs, such as `std::process::exit` from `process`, aren't
+        // reported.
+        assert_eq!(
+            unused,
+            vec!["env: std::env::args", "spawn: std::process::**::spawn"]
+        );
+
+        checker.record_used_excludes();
+        checker.apis_for_name_iterator("std::env::args".split("::"));
+        checker.apis_for_name_iterator("std::process::spawn".split("::"));
         assert!(checker.check_unused().is_empty());
     }
 }
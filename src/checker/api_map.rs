@@ -15,22 +15,30 @@ use fxhash::FxHashSet;
 #[derive(Default)]
 pub(super) struct ApiMap {
     apis: FxHashSet<PermissionName>,
+    /// For each API that was excluded from this node, the index of each `exclude` entry
+    /// responsible.
+    excluded_by: Vec<(PermissionName, usize)>,
     map: FxHashMap<String, Box<ApiMap>>,
 }
 
 impl ApiMap {
-    /// Returns the permissions for the path produced by `key_it`. The permissions are those on
-    /// whatever node we reach when either `key_it` ends or we have no child node for the next value
-    /// it produces. i.e. it's the deepest node that is a prefix of the name produced by `key_it`.
-    pub(super) fn get<'a>(
-        &self,
-        mut key_it: impl Iterator<Item = &'a str>,
-    ) -> &FxHashSet<PermissionName> {
+    /// Returns the node for the path produced by `key_it`. This is whatever node we reach when
+    /// either `key_it` ends or we have no child node for the next value it produces. i.e. it's the
+    /// deepest node that is a prefix of the name produced by `key_it`.
+    pub(super) fn get_node<'a>(&self, mut key_it: impl Iterator<Item = &'a str>) -> &ApiMap {
         key_it
             .next()
             .and_then(|key| self.map.get(key))
-            .map(|sub| sub.get(key_it))
-            .unwrap_or(&self.apis) as _
+            .map(|sub| sub.get_node(key_it))
+            .unwrap_or(self)
+    }
+
+    pub(super) fn apis(&self) -> &FxHashSet<PermissionName> {
+        &self.apis
+    }
+
+    pub(super) fn excluded_by(&self) -> &[(PermissionName, usize)] {
+        &self.excluded_by
     }
 
     /// Creates nodes to represent the name produced by `key_it`. This should be called for all path
@@ -68,8 +76,119 @@ impl ApiMap {
         }
     }
 
+    /// Removes `permission` from this node and all child nodes. On each node that had
+    /// `permission`, either until now or until an earlier exclude removed it, `exclude_index` is
+    /// recorded as having excluded it.
+    pub(super) fn exclude_from_subtree(
+        &mut self,
+        permission: &PermissionName,
+        exclude_index: usize,
+    ) {
+        if self.apis.remove(permission)
+            || self
+                .excluded_by
+                .iter()
+                .any(|(excluded, _)| excluded == permission)
+        {
+            self.excluded_by.push((permission.clone(), exclude_index));
+        }
+        for subtree in self.map.values_mut() {
+            subtree.exclude_from_subtree(permission, exclude_index);
+        }
+    }
+
+    /// Adds `permission` to this node and all child nodes, recording `exclude_index` as the reason.
+    /// Used when the map holds excluded rather than included permissions.
+    pub(super) fn add_exclusion_to_subtree(
+        &mut self,
+        permission: &PermissionName,
+        exclude_index: usize,
+    ) {
+        self.apis.insert(permission.clone());
+        self.excluded_by.push((permission.clone(), exclude_index));
+        for subtree in self.map.values_mut() {
+            subtree.add_exclusion_to_subtree(permission, exclude_index);
+        }
+    }
+
     pub(crate) fn clear(&mut self) {
         self.apis.clear();
+        self.excluded_by.clear();
         self.map.clear();
     }
 }
+
+/// An include or exclude path containing `*` or `**` segments. These can't be stored in an
+/// `ApiMap`, so are matched separately.
+pub(super) struct GlobPath {
+    parts: Vec<GlobPart>,
+    pub(super) permission: PermissionName,
+}
+
+enum GlobPart {
+    Literal(String),
+    /// `*`, which matches exactly one segment.
+    AnySegment,
+    /// `**`, which matches any number of segments, including none.
+    AnySegments,
+}
+
+impl GlobPath {
+    pub(super) fn new<'a>(
+        parts: impl Iterator<Item = &'a str>,
+        permission: PermissionName,
+    ) -> Self {
+        let parts = parts
+            .map(|part| match part {
+                "*" => GlobPart::AnySegment,
+                "**" => GlobPart::AnySegments,
+                _ => GlobPart::Literal(part.to_owned()),
+            })
+            .collect();
+        Self { parts, permission }
+    }
+
+    /// Returns whether this glob matches some prefix of `path`, in keeping with how paths without
+    /// globs match everything under them.
+    pub(super) fn matches_prefix_of(&self, path: &[&str]) -> bool {
+        fn matches(glob: &[GlobPart], path: &[&str]) -> bool {
+            let Some((first, rest)) = glob.split_first() else {
+                return true;
+            };
+            match first {
+                GlobPart::Literal(literal) => {
+                    path.first() == Some(&literal.as_str()) && matches(rest, &path[1..])
+                }
+                GlobPart::AnySegment => !path.is_empty() && matches(rest, &path[1..]),
+                GlobPart::AnySegments => (0..=path.len()).any(|skip| matches(rest, &path[skip..])),
+            }
+        }
+        matches(&self.parts, path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn glob_matches(glob: &str, path: &str) -> bool {
+        GlobPath::new(glob.split("::"), PermissionName::from("x"))
+            .matches_prefix_of(&path.split("::").collect::<Vec<_>>())
+    }
+
+    #[test]
+    fn glob_matching() {
+        assert!(glob_matches("std::*::spawn", "std::thread::spawn"));
+        assert!(glob_matches("std::*::spawn", "std::thread::spawn::closure"));
+        assert!(!glob_matches("std::*::spawn", "std::spawn"));
+        assert!(!glob_matches("std::*::spawn", "std::a::b::spawn"));
+        assert!(glob_matches("tokio::net::**", "tokio::net"));
+        assert!(glob_matches("tokio::net::**", "tokio::net::tcp::TcpStream"));
+        assert!(glob_matches("tokio::**::connect", "tokio::connect"));
+        assert!(glob_matches(
+            "tokio::**::connect",
+            "tokio::net::tcp::connect"
+        ));
+        assert!(!glob_matches("tokio::**::connect", "tokio::net::tcp::bind"));
+    }
+}
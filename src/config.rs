@@ -1,9 +1,12 @@
 use crate::crate_index::BuildScriptId;
 use crate::crate_index::CrateIndex;
+use crate::crate_index::CrateSel;
 use crate::crate_index::PackageId;
 use crate::problem::AvailableApi;
 use crate::problem::Problem;
 use crate::problem::ProblemList;
+use crate::problem::Severity;
+use crate::proxy::target::BuildTargets;
 use anyhow::anyhow;
 use anyhow::bail;
 use anyhow::Context;
@@ -11,10 +14,12 @@ use anyhow::Result;
 use serde::Deserialize;
 use serde::Serialize;
 use std::collections::BTreeMap;
+use std::collections::BTreeSet;
 use std::fmt::Display;
 use std::path::Path;
 use std::path::PathBuf;
 use std::sync::Arc;
+use std::time::Duration;
 
 pub(crate) mod built_in;
 
@@ -23,6 +28,12 @@ pub(crate) const MAX_VERSION: i64 = 1;
 #[derive(Deserialize, Serialize, Debug, Default, Clone, PartialEq, Eq)]
 #[serde(deny_unknown_fields)]
 pub(crate) struct Config {
+    /// Other config files whose API definitions and package permissions should be merged into this
+    /// one. Paths are relative to the directory containing the file that imports them. Emptied once
+    /// the imports have been loaded.
+    #[serde(default, alias = "extends", skip_serializing_if = "Vec::is_empty")]
+    pub(crate) import: Vec<String>,
+
     pub(crate) common: CommonConfig,
 
     #[serde(default, rename = "api")]
@@ -37,9 +48,10 @@ pub(crate) struct Config {
 
 /// Selects either the primary crate of a package or the build script of a crate. In the latter
 /// case, the format of the name is for example "foo.build" where foo is the name of the primary
-/// package. This struct is like CrateSel, but without any version information. We use this when
-/// we're referring to the cackle configuration, since we don't distinguish between different
-/// versions of crates when granting permissions.
+/// package. This struct is like CrateSel, but usually without any version information. We use this
+/// when we're referring to the cackle configuration, where permissions are normally granted to all
+/// versions of a package. A name can be qualified with a version, e.g. "foo@1.2.3", for config that
+/// only applies to that version.
 #[derive(Debug, Hash, PartialEq, Eq, Clone, Serialize, Deserialize, PartialOrd, Ord)]
 #[serde(transparent)]
 pub(crate) struct CrateName(pub(crate) Arc<str>);
@@ -55,11 +67,76 @@ pub(crate) struct CommonConfig {
     #[serde(default)]
     pub(crate) build_flags: Option<Vec<String>>,
 
-    #[serde(default)]
+    /// Names of APIs from the built-in catalog to use. `use_builtin_perms` is accepted as an
+    /// alternative name.
+    #[serde(default, alias = "use_builtin_perms")]
     pub(crate) import_std: Vec<String>,
 
     #[serde(default)]
     pub(crate) features: Vec<String>,
+
+    /// Path prefixes that build scripts may pass to `cargo:rustc-link-search`. A prefix of
+    /// `$OUT_DIR` refers to the build script's output directory.
+    #[serde(default)]
+    pub(crate) allow_link_search: Vec<String>,
+
+    /// If true, only API usages in code that is reachable from the binary's entry points are
+    /// reported.
+    #[serde(default)]
+    pub(crate) reachable_code_only: bool,
+
+    /// Whether every package that produces problems must have an explicit entry in the config.
+    #[serde(default)]
+    pub(crate) require_explicit_decisions: bool,
+
+    /// SPDX license identifiers that dependencies may use. If empty, licenses aren't checked.
+    #[serde(default)]
+    pub(crate) allowed_licenses: Vec<String>,
+
+    /// Whether dependencies without a license are permitted. Defaults to true.
+    #[serde(default)]
+    pub(crate) allow_unknown_license: Option<bool>,
+
+    /// A command to run before allowing a link to complete, e.g. to verify the provenance of the
+    /// objects being linked. It's passed the paths of the objects as arguments and is only run if
+    /// the link has no other problems. Relative paths are relative to the directory containing the
+    /// config file.
+    #[serde(default)]
+    pub(crate) pre_link_hook: Option<PathBuf>,
+
+    /// Whether to warn about dependencies whose version has been yanked from crates.io, according
+    /// to cargo's local cache of the index.
+    #[serde(default)]
+    pub(crate) check_yanked: bool,
+
+    /// Permissions that must never be granted by automatically applied edits. Besides API names,
+    /// `unsafe`, `proc_macro` and `shell` may be listed.
+    #[serde(default)]
+    pub(crate) never_auto_grant: Vec<PermissionName>,
+
+    /// APIs that all crates are permitted to use, unless they list them in `deny_apis`.
+    #[serde(default)]
+    pub(crate) allow_all: Vec<PermissionName>,
+
+    /// Glob patterns for source files that shouldn't be analysed, e.g. vendored or generated code.
+    /// Relative patterns are relative to the workspace root.
+    #[serde(default)]
+    pub(crate) ignore_paths: Vec<String>,
+
+    /// Whether to skip analysis of packages that are only in the dependency tree as
+    /// dev-dependencies.
+    #[serde(default)]
+    pub(crate) ignore_dev_dependencies: bool,
+
+    /// Whether to skip analysis of packages that are only in the dependency tree as
+    /// build-dependencies.
+    #[serde(default)]
+    pub(crate) ignore_build_dependencies: bool,
+
+    /// The linker to run once a link has been checked. If not set, we use whatever rustc would have
+    /// used.
+    #[serde(default)]
+    pub(crate) real_linker: Option<String>,
 }
 
 #[derive(Deserialize, Serialize, Debug, Default, Clone, PartialEq, Eq, Hash)]
@@ -71,7 +148,37 @@ pub(crate) struct SandboxConfig {
     #[serde(default)]
     pub(crate) extra_args: Vec<String>,
 
+    /// Paths outside the usual set that build scripts may read, e.g. a shared directory of schemas.
+    #[serde(default)]
+    pub(crate) extra_read_only_paths: Vec<PathBuf>,
+
+    /// Paths outside the usual set that build scripts may read and write.
+    #[serde(default)]
+    pub(crate) extra_read_write_paths: Vec<PathBuf>,
+
     pub(crate) allow_network: Option<bool>,
+
+    /// How long a build script may run before it's killed. 0 means no limit. Defaults to
+    /// `DEFAULT_BUILD_SCRIPT_TIMEOUT_SECS` for sandboxed build scripts and no limit otherwise.
+    pub(crate) timeout_secs: Option<u64>,
+}
+
+/// Some build scripts compile large C or C++ libraries, which can take several minutes, so this is
+/// intended to be long enough to not get in the way of those, while still stopping a hung build
+/// script from blocking a build indefinitely.
+const DEFAULT_BUILD_SCRIPT_TIMEOUT_SECS: u64 = 300;
+
+impl SandboxConfig {
+    /// Returns how long a build script may run for, or None if there's no limit.
+    pub(crate) fn timeout(&self) -> Option<Duration> {
+        match self
+            .timeout_secs
+            .unwrap_or(DEFAULT_BUILD_SCRIPT_TIMEOUT_SECS)
+        {
+            0 => None,
+            secs => Some(Duration::from_secs(secs)),
+        }
+    }
 }
 
 #[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq, Default, Hash)]
@@ -85,6 +192,45 @@ pub(crate) struct PermConfig {
 
     #[serde(default)]
     pub(crate) no_auto_detect: Vec<CrateName>,
+
+    /// Other permissions whose include and exclude lists should be merged into this permission.
+    #[serde(default)]
+    pub(crate) includes_perms: Vec<PermissionName>,
+
+    /// A human-readable description of what this API permits. Shown when reviewing whether to
+    /// allow a package to use the API.
+    #[serde(default)]
+    pub(crate) description: Option<String>,
+
+    /// How serious it is for a package to use this API without being permitted to. Setting this to
+    /// "warning" lets you see which packages use an API before you start enforcing it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) severity: Option<Severity>,
+
+    /// The maximum number of usages of this API to list for each problem. Overrides
+    /// --usage-report-cap. -1 means no limit.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) report_cap: Option<i64>,
+
+    /// Whether only usages at build time, i.e. from build scripts and proc macros, should be
+    /// reported. Defaults to false, except for the built-in `nondeterministic` API.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) build_time_only: Option<bool>,
+
+    /// Additional paths that are only part of this API when building for particular targets.
+    /// Merged into `include` and `exclude` once we know what we're building for.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub(crate) target: BTreeMap<String, TargetPermConfig>,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq, Default, Hash)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct TargetPermConfig {
+    #[serde(default)]
+    pub(crate) include: Vec<ApiPath>,
+
+    #[serde(default)]
+    pub(crate) exclude: Vec<ApiPath>,
 }
 
 #[derive(Deserialize, Serialize, Debug, Hash, PartialEq, Eq, PartialOrd, Ord, Clone)]
@@ -106,9 +252,14 @@ pub(crate) enum SandboxKind {
     Inherit,
     Disabled,
     Bubblewrap,
+    Landlock,
 }
 
-pub(crate) const SANDBOX_KINDS: &[SandboxKind] = &[SandboxKind::Disabled, SandboxKind::Bubblewrap];
+pub(crate) const SANDBOX_KINDS: &[SandboxKind] = &[
+    SandboxKind::Disabled,
+    SandboxKind::Bubblewrap,
+    SandboxKind::Landlock,
+];
 
 #[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq, Default)]
 #[serde(deny_unknown_fields)]
@@ -116,15 +267,47 @@ pub(crate) struct PackageConfig {
     #[serde(default)]
     pub(crate) allow_unsafe: bool,
 
+    /// Modules in which unsafe code is permitted, e.g. `mycrate::ffi`. Unsafe code elsewhere in the
+    /// crate is still reported.
+    #[serde(default)]
+    pub(crate) allow_unsafe_in: Vec<String>,
+
     #[serde(default)]
     pub(crate) allow_build_instructions: Vec<String>,
 
     #[serde(default)]
     pub(crate) allow_apis: Vec<PermissionName>,
 
+    /// Paths of individual functions that this crate may use, even though they're part of an API
+    /// that it isn't allowed to use, e.g. `std::fs::metadata`.
+    #[serde(default)]
+    pub(crate) allow_symbols: Vec<String>,
+
+    /// Environment variables that this crate may read, even though it isn't allowed the API that
+    /// covers reading them, e.g. `HOME`.
+    #[serde(default)]
+    pub(crate) allow_env_vars: Vec<String>,
+
+    /// APIs from `common.allow_all` that this crate isn't permitted to use.
+    #[serde(default)]
+    pub(crate) deny_apis: Vec<PermissionName>,
+
     #[serde(default)]
     pub(crate) allow_proc_macro: bool,
 
+    #[serde(default)]
+    pub(crate) allow_shell: bool,
+
+    /// Don't report API usages that we can't attribute to a source location, e.g. from C code
+    /// without debug info. Usages with a known location are still reported.
+    #[serde(default)]
+    pub(crate) allow_unknown_locations: bool,
+
+    /// Don't warn if the version of this package that we're using has been yanked from crates.io.
+    /// Only relevant if `common.check_yanked` is set.
+    #[serde(default)]
+    pub(crate) allow_yanked: bool,
+
     /// Configuration for this crate's build.rs. Only used during parsing, after
     /// which it's flattened out.
     build: Option<Box<PackageConfig>>,
@@ -134,17 +317,204 @@ pub(crate) struct PackageConfig {
 
     #[serde(default)]
     pub(crate) import: Option<Vec<String>>,
+
+    /// Permissions that only apply when building for particular targets. Keys are either target
+    /// triples or `cfg(...)` expressions, like cargo's `[target]` tables.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub(crate) target: BTreeMap<String, TargetPackageConfig>,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq, Default)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct TargetPackageConfig {
+    #[serde(default)]
+    pub(crate) allow_apis: Vec<PermissionName>,
 }
 
+/// The key that, when set to true in a table of a config file passed as an override, causes the
+/// table to replace the corresponding table from earlier files rather than being merged into it.
+const OVERRIDE_KEY: &str = "override";
+
 pub(crate) fn parse_file(cackle_path: &Path, crate_index: &CrateIndex) -> Result<Arc<Config>> {
+    parse_files(cackle_path, &[], crate_index)
+}
+
+/// Parses `cackle_path`, then merges each of `overrides` into it in turn, so later files take
+/// precedence over earlier ones. See `merge_toml` for how the files are merged.
+pub(crate) fn parse_files(
+    cackle_path: &Path,
+    overrides: &[PathBuf],
+    crate_index: &CrateIndex,
+) -> Result<Arc<Config>> {
+    let mut config = if overrides.is_empty() {
+        parse_file_and_imports(cackle_path, &mut Vec::new())?
+    } else {
+        let mut merged = toml::Table::new();
+        for path in std::iter::once(cackle_path).chain(overrides.iter().map(PathBuf::as_path)) {
+            merge_toml(&mut merged, read_toml_table(path)?);
+        }
+        let mut config: Config = toml::Value::Table(merged)
+            .try_into()
+            .with_context(|| format!("Failed to parse {} with overrides", cackle_path.display()))?;
+        merge_built_ins(&mut config)?;
+        flatten(&mut config);
+        load_file_imports(&mut config, cackle_path, &mut Vec::new())?;
+        config
+    };
+    config.load_imports(crate_index)?;
+    crate::config_validation::validate(&config, cackle_path)?;
+    Ok(Arc::new(config))
+}
+
+/// Reads `path` as a TOML table. Any imports are made absolute, since once merged with other files,
+/// we'd no longer know which file's directory they're relative to.
+fn read_toml_table(path: &Path) -> Result<toml::Table> {
+    let contents = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to open {}", path.display()))?;
+    let mut table: toml::Table =
+        toml::from_str(&contents).with_context(|| format!("Failed to parse {}", path.display()))?;
+    let canonical_path = path
+        .canonicalize()
+        .with_context(|| format!("Failed to canonicalize {}", path.display()))?;
+    let dir = canonical_path.parent().unwrap_or(Path::new(""));
+    for key in ["import", "extends"] {
+        if let Some(toml::Value::Array(imports)) = table.get_mut(key) {
+            for import in imports {
+                if let toml::Value::String(import) = import {
+                    *import = dir.join(&*import).display().to_string();
+                }
+            }
+        }
+    }
+    Ok(table)
+}
+
+/// Merges `overlay` into `base`. Tables are merged recursively, arrays are appended to and other
+/// values are replaced. A table in `overlay` that sets `override = true` instead replaces the
+/// corresponding table in `base`.
+///
+/// This differs from `import`, where a local entry replaces an imported one wholesale. An imported
+/// file is a shared base that each workspace adapts, whereas files passed on the command line
+/// typically add a few permissions for a particular environment, which would be tedious if every
+/// table had to be repeated in full. `override = true` gives the `import` behaviour where needed.
+fn merge_toml(base: &mut toml::Table, overlay: toml::Table) {
+    for (key, mut value) in overlay {
+        let merged = match (base.get_mut(&key), &mut value) {
+            (Some(toml::Value::Table(base_table)), toml::Value::Table(table))
+                if table.get(OVERRIDE_KEY) != Some(&toml::Value::Boolean(true)) =>
+            {
+                merge_toml(base_table, std::mem::take(table));
+                true
+            }
+            (Some(toml::Value::Array(base_array)), toml::Value::Array(array)) => {
+                base_array.append(array);
+                true
+            }
+            _ => false,
+        };
+        if !merged {
+            remove_override_markers(&mut value);
+            base.insert(key, value);
+        }
+    }
+}
+
+/// Returns the tables that are replaced by setting `override = true` in one of `overrides`,
+/// together with the file that replaces them. Each table is identified by its path of keys, e.g.
+/// `["pkg", "foo"]`. Edits made to these tables in the main config file would have no effect.
+pub(crate) fn overridden_tables(overrides: &[PathBuf]) -> Result<Vec<(Vec<String>, PathBuf)>> {
+    let mut tables = Vec::new();
+    for path in overrides {
+        add_overridden_tables(&read_toml_table(path)?, &mut Vec::new(), path, &mut tables);
+    }
+    Ok(tables)
+}
+
+fn add_overridden_tables(
+    table: &toml::Table,
+    keys: &mut Vec<String>,
+    path: &Path,
+    out: &mut Vec<(Vec<String>, PathBuf)>,
+) {
+    if table.get(OVERRIDE_KEY) == Some(&toml::Value::Boolean(true)) {
+        out.push((keys.clone(), path.to_owned()));
+        return;
+    }
+    for (key, value) in table {
+        if let toml::Value::Table(child) = value {
+            keys.push(key.clone());
+            add_overridden_tables(child, keys, path, out);
+            keys.pop();
+        }
+    }
+}
+
+fn remove_override_markers(value: &mut toml::Value) {
+    if let toml::Value::Table(table) = value {
+        table.remove(OVERRIDE_KEY);
+        for (_, value) in table.iter_mut() {
+            remove_override_markers(value);
+        }
+    }
+}
+
+/// Parses `cackle_path` and merges in the config files that it imports, recursively.
+fn parse_file_and_imports(cackle_path: &Path, import_stack: &mut Vec<PathBuf>) -> Result<Config> {
     let cackle: String = std::fs::read_to_string(cackle_path)
         .with_context(|| format!("Failed to open {}", cackle_path.display()))?;
 
     let mut config =
         parse(&cackle).with_context(|| format!("Failed to parse {}", cackle_path.display()))?;
-    config.load_imports(crate_index)?;
-    crate::config_validation::validate(&config, cackle_path)?;
-    Ok(Arc::new(config))
+    load_file_imports(&mut config, cackle_path, import_stack)?;
+    Ok(config)
+}
+
+/// Merges into `config`, which was loaded from `cackle_path`, the config files that it imports,
+/// recursively. Entries in `config` take precedence over imported entries with the same name, then
+/// entries from earlier imports take precedence over those from later imports. `import_stack`
+/// contains the canonical paths of the files that are currently being imported, which lets us
+/// detect cycles.
+fn load_file_imports(
+    config: &mut Config,
+    cackle_path: &Path,
+    import_stack: &mut Vec<PathBuf>,
+) -> Result<()> {
+    let imports = std::mem::take(&mut config.import);
+    if imports.is_empty() {
+        return Ok(());
+    }
+    let canonical_path = cackle_path
+        .canonicalize()
+        .with_context(|| format!("Failed to canonicalize {}", cackle_path.display()))?;
+    let dir = canonical_path.parent().unwrap_or(Path::new(""));
+    import_stack.push(canonical_path.clone());
+    for import in imports {
+        let import_path = dir.join(&import);
+        let canonical_import = import_path.canonicalize().with_context(|| {
+            format!(
+                "Failed to find `{}` imported by {}",
+                import_path.display(),
+                cackle_path.display()
+            )
+        })?;
+        if let Some(index) = import_stack.iter().position(|p| p == &canonical_import) {
+            let cycle: Vec<String> = import_stack[index..]
+                .iter()
+                .chain(std::iter::once(&canonical_import))
+                .map(|path| path.display().to_string())
+                .collect();
+            bail!("Config files import each other: {}", cycle.join(" -> "));
+        }
+        let imported = parse_file_and_imports(&canonical_import, import_stack)?;
+        for (perm_name, perm_config) in imported.apis {
+            config.apis.entry(perm_name).or_insert(perm_config);
+        }
+        for (crate_name, pkg_config) in imported.packages {
+            config.packages.entry(crate_name).or_insert(pkg_config);
+        }
+    }
+    import_stack.pop();
+    Ok(())
 }
 
 fn parse(cackle: &str) -> Result<Config> {
@@ -163,10 +533,21 @@ fn merge_built_ins(config: &mut Config) -> Result<()> {
         let perm = PermissionName::new(imp.as_str());
         let built_in_api = built_ins
             .get(&perm)
-            .ok_or_else(|| anyhow!("Unknown API `{imp}` in import_std"))?;
-        let api = config.apis.entry(perm).or_insert_with(Default::default);
-        api.include.extend(built_in_api.include.iter().cloned());
+            .ok_or_else(|| anyhow!("Unknown built-in API `{imp}`"))?;
+        let api = config.apis.entry(perm).or_default();
+        // If the user explicitly excludes a path that the built-in API includes, the exclude would
+        // win anyway, so we drop the include rather than having it look like a conflict.
+        let include: Vec<ApiPath> = built_in_api
+            .include
+            .iter()
+            .filter(|path| !api.exclude.contains(path))
+            .cloned()
+            .collect();
+        api.include.extend(include);
         api.exclude.extend(built_in_api.exclude.iter().cloned());
+        if api.build_time_only.is_none() {
+            api.build_time_only = built_in_api.build_time_only;
+        }
     }
     Ok(())
 }
@@ -218,6 +599,97 @@ impl Config {
         Ok(())
     }
 
+    /// Returns the include and exclude paths for `perm_name`, including those of any permissions
+    /// that it references via `includes_perms`, recursively. Each exclude path is paired with the
+    /// permission whose definition contains it.
+    pub(crate) fn resolved_perm_paths(
+        &self,
+        perm_name: &PermissionName,
+    ) -> (Vec<&ApiPath>, Vec<(&PermissionName, &ApiPath)>) {
+        let mut include = Vec::new();
+        let mut exclude = Vec::new();
+        for name in self.perm_components(perm_name) {
+            let perm_config = &self.apis[name];
+            include.extend(perm_config.include.iter());
+            exclude.extend(perm_config.exclude.iter().map(|path| (name, path)));
+        }
+        (include, exclude)
+    }
+
+    /// Returns `perm_name` followed by the permissions that it references via `includes_perms`,
+    /// recursively. Permissions that aren't defined are omitted. Cycles are reported by config
+    /// validation, but we still guard against them here so that we never loop forever.
+    pub(crate) fn perm_components(&self, perm_name: &PermissionName) -> Vec<&PermissionName> {
+        let mut result = Vec::new();
+        let mut visited = BTreeSet::new();
+        let mut pending: Vec<&PermissionName> = self
+            .apis
+            .get_key_value(perm_name)
+            .map(|(name, _)| name)
+            .into_iter()
+            .collect();
+        while let Some(name) = pending.pop() {
+            if !visited.insert(name) {
+                continue;
+            }
+            let Some((name, perm_config)) = self.apis.get_key_value(name) else {
+                continue;
+            };
+            result.push(name);
+            pending.extend(perm_config.includes_perms.iter());
+        }
+        result
+    }
+
+    /// Returns whether any part of the config only applies to particular targets.
+    pub(crate) fn has_target_config(&self) -> bool {
+        self.packages.values().any(|pkg| !pkg.target.is_empty())
+            || self.apis.values().any(|api| !api.target.is_empty())
+    }
+
+    /// Merges the target-specific parts of the config that apply to `targets` into the rest of the
+    /// config and discards those that don't apply. Build scripts run on the host, so their config
+    /// is matched against the host rather than the target. Permissions for targets that we're not
+    /// building for are thus never granted and never reported as unused. Package target specs can
+    /// also test `feature = "..."`, which is true for the features that `enabled_features` returns
+    /// for the package.
+    pub(crate) fn select_targets(
+        &mut self,
+        targets: &BuildTargets,
+        enabled_features: impl Fn(&CrateName) -> Vec<String>,
+    ) -> Result<()> {
+        for (crate_name, pkg_config) in &mut self.packages {
+            let target = if crate_name.is_build_script() {
+                &targets.host
+            } else {
+                &targets.target
+            };
+            if pkg_config.target.is_empty() {
+                continue;
+            }
+            let features = enabled_features(crate_name);
+            for (spec, target_config) in std::mem::take(&mut pkg_config.target) {
+                if !target.matches_with_features(&spec, &features)? {
+                    continue;
+                }
+                for api in target_config.allow_apis {
+                    if !pkg_config.allow_apis.contains(&api) {
+                        pkg_config.allow_apis.push(api);
+                    }
+                }
+            }
+        }
+        for perm_config in self.apis.values_mut() {
+            for (spec, target_config) in std::mem::take(&mut perm_config.target) {
+                if targets.target.matches(&spec)? {
+                    perm_config.include.extend(target_config.include);
+                    perm_config.exclude.extend(target_config.exclude);
+                }
+            }
+        }
+        Ok(())
+    }
+
     pub(crate) fn flattened_toml(&self) -> Result<String> {
         Ok(toml::to_string(self)?)
     }
@@ -302,6 +774,18 @@ impl PermissionName {
 }
 
 impl Config {
+    /// Returns the name under which the config for `crate_sel` is stored. This is the
+    /// version-qualified name, e.g. `foo@1.2.3`, if the config has an entry for it, otherwise just
+    /// the name, which applies to all versions.
+    pub(crate) fn crate_name_for(&self, crate_sel: &CrateSel) -> CrateName {
+        let versioned = CrateName::with_version(crate_sel);
+        if self.packages.contains_key(&versioned) {
+            versioned
+        } else {
+            CrateName::from(crate_sel)
+        }
+    }
+
     pub(crate) fn unsafe_permitted_for_crate(&self, crate_name: &CrateName) -> bool {
         self.packages
             .get(crate_name)
@@ -309,11 +793,42 @@ impl Config {
             .unwrap_or(false)
     }
 
+    pub(crate) fn yanked_permitted_for_crate(&self, crate_name: &CrateName) -> bool {
+        self.packages
+            .get(crate_name)
+            .map(|crate_config| crate_config.allow_yanked)
+            .unwrap_or(false)
+    }
+
+    /// Returns the modules in which `crate_name` is permitted to use unsafe code.
+    pub(crate) fn unsafe_permitted_modules(&self, crate_name: &CrateName) -> &[String] {
+        self.packages
+            .get(crate_name)
+            .map(|crate_config| crate_config.allow_unsafe_in.as_slice())
+            .unwrap_or_default()
+    }
+
+    pub(crate) fn shell_permitted_for_crate(&self, crate_name: &CrateName) -> bool {
+        self.packages
+            .get(crate_name)
+            .map(|crate_config| crate_config.allow_shell)
+            .unwrap_or(false)
+    }
+
+    pub(crate) fn unknown_locations_permitted_for_crate(&self, crate_name: &CrateName) -> bool {
+        self.packages
+            .get(crate_name)
+            .map(|crate_config| crate_config.allow_unknown_locations)
+            .unwrap_or(false)
+    }
+
     pub(crate) fn sandbox_config_for_build_script(
         &self,
         build_script_id: &BuildScriptId,
     ) -> SandboxConfig {
-        self.sandbox_config_for_package(&CrateName::from(build_script_id))
+        self.sandbox_config_for_package(
+            &self.crate_name_for(&CrateSel::BuildScript(build_script_id.clone())),
+        )
     }
 
     /// Returns the configuration for `package_name`, inheriting options from the default sandbox
@@ -333,9 +848,18 @@ impl Config {
         config
             .extra_args
             .extend(pkg_sandbox_config.extra_args.iter().cloned());
+        config
+            .extra_read_only_paths
+            .extend(pkg_sandbox_config.extra_read_only_paths.iter().cloned());
+        config
+            .extra_read_write_paths
+            .extend(pkg_sandbox_config.extra_read_write_paths.iter().cloned());
         if let Some(allow_network) = pkg_sandbox_config.allow_network {
             config.allow_network = Some(allow_network);
         }
+        if let Some(timeout_secs) = pkg_sandbox_config.timeout_secs {
+            config.timeout_secs = Some(timeout_secs);
+        }
         config
     }
 }
@@ -350,6 +874,18 @@ impl ApiPath {
             prefix: Arc::from(prefix),
         }
     }
+
+    /// Returns whether this path contains `*` or `**` segments.
+    pub(crate) fn is_glob(&self) -> bool {
+        self.prefix.contains('*')
+    }
+
+    /// Returns whether every `*` in this path is part of a whole `*` or `**` segment.
+    pub(crate) fn is_valid_glob(&self) -> bool {
+        self.prefix
+            .split("::")
+            .all(|part| !part.contains('*') || part == "*" || part == "**")
+    }
 }
 
 impl AsRef<str> for PermissionName {
@@ -380,6 +916,22 @@ impl CrateName {
     pub(crate) fn for_build_script(crate_name: &str) -> Self {
         Self(Arc::from(format!("{crate_name}.build").as_str()))
     }
+
+    pub(crate) fn is_build_script(&self) -> bool {
+        self.0.ends_with(".build")
+    }
+
+    /// Returns the name of the package that this crate belongs to, without any version.
+    pub(crate) fn pkg_name(&self) -> &str {
+        let name = self.0.strip_suffix(".build").unwrap_or(&self.0);
+        name.split_once('@').map_or(name, |(name, _version)| name)
+    }
+
+    /// Returns the version if the crate name is qualified with one, e.g. `foo@1.2.3`.
+    pub(crate) fn version(&self) -> Option<&str> {
+        let name = self.0.strip_suffix(".build").unwrap_or(&self.0);
+        name.split_once('@').map(|(_name, version)| version)
+    }
 }
 
 impl Display for CrateName {
@@ -408,6 +960,9 @@ pub(crate) mod testing {
 #[cfg(test)]
 mod tests {
     use super::testing::parse;
+    use crate::config::ApiPath;
+    use crate::config::CrateName;
+    use crate::config::PermissionName;
     use crate::config::SandboxKind;
     use crate::crate_index::CrateIndex;
     use std::path::PathBuf;
@@ -470,6 +1025,126 @@ mod tests {
         assert!(result.is_err());
     }
 
+    #[test]
+    fn never_auto_grant() {
+        let config = parse(
+            r#"
+            never_auto_grant = ["shell", "unsafe", "net"]
+
+            [api.net]
+            include = ["std::net"]
+        "#,
+        )
+        .unwrap();
+        assert_eq!(config.common.never_auto_grant.len(), 3);
+
+        assert!(parse(r#"never_auto_grant = ["typo"]"#).is_err());
+    }
+
+    #[test]
+    fn allow_unsafe_in() {
+        let config = parse(
+            r#"
+                [pkg.foo]
+                allow_unsafe_in = ["foo::ffi", "foo::sys::linux"]
+            "#,
+        )
+        .unwrap();
+        assert_eq!(
+            config.unsafe_permitted_modules(&"foo".into()),
+            ["foo::ffi", "foo::sys::linux"]
+        );
+        assert!(config.unsafe_permitted_modules(&"bar".into()).is_empty());
+        assert!(parse("[pkg.foo]\nallow_unsafe_in = [\"foo\"]").is_err());
+        assert!(parse("[pkg.foo]\nallow_unsafe_in = [\"foo::\"]").is_err());
+        assert!(parse("[pkg.foo]\nallow_unsafe_in = [\"foo/ffi\"]").is_err());
+    }
+
+    #[test]
+    fn allow_symbols() {
+        let config = parse(
+            r#"
+                [pkg.foo]
+                allow_symbols = ["std::fs::metadata"]
+            "#,
+        )
+        .unwrap();
+        assert_eq!(
+            config.packages[&"foo".into()].allow_symbols,
+            ["std::fs::metadata"]
+        );
+        assert!(parse("[pkg.foo]\nallow_symbols = [\"metadata\"]").is_err());
+    }
+
+    #[test]
+    fn ignore_paths() {
+        let config = parse(r#"ignore_paths = ["vendor/**", "/usr/include/*.h"]"#).unwrap();
+        assert_eq!(config.common.ignore_paths.len(), 2);
+
+        assert!(parse(r#"ignore_paths = ["vendor/[abc"]"#).is_err());
+    }
+
+    #[test]
+    fn allow_all() {
+        let config = parse(
+            r#"
+            allow_all = ["clock"]
+
+            [api.clock]
+            include = ["std::time"]
+
+            [pkg.crab1]
+            deny_apis = ["clock"]
+        "#,
+        )
+        .unwrap();
+        assert_eq!(config.common.allow_all, vec![PermissionName::from("clock")]);
+
+        assert!(parse(r#"allow_all = ["typo"]"#).is_err());
+        assert!(parse(
+            r#"
+            [pkg.crab1]
+            deny_apis = ["typo"]
+        "#
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn version_qualified_crate_names() {
+        use crate::crate_index::testing::pkg_id_with_version;
+        use crate::crate_index::BuildScriptId;
+        use crate::crate_index::CrateSel;
+
+        let config = parse(
+            r#"
+            [pkg.foo]
+            allow_unsafe = true
+
+            [pkg."foo@2.0.0"]
+
+            [pkg."foo@2.0.0".build]
+            allow_build_instructions = ["cargo:rustc-cfg=*"]
+        "#,
+        )
+        .unwrap();
+        let v1 = CrateSel::Primary(pkg_id_with_version("foo", 1));
+        let v2 = CrateSel::Primary(pkg_id_with_version("foo", 2));
+        assert_eq!(config.crate_name_for(&v1), CrateName::from("foo"));
+        assert_eq!(config.crate_name_for(&v2), CrateName::from("foo@2.0.0"));
+        assert!(config.unsafe_permitted_for_crate(&config.crate_name_for(&v1)));
+        assert!(!config.unsafe_permitted_for_crate(&config.crate_name_for(&v2)));
+
+        let v2_build = CrateSel::BuildScript(BuildScriptId {
+            pkg_id: pkg_id_with_version("foo", 2),
+        });
+        let build_name = config.crate_name_for(&v2_build);
+        assert_eq!(build_name, CrateName::from("foo@2.0.0.build"));
+        assert!(build_name.is_build_script());
+        assert_eq!(build_name.pkg_name(), "foo");
+        assert!(config.packages.contains_key(&build_name));
+    }
+
     #[test]
     fn crate_build_config() {
         let config = parse(
@@ -495,6 +1170,7 @@ mod tests {
                 extra_args = [
                     "--extra2",
                 ]
+                extra_read_only_paths = ["/opt/schemas"]
 
                 [pkg.b.build.sandbox]
                 kind = "Disabled"
@@ -505,6 +1181,10 @@ mod tests {
         let sandbox_a = config.sandbox_config_for_package(&"a.build".into());
         assert_eq!(sandbox_a.kind, SandboxKind::Bubblewrap);
         assert_eq!(sandbox_a.extra_args, vec!["--extra1", "--extra2"]);
+        assert_eq!(
+            sandbox_a.extra_read_only_paths,
+            vec![PathBuf::from("/opt/schemas")]
+        );
 
         let sandbox_b = config.sandbox_config_for_package(&"b.build".into());
         assert_eq!(sandbox_b.kind, SandboxKind::Disabled);
@@ -527,7 +1207,8 @@ mod tests {
     fn flattened_config_roundtrips() {
         let crate_root = PathBuf::from(std::env::var_os("CARGO_MANIFEST_DIR").unwrap());
         let test_crates_dir = crate_root.join("test_crates");
-        let crate_index = CrateIndex::new(&test_crates_dir).unwrap();
+        let crate_index =
+            CrateIndex::new(&test_crates_dir, false, false, &Default::default()).unwrap();
         let config = super::parse_file(&test_crates_dir.join("cackle.toml"), &crate_index).unwrap();
 
         let roundtripped_config =
@@ -535,6 +1216,216 @@ mod tests {
         assert_eq!(config, roundtripped_config);
     }
 
+    #[test]
+    fn file_imports() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir(dir.path().join("shared")).unwrap();
+        let write = |path: &str, contents: &str| {
+            std::fs::write(dir.path().join(path), contents).unwrap();
+        };
+        write(
+            "shared/base.toml",
+            r#"
+                import = ["more.toml"]
+
+                [common]
+                version = 1
+
+                [api.net]
+                include = ["std::net"]
+
+                [pkg.foo]
+                allow_apis = ["net"]
+
+                [pkg.bar]
+                allow_unsafe = true
+            "#,
+        );
+        write(
+            "shared/more.toml",
+            r#"
+                [common]
+                version = 1
+
+                [api.fs]
+                include = ["std::fs"]
+
+                [api.net]
+                include = ["mio::net"]
+            "#,
+        );
+        write(
+            "cackle.toml",
+            r#"
+                import = ["shared/base.toml"]
+
+                [common]
+                version = 1
+
+                [pkg.foo]
+                allow_apis = ["fs"]
+            "#,
+        );
+        let config =
+            super::parse_file_and_imports(&dir.path().join("cackle.toml"), &mut Vec::new())
+                .unwrap();
+        assert!(config.import.is_empty());
+        assert_eq!(
+            config
+                .apis
+                .keys()
+                .map(|k| k.to_string())
+                .collect::<Vec<_>>(),
+            vec!["fs", "net"]
+        );
+        // base.toml takes precedence over the file it imports.
+        assert_eq!(
+            config.apis[&PermissionName::from("net")].include,
+            vec![ApiPath::from_str("std::net")]
+        );
+        // The local config takes precedence over imported config.
+        assert_eq!(
+            config.packages[&"foo".into()].allow_apis,
+            vec![PermissionName::from("fs")]
+        );
+        assert!(config.packages[&"bar".into()].allow_unsafe);
+        // Subprocesses get the merged config, so mustn't try to import anything.
+        assert!(!config
+            .flattened_toml()
+            .unwrap()
+            .lines()
+            .any(|line| line.starts_with("import ")));
+    }
+
+    #[test]
+    fn file_import_cycle() {
+        let dir = tempfile::tempdir().unwrap();
+        for (name, other) in [("a.toml", "b.toml"), ("b.toml", "a.toml")] {
+            std::fs::write(
+                dir.path().join(name),
+                format!("extends = [\"{other}\"]\n[common]\nversion = 1\n"),
+            )
+            .unwrap();
+        }
+        let error = super::parse_file_and_imports(&dir.path().join("a.toml"), &mut Vec::new())
+            .unwrap_err()
+            .to_string();
+        assert!(error.starts_with("Config files import each other"));
+        assert!(error.contains("a.toml -> "));
+        assert!(error.ends_with("a.toml"));
+    }
+
+    #[test]
+    fn override_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let write = |name: &str, contents: &str| {
+            let path = dir.path().join(name);
+            std::fs::write(&path, contents).unwrap();
+            path
+        };
+        let base = write(
+            "base.toml",
+            r#"
+                [common]
+                version = 1
+                reachable_code_only = true
+
+                [api.fs]
+                include = ["std::fs"]
+
+                [api.net]
+                include = ["std::net"]
+
+                [pkg.foo]
+                allow_apis = ["fs"]
+            "#,
+        );
+        let ci = write(
+            "ci.toml",
+            r#"
+                [common]
+                reachable_code_only = false
+
+                [api.fs]
+                exclude = ["std::fs::metadata"]
+
+                [pkg.foo]
+                allow_apis = ["net"]
+            "#,
+        );
+        let strict = write(
+            "strict.toml",
+            r#"
+                [common]
+                reachable_code_only = true
+
+                [pkg.foo]
+                override = true
+                allow_unsafe = true
+            "#,
+        );
+        let crate_index = CrateIndex::default();
+        let foo = CrateName::from("foo");
+
+        let config = super::parse_files(&base, std::slice::from_ref(&ci), &crate_index).unwrap();
+        // Later files win for scalars and append to lists.
+        assert!(!config.common.reachable_code_only);
+        assert_eq!(
+            config.packages[&foo].allow_apis,
+            vec![PermissionName::from("fs"), PermissionName::from("net")]
+        );
+        assert_eq!(
+            config.apis[&PermissionName::from("fs")].exclude,
+            vec![ApiPath::from_str("std::fs::metadata")]
+        );
+
+        let config =
+            super::parse_files(&base, &[ci.clone(), strict.clone()], &crate_index).unwrap();
+        assert!(config.common.reachable_code_only);
+        // The override marker replaces what earlier files said about the package.
+        assert!(config.packages[&foo].allow_apis.is_empty());
+        assert!(config.packages[&foo].allow_unsafe);
+        assert_eq!(
+            super::overridden_tables(&[ci.clone(), strict.clone()]).unwrap(),
+            vec![(vec!["pkg".to_owned(), "foo".to_owned()], strict.clone())]
+        );
+
+        // Reversing the order of the overrides reverses which one wins.
+        let config = super::parse_files(&base, &[strict, ci], &crate_index).unwrap();
+        assert!(!config.common.reachable_code_only);
+        assert_eq!(
+            config.packages[&foo].allow_apis,
+            vec![PermissionName::from("net")]
+        );
+        assert!(config.packages[&foo].allow_unsafe);
+    }
+
+    #[test]
+    fn includes_perms_cycle() {
+        let result = parse(
+            r#"
+            [api.a]
+            includes_perms = ["b"]
+
+            [api.b]
+            includes_perms = ["a"]
+            "#,
+        );
+        assert!(result.is_err());
+        assert!(result.unwrap_err().to_string().contains("includes itself"));
+    }
+
+    #[test]
+    fn includes_perms_unknown() {
+        let result = parse(
+            r#"
+            [api.a]
+            includes_perms = ["typo"]
+            "#,
+        );
+        assert!(result.is_err());
+    }
+
     #[test]
     fn duplicate_allow_api() {
         let result = parse(
@@ -553,4 +1444,113 @@ mod tests {
         println!("{}", result.as_ref().unwrap_err());
         assert!(result.unwrap_err().to_string().contains("terminate"));
     }
+
+    #[test]
+    fn target_specific_config() {
+        use crate::proxy::target::BuildTargets;
+        use crate::proxy::target::TargetCfg;
+
+        let config = parse(
+            r#"
+            [api.net]
+            include = ["std::net"]
+            target."cfg(unix)".include = ["std::os::unix::net"]
+
+            [pkg.foo]
+            allow_apis = ["net"]
+            target."cfg(unix)".allow_apis = ["net"]
+            target.wasm32-unknown-unknown.allow_apis = ["fs"]
+
+            [api.fs]
+            include = ["std::fs"]
+
+            [pkg.foo.build]
+            target."cfg(unix)".allow_apis = ["fs"]
+            "#,
+        )
+        .unwrap();
+        let wasm = TargetCfg::for_testing("wasm32-unknown-unknown", &["target_arch=\"wasm32\""]);
+        let linux = TargetCfg::for_testing("x86_64-unknown-linux-gnu", &["unix"]);
+        let select = |target: &TargetCfg| {
+            let mut config = (*config).clone();
+            config
+                .select_targets(
+                    &BuildTargets {
+                        target: target.clone(),
+                        host: linux.clone(),
+                    },
+                    |_| Vec::new(),
+                )
+                .unwrap();
+            assert!(!config.has_target_config());
+            config
+        };
+
+        let config = select(&wasm);
+        let foo = &config.packages[&"foo".into()];
+        assert_eq!(foo.allow_apis, vec!["net".into(), "fs".into()]);
+        assert_eq!(config.apis[&"net".into()].include.len(), 1);
+        let foo_build = &config.packages[&"foo.build".into()];
+        assert_eq!(foo_build.allow_apis, vec!["fs".into()]);
+
+        let config = select(&linux);
+        let foo = &config.packages[&"foo".into()];
+        assert_eq!(foo.allow_apis, vec![PermissionName::from("net")]);
+        assert_eq!(config.apis[&"net".into()].include.len(), 2);
+    }
+
+    #[test]
+    fn feature_specific_config() {
+        use crate::proxy::target::BuildTargets;
+        use crate::proxy::target::TargetCfg;
+
+        let config = parse(
+            r#"
+            [api.net]
+            include = ["std::net"]
+
+            [pkg.foo]
+            target.'cfg(feature = "online")'.allow_apis = ["net"]
+            "#,
+        )
+        .unwrap();
+        let linux = TargetCfg::for_testing("x86_64-unknown-linux-gnu", &["unix"]);
+        let targets = BuildTargets {
+            target: linux.clone(),
+            host: linux,
+        };
+
+        let mut offline = (*config).clone();
+        offline.select_targets(&targets, |_| Vec::new()).unwrap();
+        assert!(offline.packages[&"foo".into()].allow_apis.is_empty());
+
+        let mut online = (*config).clone();
+        online
+            .select_targets(&targets, |crate_name| {
+                if crate_name.as_ref() == "foo" {
+                    vec!["online".to_owned()]
+                } else {
+                    Vec::new()
+                }
+            })
+            .unwrap();
+        assert_eq!(
+            online.packages[&"foo".into()].allow_apis,
+            vec![PermissionName::from("net")]
+        );
+    }
+
+    #[test]
+    fn invalid_target() {
+        let result = parse(
+            r#"
+            [api.net]
+            include = ["std::net"]
+
+            [pkg.foo]
+            target."cfg(unix".allow_apis = ["net"]
+            "#,
+        );
+        assert!(result.unwrap_err().to_string().contains("cfg(unix"));
+    }
 }
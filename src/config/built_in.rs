@@ -38,6 +38,22 @@ pub(crate) fn get_built_ins() -> BTreeMap<PermissionName, PermConfig> {
         ),
     );
     result.insert(PermissionName::from("env"), perm(&["std::env"], &[]));
+    result.insert(
+        PermissionName::from("env_vars"),
+        perm(
+            &[
+                "std::env::_var",
+                "std::env::_var_os",
+                "std::env::remove_var",
+                "std::env::set_var",
+                "std::env::var",
+                "std::env::var_os",
+                "std::env::vars",
+                "std::env::vars_os",
+            ],
+            &[],
+        ),
+    );
     result.insert(
         PermissionName::from("net"),
         perm(
@@ -54,9 +70,11 @@ pub(crate) fn get_built_ins() -> BTreeMap<PermissionName, PermConfig> {
         perm(
             &[
                 "std::process",
-                "std::unix::process",
-                "std::windows::process",
+                // Includes `CommandExt::exec`, which replaces the current process.
+                "std::os::unix::process",
+                "std::os::windows::process",
             ],
+            // Terminating the current process is covered by `terminate`.
             &["std::process::abort", "std::process::exit"],
         ),
     );
@@ -64,6 +82,78 @@ pub(crate) fn get_built_ins() -> BTreeMap<PermissionName, PermConfig> {
         PermissionName::from("terminate"),
         perm(&["std::process::abort", "std::process::exit"], &[]),
     );
+    result.insert(
+        PermissionName::from("clock"),
+        perm(
+            &["std::time::Instant::now", "std::time::SystemTime::now"],
+            &[],
+        ),
+    );
+    result.insert(PermissionName::from("thread"), perm(&["std::thread"], &[]));
+    // Not a capability, but some projects, e.g. for embedded targets, need to avoid code that can
+    // panic. This is noisy, since most crates can panic, so is intended to be allowed for all crates
+    // with `allow_all`, then denied for particular crates with `deny_apis`.
+    result.insert(
+        PermissionName::from("panic"),
+        perm(
+            &[
+                "core::panicking",
+                "std::panicking",
+                "std::panic::panic_any",
+                "core::option::Option::expect",
+                "core::option::Option::unwrap",
+                "core::option::expect_failed",
+                "core::option::unwrap_failed",
+                "core::result::Result::expect",
+                "core::result::Result::expect_err",
+                "core::result::Result::unwrap",
+                "core::result::Result::unwrap_err",
+                "core::result::unwrap_failed",
+            ],
+            &[],
+        ),
+    );
+    // Sources of nondeterminism. When used by a build script or proc macro, these can make the build
+    // unreproducible, e.g. by embedding a timestamp or random seed into generated code. Using them
+    // at runtime is normal, so by default, we only report usage at build time.
+    result.insert(PermissionName::from("nondeterministic"), {
+        let mut api = perm(
+            &[
+                "std::time::Instant::now",
+                "std::time::SystemTime::now",
+                "getrandom::getrandom",
+                "getrandom::getrandom_uninit",
+                "rand::random",
+                "rand::thread_rng",
+                "rand::rngs::thread::thread_rng",
+                "rand::rngs::OsRng",
+                "rand_core::os::OsRng",
+            ],
+            &[],
+        );
+        api.build_time_only = Some(true);
+        api
+    });
+    // Loading shared libraries at runtime. The loaded code is never seen by our analysis. These
+    // aren't part of std, but they're common enough that they're worth having built in.
+    result.insert(
+        PermissionName::from("dynamic_loading"),
+        perm(
+            &[
+                "libloading::Library",
+                "libloading::os::unix::Library",
+                "libloading::os::windows::Library",
+                "libc::dlopen",
+                "libc::dlmopen",
+            ],
+            &[],
+        ),
+    );
+    // Anything below the portable surface of std. This overlaps with other APIs, e.g. `fs`.
+    result.insert(
+        PermissionName::from("platform_specific"),
+        perm(&["std::os::unix", "std::os::windows"], &[]),
+    );
     result
 }
 
@@ -72,5 +162,11 @@ fn perm(include: &[&str], exclude: &[&str]) -> PermConfig {
         include: include.iter().map(|s| ApiPath::from_str(s)).collect(),
         exclude: exclude.iter().map(|s| ApiPath::from_str(s)).collect(),
         no_auto_detect: Vec::new(),
+        includes_perms: Vec::new(),
+        description: None,
+        severity: None,
+        report_cap: None,
+        build_time_only: None,
+        target: Default::default(),
     }
 }
@@ -1,24 +1,35 @@
 //! This module is responsible for applying automatic edits to cackle.toml.
 
+use crate::config::ApiPath;
 use crate::config::CrateName;
 use crate::config::PermissionName;
 use crate::config::SandboxKind;
+use crate::config::MAX_VERSION;
+use crate::crate_index::CrateIndex;
+use crate::crate_index::CrateSel;
 use crate::problem::ApiUsages;
 use crate::problem::AvailableApi;
+use crate::problem::IncludedAndExcluded;
 use crate::problem::PossibleExportedApi;
 use crate::problem::Problem;
 use crate::problem::ProblemList;
 use crate::problem::UnusedAllowApi;
+use crate::problem::UnusedAllowSymbols;
+use crate::problem_store::ProblemStore;
+use crate::problem_store::ProblemStoreIndex;
 use anyhow::anyhow;
+use anyhow::bail;
 use anyhow::Result;
 use std::borrow::Borrow;
 use std::borrow::Cow;
 use std::fmt::Display;
 use std::path::Path;
+use std::path::PathBuf;
 use toml_edit::Array;
 use toml_edit::Document;
 use toml_edit::Formatted;
 use toml_edit::Item;
+use toml_edit::Key;
 use toml_edit::Value;
 
 #[derive(Clone)]
@@ -32,6 +43,19 @@ pub(crate) trait Edit {
 
     fn help(&self) -> Cow<'static, str>;
 
+    /// Identifies what sort of edit this is. Together with `package_independent_args`, this lets us
+    /// find the edit for one problem that does the same thing as an edit for a similar problem,
+    /// which may be for a different package.
+    fn kind(&self) -> &'static str {
+        std::any::type_name::<Self>()
+    }
+
+    /// Returns the details of what this edit does, other than which package it does it for, e.g.
+    /// the APIs that it allows.
+    fn package_independent_args(&self) -> Vec<String> {
+        Vec::new()
+    }
+
     /// Applies the edit to the editor.
     fn apply(&self, editor: &mut ConfigEditor) -> Result<()>;
 
@@ -42,6 +66,13 @@ pub(crate) trait Edit {
         ProblemList::default()
     }
 
+    /// Returns the permissions that this edit would grant. Besides API names, these may be
+    /// `unsafe`, `proc_macro` or `shell`. Edits that grant permissions listed in
+    /// `never_auto_grant` are never applied automatically.
+    fn granted_permissions(&self) -> Vec<PermissionName> {
+        Vec::new()
+    }
+
     /// Whether the problem that produced this edit can be resolved if this edit produces no diff.
     /// This should be overriden for any problems that are expected to produce no diff.
     fn resolve_problem_if_edit_is_empty(&self) -> bool {
@@ -49,6 +80,115 @@ pub(crate) trait Edit {
     }
 }
 
+/// Determines which fixes may be applied automatically, e.g. by `--fix` or when auto-accepting
+/// single edits.
+#[derive(Default)]
+pub(crate) struct AutoFixPolicy {
+    never_auto_grant: Vec<PermissionName>,
+    /// Tables that a config file passed as an override replaces by setting `override = true`,
+    /// together with that file.
+    overridden_tables: Vec<(Vec<String>, PathBuf)>,
+}
+
+impl AutoFixPolicy {
+    /// Loads the policy from the config at `config_path` merged with `overrides`.
+    pub(crate) fn load(
+        config_path: &Path,
+        overrides: &[PathBuf],
+        crate_index: &CrateIndex,
+    ) -> Result<Self> {
+        if !config_path.exists() {
+            return Ok(Self::default());
+        }
+        let config = crate::config::parse_files(config_path, overrides, crate_index)?;
+        Ok(Self {
+            never_auto_grant: config.common.never_auto_grant.clone(),
+            overridden_tables: crate::config::overridden_tables(overrides)?,
+        })
+    }
+
+    /// Returns an error if applying `edit` to `editor` would change a table that is replaced by one
+    /// of the override files, since the change would then have no effect.
+    pub(crate) fn check_not_shadowed(&self, editor: &ConfigEditor, edit: &dyn Edit) -> Result<()> {
+        if self.overridden_tables.is_empty() {
+            return Ok(());
+        }
+        let mut edited = editor.clone();
+        // If the edit fails, we leave it to the caller to report the error when it applies it.
+        if edit.apply(&mut edited).is_err() {
+            return Ok(());
+        }
+        let before: toml::Table = toml::from_str(&editor.to_toml())?;
+        let after: toml::Table = toml::from_str(&edited.to_toml())?;
+        for (keys, path) in &self.overridden_tables {
+            if table_at(&before, keys) != table_at(&after, keys) {
+                bail!(
+                    "`{}` is replaced by `override = true` in `{}`",
+                    keys.join("."),
+                    path.display()
+                );
+            }
+        }
+        Ok(())
+    }
+}
+
+fn table_at<'a>(table: &'a toml::Table, keys: &[String]) -> Option<&'a toml::Value> {
+    let (first, rest) = keys.split_first()?;
+    rest.iter()
+        .try_fold(table.get(first)?, |value, key| value.get(key))
+}
+
+/// Returns the first problem in `pstore` that has a single fix that `policy` permits us to apply to
+/// `editor`. Single fixes that we skip are added to `skipped`.
+pub(crate) fn first_single_fix(
+    pstore: &ProblemStore,
+    editor: &ConfigEditor,
+    policy: &AutoFixPolicy,
+    skipped: &mut Vec<String>,
+) -> Option<(ProblemStoreIndex, Box<dyn Edit>)> {
+    pstore
+        .iterate_with_duplicates()
+        .find_map(|(index, problem)| {
+            single_fix(problem, editor, policy, skipped).map(|edit| (index, edit))
+        })
+}
+
+/// Returns the fix for `problem` if it has only a single fix and `policy` permits us to apply it to
+/// `editor`. That is, the fix doesn't grant any permissions in `never_auto_grant` and doesn't edit
+/// a table that an override file replaces. If we skip a single fix, it's added to `skipped`.
+pub(crate) fn single_fix(
+    problem: &Problem,
+    editor: &ConfigEditor,
+    policy: &AutoFixPolicy,
+    skipped: &mut Vec<String>,
+) -> Option<Box<dyn Edit>> {
+    let mut edits = fixes_for_problem(problem);
+    if edits.len() != 1 {
+        return None;
+    }
+    let edit = edits.pop().unwrap();
+    let disallowed: Vec<String> = edit
+        .granted_permissions()
+        .into_iter()
+        .filter(|perm| policy.never_auto_grant.contains(perm))
+        .map(|perm| format!("`{perm}`"))
+        .collect();
+    let reason = if disallowed.is_empty() {
+        match policy.check_not_shadowed(editor, &*edit) {
+            Ok(()) => return Some(edit),
+            Err(error) => error.to_string(),
+        }
+    } else {
+        format!("grants {}", disallowed.join(", "))
+    };
+    let description = format!("{} ({reason})", edit.title());
+    if !skipped.contains(&description) {
+        skipped.push(description);
+    }
+    None
+}
+
 /// Returns possible fixes for `problem`.
 pub(crate) fn fixes_for_problem(problem: &Problem) -> Vec<Box<dyn Edit>> {
     let mut edits: Vec<Box<dyn Edit>> = Vec::new();
@@ -71,6 +211,9 @@ pub(crate) fn fixes_for_problem(problem: &Problem) -> Vec<Box<dyn Edit>> {
             edits.push(Box::new(ImportApi(available.clone())));
             edits.push(Box::new(InlineApi(available.clone())));
             edits.push(Box::new(IgnoreApi(available.clone())));
+            if !available.config.include.is_empty() {
+                edits.push(Box::new(ExcludeAvailableApi(available.clone())));
+            }
         }
         Problem::DisallowedApiUsage(usage) => {
             edits.push(Box::new(AllowApiUsage {
@@ -79,35 +222,63 @@ pub(crate) fn fixes_for_problem(problem: &Problem) -> Vec<Box<dyn Edit>> {
         }
         Problem::IsProcMacro(pkg_id) => {
             edits.push(Box::new(AllowProcMacro {
-                crate_name: pkg_id.into(),
+                crate_sel: CrateSel::Primary(pkg_id.clone()),
             }));
         }
-        Problem::BuildScriptFailed(failure) => {
-            if failure.output.sandbox_config.kind != SandboxKind::Disabled {
-                let crate_name = CrateName::from(&failure.build_script_id);
-                if !failure.output.sandbox_config.allow_network.unwrap_or(false) {
-                    edits.push(Box::new(SandboxAllowNetwork {
-                        crate_name: crate_name.clone(),
-                    }));
-                }
-                edits.push(Box::new(DisableSandbox { crate_name }));
+        Problem::BuildScriptFailed(failure)
+            if failure.output.sandbox_config.kind != SandboxKind::Disabled =>
+        {
+            let crate_sel = CrateSel::BuildScript(failure.build_script_id.clone());
+            if !failure.output.sandbox_config.allow_network.unwrap_or(false) {
+                edits.push(Box::new(SandboxAllowNetwork {
+                    crate_sel: crate_sel.clone(),
+                }));
             }
+            edits.push(Box::new(DisableSandbox { crate_sel }));
         }
         Problem::DisallowedBuildInstruction(failure) => {
             edits.append(&mut edits_for_build_instruction(failure));
         }
         Problem::DisallowedUnsafe(failure) => edits.push(Box::new(AllowUnsafe {
-            crate_name: CrateName::from(&failure.crate_sel),
+            crate_sel: failure.crate_sel.clone(),
+        })),
+        Problem::ShellInvocation(info) => edits.push(Box::new(AllowShell {
+            crate_sel: info.crate_sel.clone(),
+        })),
+        Problem::YankedVersion(pkg_id) => edits.push(Box::new(AllowYanked {
+            crate_sel: CrateSel::Primary(pkg_id.clone()),
         })),
+        Problem::DisallowedLicense(info) => match &info.license {
+            Some(license) => {
+                for id in crate::licenses::license_ids(license) {
+                    edits.push(Box::new(AllowLicense(id.to_owned())));
+                }
+            }
+            None => edits.push(Box::new(AllowUnknownLicense)),
+        },
         Problem::UnusedAllowApi(failure) => edits.push(Box::new(RemoveUnusedAllowApis {
             unused: failure.clone(),
         })),
+        Problem::UnusedAllowSymbols(failure) => edits.push(Box::new(RemoveUnusedAllowSymbols {
+            unused: failure.clone(),
+        })),
         Problem::UnusedPackageConfig(crate_name) => edits.push(Box::new(RemoveUnusedPkgConfig {
             crate_name: crate_name.clone(),
         })),
         Problem::PossibleExportedApi(info) => {
             edits.push(Box::new(ExtendApi(info.clone())));
             edits.push(Box::new(NoDetectApi(info.clone())));
+            edits.push(Box::new(ExcludeExportedApi(info.clone())));
+        }
+        Problem::IncludedAndExcluded(info) => {
+            edits.push(Box::new(RemoveApiPath {
+                info: info.clone(),
+                list: "include",
+            }));
+            edits.push(Box::new(RemoveApiPath {
+                info: info.clone(),
+                list: "exclude",
+            }));
         }
         _ => {}
     }
@@ -138,6 +309,22 @@ impl ConfigEditor {
         self.document.to_string()
     }
 
+    /// Returns the name to use when editing the config for `crate_sel`. If the config already has
+    /// an entry for this specific version of the package, we edit that, otherwise we edit the entry
+    /// that applies to all versions.
+    fn crate_name_for(&self, crate_sel: &CrateSel) -> CrateName {
+        let versioned = CrateName::with_version(crate_sel);
+        let mut item = self.document.as_table().get("pkg");
+        for part in pkg_path(&versioned).skip(1) {
+            item = item.and_then(|item| item.get(part));
+        }
+        if item.is_some_and(Item::is_table) {
+            versioned
+        } else {
+            CrateName::from(crate_sel)
+        }
+    }
+
     fn pkg_table(&mut self, crate_name: &CrateName) -> Result<&mut toml_edit::Table> {
         self.table(pkg_path(crate_name))
     }
@@ -206,6 +393,52 @@ impl ConfigEditor {
         Ok(())
     }
 
+    /// Rewrites any parts of the config that use deprecated forms to their current equivalents.
+    /// Comments and formatting are preserved where possible. Returns a description of each change
+    /// that was made.
+    pub(crate) fn migrate(&mut self) -> Result<Vec<String>> {
+        let mut changes = Vec::new();
+        let common = self.common_table()?;
+        match common
+            .get("version")
+            .and_then(|version| version.as_integer())
+        {
+            None => {
+                common.insert("version", toml_edit::value(MAX_VERSION));
+                changes.push(format!("Added `version = {MAX_VERSION}`"));
+            }
+            Some(version) if version < MAX_VERSION => {
+                common.insert("version", toml_edit::value(MAX_VERSION));
+                changes.push(format!("Updated version from {version} to {MAX_VERSION}"));
+            }
+            Some(_) => {}
+        }
+        if common.contains_key("use_builtin_perms") {
+            if common.contains_key("import_std") {
+                let old = common
+                    .remove("use_builtin_perms")
+                    .and_then(|item| item.into_value().ok()?.as_array().cloned())
+                    .ok_or_else(|| anyhow!("use_builtin_perms must be an array"))?;
+                let imports = common["import_std"]
+                    .as_array_mut()
+                    .ok_or_else(|| anyhow!("import_std must be an array"))?;
+                for api in old.iter().filter_map(|api| api.as_str()) {
+                    if !imports
+                        .iter()
+                        .any(|existing| existing.as_str() == Some(api))
+                    {
+                        imports.push_formatted(create_string(api.to_owned()));
+                    }
+                }
+                changes.push("Merged `use_builtin_perms` into `import_std`".to_owned());
+            } else {
+                rename_key(common, "use_builtin_perms", "import_std");
+                changes.push("Renamed `use_builtin_perms` to `import_std`".to_owned());
+            }
+        }
+        Ok(changes)
+    }
+
     pub(crate) fn toggle_std_import(&mut self, api: &str) -> Result<()> {
         let imports = self
             .common_table()?
@@ -228,12 +461,19 @@ impl ConfigEditor {
         Ok(())
     }
 
+    /// Adds `path` to the paths excluded from `api`, creating the API's table if necessary.
+    pub(crate) fn add_exclude(&mut self, api: &PermissionName, path: &ApiPath) -> Result<()> {
+        let table = self.table(["api", api.name.as_ref()].into_iter())?;
+        add_to_array(table, "exclude", &[path])
+    }
+
     pub(crate) fn set_sandbox_kind(&mut self, sandbox_kind: SandboxKind) -> Result<()> {
         crate::sandbox::verify_kind(sandbox_kind)?;
         let sandbox_kind = match sandbox_kind {
             SandboxKind::Inherit => "Inherit",
             SandboxKind::Disabled => "Disabled",
             SandboxKind::Bubblewrap => "Bubblewrap",
+            SandboxKind::Landlock => "Landlock",
         };
         self.table(["sandbox"].into_iter())?
             .insert("kind", toml_edit::value(sandbox_kind));
@@ -242,7 +482,14 @@ impl ConfigEditor {
 }
 
 fn pkg_path(crate_name: &CrateName) -> impl Iterator<Item = &str> + Clone {
-    std::iter::once("pkg").chain(crate_name.as_ref().split('.'))
+    // We split off `.build` rather than splitting on every `.`, since version-qualified names, e.g.
+    // `foo@1.2.3`, contain dots.
+    let name = crate_name.as_ref();
+    let (name, build) = match name.strip_suffix(".build") {
+        Some(name) => (name, Some("build")),
+        None => (name, None),
+    };
+    ["pkg", name].into_iter().chain(build)
 }
 
 fn edits_for_build_instruction(
@@ -253,7 +500,7 @@ fn edits_for_build_instruction(
     let mut suffix = "";
     loop {
         out.push(Box::new(AllowBuildInstruction {
-            crate_name: CrateName::from(&failure.build_script_id),
+            crate_sel: CrateSel::BuildScript(failure.build_script_id.clone()),
             instruction: format!("{instruction}{suffix}"),
         }));
         suffix = "*";
@@ -355,6 +602,10 @@ impl Edit for SelectSandbox {
         format!("{:?}", self.0)
     }
 
+    fn package_independent_args(&self) -> Vec<String> {
+        vec![format!("{:?}", self.0)]
+    }
+
     fn help(&self) -> Cow<'static, str> {
         "Select what kind of sandbox you'd like to use. At the moment the sandbox is only used \
          for running build scripts (build.rs). Hopefully eventually we'll also run proc-macros \
@@ -375,6 +626,10 @@ impl Edit for ImportStdApi {
         format!("Import std API `{}`", self.0)
     }
 
+    fn package_independent_args(&self) -> Vec<String> {
+        vec![self.0.to_string()]
+    }
+
     fn help(&self) -> Cow<'static, str> {
         "This imports an std API that's built into Cackle. Subsequent versions of Cackle may \
          add/remove paths from this API if it turns out that there were inaccuracies."
@@ -397,6 +652,10 @@ impl Edit for ImportApi {
         )
     }
 
+    fn package_independent_args(&self) -> Vec<String> {
+        vec![self.0.api.to_string()]
+    }
+
     fn help(&self) -> Cow<'static, str> {
         "Imports an API definition that was provided by a third-party crate. Future versions of \
          that crate may adjust these API definitions, hopefully to make them more accurate or \
@@ -405,7 +664,8 @@ impl Edit for ImportApi {
     }
 
     fn apply(&self, editor: &mut ConfigEditor) -> Result<()> {
-        let table = editor.pkg_table(&CrateName::from(&self.0.pkg_id))?;
+        let crate_name = editor.crate_name_for(&CrateSel::Primary(self.0.pkg_id.clone()));
+        let table = editor.pkg_table(&crate_name)?;
         add_to_array(table, "import", &[&self.0.api.name])
     }
 }
@@ -417,6 +677,10 @@ impl Edit for InlineStdApi {
         format!("Inline std API `{}`", self.0)
     }
 
+    fn package_independent_args(&self) -> Vec<String> {
+        vec![self.0.to_string()]
+    }
+
     fn help(&self) -> Cow<'static, str> {
         "This copies the built-in API definition into your cackle.toml. Changes to the API \
          definition in future versions of cackle will not affect your configuration. Selecting \
@@ -448,6 +712,10 @@ impl Edit for InlineApi {
         )
     }
 
+    fn package_independent_args(&self) -> Vec<String> {
+        vec![self.0.api.to_string()]
+    }
+
     fn help(&self) -> Cow<'static, str> {
         "Inlines an API definition from a third-party crate. This lets you adjust this API \
          definition. It does however mean that any changes made to the API definition by the \
@@ -511,6 +779,10 @@ impl Edit for IgnoreStdApi {
         format!("Ignore std API `{}`", self.0)
     }
 
+    fn package_independent_args(&self) -> Vec<String> {
+        vec![self.0.to_string()]
+    }
+
     fn help(&self) -> Cow<'static, str> {
         "Don't import or inline this API definition. Select this if you don't care if crates use \
         this category of API."
@@ -537,6 +809,10 @@ impl Edit for IgnoreApi {
         )
     }
 
+    fn package_independent_args(&self) -> Vec<String> {
+        vec![self.0.api.to_string()]
+    }
+
     fn help(&self) -> Cow<'static, str> {
         "Don't import or inline this API definition. Select this if you don't care if crates use \
         this category of API."
@@ -545,9 +821,15 @@ impl Edit for IgnoreApi {
 
     fn apply(&self, editor: &mut ConfigEditor) -> Result<()> {
         // Make sure the `import` table exists, otherwise we'll continue to warn about unused
-        // imports.
-        let table = editor.pkg_table(&CrateName::from(&self.0.pkg_id))?;
-        get_or_create_array(table, "import")?;
+        // imports. We don't add anything to it, so it's written as `[]` rather than spread over
+        // several lines like the arrays that we add to.
+        let crate_name = editor.crate_name_for(&CrateSel::Primary(self.0.pkg_id.clone()));
+        let table = editor.pkg_table(&crate_name)?;
+        table
+            .entry("import")
+            .or_insert_with(|| Item::Value(Value::Array(Array::new())))
+            .as_array()
+            .ok_or_else(|| anyhow!("import should be an array"))?;
         Ok(())
     }
 
@@ -563,6 +845,10 @@ impl Edit for ExtendApi {
         format!("Extend API `{}` with `{}`", self.0.api, self.0.api_path(),)
     }
 
+    fn package_independent_args(&self) -> Vec<String> {
+        vec![self.0.api.to_string()]
+    }
+
     fn help(&self) -> Cow<'static, str> {
         format!(
             "Classify usages of `{}` as the API `{}`",
@@ -594,6 +880,10 @@ impl Edit for NoDetectApi {
         )
     }
 
+    fn package_independent_args(&self) -> Vec<String> {
+        vec![self.0.api.to_string()]
+    }
+
     fn help(&self) -> Cow<'static, str> {
         format!(
             "Ignore this possible exported API. Select this if you've looked at `{}` and \
@@ -611,6 +901,66 @@ impl Edit for NoDetectApi {
     }
 }
 
+struct ExcludeExportedApi(PossibleExportedApi);
+
+impl Edit for ExcludeExportedApi {
+    fn title(&self) -> String {
+        format!("Exclude `{}` from API `{}`", self.0.api_path(), self.0.api)
+    }
+
+    fn package_independent_args(&self) -> Vec<String> {
+        vec![self.0.api.to_string()]
+    }
+
+    fn help(&self) -> Cow<'static, str> {
+        format!(
+            "Record that `{}` isn't part of the API `{}`, despite its name. Select this if \
+             you've looked at `{}` and determined that it's a false positive. Unlike not \
+             detecting the API in this package, other modules of the package with the same name \
+             will still be reported.",
+            self.0.api_path(),
+            self.0.api,
+            self.0.pkg_id.name(),
+        )
+        .into()
+    }
+
+    fn apply(&self, editor: &mut ConfigEditor) -> Result<()> {
+        editor.add_exclude(&self.0.api, &self.0.api_path())
+    }
+}
+
+struct ExcludeAvailableApi(AvailableApi);
+
+impl Edit for ExcludeAvailableApi {
+    fn title(&self) -> String {
+        format!(
+            "Exclude paths of API `{}` provided by package `{}`",
+            self.0.api,
+            CrateName::from(&self.0.pkg_id)
+        )
+    }
+
+    fn package_independent_args(&self) -> Vec<String> {
+        vec![self.0.api.to_string()]
+    }
+
+    fn help(&self) -> Cow<'static, str> {
+        "Don't import this API definition and exclude the paths that it includes from your own \
+         API with the same name. Select this if the package has classified some of its paths as \
+         this API, but you don't consider them to be."
+            .into()
+    }
+
+    fn apply(&self, editor: &mut ConfigEditor) -> Result<()> {
+        for path in &self.0.config.include {
+            editor.add_exclude(&self.0.api, path)?;
+        }
+        // As with inlining, we need to ignore the API, otherwise we'll keep asking about it.
+        IgnoreApi(self.0.clone()).apply(editor)
+    }
+}
+
 struct AllowApiUsage {
     usage: ApiUsages,
 }
@@ -633,15 +983,34 @@ impl Edit for AllowApiUsage {
         }
     }
 
+    fn package_independent_args(&self) -> Vec<String> {
+        let mut apis: Vec<String> = self.usage.usages.keys().map(|u| u.to_string()).collect();
+        apis.sort();
+        apis
+    }
+
     fn help(&self) -> Cow<'static, str> {
-        "Allow this package to use the specified category of API.".into()
+        let help = "Allow this package to use the specified category of API.";
+        if self.usage.descriptions.is_empty() {
+            return help.into();
+        }
+        let mut help = help.to_owned();
+        for (api, description) in &self.usage.descriptions {
+            help.push_str(&format!("\n\n`{api}`: {description}"));
+        }
+        help.into()
     }
 
     fn apply(&self, editor: &mut ConfigEditor) -> Result<()> {
-        let table = editor.pkg_table(&CrateName::from(&self.usage.crate_sel))?;
+        let crate_name = editor.crate_name_for(&self.usage.crate_sel);
+        let table = editor.pkg_table(&crate_name)?;
         let keys: Vec<_> = self.usage.usages.keys().map(|perm| &perm.name).collect();
         add_to_array(table, "allow_apis", &keys)
     }
+
+    fn granted_permissions(&self) -> Vec<PermissionName> {
+        self.usage.usages.keys().cloned().collect()
+    }
 }
 
 struct RemoveUnusedAllowApis {
@@ -680,6 +1049,93 @@ impl Edit for RemoveUnusedAllowApis {
     }
 }
 
+struct RemoveUnusedAllowSymbols {
+    unused: UnusedAllowSymbols,
+}
+
+impl Edit for RemoveUnusedAllowSymbols {
+    fn title(&self) -> String {
+        "Remove unused allowed symbols".to_owned()
+    }
+
+    fn help(&self) -> Cow<'static, str> {
+        "Remove these symbols from the list of symbols that this package is allowed to use.".into()
+    }
+
+    fn apply(&self, editor: &mut ConfigEditor) -> Result<()> {
+        let Some(table) = editor.opt_pkg_table(&self.unused.crate_name)? else {
+            return Ok(());
+        };
+        let Some(allow_symbols) = get_array(table, "allow_symbols")? else {
+            return Ok(());
+        };
+        for symbol in &self.unused.symbols {
+            let index_and_entry = allow_symbols
+                .iter()
+                .enumerate()
+                .find(|(_, allowed)| allowed.as_str() == Some(symbol.as_str()));
+            if let Some((index, _)) = index_and_entry {
+                allow_symbols.remove(index);
+            }
+        }
+        if allow_symbols.is_empty() {
+            table.remove("allow_symbols");
+        }
+        Ok(())
+    }
+}
+
+struct RemoveApiPath {
+    info: IncludedAndExcluded,
+    /// Either "include" or "exclude".
+    list: &'static str,
+}
+
+impl Edit for RemoveApiPath {
+    fn title(&self) -> String {
+        format!(
+            "Remove `{}` from the {}s of API `{}`",
+            self.info.path, self.list, self.info.api
+        )
+    }
+
+    fn package_independent_args(&self) -> Vec<String> {
+        vec![
+            self.info.api.to_string(),
+            self.info.path.to_string(),
+            self.list.to_owned(),
+        ]
+    }
+
+    fn help(&self) -> Cow<'static, str> {
+        "The path is both included and excluded, in which case the exclude takes precedence. \
+         Remove one of them to make the intent clear."
+            .into()
+    }
+
+    fn apply(&self, editor: &mut ConfigEditor) -> Result<()> {
+        let Some(table) = editor.opt_table(["api", self.info.api.name.as_ref()].into_iter())?
+        else {
+            bail!("API `{}` isn't defined in cackle.toml", self.info.api);
+        };
+        let Some(array) = get_array(table, self.list)? else {
+            bail!("API `{}` has no {} list", self.info.api, self.list);
+        };
+        let path = self.info.path.to_string();
+        let Some(index) = array
+            .iter()
+            .position(|value| value.as_str() == Some(path.as_str()))
+        else {
+            bail!("`{path}` not found in {} list", self.list);
+        };
+        array.remove(index);
+        if array.is_empty() {
+            table.remove(self.list);
+        }
+        Ok(())
+    }
+}
+
 struct RemoveUnusedPkgConfig {
     crate_name: CrateName,
 }
@@ -730,17 +1186,34 @@ fn get_or_create_array<'table>(
     Ok(array)
 }
 
+/// Renames the key `old` in `table` to `new`, keeping its position, value and any comments.
+fn rename_key(table: &mut toml_edit::Table, old: &str, new: &str) {
+    let keys: Vec<String> = table.iter().map(|(key, _)| key.to_owned()).collect();
+    let entries: Vec<(Key, Item)> = keys
+        .iter()
+        .filter_map(|key| table.remove_entry(key))
+        .collect();
+    for (key, item) in entries {
+        let key = if key.get() == old {
+            Key::new(new).with_decor(key.decor().clone())
+        } else {
+            key
+        };
+        table.insert_formatted(&key, item);
+    }
+}
+
 fn create_string(value: String) -> Value {
     Value::String(Formatted::new(value)).decorated("\n    ", "")
 }
 
 struct AllowProcMacro {
-    crate_name: CrateName,
+    crate_sel: CrateSel,
 }
 
 impl Edit for AllowProcMacro {
     fn title(&self) -> String {
-        format!("Allow proc macro `{}`", self.crate_name)
+        format!("Allow proc macro `{}`", CrateName::from(&self.crate_sel))
     }
 
     fn help(&self) -> Cow<'static, str> {
@@ -750,14 +1223,19 @@ impl Edit for AllowProcMacro {
     }
 
     fn apply(&self, editor: &mut ConfigEditor) -> Result<()> {
-        let table = editor.pkg_table(&self.crate_name)?;
+        let crate_name = editor.crate_name_for(&self.crate_sel);
+        let table = editor.pkg_table(&crate_name)?;
         table["allow_proc_macro"] = toml_edit::value(true);
         Ok(())
     }
+
+    fn granted_permissions(&self) -> Vec<PermissionName> {
+        vec![PermissionName::new("proc_macro")]
+    }
 }
 
 struct AllowBuildInstruction {
-    crate_name: CrateName,
+    crate_sel: CrateSel,
     instruction: String,
 }
 
@@ -765,10 +1243,15 @@ impl Edit for AllowBuildInstruction {
     fn title(&self) -> String {
         format!(
             "Allow build script for `{}` to emit instruction `{}`",
-            self.crate_name, self.instruction
+            CrateName::from(&self.crate_sel),
+            self.instruction
         )
     }
 
+    fn package_independent_args(&self) -> Vec<String> {
+        vec![self.instruction.clone()]
+    }
+
     fn help(&self) -> Cow<'static, str> {
         "Allow this crate's build.rs to emit build instructions that match the specified pattern. \
          Some build instructions can be used to add arguments to the linker, which can then be \
@@ -777,18 +1260,19 @@ impl Edit for AllowBuildInstruction {
     }
 
     fn apply(&self, editor: &mut ConfigEditor) -> Result<()> {
-        let table = editor.pkg_table(&self.crate_name)?;
+        let crate_name = editor.crate_name_for(&self.crate_sel);
+        let table = editor.pkg_table(&crate_name)?;
         add_to_array(table, "allow_build_instructions", &[&self.instruction])
     }
 }
 
 struct DisableSandbox {
-    crate_name: CrateName,
+    crate_sel: CrateSel,
 }
 
 impl Edit for DisableSandbox {
     fn title(&self) -> String {
-        format!("Disable sandbox for `{}`", self.crate_name)
+        format!("Disable sandbox for `{}`", CrateName::from(&self.crate_sel))
     }
 
     fn help(&self) -> Cow<'static, str> {
@@ -799,19 +1283,23 @@ impl Edit for DisableSandbox {
     }
 
     fn apply(&self, editor: &mut ConfigEditor) -> Result<()> {
-        let table = editor.pkg_sandbox_table(&self.crate_name)?;
+        let crate_name = editor.crate_name_for(&self.crate_sel);
+        let table = editor.pkg_sandbox_table(&crate_name)?;
         table["kind"] = toml_edit::value("Disabled");
         Ok(())
     }
 }
 
 struct AllowUnsafe {
-    crate_name: CrateName,
+    crate_sel: CrateSel,
 }
 
 impl Edit for AllowUnsafe {
     fn title(&self) -> String {
-        format!("Allow package `{}` to use unsafe code", self.crate_name)
+        format!(
+            "Allow package `{}` to use unsafe code",
+            CrateName::from(&self.crate_sel)
+        )
     }
 
     fn help(&self) -> Cow<'static, str> {
@@ -824,19 +1312,122 @@ impl Edit for AllowUnsafe {
     }
 
     fn apply(&self, editor: &mut ConfigEditor) -> Result<()> {
-        let table = editor.pkg_table(&self.crate_name)?;
+        let crate_name = editor.crate_name_for(&self.crate_sel);
+        let table = editor.pkg_table(&crate_name)?;
         table["allow_unsafe"] = toml_edit::value(true);
         Ok(())
     }
+
+    fn granted_permissions(&self) -> Vec<PermissionName> {
+        vec![PermissionName::new("unsafe")]
+    }
+}
+
+struct AllowShell {
+    crate_sel: CrateSel,
+}
+
+impl Edit for AllowShell {
+    fn title(&self) -> String {
+        format!(
+            "Allow package `{}` to run a shell",
+            CrateName::from(&self.crate_sel)
+        )
+    }
+
+    fn help(&self) -> Cow<'static, str> {
+        "Allow this crate to spawn a shell. A shell will run whatever script it's given, so if \
+         any part of the script comes from untrusted input, this could allow arbitrary commands to \
+         be run. Check that the script is either fixed or properly escaped."
+            .into()
+    }
+
+    fn apply(&self, editor: &mut ConfigEditor) -> Result<()> {
+        let crate_name = editor.crate_name_for(&self.crate_sel);
+        let table = editor.pkg_table(&crate_name)?;
+        table["allow_shell"] = toml_edit::value(true);
+        Ok(())
+    }
+
+    fn granted_permissions(&self) -> Vec<PermissionName> {
+        vec![PermissionName::new("shell")]
+    }
+}
+
+struct AllowYanked {
+    crate_sel: CrateSel,
+}
+
+impl Edit for AllowYanked {
+    fn title(&self) -> String {
+        format!(
+            "Allow yanked version of `{}`",
+            CrateName::from(&self.crate_sel)
+        )
+    }
+
+    fn help(&self) -> Cow<'static, str> {
+        "Stop warning that the version of this package being used has been yanked. Versions are \
+         usually yanked because they're broken or have a security problem, so consider updating \
+         to a version that hasn't been yanked instead."
+            .into()
+    }
+
+    fn apply(&self, editor: &mut ConfigEditor) -> Result<()> {
+        let crate_name = editor.crate_name_for(&self.crate_sel);
+        let table = editor.pkg_table(&crate_name)?;
+        table["allow_yanked"] = toml_edit::value(true);
+        Ok(())
+    }
+}
+
+struct AllowLicense(String);
+
+impl Edit for AllowLicense {
+    fn title(&self) -> String {
+        format!("Allow license `{}`", self.0)
+    }
+
+    fn package_independent_args(&self) -> Vec<String> {
+        vec![self.0.clone()]
+    }
+
+    fn help(&self) -> Cow<'static, str> {
+        "Adds this license to the list of licenses that all dependencies may use.".into()
+    }
+
+    fn apply(&self, editor: &mut ConfigEditor) -> Result<()> {
+        add_to_array(editor.common_table()?, "allowed_licenses", &[&self.0])
+    }
+}
+
+struct AllowUnknownLicense;
+
+impl Edit for AllowUnknownLicense {
+    fn title(&self) -> String {
+        "Allow packages without a license".to_owned()
+    }
+
+    fn help(&self) -> Cow<'static, str> {
+        "Stops reporting dependencies that don't specify a license.".into()
+    }
+
+    fn apply(&self, editor: &mut ConfigEditor) -> Result<()> {
+        editor.common_table()?["allow_unknown_license"] = toml_edit::value(true);
+        Ok(())
+    }
 }
 
 struct SandboxAllowNetwork {
-    crate_name: CrateName,
+    crate_sel: CrateSel,
 }
 
 impl Edit for SandboxAllowNetwork {
     fn title(&self) -> String {
-        format!("Permit network from sandbox for `{}`", self.crate_name)
+        format!(
+            "Permit network from sandbox for `{}`",
+            CrateName::from(&self.crate_sel)
+        )
     }
 
     fn help(&self) -> Cow<'static, str> {
@@ -846,7 +1437,8 @@ impl Edit for SandboxAllowNetwork {
     }
 
     fn apply(&self, editor: &mut ConfigEditor) -> Result<()> {
-        let table = editor.pkg_sandbox_table(&self.crate_name)?;
+        let crate_name = editor.crate_name_for(&self.crate_sel);
+        let table = editor.pkg_sandbox_table(&crate_name)?;
         table["allow_network"] = toml_edit::value(true);
         Ok(())
     }
@@ -860,34 +1452,41 @@ impl Display for dyn Edit {
 
 #[cfg(test)]
 mod tests {
+    use super::single_fix;
+    use super::AutoFixPolicy;
     use super::ConfigEditor;
     use super::Edit;
     use super::InlineStdApi;
+    use crate::config::ApiPath;
     use crate::config::Config;
+    use crate::config::PermConfig;
     use crate::config::PermissionName;
     use crate::config::SandboxConfig;
     use crate::config_editor::fixes_for_problem;
     use crate::crate_index::testing::build_script_id;
     use crate::crate_index::testing::pkg_id;
+    use crate::crate_index::testing::pkg_id_with_version;
     use crate::crate_index::CrateSel;
     use crate::location::SourceLocation;
-    use crate::problem::ApiUsages;
+    use crate::problem::AvailableApi;
     use crate::problem::DisallowedBuildInstruction;
+    use crate::problem::IncludedAndExcluded;
+    use crate::problem::PossibleExportedApi;
     use crate::problem::Problem;
     use crate::proxy::rpc::BuildScriptOutput;
+    use crate::symbol::Symbol;
     use indoc::indoc;
     use std::path::Path;
     use std::path::PathBuf;
     use std::sync::Arc;
 
     fn disallowed_apis(pkg_name: &str, apis: &[&'static str]) -> Problem {
-        Problem::DisallowedApiUsage(ApiUsages {
-            crate_sel: CrateSel::Primary(pkg_id(pkg_name)),
-            usages: apis
-                .iter()
+        Problem::DisallowedApiUsage(crate::problem::testing::api_usages(
+            CrateSel::Primary(pkg_id(pkg_name)),
+            apis.iter()
                 .map(|n| (PermissionName::from(*n), vec![]))
                 .collect(),
-        })
+        ))
     }
 
     #[track_caller]
@@ -916,6 +1515,83 @@ mod tests {
         );
     }
 
+    #[test]
+    fn fix_missing_api_version_qualified() {
+        let problem = |major| {
+            let Problem::DisallowedApiUsage(mut usages) = disallowed_apis("crab1", &["net"]) else {
+                unreachable!();
+            };
+            usages.crate_sel = CrateSel::Primary(pkg_id_with_version("crab1", major));
+            Problem::DisallowedApiUsage(usages)
+        };
+        let initial = indoc! {r#"
+            [pkg."crab1@2.0.0"]
+            allow_apis = [
+                "fs",
+            ]
+        "#};
+        // If there's an entry for the specific version, that's what we edit.
+        check(
+            initial,
+            &[(0, problem(2))],
+            indoc! {r#"
+                [pkg."crab1@2.0.0"]
+                allow_apis = [
+                    "fs",
+                    "net",
+                ]
+            "#},
+        );
+        // Otherwise, we edit the entry that applies to all versions.
+        check(
+            initial,
+            &[(0, problem(1))],
+            indoc! {r#"
+                [pkg."crab1@2.0.0"]
+                allow_apis = [
+                    "fs",
+                ]
+
+                [pkg.crab1]
+                allow_apis = [
+                    "net",
+                ]
+            "#},
+        );
+    }
+
+    #[test]
+    fn fix_included_and_excluded() {
+        let problem = Problem::IncludedAndExcluded(IncludedAndExcluded {
+            api: PermissionName::from("env"),
+            path: crate::config::ApiPath::from_str("std::env::var"),
+        });
+        let initial = indoc! {r#"
+            [api.env]
+            include = ["std::env", "std::env::var"]
+            exclude = ["std::env::var"]
+        "#};
+        check(
+            initial,
+            &[(0, problem.clone())],
+            indoc! {r#"
+                [api.env]
+                include = ["std::env"]
+                exclude = ["std::env::var"]
+            "#,
+            },
+        );
+        check(
+            initial,
+            &[(1, problem)],
+            indoc! {r#"
+                [api.env]
+                include = ["std::env", "std::env::var"]
+            "#,
+            },
+        );
+    }
+
     #[test]
     fn fix_missing_api_build_script() {
         check(
@@ -937,6 +1613,7 @@ mod tests {
         let problem = Problem::DisallowedBuildInstruction(DisallowedBuildInstruction {
             build_script_id: build_script_id("crab1"),
             instruction: "cargo:rustc-env=SOME_VAR=/home/some-path".to_owned(),
+            emitted: Vec::new(),
         });
         check(
             "",
@@ -1005,6 +1682,19 @@ mod tests {
         );
     }
 
+    #[test]
+    fn fix_allow_yanked() {
+        check(
+            "",
+            &[(0, Problem::YankedVersion(pkg_id("crab1")))],
+            indoc! {r#"
+                [pkg.crab1]
+                allow_yanked = true
+            "#,
+            },
+        );
+    }
+
     #[test]
     fn fix_allow_unsafe() {
         check(
@@ -1024,6 +1714,105 @@ mod tests {
         );
     }
 
+    #[test]
+    fn fix_allow_unsafe_qualified() {
+        check(
+            indoc! {r#"
+                [pkg."crab1@2.0.0"]
+                allow_apis = [
+                    "fs",
+                ]
+            "#},
+            &[(
+                0,
+                Problem::DisallowedUnsafe(crate::proxy::rpc::UnsafeUsage {
+                    crate_sel: CrateSel::Primary(pkg_id_with_version("crab1", 2)),
+                    locations: vec![SourceLocation::new(Path::new("main.rs"), 10, None)],
+                }),
+            )],
+            indoc! {r#"
+                [pkg."crab1@2.0.0"]
+                allow_apis = [
+                    "fs",
+                ]
+                allow_unsafe = true
+            "#,
+            },
+        );
+    }
+
+    #[test]
+    fn single_fix_skips_overridden_table() {
+        let policy = AutoFixPolicy {
+            never_auto_grant: Vec::new(),
+            overridden_tables: vec![(
+                vec!["pkg".to_owned(), "crab1".to_owned()],
+                PathBuf::from("ci.toml"),
+            )],
+        };
+        let unsafe_usage = |name| {
+            Problem::DisallowedUnsafe(crate::proxy::rpc::UnsafeUsage {
+                crate_sel: CrateSel::Primary(pkg_id(name)),
+                locations: vec![SourceLocation::new(Path::new("main.rs"), 10, None)],
+            })
+        };
+        let editor = ConfigEditor::from_toml_string("").unwrap();
+        let mut skipped = Vec::new();
+        assert!(single_fix(&unsafe_usage("crab1"), &editor, &policy, &mut skipped).is_none());
+        assert_eq!(
+            skipped,
+            vec!["Allow package `crab1` to use unsafe code \
+                 (`pkg.crab1` is replaced by `override = true` in `ci.toml`)"
+                .to_owned()]
+        );
+        assert!(single_fix(&unsafe_usage("crab2"), &editor, &policy, &mut skipped).is_some());
+    }
+
+    #[test]
+    fn fix_allow_shell() {
+        check(
+            "",
+            &[(
+                0,
+                Problem::ShellInvocation(crate::problem::ShellInvocation {
+                    crate_sel: CrateSel::Primary(pkg_id("crab1")),
+                    shell: "sh".to_owned(),
+                    location: SourceLocation::new(Path::new("main.rs"), 10, None),
+                }),
+            )],
+            indoc! {r#"
+                [pkg.crab1]
+                allow_shell = true
+            "#,
+            },
+        );
+    }
+
+    #[test]
+    fn fix_disallowed_license() {
+        let problem = Problem::DisallowedLicense(crate::problem::DisallowedLicense {
+            pkg_id: pkg_id("crab1"),
+            license: Some("GPL-3.0 OR BSD-3-Clause".to_owned()),
+        });
+        check(
+            indoc! {r#"
+                [common]
+                allowed_licenses = [
+                    "MIT",
+                ]
+            "#},
+            &[(1, problem)],
+            indoc! {r#"
+                [common]
+                allowed_licenses = [
+                    "BSD-3-Clause",
+                    "MIT",
+                ]
+            "#,
+            },
+        );
+    }
+
     #[test]
     fn build_script_failed() {
         let failure = Problem::BuildScriptFailed(crate::problem::BuildScriptFailed {
@@ -1035,9 +1824,14 @@ mod tests {
                 sandbox_config: SandboxConfig {
                     kind: crate::config::SandboxKind::Bubblewrap,
                     extra_args: vec![],
+                    extra_read_only_paths: vec![],
+                    extra_read_write_paths: vec![],
                     allow_network: None,
+                    timeout_secs: None,
                 },
                 build_script: PathBuf::new(),
+                out_dir: PathBuf::new(),
+                timed_out_after: None,
             },
             build_script_id: build_script_id("crab1"),
         });
@@ -1087,6 +1881,31 @@ mod tests {
         );
     }
 
+    #[test]
+    fn unused_allow_symbols() {
+        let failure = Problem::UnusedAllowSymbols(crate::problem::UnusedAllowSymbols {
+            crate_name: "crab1".into(),
+            symbols: vec!["std::fs::metadata".to_owned()],
+        });
+        check(
+            indoc! {r#"
+                [pkg.crab1]
+                allow_symbols = [
+                    "std::fs::metadata",
+                    "std::fs::read",
+                ]
+            "#},
+            &[(0, failure)],
+            indoc! {r#"
+                [pkg.crab1]
+                allow_symbols = [
+                    "std::fs::read",
+                ]
+            "#,
+            },
+        );
+    }
+
     #[test]
     fn unused_allow_api_empty() {
         let failure = Problem::UnusedAllowApi(crate::problem::UnusedAllowApi {
@@ -1176,4 +1995,124 @@ mod tests {
         let built_ins = crate::config::built_in::get_built_ins();
         assert_eq!(built_ins.get(&fs_perm), config.apis.get(&fs_perm));
     }
+
+    #[test]
+    fn migrate_old_config() {
+        let mut editor = ConfigEditor::from_toml_string(indoc! {r#"
+            [common]
+            version = 1
+            # APIs from std that we care about.
+            use_builtin_perms = ["fs", "net"]
+            explicit_build_scripts = true
+
+            [pkg.crab1]
+            allow_apis = ["fs"]
+        "#})
+        .unwrap();
+        assert_eq!(
+            editor.migrate().unwrap(),
+            vec!["Renamed `use_builtin_perms` to `import_std`"]
+        );
+        assert_eq!(
+            editor.to_toml(),
+            indoc! {r#"
+                [common]
+                version = 1
+                # APIs from std that we care about.
+                import_std = ["fs", "net"]
+                explicit_build_scripts = true
+
+                [pkg.crab1]
+                allow_apis = ["fs"]
+            "#}
+        );
+        // Migrating again shouldn't change anything.
+        assert!(editor.migrate().unwrap().is_empty());
+    }
+
+    #[test]
+    fn migrate_merges_imports() {
+        let mut editor = ConfigEditor::from_toml_string(indoc! {r#"
+            [common]
+            use_builtin_perms = ["fs", "net"]
+            import_std = [
+                "net",
+            ]
+        "#})
+        .unwrap();
+        assert_eq!(
+            editor.migrate().unwrap(),
+            vec![
+                "Added `version = 1`",
+                "Merged `use_builtin_perms` into `import_std`"
+            ]
+        );
+        assert_eq!(
+            editor.to_toml(),
+            indoc! {r#"
+                [common]
+                import_std = [
+                    "net",
+                    "fs",
+                ]
+                version = 1
+            "#}
+        );
+    }
+
+    #[test]
+    fn exclude_possible_exported_api() {
+        let problem = Problem::PossibleExportedApi(PossibleExportedApi {
+            pkg_id: pkg_id("crab1"),
+            api: PermissionName::from("fs"),
+            symbol: Symbol::borrowed(b"crab1::fs::read").to_heap(),
+        });
+        let edit = &fixes_for_problem(&problem)[2];
+        assert!(edit.replacement_problems().is_empty());
+        check(
+            indoc! {r#"
+                [api.fs]
+                include = ["std::fs"]
+            "#},
+            &[(2, problem)],
+            indoc! {r#"
+                [api.fs]
+                include = ["std::fs"]
+                exclude = [
+                    "crab1::fs",
+                ]
+            "#},
+        );
+    }
+
+    #[test]
+    fn exclude_available_api() {
+        let problem = Problem::AvailableApi(AvailableApi {
+            pkg_id: pkg_id("crab1"),
+            api: PermissionName::from("fs"),
+            config: PermConfig {
+                include: vec![
+                    ApiPath::from_str("crab1::fs"),
+                    ApiPath::from_str("crab1::io"),
+                ],
+                ..Default::default()
+            },
+        });
+        let edit = &fixes_for_problem(&problem)[3];
+        assert!(edit.replacement_problems().is_empty());
+        check(
+            "",
+            &[(3, problem)],
+            indoc! {r#"
+                [api.fs]
+                exclude = [
+                    "crab1::fs",
+                    "crab1::io",
+                ]
+
+                [pkg.crab1]
+                import = []
+            "#},
+        );
+    }
 }
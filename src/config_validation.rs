@@ -1,3 +1,4 @@
+use crate::config::ApiPath;
 use crate::config::Config;
 use crate::config::CrateName;
 use crate::config::PermissionName;
@@ -6,6 +7,7 @@ use fxhash::FxHashSet;
 use std::fmt::Display;
 use std::path::Path;
 use std::path::PathBuf;
+use std::str::FromStr;
 
 #[derive(Debug)]
 pub(crate) struct InvalidConfig {
@@ -19,8 +21,17 @@ enum Problem {
     DuplicateAllowedApi(PermissionName),
     DisallowedSandboxConfig(CrateName),
     UnsupportedVersion(i64),
+    PermissionCycle(PermissionName),
+    InvalidTarget(String),
+    InvalidGlob(ApiPath),
+    InvalidIgnorePath(String),
+    InvalidUnsafeModule(String),
+    InvalidAllowedSymbol(String),
 }
 
+/// Permissions that aren't APIs, but can still be listed in `never_auto_grant`.
+const NON_API_PERMISSIONS: &[&str] = &["unsafe", "proc_macro", "shell"];
+
 pub(crate) fn validate(config: &Config, config_path: &Path) -> Result<(), InvalidConfig> {
     let mut problems = Vec::new();
     if config.common.version < 1 || config.common.version > MAX_VERSION {
@@ -37,10 +48,81 @@ pub(crate) fn validate(config: &Config, config_path: &Path) -> Result<(), Invali
                 problems.push(Problem::DuplicateAllowedApi(permission_name.clone()))
             }
         }
-        if crate_config.sandbox.is_some() && !name.as_ref().ends_with(".build") {
+        for (spec, target_config) in &crate_config.target {
+            check_target_spec(spec, &mut problems);
+            let mut used = FxHashSet::default();
+            for permission_name in &target_config.allow_apis {
+                if !permission_names.contains(permission_name) {
+                    problems.push(Problem::UnknownPermission(permission_name.clone()));
+                }
+                if !used.insert(permission_name) {
+                    problems.push(Problem::DuplicateAllowedApi(permission_name.clone()))
+                }
+            }
+        }
+        for permission_name in &crate_config.deny_apis {
+            if !permission_names.contains(permission_name) {
+                problems.push(Problem::UnknownPermission(permission_name.clone()));
+            }
+        }
+        for module in &crate_config.allow_unsafe_in {
+            if !is_valid_module_path(module) {
+                problems.push(Problem::InvalidUnsafeModule(module.clone()));
+            }
+        }
+        for symbol in &crate_config.allow_symbols {
+            if !is_valid_module_path(symbol) {
+                problems.push(Problem::InvalidAllowedSymbol(symbol.clone()));
+            }
+        }
+        if crate_config.sandbox.is_some() && !name.is_build_script() {
             problems.push(Problem::DisallowedSandboxConfig(name.clone()))
         }
     }
+    for permission_name in &config.common.allow_all {
+        if !permission_names.contains(permission_name) {
+            problems.push(Problem::UnknownPermission(permission_name.clone()));
+        }
+    }
+    for permission_name in &config.common.never_auto_grant {
+        if !permission_names.contains(permission_name)
+            && !NON_API_PERMISSIONS.contains(&permission_name.name.as_ref())
+        {
+            problems.push(Problem::UnknownPermission(permission_name.clone()));
+        }
+    }
+    for pattern in &config.common.ignore_paths {
+        if glob::Pattern::new(pattern).is_err() {
+            problems.push(Problem::InvalidIgnorePath(pattern.clone()));
+        }
+    }
+    for (perm_name, perm_config) in &config.apis {
+        for included in &perm_config.includes_perms {
+            if !permission_names.contains(included) {
+                problems.push(Problem::UnknownPermission(included.clone()));
+            }
+        }
+        for spec in perm_config.target.keys() {
+            check_target_spec(spec, &mut problems);
+        }
+        let target_paths = perm_config
+            .target
+            .values()
+            .flat_map(|target| target.include.iter().chain(target.exclude.iter()));
+        for path in perm_config
+            .include
+            .iter()
+            .chain(perm_config.exclude.iter())
+            .chain(target_paths)
+        {
+            if !path.is_valid_glob() {
+                problems.push(Problem::InvalidGlob(path.clone()));
+            }
+        }
+        if perm_includes_itself(config, perm_name) {
+            problems.push(Problem::PermissionCycle(perm_name.clone()));
+        }
+    }
     if problems.is_empty() {
         Ok(())
     } else {
@@ -51,6 +133,47 @@ pub(crate) fn validate(config: &Config, config_path: &Path) -> Result<(), Invali
     }
 }
 
+/// Checks that `spec` is either a target triple or a valid `cfg(...)` expression.
+fn check_target_spec(spec: &str, problems: &mut Vec<Problem>) {
+    if cargo_platform::Platform::from_str(spec).is_err() {
+        problems.push(Problem::InvalidTarget(spec.to_owned()));
+    }
+}
+
+/// Returns whether `module` is a path like `mycrate::ffi`. A bare crate name isn't accepted, since
+/// `allow_unsafe` should be used to permit unsafe throughout a crate.
+fn is_valid_module_path(module: &str) -> bool {
+    let segments: Vec<&str> = module.split("::").collect();
+    segments.len() >= 2
+        && segments.iter().all(|segment| {
+            !segment.is_empty()
+                && !segment.starts_with(|ch: char| ch.is_ascii_digit())
+                && segment.chars().all(|ch| ch.is_alphanumeric() || ch == '_')
+        })
+}
+
+/// Returns whether `perm_name` can reach itself by following `includes_perms`.
+fn perm_includes_itself(config: &Config, perm_name: &PermissionName) -> bool {
+    let mut visited = FxHashSet::default();
+    let mut pending: Vec<&PermissionName> = config
+        .apis
+        .get(perm_name)
+        .map(|perm_config| perm_config.includes_perms.iter().collect())
+        .unwrap_or_default();
+    while let Some(name) = pending.pop() {
+        if name == perm_name {
+            return true;
+        }
+        if !visited.insert(name) {
+            continue;
+        }
+        if let Some(perm_config) = config.apis.get(name) {
+            pending.extend(perm_config.includes_perms.iter());
+        }
+    }
+    false
+}
+
 impl Display for InvalidConfig {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         writeln!(f, "Invalid config {}", self.config_path.display())?;
@@ -63,6 +186,30 @@ impl Display for InvalidConfig {
                 Problem::UnsupportedVersion(version) => {
                     write!(f, "  Unsupported version '{version}'")?
                 }
+                Problem::PermissionCycle(perm_name) => write!(
+                    f,
+                    "  Permission `{perm_name}` includes itself via `includes_perms`"
+                )?,
+                Problem::InvalidGlob(path) => write!(
+                    f,
+                    "  Invalid path `{path}`, `*` and `**` must be whole path segments"
+                )?,
+                Problem::InvalidIgnorePath(pattern) => {
+                    write!(f, "  Invalid glob pattern `{pattern}` in `ignore_paths`")?
+                }
+                Problem::InvalidUnsafeModule(module) => write!(
+                    f,
+                    "  Invalid module `{module}` in `allow_unsafe_in`, expected e.g. `mycrate::ffi`"
+                )?,
+                Problem::InvalidAllowedSymbol(symbol) => write!(
+                    f,
+                    "  Invalid symbol `{symbol}` in `allow_symbols`, expected e.g. \
+                     `std::fs::metadata`"
+                )?,
+                Problem::InvalidTarget(spec) => write!(
+                    f,
+                    "  Invalid target `{spec}`, expected a target triple or `cfg(...)`"
+                )?,
                 Problem::DisallowedSandboxConfig(crate_name) => write!(
                     f,
                     "  Sandbox config for regular package `{crate_name}` isn't permitted"
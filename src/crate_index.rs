@@ -6,21 +6,46 @@ use anyhow::Context;
 use anyhow::Result;
 use cargo_metadata::camino::Utf8PathBuf;
 use cargo_metadata::semver::Version;
+use cargo_metadata::Metadata;
 use fxhash::FxHashMap;
+use fxhash::FxHashSet;
+use log::warn;
 use serde::Deserialize;
 use serde::Serialize;
 use std::borrow::Cow;
+use std::collections::BTreeMap;
+use std::collections::VecDeque;
 use std::fmt::Display;
 use std::path::Path;
 use std::path::PathBuf;
 use std::sync::Arc;
 
+mod metadata_cache;
+
 #[derive(Default, Debug)]
 pub(crate) struct CrateIndex {
     pub(crate) manifest_path: PathBuf,
     pub(crate) package_infos: FxHashMap<PackageId, PackageInfo>,
     dir_to_pkg_id: FxHashMap<PathBuf, PackageId>,
     pkg_name_to_ids: FxHashMap<String, Vec<PackageId>>,
+    /// The workspace members. These are the roots of the dependency tree.
+    pub(crate) roots: Vec<PackageId>,
+    /// For each package other than the roots, the package that depends on it on the shortest path
+    /// from a root.
+    reached_from: FxHashMap<PackageId, PackageId>,
+    /// Where the output of `cargo metadata` is cached, if caching is enabled.
+    metadata_cache_path: Option<PathBuf>,
+    /// The feature options that `cargo metadata` was run with, which determine which features are
+    /// resolved as enabled.
+    pub(crate) feature_options: FeatureOptions,
+}
+
+/// The feature options that cargo is run with.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) struct FeatureOptions {
+    pub(crate) features: Vec<String>,
+    pub(crate) all_features: bool,
+    pub(crate) no_default_features: bool,
 }
 
 #[derive(Debug, Clone, Hash, PartialEq, Eq, Serialize, Deserialize)]
@@ -50,9 +75,95 @@ pub(crate) struct PackageInfo {
     pub(crate) directory: Utf8PathBuf,
     pub(crate) description: Option<String>,
     pub(crate) documentation: Option<String>,
+    /// The license as an SPDX expression.
+    pub(crate) license: Option<String>,
+    /// Whether the package comes from crates.io, as opposed to e.g. a path or git dependency.
+    pub(crate) from_crates_io: bool,
+    /// Whether the package has no source, i.e. it's part of the workspace or is a path dependency,
+    /// so its code is under the user's control.
+    pub(crate) is_local: bool,
     crate_name: CrateName,
     build_script_name: Option<CrateName>,
-    is_proc_macro: bool,
+    pub(crate) is_proc_macro: bool,
+    pub(crate) has_build_script: bool,
+    /// Packages that this package depends on, including build and dev dependencies. Sorted by name.
+    pub(crate) dependencies: Vec<PackageId>,
+    /// For optional dependencies, the feature of this package that enabled the dependency.
+    pub(crate) dependency_features: FxHashMap<PackageId, String>,
+    /// For each dependency, how this package depends on it. Dependencies for which cargo didn't
+    /// report a kind are treated as normal dependencies.
+    pub(crate) dependency_kinds: FxHashMap<PackageId, DependencyKinds>,
+    /// The features that cargo metadata resolved as enabled with the index's feature options.
+    pub(crate) resolved_features: Vec<String>,
+}
+
+/// The ways in which one package depends on another. A package can depend on the same package in
+/// more than one way, e.g. as both a normal and a dev-dependency.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub(crate) struct DependencyKinds {
+    pub(crate) normal: bool,
+    pub(crate) dev: bool,
+    pub(crate) build: bool,
+}
+
+/// The chain of dependencies via which a package ends up in the dependency tree, starting from a
+/// workspace member. Empty for workspace members themselves.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub(crate) struct DependencyPath {
+    pub(crate) steps: Vec<DependencyStep>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct DependencyStep {
+    pub(crate) pkg_id: PackageId,
+    /// The feature of this package that enabled the next package in the path, if that package is
+    /// an optional dependency.
+    pub(crate) feature: Option<String>,
+}
+
+impl FeatureOptions {
+    /// Returns the arguments that select these features when passed to cargo.
+    pub(crate) fn cargo_args(&self) -> Vec<String> {
+        let mut args = Vec::new();
+        if self.all_features {
+            args.push("--all-features".to_owned());
+        } else if !self.features.is_empty() {
+            args.push("--features".to_owned());
+            args.push(self.features.join(","));
+        }
+        if self.no_default_features {
+            args.push("--no-default-features".to_owned());
+        }
+        args
+    }
+}
+
+fn run_cargo_metadata(
+    manifest_path: &Path,
+    offline: bool,
+    feature_options: &FeatureOptions,
+) -> Result<Metadata> {
+    let mut command = cargo_metadata::MetadataCommand::new();
+    command.manifest_path(manifest_path);
+    let mut other_options = feature_options.cargo_args();
+    if offline {
+        other_options.push("--offline".to_owned());
+    }
+    command.other_options(other_options);
+    command.exec().map_err(|error| {
+        let offline_failure = match &error {
+            cargo_metadata::Error::CargoMetadata { stderr } => {
+                crate::proxy::cargo::is_offline_failure(stderr)
+            }
+            _ => false,
+        };
+        let error = anyhow::Error::from(error);
+        if offline_failure {
+            error.context(crate::proxy::cargo::OFFLINE_FAILURE_MESSAGE)
+        } else {
+            error.context("Failed to run `cargo metadata`")
+        }
+    })
 }
 
 /// The name of the environment variable that we use to pass a list of non-unique package names to
@@ -63,30 +174,89 @@ pub(crate) struct PackageInfo {
 const MULTIPLE_VERSION_PKG_NAMES_ENV: &str = "CACKLE_MULTIPLE_VERSION_PKG_NAMES";
 
 impl CrateIndex {
-    pub(crate) fn new(dir: &Path) -> Result<Self> {
+    /// Builds an index from the output of `cargo metadata` for the workspace in `dir`, with features
+    /// resolved as they would be when building with `feature_options`. If `use_cache` is set, then a
+    /// cached copy of the metadata is used if none of the manifests have changed since it was
+    /// written.
+    pub(crate) fn new(
+        dir: &Path,
+        offline: bool,
+        use_cache: bool,
+        feature_options: &FeatureOptions,
+    ) -> Result<Self> {
         let manifest_path = dir.join("Cargo.toml");
-        let metadata = cargo_metadata::MetadataCommand::new()
-            .manifest_path(&manifest_path)
-            .exec()?;
+        let cache_path = metadata_cache::cache_path(dir);
+        if !use_cache {
+            let metadata = run_cargo_metadata(&manifest_path, offline, feature_options)?;
+            return Ok(Self::from_metadata(
+                manifest_path,
+                feature_options,
+                &metadata,
+            ));
+        }
+        let metadata = match metadata_cache::load(&cache_path, offline, feature_options) {
+            Some(metadata) => metadata,
+            None => {
+                let metadata = run_cargo_metadata(&manifest_path, offline, feature_options)?;
+                // Failing to cache the metadata just means we'll need to run `cargo metadata`
+                // again next time.
+                if let Err(error) = metadata_cache::store(
+                    &cache_path,
+                    &manifest_path,
+                    offline,
+                    feature_options,
+                    &metadata,
+                ) {
+                    warn!("Failed to cache cargo metadata: {error:#}");
+                }
+                metadata
+            }
+        };
+        Ok(Self {
+            metadata_cache_path: Some(cache_path),
+            ..Self::from_metadata(manifest_path, feature_options, &metadata)
+        })
+    }
+
+    /// Runs `f`, which may clean the target directory, without losing the metadata cache.
+    pub(crate) fn preserving_metadata_cache<T>(&self, f: impl FnOnce() -> Result<T>) -> Result<T> {
+        match &self.metadata_cache_path {
+            Some(cache_path) => metadata_cache::preserving(cache_path, f),
+            None => f(),
+        }
+    }
+
+    fn from_metadata(
+        manifest_path: PathBuf,
+        feature_options: &FeatureOptions,
+        metadata: &Metadata,
+    ) -> Self {
         let mut mapping = CrateIndex {
             manifest_path,
+            feature_options: feature_options.clone(),
             ..Self::default()
         };
         let mut name_counts = FxHashMap::default();
         for package in &metadata.packages {
             *name_counts.entry(&package.name).or_default() += 1;
         }
+        let mut metadata_ids = FxHashMap::default();
         for package in &metadata.packages {
             let pkg_id = PackageId {
                 name: Arc::from(package.name.as_str()),
                 version: package.version.clone(),
                 name_is_unique: name_counts.get(&package.name) == Some(&1),
             };
+            metadata_ids.insert(&package.id, pkg_id.clone());
             let mut is_proc_macro = false;
+            let mut has_build_script = false;
             for target in &package.targets {
                 if target.kind.iter().any(|kind| kind == "proc-macro") {
                     is_proc_macro = true;
                 }
+                if target.kind.iter().any(|kind| kind == "custom-build") {
+                    has_build_script = true;
+                }
             }
             if let Some(dir) = package.manifest_path.parent() {
                 let crate_name: CrateName = package.name.as_str().into();
@@ -96,9 +266,20 @@ impl CrateIndex {
                         directory: dir.to_path_buf(),
                         description: package.description.clone(),
                         documentation: package.documentation.clone(),
+                        license: package.license.clone(),
+                        from_crates_io: package
+                            .source
+                            .as_ref()
+                            .is_some_and(|source| source.is_crates_io()),
+                        is_local: package.source.is_none(),
                         crate_name: crate_name.clone(),
                         build_script_name: Some(CrateName::for_build_script(&package.name)),
                         is_proc_macro,
+                        has_build_script,
+                        dependencies: Vec::new(),
+                        dependency_features: FxHashMap::default(),
+                        dependency_kinds: FxHashMap::default(),
+                        resolved_features: Vec::new(),
                     },
                 );
                 mapping
@@ -114,7 +295,164 @@ impl CrateIndex {
         for package_ids in mapping.pkg_name_to_ids.values_mut() {
             package_ids.sort_by_key(|pkg_id| pkg_id.version.clone());
         }
-        Ok(mapping)
+        let packages_by_id: FxHashMap<_, _> = metadata
+            .packages
+            .iter()
+            .map(|package| (&package.id, package))
+            .collect();
+        if let Some(resolve) = &metadata.resolve {
+            for node in &resolve.nodes {
+                let Some(info) = metadata_ids
+                    .get(&node.id)
+                    .and_then(|pkg_id| mapping.package_infos.get_mut(pkg_id))
+                else {
+                    continue;
+                };
+                info.dependencies = node
+                    .dependencies
+                    .iter()
+                    .filter_map(|id| metadata_ids.get(id).cloned())
+                    .collect();
+                info.dependencies.sort_by(PackageId::name_and_version_cmp);
+                info.resolved_features = node.features.clone();
+                for dep in &node.deps {
+                    let Some(dep_pkg_id) = metadata_ids.get(&dep.pkg) else {
+                        continue;
+                    };
+                    let mut kinds = DependencyKinds::default();
+                    for kind_info in &dep.dep_kinds {
+                        match kind_info.kind {
+                            cargo_metadata::DependencyKind::Development => kinds.dev = true,
+                            cargo_metadata::DependencyKind::Build => kinds.build = true,
+                            _ => kinds.normal = true,
+                        }
+                    }
+                    if kinds == DependencyKinds::default() {
+                        kinds.normal = true;
+                    }
+                    info.dependency_kinds.insert(dep_pkg_id.clone(), kinds);
+                }
+                let Some(package) = packages_by_id.get(&node.id) else {
+                    continue;
+                };
+                for dep_id in &node.dependencies {
+                    let (Some(dep_pkg_id), Some(dep_package)) =
+                        (metadata_ids.get(dep_id), packages_by_id.get(dep_id))
+                    else {
+                        continue;
+                    };
+                    // Features refer to optional dependencies by the name used in Cargo.toml, which
+                    // might be a rename of the package name.
+                    let Some(dep_key) = package
+                        .dependencies
+                        .iter()
+                        .find(|dep| dep.optional && dep.name == dep_package.name)
+                        .map(|dep| dep.rename.as_ref().unwrap_or(&dep.name))
+                    else {
+                        continue;
+                    };
+                    if let Some(feature) =
+                        enabling_feature(dep_key, &node.features, &package.features)
+                    {
+                        info.dependency_features
+                            .insert(dep_pkg_id.clone(), feature.to_owned());
+                    }
+                }
+            }
+        }
+        mapping.roots = metadata
+            .workspace_members
+            .iter()
+            .filter_map(|id| metadata_ids.get(id).cloned())
+            .collect();
+        mapping.roots.sort_by(PackageId::name_and_version_cmp);
+        mapping.compute_reached_from();
+        mapping
+    }
+
+    /// Does a breadth-first search of the dependency graph from the roots, recording how we first
+    /// reached each package. This gives us the shortest path to each package.
+    fn compute_reached_from(&mut self) {
+        self.reached_from.clear();
+        let mut queue: VecDeque<&PackageId> = self.roots.iter().collect();
+        let mut visited: FxHashSet<&PackageId> = self.roots.iter().collect();
+        while let Some(pkg_id) = queue.pop_front() {
+            let Some(info) = self.package_infos.get(pkg_id) else {
+                continue;
+            };
+            for dep in &info.dependencies {
+                if visited.insert(dep) {
+                    self.reached_from.insert(dep.clone(), pkg_id.clone());
+                    queue.push_back(dep);
+                }
+            }
+        }
+    }
+
+    /// Returns the packages that are only in the dependency tree as dev-dependencies (if
+    /// `ignore_dev`) or as build-dependencies (if `ignore_build`), either directly or via other
+    /// packages. A package that can also be reached some other way isn't included.
+    pub(crate) fn packages_only_reachable_via(
+        &self,
+        ignore_dev: bool,
+        ignore_build: bool,
+    ) -> FxHashSet<PackageId> {
+        if !ignore_dev && !ignore_build {
+            return FxHashSet::default();
+        }
+        let mut queue: VecDeque<&PackageId> = self.roots.iter().collect();
+        let mut visited: FxHashSet<&PackageId> = self.roots.iter().collect();
+        while let Some(pkg_id) = queue.pop_front() {
+            let Some(info) = self.package_infos.get(pkg_id) else {
+                continue;
+            };
+            for dep in &info.dependencies {
+                let kinds = info
+                    .dependency_kinds
+                    .get(dep)
+                    .copied()
+                    .unwrap_or(DependencyKinds {
+                        normal: true,
+                        ..DependencyKinds::default()
+                    });
+                let followed =
+                    kinds.normal || (kinds.dev && !ignore_dev) || (kinds.build && !ignore_build);
+                if followed && visited.insert(dep) {
+                    queue.push_back(dep);
+                }
+            }
+        }
+        self.package_infos
+            .keys()
+            .filter(|pkg_id| !visited.contains(pkg_id))
+            .cloned()
+            .collect()
+    }
+
+    /// Returns the shortest chain of dependencies via which `pkg_id` is in the dependency tree.
+    pub(crate) fn dependency_path(&self, pkg_id: &PackageId) -> DependencyPath {
+        let mut steps = vec![DependencyStep {
+            pkg_id: pkg_id.clone(),
+            feature: None,
+        }];
+        let mut current = pkg_id;
+        while let Some(parent) = self.reached_from.get(current) {
+            let feature = self
+                .package_infos
+                .get(parent)
+                .and_then(|info| info.dependency_features.get(current).cloned());
+            steps.push(DependencyStep {
+                pkg_id: parent.clone(),
+                feature,
+            });
+            current = parent;
+        }
+        if steps.len() == 1 {
+            // `pkg_id` is either a root or isn't in the dependency tree.
+            return DependencyPath::default();
+        }
+        steps.reverse();
+        DependencyPath { steps }
     }
 
     /// Adds an environment variable to `command` that allows subprocesses to determine whether a
@@ -139,6 +477,25 @@ impl CrateIndex {
             .and_then(|pkg_ids| pkg_ids.last())
     }
 
+    /// Returns the features that cargo resolved as enabled for the package of `crate_name`, given
+    /// the index's feature options. If `crate_name` doesn't specify a version and there are several
+    /// versions of the package, then features enabled for any of them are returned.
+    pub(crate) fn enabled_features(&self, crate_name: &CrateName) -> Vec<String> {
+        let version = crate_name.version();
+        let mut features: Vec<String> = self
+            .pkg_name_to_ids
+            .get(crate_name.pkg_name())
+            .into_iter()
+            .flatten()
+            .filter(|pkg_id| version.map_or(true, |v| pkg_id.version.to_string() == v))
+            .filter_map(|pkg_id| self.package_infos.get(pkg_id))
+            .flat_map(|info| info.resolved_features.iter().cloned())
+            .collect();
+        features.sort();
+        features.dedup();
+        features
+    }
+
     pub(crate) fn package_info(&self, pkg_id: &PackageId) -> Option<&PackageInfo> {
         self.package_infos.get(pkg_id)
     }
@@ -153,6 +510,20 @@ impl CrateIndex {
         self.package_infos.keys()
     }
 
+    /// Returns whether `pkg_id` is part of the workspace or a path dependency, as opposed to coming
+    /// from a registry or git.
+    pub(crate) fn is_local(&self, pkg_id: &PackageId) -> bool {
+        self.package_infos
+            .get(pkg_id)
+            .is_some_and(|info| info.is_local)
+    }
+
+    pub(crate) fn is_proc_macro(&self, pkg_id: &PackageId) -> bool {
+        self.package_infos
+            .get(pkg_id)
+            .is_some_and(|info| info.is_proc_macro)
+    }
+
     pub(crate) fn proc_macros(&self) -> impl Iterator<Item = &PackageId> {
         self.package_infos.iter().filter_map(|(pkg_id, info)| {
             if info.is_proc_macro {
@@ -163,10 +534,24 @@ impl CrateIndex {
         })
     }
 
-    pub(crate) fn crate_names(&self) -> impl Iterator<Item = &CrateName> {
-        self.package_infos
-            .values()
-            .flat_map(|info| std::iter::once(&info.crate_name).chain(info.build_script_name.iter()))
+    /// Returns the names by which the config can refer to crates in the index. Each crate can be
+    /// referred to either by just its name, or by its name and version, e.g. `foo@1.2.3`.
+    pub(crate) fn crate_names(&self) -> impl Iterator<Item = CrateName> + '_ {
+        self.package_infos.iter().flat_map(|(pkg_id, info)| {
+            let mut names = vec![
+                info.crate_name.clone(),
+                CrateName::with_version(&CrateSel::Primary(pkg_id.clone())),
+            ];
+            if let Some(build_script_name) = &info.build_script_name {
+                names.push(build_script_name.clone());
+                names.push(CrateName::with_version(&CrateSel::BuildScript(
+                    BuildScriptId {
+                        pkg_id: pkg_id.clone(),
+                    },
+                )));
+            }
+            names
+        })
     }
 
     /// Returns the ID of the package that contains the specified path, if any. This is used as a
@@ -174,6 +559,18 @@ impl CrateIndex {
     /// example in the case of crates that compile C code, since the C code won't be in the deps
     /// file. This function however doesn't differentiate between the build script for a package and
     /// the other source files in that package, so should only be used as a fallback.
+    /// Returns the package that `path`, an output of rustc such as an rlib, was compiled from. We
+    /// find the crate root from the deps file that rustc wrote alongside the output, then the
+    /// package that contains it. Relative paths in the deps file are relative to the workspace
+    /// root, which is where cargo runs rustc.
+    pub(crate) fn package_id_for_output(&self, path: &Path) -> Option<&PackageId> {
+        let deps_path = crate::deps::deps_path_for_output(path)?;
+        let sources = crate::deps::source_files_from_deps_file(&deps_path).ok()?;
+        let base_dir = self.manifest_path.parent().unwrap_or(Path::new(""));
+        let root = base_dir.join(sources.first()?);
+        self.package_id_for_path(&root.canonicalize().unwrap_or(root))
+    }
+
     pub(crate) fn package_id_for_path(&self, mut path: &Path) -> Option<&PackageId> {
         loop {
             if let Some(pkg_id) = self.dir_to_pkg_id.get(path) {
@@ -212,7 +609,11 @@ impl PackageId {
         &self.version
     }
 
-    pub(crate) fn crate_name(&self) -> Cow<str> {
+    fn name_and_version_cmp(a: &PackageId, b: &PackageId) -> std::cmp::Ordering {
+        (&a.name, &a.version).cmp(&(&b.name, &b.version))
+    }
+
+    pub(crate) fn crate_name(&self) -> Cow<'_, str> {
         if self.name.contains('-') {
             self.name.replace('-', "_").into()
         } else {
@@ -221,6 +622,35 @@ impl PackageId {
     }
 }
 
+/// Returns which of `enabled_features` caused the optional dependency `dep_key` to be enabled.
+/// `feature_defs` are the package's feature definitions from Cargo.toml. If an explicit feature
+/// enabled the dependency, that's preferred over the implicit feature with the same name as the
+/// dependency.
+fn enabling_feature<'a>(
+    dep_key: &str,
+    enabled_features: &'a [String],
+    feature_defs: &BTreeMap<String, Vec<String>>,
+) -> Option<&'a str> {
+    let enables_dep = |value: &String| {
+        // `dep?/feature` only enables a feature of the dependency if something else enabled it.
+        value.strip_prefix("dep:") == Some(dep_key)
+            || value == dep_key
+            || value
+                .strip_prefix(dep_key)
+                .is_some_and(|rest| rest.starts_with('/'))
+    };
+    enabled_features
+        .iter()
+        .filter(|feature| *feature != dep_key)
+        .find(|feature| {
+            feature_defs
+                .get(*feature)
+                .is_some_and(|values| values.iter().any(enables_dep))
+        })
+        .or_else(|| enabled_features.iter().find(|feature| *feature == dep_key))
+        .map(String::as_str)
+}
+
 fn get_env(key: &str) -> Result<String> {
     std::env::var(key).with_context(|| format!("Failed to get environment variable {key}"))
 }
@@ -273,6 +703,24 @@ impl Display for CrateSel {
     }
 }
 
+impl Display for DependencyPath {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut first = true;
+        for step in &self.steps {
+            if first {
+                first = false;
+            } else {
+                write!(f, " → ")?;
+            }
+            write!(f, "{}", step.pkg_id)?;
+            if let Some(feature) = &step.feature {
+                write!(f, " (feature `{feature}`)")?;
+            }
+        }
+        Ok(())
+    }
+}
+
 impl Display for BuildScriptId {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         CrateSel::BuildScript(self.clone()).fmt(f)
@@ -291,6 +739,19 @@ impl From<&BuildScriptId> for CrateName {
     }
 }
 
+impl CrateName {
+    /// Returns the name of `crate_sel` qualified with the package version, e.g. `foo@1.2.3` or
+    /// `foo@1.2.3.build`. Config for a name like this only applies to that version.
+    pub(crate) fn with_version(crate_sel: &CrateSel) -> Self {
+        let pkg_id = crate_sel.pkg_id();
+        let name = format!("{}@{}", pkg_id.name, pkg_id.version);
+        match crate_sel {
+            CrateSel::Primary(_) => CrateName::from(name.as_str()),
+            CrateSel::BuildScript(_) => CrateName::for_build_script(&name),
+        }
+    }
+}
+
 impl From<&CrateSel> for CrateName {
     fn from(value: &CrateSel) -> Self {
         match value {
@@ -313,7 +774,10 @@ pub(crate) mod testing {
     use super::PackageId;
     use super::PackageInfo;
     use crate::config::CrateName;
+    use cargo_metadata::camino::Utf8PathBuf;
     use cargo_metadata::semver::Version;
+    use fxhash::FxHashMap;
+    use std::path::Path;
     use std::sync::Arc;
 
     pub(crate) fn pkg_id(name: &str) -> PackageId {
@@ -324,6 +788,15 @@ pub(crate) mod testing {
         }
     }
 
+    /// Returns the ID of one of several versions of the package `name`.
+    pub(crate) fn pkg_id_with_version(name: &str, major: u64) -> PackageId {
+        PackageId {
+            name: Arc::from(name),
+            version: Version::new(major, 0, 0),
+            name_is_unique: false,
+        }
+    }
+
     pub(crate) fn build_script_id(name: &str) -> BuildScriptId {
         BuildScriptId {
             pkg_id: pkg_id(name),
@@ -331,25 +804,212 @@ pub(crate) mod testing {
     }
 
     pub(crate) fn index_with_package_names(package_names: &[&str]) -> Arc<CrateIndex> {
-        let package_infos = package_names
+        let packages: Vec<(&str, Option<&str>)> =
+            package_names.iter().map(|name| (*name, None)).collect();
+        index_with_licenses(&packages)
+    }
+
+    /// Returns an index containing the supplied packages, each with the specified license.
+    pub(crate) fn index_with_licenses(packages: &[(&str, Option<&str>)]) -> Arc<CrateIndex> {
+        let package_infos = packages
             .iter()
-            .map(|name| {
-                (
-                    pkg_id(name),
-                    PackageInfo {
-                        directory: Default::default(),
-                        description: Default::default(),
-                        documentation: Default::default(),
-                        crate_name: CrateName(Arc::from(*name)),
-                        build_script_name: Default::default(),
-                        is_proc_macro: Default::default(),
-                    },
-                )
-            })
+            .map(|(name, license)| (pkg_id(name), package_info(name, *license)))
+            .collect();
+        Arc::new(CrateIndex {
+            package_infos,
+            ..CrateIndex::default()
+        })
+    }
+
+    /// Returns an index containing just the package `name`, whose directory is `dir`.
+    pub(crate) fn index_with_package_dir(name: &str, dir: &Path) -> Arc<CrateIndex> {
+        let mut info = package_info(name, None);
+        info.directory = Utf8PathBuf::from_path_buf(dir.to_owned()).unwrap();
+        Arc::new(CrateIndex {
+            package_infos: [(pkg_id(name), info)].into_iter().collect(),
+            dir_to_pkg_id: [(dir.to_owned(), pkg_id(name))].into_iter().collect(),
+            ..CrateIndex::default()
+        })
+    }
+
+    /// Returns an index containing the `local` packages, which are in the workspace, and the
+    /// `registry` packages, which come from crates.io.
+    pub(crate) fn index_with_registry_packages(
+        local: &[&str],
+        registry: &[&str],
+    ) -> Arc<CrateIndex> {
+        let mut package_infos: FxHashMap<PackageId, PackageInfo> = local
+            .iter()
+            .map(|name| (pkg_id(name), package_info(name, None)))
             .collect();
+        for name in registry {
+            let mut info = package_info(name, None);
+            info.is_local = false;
+            info.from_crates_io = true;
+            package_infos.insert(pkg_id(name), info);
+        }
         Arc::new(CrateIndex {
             package_infos,
             ..CrateIndex::default()
         })
     }
+
+    pub(crate) fn package_info(name: &str, license: Option<&str>) -> PackageInfo {
+        PackageInfo {
+            directory: Default::default(),
+            description: Default::default(),
+            documentation: Default::default(),
+            license: license.map(str::to_owned),
+            from_crates_io: Default::default(),
+            is_local: true,
+            crate_name: CrateName(Arc::from(name)),
+            build_script_name: Default::default(),
+            is_proc_macro: Default::default(),
+            has_build_script: Default::default(),
+            dependencies: Default::default(),
+            dependency_features: Default::default(),
+            dependency_kinds: Default::default(),
+            resolved_features: Default::default(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::testing::index_with_package_names;
+    use super::testing::pkg_id;
+    use super::testing::pkg_id_with_version;
+    use super::*;
+
+    fn features(defs: &[(&str, &[&str])]) -> BTreeMap<String, Vec<String>> {
+        defs.iter()
+            .map(|(name, values)| {
+                (
+                    name.to_string(),
+                    values.iter().map(|v| v.to_string()).collect(),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn enabling_features() {
+        let defs = features(&[
+            ("default", &["std"]),
+            ("std", &[]),
+            ("tls", &["dep:openssl"]),
+            ("fast", &["zlib/simd"]),
+            ("weak", &["zlib?/std"]),
+        ]);
+        let enabled =
+            |names: &[&str]| -> Vec<String> { names.iter().map(|n| n.to_string()).collect() };
+        let openssl = enabled(&["default", "std", "tls"]);
+        assert_eq!(enabling_feature("openssl", &openssl, &defs), Some("tls"));
+        let zlib = enabled(&["fast", "zlib"]);
+        assert_eq!(enabling_feature("zlib", &zlib, &defs), Some("fast"));
+        let implicit = enabled(&["weak", "zlib"]);
+        assert_eq!(enabling_feature("zlib", &implicit, &defs), Some("zlib"));
+        assert_eq!(enabling_feature("zlib", &enabled(&["std"]), &defs), None);
+    }
+
+    #[test]
+    fn enabled_features() {
+        let mut index = Arc::try_unwrap(index_with_package_names(&["app"])).unwrap();
+        let v1 = pkg_id_with_version("dep", 1);
+        let v2 = pkg_id_with_version("dep", 2);
+        index
+            .pkg_name_to_ids
+            .insert("dep".to_owned(), vec![v1.clone(), v2.clone()]);
+        for (pkg_id, features) in [(&v1, &["std"][..]), (&v2, &["default", "std"][..])] {
+            let mut info = testing::package_info("dep", None);
+            info.resolved_features = features.iter().map(|f| f.to_string()).collect();
+            index.package_infos.insert(pkg_id.clone(), info);
+        }
+        let features = |name: &str| index.enabled_features(&CrateName::from(name));
+        assert_eq!(features("dep@1.0.0"), vec!["std"]);
+        assert_eq!(features("dep@2.0.0.build"), vec!["default", "std"]);
+        assert_eq!(features("dep"), vec!["default", "std"]);
+        assert!(features("app").is_empty());
+        assert!(features("other").is_empty());
+    }
+
+    #[test]
+    fn shortest_dependency_path() {
+        let mut index =
+            Arc::try_unwrap(index_with_package_names(&["app", "foo", "bar", "openssl"])).unwrap();
+        let deps = [
+            ("app", &["foo", "bar"][..]),
+            ("foo", &["bar"][..]),
+            ("bar", &["openssl"][..]),
+        ];
+        for (name, dependencies) in deps {
+            let info = index.package_infos.get_mut(&pkg_id(name)).unwrap();
+            info.dependencies = dependencies.iter().map(|dep| pkg_id(dep)).collect();
+        }
+        index
+            .package_infos
+            .get_mut(&pkg_id("bar"))
+            .unwrap()
+            .dependency_features
+            .insert(pkg_id("openssl"), "tls".to_owned());
+        index.roots = vec![pkg_id("app")];
+        index.compute_reached_from();
+        assert_eq!(
+            index.dependency_path(&pkg_id("openssl")).to_string(),
+            "app → bar (feature `tls`) → openssl"
+        );
+        assert!(index.dependency_path(&pkg_id("app")).steps.is_empty());
+    }
+
+    #[test]
+    fn packages_only_reachable_via_dev_or_build() {
+        let mut index = Arc::try_unwrap(index_with_package_names(&[
+            "app", "serde", "tempfile", "cc", "rand", "libc",
+        ]))
+        .unwrap();
+        let normal = DependencyKinds {
+            normal: true,
+            ..DependencyKinds::default()
+        };
+        let dev = DependencyKinds {
+            dev: true,
+            ..DependencyKinds::default()
+        };
+        let build = DependencyKinds {
+            build: true,
+            ..DependencyKinds::default()
+        };
+        let deps = [
+            (
+                "app",
+                &[("serde", normal), ("tempfile", dev), ("cc", build)][..],
+            ),
+            // `rand` is only used by a dev-dependency, so is effectively a dev-dependency.
+            ("tempfile", &[("rand", normal), ("libc", normal)][..]),
+            // `libc` is also reachable via a normal dependency, so is always analysed.
+            ("serde", &[("libc", normal)][..]),
+        ];
+        for (name, dependencies) in deps {
+            let info = index.package_infos.get_mut(&pkg_id(name)).unwrap();
+            info.dependencies = dependencies.iter().map(|(dep, _)| pkg_id(dep)).collect();
+            info.dependency_kinds = dependencies
+                .iter()
+                .map(|(dep, kinds)| (pkg_id(dep), *kinds))
+                .collect();
+        }
+        index.roots = vec![pkg_id("app")];
+        let names = |ignore_dev, ignore_build| {
+            let mut names: Vec<String> = index
+                .packages_only_reachable_via(ignore_dev, ignore_build)
+                .iter()
+                .map(|pkg_id| pkg_id.name().to_owned())
+                .collect();
+            names.sort();
+            names
+        };
+        assert!(names(false, false).is_empty());
+        assert_eq!(names(true, false), vec!["rand", "tempfile"]);
+        assert_eq!(names(false, true), vec!["cc"]);
+        assert_eq!(names(true, true), vec!["cc", "rand", "tempfile"]);
+    }
 }
@@ -0,0 +1,359 @@
+//! Caches the output of `cargo metadata`, which can be slow on large workspaces. The cache records
+//! the size and modification time of each file that could affect the output: manifests that could
+//! be edited (the workspace's own and those of other local packages), the lock file and cargo's
+//! config files, which can patch or replace sources. It also records settings that don't come from
+//! files, such as whether we're offline and which features are selected. If any of these change, the cache is ignored and `cargo
+//! metadata` is run again.
+
+use super::FeatureOptions;
+use crate::proxy::rpc::CACKLE_VERSION;
+use anyhow::Context;
+use anyhow::Result;
+use cargo_metadata::Metadata;
+use serde::Deserialize;
+use serde::Serialize;
+use std::ffi::OsString;
+use std::path::Path;
+use std::path::PathBuf;
+use std::time::UNIX_EPOCH;
+
+const TARGET_DIR_ENV: &str = "CARGO_TARGET_DIR";
+
+#[derive(Serialize, Deserialize)]
+struct CachedMetadata {
+    /// The version of cackle that wrote the cache. A different version might construct its index
+    /// from different parts of the metadata, so we don't trust caches from other versions.
+    cackle_version: String,
+    settings: Settings,
+    inputs: Vec<InputFile>,
+    metadata: Metadata,
+}
+
+/// Things other than the contents of files that affect the output of `cargo metadata`.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
+struct Settings {
+    /// Whether `cargo metadata` was run with `--offline`, which can change how dependencies
+    /// resolve.
+    offline: bool,
+    /// The feature options, which determine which features are resolved as enabled.
+    features: FeatureOptions,
+    /// The value of `CARGO_TARGET_DIR`, which determines the target directory in the metadata.
+    target_dir_env: Option<PathBuf>,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
+struct InputFile {
+    path: PathBuf,
+    /// Nanoseconds since the Unix epoch. None if the file didn't exist or its modification time
+    /// wasn't available.
+    modified: Option<u64>,
+    len: u64,
+}
+
+/// Returns where the cache for the workspace in `dir` is stored.
+pub(super) fn cache_path(dir: &Path) -> PathBuf {
+    target_dir(dir, std::env::var_os(TARGET_DIR_ENV))
+        .join("cackle")
+        .join("metadata.json")
+}
+
+/// Returns the cached metadata from `cache_path` if there is some, it was obtained with the same
+/// settings and none of the files from which it was derived have changed since it was written.
+pub(super) fn load(
+    cache_path: &Path,
+    offline: bool,
+    features: &FeatureOptions,
+) -> Option<Metadata> {
+    let json = std::fs::read_to_string(cache_path).ok()?;
+    let cached: CachedMetadata = serde_json::from_str(&json).ok()?;
+    if cached.cackle_version != CACKLE_VERSION
+        || cached.settings != Settings::current(offline, features)
+        || cached
+            .inputs
+            .iter()
+            .any(|input| InputFile::new(&input.path) != *input)
+    {
+        return None;
+    }
+    Some(cached.metadata)
+}
+
+/// Writes `metadata`, which was obtained for the workspace with the specified manifest, to
+/// `cache_path`.
+pub(super) fn store(
+    cache_path: &Path,
+    manifest_path: &Path,
+    offline: bool,
+    features: &FeatureOptions,
+    metadata: &Metadata,
+) -> Result<()> {
+    let cached = CachedMetadata {
+        cackle_version: CACKLE_VERSION.to_owned(),
+        settings: Settings::current(offline, features),
+        inputs: input_files(manifest_path, metadata),
+        metadata: metadata.clone(),
+    };
+    if let Some(dir) = cache_path.parent() {
+        std::fs::create_dir_all(dir)
+            .with_context(|| format!("Failed to create directory `{}`", dir.display()))?;
+    }
+    crate::fs::write_atomic(cache_path, &serde_json::to_string(&cached)?)
+}
+
+/// Runs `f`, then puts back the cache at `cache_path` if `f` removed it. The cache is in the output
+/// directory of our default profile, which we clean before most builds.
+pub(super) fn preserving<T>(cache_path: &Path, f: impl FnOnce() -> Result<T>) -> Result<T> {
+    let contents = std::fs::read_to_string(cache_path).ok();
+    let result = f()?;
+    if let Some(contents) = contents {
+        if !cache_path.exists() {
+            if let Some(dir) = cache_path.parent() {
+                std::fs::create_dir_all(dir)
+                    .with_context(|| format!("Failed to create directory `{}`", dir.display()))?;
+            }
+            crate::fs::write_atomic(cache_path, &contents)?;
+        }
+    }
+    Ok(result)
+}
+
+/// Returns the target directory that cargo will use for the workspace in `dir`. This is
+/// `CARGO_TARGET_DIR` if set, otherwise `build.target-dir` from the nearest cargo config file that
+/// sets it, otherwise `target` in the workspace. `env_target_dir` is the value of `CARGO_TARGET_DIR`.
+fn target_dir(dir: &Path, env_target_dir: Option<OsString>) -> PathBuf {
+    if let Some(target_dir) = env_target_dir {
+        return dir.join(target_dir);
+    }
+    for config_path in cargo_config_files(dir) {
+        let Ok(contents) = std::fs::read_to_string(&config_path) else {
+            continue;
+        };
+        let Ok(table) = contents.parse::<toml::Table>() else {
+            continue;
+        };
+        if let Some(target_dir) = table
+            .get("build")
+            .and_then(|build| build.get("target-dir"))
+            .and_then(|target_dir| target_dir.as_str())
+        {
+            // Relative paths are relative to the directory containing `.cargo`.
+            if let Some(base) = config_path.parent().and_then(Path::parent) {
+                return base.join(target_dir);
+            }
+        }
+    }
+    dir.join("target")
+}
+
+/// Returns the paths of the config files that cargo would read when run in `dir`, most specific
+/// first, whether or not they exist.
+fn cargo_config_files(dir: &Path) -> Vec<PathBuf> {
+    let dir = dir.canonicalize().unwrap_or_else(|_| dir.to_owned());
+    let mut cargo_dirs: Vec<PathBuf> = dir
+        .ancestors()
+        .map(|ancestor| ancestor.join(".cargo"))
+        .collect();
+    cargo_dirs.extend(crate::fs::cargo_home());
+    cargo_dirs
+        .iter()
+        .flat_map(|cargo_dir| [cargo_dir.join("config.toml"), cargo_dir.join("config")])
+        .collect()
+}
+
+fn input_files(manifest_path: &Path, metadata: &Metadata) -> Vec<InputFile> {
+    let mut paths = vec![
+        manifest_path.to_owned(),
+        metadata
+            .workspace_root
+            .join("Cargo.lock")
+            .into_std_path_buf(),
+    ];
+    // Packages without a source are local, so their manifests might be edited. Packages from a
+    // registry or git are pinned by the lock file.
+    paths.extend(
+        metadata
+            .packages
+            .iter()
+            .filter(|package| package.source.is_none())
+            .map(|package| package.manifest_path.clone().into_std_path_buf()),
+    );
+    // Config files that don't exist are recorded too, so that creating one invalidates the cache.
+    if let Some(dir) = manifest_path.parent() {
+        paths.extend(cargo_config_files(dir));
+    }
+    paths.sort();
+    paths.dedup();
+    paths.iter().map(|path| InputFile::new(path)).collect()
+}
+
+impl Settings {
+    fn current(offline: bool, features: &FeatureOptions) -> Self {
+        Self {
+            offline,
+            features: features.clone(),
+            target_dir_env: std::env::var_os(TARGET_DIR_ENV).map(PathBuf::from),
+        }
+    }
+}
+
+impl InputFile {
+    fn new(path: &Path) -> Self {
+        let file_metadata = std::fs::metadata(path).ok();
+        let modified = file_metadata
+            .as_ref()
+            .and_then(|m| m.modified().ok())
+            .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+            .and_then(|duration| u64::try_from(duration.as_nanos()).ok());
+        Self {
+            path: path.to_owned(),
+            modified,
+            len: file_metadata.map_or(0, |m| m.len()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crate_index::CrateIndex;
+
+    fn metadata(workspace_root: &Path) -> Metadata {
+        serde_json::from_value(serde_json::json!({
+            "packages": [],
+            "workspace_members": [],
+            "resolve": null,
+            "workspace_root": workspace_root,
+            "target_directory": workspace_root.join("target"),
+            "version": 1,
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn invalidated_by_manifest_change() {
+        let dir = tempfile::tempdir().unwrap();
+        let manifest_path = dir.path().join("Cargo.toml");
+        std::fs::write(&manifest_path, "[package]\nname = \"foo\"\n").unwrap();
+        std::fs::write(dir.path().join("Cargo.lock"), "").unwrap();
+        let cache_path = dir.path().join("metadata.json");
+        let features = FeatureOptions::default();
+
+        assert!(load(&cache_path, false, &features).is_none());
+        store(
+            &cache_path,
+            &manifest_path,
+            false,
+            &features,
+            &metadata(dir.path()),
+        )
+        .unwrap();
+        let loaded = load(&cache_path, false, &features).unwrap();
+        assert_eq!(loaded.workspace_root.as_std_path(), dir.path());
+        assert!(load(&cache_path, true, &features).is_none());
+        let all_features = FeatureOptions {
+            all_features: true,
+            ..FeatureOptions::default()
+        };
+        assert!(load(&cache_path, false, &all_features).is_none());
+
+        std::fs::write(&manifest_path, "[package]\nname = \"foobar\"\n").unwrap();
+        assert!(load(&cache_path, false, &features).is_none());
+    }
+
+    #[test]
+    fn invalidated_by_cargo_config() {
+        let dir = tempfile::tempdir().unwrap();
+        let manifest_path = dir.path().join("Cargo.toml");
+        std::fs::write(&manifest_path, "[package]\nname = \"foo\"\n").unwrap();
+        let cache_path = dir.path().join("metadata.json");
+        let features = FeatureOptions::default();
+
+        store(
+            &cache_path,
+            &manifest_path,
+            false,
+            &features,
+            &metadata(dir.path()),
+        )
+        .unwrap();
+        assert!(load(&cache_path, false, &features).is_some());
+
+        std::fs::create_dir(dir.path().join(".cargo")).unwrap();
+        std::fs::write(
+            dir.path().join(".cargo").join("config.toml"),
+            "[patch.crates-io]\nbar = { path = \"../bar\" }\n",
+        )
+        .unwrap();
+        assert!(load(&cache_path, false, &features).is_none());
+    }
+
+    #[test]
+    fn target_dir_from_cargo_config() {
+        let dir = tempfile::tempdir().unwrap();
+        let workspace = dir.path().join("workspace");
+        std::fs::create_dir_all(workspace.join(".cargo")).unwrap();
+        assert_eq!(target_dir(&workspace, None), workspace.join("target"));
+        std::fs::write(
+            workspace.join(".cargo").join("config.toml"),
+            "[build]\ntarget-dir = \"../out\"\n",
+        )
+        .unwrap();
+        assert_eq!(
+            target_dir(&workspace, None),
+            workspace.canonicalize().unwrap().join("../out")
+        );
+        assert_eq!(
+            target_dir(&workspace, Some("/tmp/target".into())),
+            PathBuf::from("/tmp/target")
+        );
+    }
+
+    #[test]
+    fn rebuilt_when_invalidated() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir(dir.path().join("src")).unwrap();
+        std::fs::write(dir.path().join("src").join("lib.rs"), "").unwrap();
+        let manifest_path = dir.path().join("Cargo.toml");
+        let write_manifest = |name: &str| {
+            std::fs::write(
+                &manifest_path,
+                format!("[package]\nname = \"{name}\"\nversion = \"0.1.0\"\n\n[workspace]\n"),
+            )
+            .unwrap();
+        };
+        let package_names = |index: &CrateIndex| {
+            index
+                .package_infos
+                .keys()
+                .map(|pkg_id| pkg_id.name().to_owned())
+                .collect::<Vec<_>>()
+        };
+
+        let features = FeatureOptions::default();
+        write_manifest("foo");
+        let index = CrateIndex::new(dir.path(), true, true, &features).unwrap();
+        assert_eq!(package_names(&index), vec!["foo"]);
+        let cache_path = cache_path(dir.path());
+        assert!(load(&cache_path, true, &features).is_some());
+
+        write_manifest("foobar");
+        let index = CrateIndex::new(dir.path(), true, true, &features).unwrap();
+        assert_eq!(package_names(&index), vec!["foobar"]);
+        let cached = load(&cache_path, true, &features).unwrap();
+        assert_eq!(cached.packages[0].name, "foobar");
+    }
+
+    #[test]
+    fn preserved_when_removed() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache_path = dir.path().join("cackle").join("metadata.json");
+        std::fs::create_dir_all(cache_path.parent().unwrap()).unwrap();
+        std::fs::write(&cache_path, "{}").unwrap();
+        preserving(&cache_path, || {
+            std::fs::remove_dir_all(dir.path().join("cackle"))?;
+            Ok(())
+        })
+        .unwrap();
+        assert_eq!(std::fs::read_to_string(&cache_path).unwrap(), "{}");
+    }
+}
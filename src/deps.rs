@@ -23,6 +23,29 @@ pub(crate) fn source_files_from_rustc_args(
         .collect())
 }
 
+/// Returns where rustc wrote the deps file for the compilation that produced `output_path`, which
+/// is an rlib or an object file. Rustc names all outputs of a compilation with the crate name
+/// followed by the extra filename, e.g. `libfoo-0188200cb614ae3d.rlib` or
+/// `foo-0188200cb614ae3d.foo.6b5a1b1e-cgu.0.rcgu.o`, and the deps file is `foo-0188200cb614ae3d.d`.
+pub(crate) fn deps_path_for_output(output_path: &Path) -> Option<PathBuf> {
+    let filename = output_path.file_name()?.to_str()?;
+    let filename = if output_path.extension().is_some_and(|ext| ext == "rlib") {
+        filename.strip_prefix("lib")?
+    } else {
+        filename
+    };
+    let (stem, _) = filename.split_once('.')?;
+    Some(output_path.with_file_name(format!("{stem}.d")))
+}
+
+/// Returns the source files listed in the deps file at `deps_path`. The crate root is first.
+/// Relative paths are as written by rustc, so are relative to the directory rustc was run in.
+pub(crate) fn source_files_from_deps_file(deps_path: &Path) -> Result<Vec<PathBuf>> {
+    let deps = std::fs::read_to_string(deps_path)
+        .with_context(|| format!("Failed to read deps file `{}`", deps_path.display()))?;
+    parse_deps(&deps)
+}
+
 fn parse_deps(deps_text: &str) -> Result<Vec<PathBuf>> {
     let mut deps = Vec::new();
     for line in deps_text.lines() {
@@ -72,9 +95,11 @@ fn deps_path_from_rustc_args(mut args: impl Iterator<Item = String>) -> Result<O
 
 #[cfg(test)]
 mod tests {
+    use super::deps_path_for_output;
     use super::deps_path_from_rustc_args;
     use super::parse_deps;
     use anyhow::Result;
+    use std::path::Path;
     use std::path::PathBuf;
 
     fn deps_path(args: &[&str]) -> Result<Option<PathBuf>> {
@@ -155,4 +180,18 @@ mod tests {
             &["foo/src/lib.rs", "/some/absolute/path/extra.rs"]
         )
     }
+
+    #[test]
+    fn test_deps_path_for_output() {
+        let dir = Path::new("/t/debug/deps");
+        assert_eq!(
+            deps_path_for_output(&dir.join("libfoo-0188.rlib")),
+            Some(dir.join("foo-0188.d"))
+        );
+        assert_eq!(
+            deps_path_for_output(&dir.join("libc-12ab.libc.6b5a1b1e-cgu.0.rcgu.o")),
+            Some(dir.join("libc-12ab.d"))
+        );
+        assert_eq!(deps_path_for_output(&dir.join("liblibc")), None);
+    }
 }
@@ -0,0 +1,278 @@
+//! The public interface for running our analysis from other programs. Everything here is a façade
+//! over our internal types, which are free to change. The items re-exported from the crate root are
+//! the only ones covered by semver.
+
+use crate::location::SourceLocation;
+use crate::problem;
+use crate::ui::collecting::CollectingUi;
+use crate::App;
+use crate::Args;
+use anyhow::anyhow;
+use anyhow::Result;
+use clap::Parser;
+use std::ffi::OsString;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::sync::Mutex;
+
+pub use crate::problem::Severity;
+
+/// Checks a workspace against a `cackle.toml`.
+///
+/// Programs using this must call [`crate::handle_wrapped_binaries`] at the start of `main`.
+#[derive(Debug, Clone)]
+pub struct Cackle {
+    config_path: PathBuf,
+    manifest_dir: PathBuf,
+    features: Vec<String>,
+    target: Option<String>,
+    jobs: Option<usize>,
+}
+
+/// The problems found by [`Cackle::analyze`].
+#[derive(Debug, Clone, Default)]
+pub struct ProblemList {
+    problems: Vec<Problem>,
+}
+
+/// A problem found while checking.
+#[derive(Debug, Clone)]
+pub struct Problem {
+    inner: problem::Problem,
+    severity: Severity,
+}
+
+/// A single use of an API that a package isn't permitted to use.
+#[derive(Debug, Clone)]
+pub struct ApiUsage {
+    api: String,
+    from: String,
+    to: String,
+    location: SourceLocation,
+}
+
+impl Cackle {
+    /// Creates a check of the workspace in `manifest_dir` (the directory containing `Cargo.toml`)
+    /// using the configuration at `config_path`.
+    pub fn new(config_path: impl Into<PathBuf>, manifest_dir: impl Into<PathBuf>) -> Self {
+        Self {
+            config_path: config_path.into(),
+            manifest_dir: manifest_dir.into(),
+            features: Vec::new(),
+            target: None,
+            jobs: None,
+        }
+    }
+
+    /// Sets features to build with. These replace any features in the config.
+    pub fn features(mut self, features: Vec<String>) -> Self {
+        self.features = features;
+        self
+    }
+
+    /// Sets the target triple to build for. Defaults to the host.
+    pub fn target(mut self, target: impl Into<String>) -> Self {
+        self.target = Some(target.into());
+        self
+    }
+
+    /// Sets the number of threads used to analyse object files. Defaults to the number of CPUs.
+    pub fn jobs(mut self, jobs: usize) -> Self {
+        self.jobs = Some(jobs);
+        self
+    }
+
+    /// Builds the workspace and checks it against the configuration, returning the problems found.
+    /// As with `cackle check`, analysis stops once problems with severity [`Severity::Error`] have
+    /// been found, so not all problems may be returned. An error is returned if the check couldn't
+    /// be run, e.g. because the build failed. Output from cargo is passed through to our stdout and
+    /// stderr.
+    pub fn analyze(&self) -> Result<ProblemList> {
+        let args = self.args()?;
+        let (abort_sender, abort_recv) = std::sync::mpsc::channel();
+        let collected = Arc::new(Mutex::new(Vec::new()));
+        let ui = CollectingUi::new(collected.clone(), abort_sender.clone());
+        let app = App::new(args, abort_sender, Some(Box::new(ui)))?;
+        app.run_without_reporting(abort_recv)?;
+        let collected = std::mem::take(&mut *collected.lock().unwrap());
+        Ok(ProblemList {
+            problems: collected
+                .into_iter()
+                .map(|(inner, severity)| Problem { inner, severity })
+                .collect(),
+        })
+    }
+
+    /// Returns the arguments that are equivalent to running `cackle check`. We go via the
+    /// command-line parser so that we get the same defaults.
+    fn args(&self) -> Result<Args> {
+        let mut argv: Vec<OsString> = vec![
+            "cackle".into(),
+            "--quiet".into(),
+            "--path".into(),
+            self.manifest_dir.clone().into(),
+            "--cackle-path".into(),
+            self.config_path.clone().into(),
+        ];
+        for feature in &self.features {
+            argv.push("--features".into());
+            argv.push(feature.into());
+        }
+        if let Some(target) = &self.target {
+            argv.push("--target".into());
+            argv.push(target.into());
+        }
+        if let Some(jobs) = self.jobs {
+            argv.push("--jobs".into());
+            argv.push(jobs.to_string().into());
+        }
+        argv.push("check".into());
+        let mut args = Args::try_parse_from(argv).map_err(|error| anyhow!("{error}"))?;
+        args.colour = crate::colour::Colour::Never;
+        Ok(args)
+    }
+}
+
+impl ProblemList {
+    pub fn iter(&self) -> impl Iterator<Item = &Problem> {
+        self.problems.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.problems.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.problems.is_empty()
+    }
+
+    /// Returns whether any of the problems is an error, which `cackle check` would fail on.
+    pub fn has_errors(&self) -> bool {
+        self.problems
+            .iter()
+            .any(|problem| problem.severity == Severity::Error)
+    }
+}
+
+impl IntoIterator for ProblemList {
+    type Item = Problem;
+    type IntoIter = std::vec::IntoIter<Problem>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.problems.into_iter()
+    }
+}
+
+impl Problem {
+    pub fn severity(&self) -> Severity {
+        self.severity
+    }
+
+    /// The kind of problem, e.g. "DisallowedApiUsage". These are the same as the kinds in JSON
+    /// output.
+    pub fn kind(&self) -> &'static str {
+        crate::ui::json::kind(&self.inner)
+    }
+
+    /// The name of the package that the problem relates to, if any.
+    pub fn package(&self) -> Option<&str> {
+        self.inner.pkg_id().map(|pkg_id| pkg_id.name())
+    }
+
+    /// The permissions (APIs) that the problem relates to.
+    pub fn permissions(&self) -> Vec<String> {
+        crate::ui::json::permissions(&self.inner)
+    }
+
+    /// Source locations related to the problem.
+    pub fn locations(&self) -> Vec<SourceLocation> {
+        self.inner.source_locations().into_iter().cloned().collect()
+    }
+
+    /// For problems with kind "DisallowedApiUsage", each usage of a disallowed API.
+    pub fn api_usages(&self) -> Vec<ApiUsage> {
+        let problem::Problem::DisallowedApiUsage(usages) = &self.inner else {
+            return Vec::new();
+        };
+        usages
+            .usages
+            .iter()
+            .flat_map(|(api, usages)| {
+                usages.iter().map(move |usage| ApiUsage {
+                    api: api.to_string(),
+                    from: usage.from.to_string(),
+                    to: usage.to.to_string(),
+                    location: usage.source_location.clone(),
+                })
+            })
+            .collect()
+    }
+}
+
+impl std::fmt::Display for Problem {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.inner.fmt(f)
+    }
+}
+
+impl ApiUsage {
+    /// The API that was used.
+    pub fn api(&self) -> &str {
+        &self.api
+    }
+
+    /// The function (or other item) that used the API.
+    pub fn from(&self) -> &str {
+        &self.from
+    }
+
+    /// What was referenced that belongs to the API.
+    pub fn to(&self) -> &str {
+        &self.to
+    }
+
+    /// Where the usage occurred.
+    pub fn location(&self) -> &SourceLocation {
+        &self.location
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crate_index::testing::pkg_id;
+    use crate::crate_index::CrateSel;
+    use crate::problem::testing::api_usage;
+    use std::collections::BTreeMap;
+    use std::path::Path;
+
+    #[test]
+    fn api_usages() {
+        let usage = api_usage(
+            "crab1::connect",
+            "std::net::TcpStream::connect",
+            SourceLocation::new(Path::new("src/lib.rs"), 10, Some(5)),
+        );
+        let usages = crate::problem::testing::api_usages(
+            CrateSel::Primary(pkg_id("crab1")),
+            BTreeMap::from([("net".into(), vec![usage])]),
+        );
+        let problem = Problem {
+            inner: problem::Problem::DisallowedApiUsage(usages),
+            severity: Severity::Error,
+        };
+        assert_eq!(problem.kind(), "DisallowedApiUsage");
+        assert_eq!(problem.package(), Some("crab1"));
+        assert_eq!(problem.permissions(), vec!["net"]);
+        let api_usages = problem.api_usages();
+        assert_eq!(api_usages.len(), 1);
+        assert_eq!(api_usages[0].api(), "net");
+        assert_eq!(api_usages[0].from(), "crab1::connect");
+        assert_eq!(api_usages[0].to(), "std::net::TcpStream::connect");
+        assert_eq!(api_usages[0].location().line(), 10);
+        let list = ProblemList {
+            problems: vec![problem],
+        };
+        assert!(list.has_errors());
+    }
+}
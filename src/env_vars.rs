@@ -0,0 +1,231 @@
+//! Works out which environment variable a call to `std::env::var` or `std::env::var_os` reads, so
+//! that crates can be permitted to read particular variables without being allowed to read all of
+//! them. We can only tell if the name is passed as a string literal, which we find by tokenising the
+//! source at the location of the call.
+
+use crate::location::SourceLocation;
+use crate::names::Name;
+use fxhash::FxHashMap;
+use rustc_ap_rustc_lexer::LiteralKind;
+use rustc_ap_rustc_lexer::TokenKind;
+use std::fmt::Display;
+use std::path::PathBuf;
+
+/// Functions that read a single environment variable, whose name is their first argument.
+const SINGLE_VAR_FUNCTIONS: &[&str] = &["var", "var_os", "_var", "_var_os"];
+
+/// Functions that read all environment variables.
+const ALL_VARS_FUNCTIONS: &[&str] = &["vars", "vars_os"];
+
+/// The environment variable read by a usage of an API.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub(crate) enum EnvVar {
+    Named(String),
+    /// The name isn't a string literal, or all variables are read, e.g. via `std::env::vars`.
+    Unknown,
+}
+
+/// Returns whether `name` is a function from `std::env` that reads environment variables.
+pub(crate) fn is_env_read(name: &Name) -> bool {
+    let [std, env, function] = name.parts.as_slice() else {
+        return false;
+    };
+    &**std == "std"
+        && &**env == "env"
+        && (SINGLE_VAR_FUNCTIONS.contains(&&**function)
+            || ALL_VARS_FUNCTIONS.contains(&&**function))
+}
+
+/// Finds which environment variables are read at particular source locations. A file often
+/// contains several reads, so we keep the tokens of each file that we've looked at.
+#[derive(Default)]
+pub(crate) struct EnvVarFinder {
+    /// None if the file couldn't be read.
+    files: FxHashMap<PathBuf, Option<TokenisedSource>>,
+}
+
+impl EnvVarFinder {
+    /// Returns the variable read by the call at `location`.
+    pub(crate) fn var_read_at(&mut self, location: &SourceLocation) -> EnvVar {
+        if location.is_unknown() {
+            return EnvVar::Unknown;
+        }
+        self.files
+            .entry(location.filename().to_owned())
+            .or_insert_with(|| {
+                std::fs::read_to_string(location.filename())
+                    .ok()
+                    .map(TokenisedSource::new)
+            })
+            .as_ref()
+            .and_then(|source| source.var_read_at(location.line(), location.column()))
+            .map_or(EnvVar::Unknown, EnvVar::Named)
+    }
+}
+
+struct TokenisedSource {
+    source: String,
+    /// The byte offset at which each line starts.
+    line_starts: Vec<usize>,
+    /// All tokens other than whitespace and comments.
+    tokens: Vec<Token>,
+}
+
+#[derive(Clone, Copy)]
+struct Token {
+    kind: TokenKind,
+    start: usize,
+    len: usize,
+}
+
+impl TokenisedSource {
+    fn new(source: String) -> Self {
+        let line_starts = std::iter::once(0)
+            .chain(source.match_indices('\n').map(|(offset, _)| offset + 1))
+            .collect();
+        let mut tokens = Vec::new();
+        let mut start = 0;
+        for token in rustc_ap_rustc_lexer::tokenize(&source) {
+            if !matches!(
+                token.kind,
+                TokenKind::Whitespace
+                    | TokenKind::LineComment { .. }
+                    | TokenKind::BlockComment { .. }
+            ) {
+                tokens.push(Token {
+                    kind: token.kind,
+                    start,
+                    len: token.len,
+                });
+            }
+            start += token.len;
+        }
+        Self {
+            source,
+            line_starts,
+            tokens,
+        }
+    }
+
+    fn text(&self, token: &Token) -> &str {
+        &self.source[token.start..token.start + token.len]
+    }
+
+    /// Looks for a call like `var("NAME")` starting on `line` at or after `column`, both of which
+    /// are 1-based, and returns the name if found. Without a column, we can't tell which call is
+    /// meant if there's more than one on the line, so we only return a name if there's just one.
+    fn var_read_at(&self, line: u32, column: Option<u32>) -> Option<String> {
+        let line_index = line.checked_sub(1)? as usize;
+        let line_start = *self.line_starts.get(line_index)?;
+        let line_end = self
+            .line_starts
+            .get(line_index + 1)
+            .copied()
+            .unwrap_or(self.source.len());
+        let start = line_start + column.map_or(0, |column| column.saturating_sub(1) as usize);
+        let first = self.tokens.partition_point(|token| token.start < start);
+        let tokens = &self.tokens[first..];
+
+        // The call needs to start on the line of the usage, but its arguments might be on the
+        // lines following.
+        let mut calls = tokens
+            .windows(2)
+            .take_while(|window| window[0].start < line_end)
+            .enumerate()
+            .filter(|(_, window)| {
+                window[0].kind == TokenKind::Ident
+                    && SINGLE_VAR_FUNCTIONS.contains(&self.text(&window[0]))
+                    && window[1].kind == TokenKind::OpenParen
+            })
+            .map(|(index, _)| index);
+        let call_index = calls.next()?;
+        if column.is_none() && calls.next().is_some() {
+            return None;
+        }
+        let [literal, after, ..] = tokens.get(call_index + 2..)? else {
+            return None;
+        };
+        if !matches!(
+            literal.kind,
+            TokenKind::Literal {
+                kind: LiteralKind::Str { terminated: true },
+                ..
+            }
+        ) || !matches!(after.kind, TokenKind::CloseParen | TokenKind::Comma)
+        {
+            return None;
+        }
+        let name = self.text(literal).strip_prefix('"')?.strip_suffix('"')?;
+        // We don't bother unescaping. Variable names that need escaping are unlikely.
+        if name.contains('\\') {
+            return None;
+        }
+        Some(name.to_owned())
+    }
+}
+
+impl Display for EnvVar {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            EnvVar::Named(name) => write!(f, "reads `{name}`"),
+            EnvVar::Unknown => write!(f, "reads unknown variable"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::TokenisedSource;
+
+    #[track_caller]
+    fn check(source: &str, line: u32, column: Option<u32>, expected: Option<&str>) {
+        assert_eq!(
+            TokenisedSource::new(source.to_owned())
+                .var_read_at(line, column)
+                .as_deref(),
+            expected,
+            "{source}"
+        );
+    }
+
+    #[test]
+    fn literal_names() {
+        check(
+            r#"let home = std::env::var("HOME");"#,
+            1,
+            Some(12),
+            Some("HOME"),
+        );
+        check(
+            r#"let home = std::env::var("HOME");"#,
+            1,
+            None,
+            Some("HOME"),
+        );
+        check(
+            "fn f() {\n    env::var_os(\n        \"PATH\",\n    )\n}",
+            2,
+            Some(5),
+            Some("PATH"),
+        );
+    }
+
+    #[test]
+    fn unknown_names() {
+        check("std::env::var(name)", 1, None, None);
+        check("std::env::var(\"A\".to_owned())", 1, None, None);
+        check("std::env::var(\"A\\u{42}\")", 1, None, None);
+        check("std::env::vars()", 1, None, None);
+        // The call starts on a different line to the usage.
+        check("foo();\nstd::env::var(\"HOME\")", 1, None, None);
+    }
+
+    #[test]
+    fn several_calls_on_a_line() {
+        let source = r#"let (a, b) = (env::var("A"), env::var("B"));"#;
+        check(source, 1, Some(15), Some("A"));
+        check(source, 1, Some(30), Some("B"));
+        // Without a column, we can't tell which call was meant.
+        check(source, 1, None, None);
+    }
+}
@@ -1,7 +1,15 @@
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub(crate) enum AppEvent {
     /// Shutdown in progress. The UI should close.
     Shutdown,
     /// New problems have been added to the problem store.
     ProblemsAdded,
+    /// With `--watch`, the check has finished and we're waiting for something to change. Contains
+    /// the error that the check failed with, if any.
+    WaitingForChanges(Option<String>),
+    /// With `--watch`, something changed and we're checking again.
+    Rescan,
+    /// The check couldn't be completed, so the problems reported so far may be incomplete. Sent
+    /// before `Shutdown`.
+    CheckFailed,
 }
@@ -1,6 +1,7 @@
 use anyhow::Context;
 use anyhow::Result;
 use std::path::Path;
+use std::path::PathBuf;
 
 /// Writes `contents` to `path`. The write is first done to a temporary filename then renamed to
 /// `path`. This means that other processes will either see the old contents or the new contents,
@@ -22,3 +23,11 @@ pub(crate) fn write_atomic(path: &Path, contents: &str) -> Result<()> {
 pub(crate) fn read_to_string(path: &Path) -> Result<String> {
     std::fs::read_to_string(path).with_context(|| format!("Failed to read {}", path.display()))
 }
+
+/// Returns where cargo keeps its registry and other state: `CARGO_HOME` if set, otherwise
+/// `~/.cargo`.
+pub(crate) fn cargo_home() -> Option<PathBuf> {
+    std::env::var_os("CARGO_HOME")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".cargo")))
+}
@@ -0,0 +1,185 @@
+//! Records, for each problem that has been reported, when it was first seen and in how many runs it
+//! has been reported. This is stored in a small file in the target directory and lets us
+//! distinguish new problems from long-standing ones.
+
+use anyhow::Context;
+use anyhow::Result;
+use fxhash::FxHashSet;
+use serde::Deserialize;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::path::PathBuf;
+use std::time::SystemTime;
+
+const SECONDS_PER_DAY: u64 = 24 * 60 * 60;
+
+/// The history as stored on disk. Keyed by problem stable ID.
+#[derive(Serialize, Deserialize, Default, Debug)]
+struct History {
+    #[serde(default)]
+    problems: BTreeMap<String, ProblemHistory>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct ProblemHistory {
+    /// When the problem was first seen, in seconds since the Unix epoch.
+    first_seen: u64,
+    /// The number of runs in which the problem was reported.
+    runs: u64,
+}
+
+pub(crate) struct HistoryFile {
+    path: PathBuf,
+    history: History,
+    /// Problems that we've already counted in this run.
+    seen_this_run: FxHashSet<String>,
+    /// The current time, in seconds since the Unix epoch.
+    now: u64,
+}
+
+/// Returns the path of the history file for the workspace at `root_path`. We keep it in the target
+/// directory so that it doesn't end up being committed along with the workspace's sources.
+pub(crate) fn history_path(root_path: &Path) -> PathBuf {
+    root_path.join("target").join("cackle").join("history.toml")
+}
+
+impl HistoryFile {
+    pub(crate) fn load(path: &Path) -> Self {
+        let now = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .map_or(0, |duration| duration.as_secs());
+        Self::load_at(path, now)
+    }
+
+    /// Loads the history from `path`. The history only affects how problems are described, so if
+    /// the file can't be read, we warn and start again with an empty history.
+    fn load_at(path: &Path, now: u64) -> Self {
+        let history = if path.exists() {
+            read_history(path).unwrap_or_else(|error| {
+                eprintln!("Warning: Ignoring problem history: {error:#}");
+                History::default()
+            })
+        } else {
+            History::default()
+        };
+        Self {
+            path: path.to_owned(),
+            history,
+            seen_this_run: FxHashSet::default(),
+            now,
+        }
+    }
+
+    /// Records that the problem with the specified ID was reported in this run. Returns what we
+    /// knew about the problem from previous runs, or `None` if it's new.
+    pub(crate) fn problem_seen(&mut self, stable_id: &str) -> Option<ProblemHistory> {
+        let entry = self
+            .history
+            .problems
+            .entry(stable_id.to_owned())
+            .or_insert(ProblemHistory {
+                first_seen: self.now,
+                runs: 0,
+            });
+        if self.seen_this_run.insert(stable_id.to_owned()) {
+            entry.runs += 1;
+        }
+        let previous_runs = entry.runs - 1;
+        (previous_runs > 0).then_some(ProblemHistory {
+            first_seen: entry.first_seen,
+            runs: previous_runs,
+        })
+    }
+
+    /// Returns a short description of how long a problem has been around, given what
+    /// `problem_seen` returned for it.
+    pub(crate) fn describe_age(&self, previous: Option<ProblemHistory>) -> String {
+        let Some(previous) = previous else {
+            return "NEW".to_owned();
+        };
+        let days = self.now.saturating_sub(previous.first_seen) / SECONDS_PER_DAY;
+        let first_seen = match days {
+            0 => "today".to_owned(),
+            1 => "1 day ago".to_owned(),
+            _ => format!("{days} days ago"),
+        };
+        format!(
+            "first seen {first_seen}, reported in {} runs",
+            previous.runs + 1
+        )
+    }
+
+    /// Writes the history back to disk. If no problems have ever been recorded, we avoid creating
+    /// the file.
+    pub(crate) fn save(&self) -> Result<()> {
+        if self.history.problems.is_empty() && !self.path.exists() {
+            return Ok(());
+        }
+        if let Some(dir) = self.path.parent() {
+            std::fs::create_dir_all(dir)
+                .with_context(|| format!("Failed to create `{}`", dir.display()))?;
+        }
+        crate::fs::write_atomic(&self.path, &toml::to_string(&self.history)?)
+    }
+}
+
+fn read_history(path: &Path) -> Result<History> {
+    let contents = crate::fs::read_to_string(path)?;
+    toml::from_str(&contents).with_context(|| format!("Failed to parse `{}`", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn history_across_runs() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = history_path(dir.path());
+        let day0 = 1_000_000;
+        let problem = "`crab1` uses API `fs`";
+
+        let mut history = HistoryFile::load_at(&path, day0);
+        history.save().unwrap();
+        assert!(!path.exists());
+        let previous = history.problem_seen(problem);
+        assert_eq!(previous, None);
+        assert_eq!(history.describe_age(previous), "NEW");
+        // Reporting the same problem again in the same run doesn't count as another run.
+        assert_eq!(history.problem_seen(problem), None);
+        history.save().unwrap();
+
+        let mut history = HistoryFile::load_at(&path, day0 + 3 * SECONDS_PER_DAY);
+        let previous = history.problem_seen(problem);
+        assert_eq!(
+            history.describe_age(previous),
+            "first seen 3 days ago, reported in 2 runs"
+        );
+        let previous = history.problem_seen("other");
+        assert_eq!(history.describe_age(previous), "NEW");
+        history.save().unwrap();
+
+        let mut history = HistoryFile::load_at(&path, day0 + 4 * SECONDS_PER_DAY);
+        assert_eq!(
+            history.problem_seen(problem),
+            Some(ProblemHistory {
+                first_seen: day0,
+                runs: 2
+            })
+        );
+    }
+
+    #[test]
+    fn unparsable_history_is_ignored() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("history.toml");
+        std::fs::write(&path, "not valid toml [").unwrap();
+        let mut history = HistoryFile::load_at(&path, 1_000_000);
+        assert_eq!(history.problem_seen("problem"), None);
+        history.save().unwrap();
+        assert!(HistoryFile::load_at(&path, 1_000_000)
+            .problem_seen("problem")
+            .is_some());
+    }
+}
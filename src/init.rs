@@ -0,0 +1,125 @@
+//! Support for `cackle init`, which writes a starter config without going through the prompts that
+//! we'd otherwise show on the first run.
+
+use crate::config::MAX_VERSION;
+use crate::crate_index::CrateIndex;
+use anyhow::bail;
+use anyhow::Result;
+use clap::Parser;
+use std::collections::BTreeSet;
+use std::fmt::Write;
+use std::path::Path;
+
+#[derive(Parser, Debug, Clone)]
+pub(crate) struct InitOptions {
+    /// Overwrite the config file if it already exists.
+    #[clap(long)]
+    force: bool,
+}
+
+/// Writes a starter config to `config_path`, mentioning any packages in `crate_index` that will
+/// need permissions that every package is denied by default.
+pub(crate) fn write_initial_config(
+    config_path: &Path,
+    crate_index: &CrateIndex,
+    options: &InitOptions,
+) -> Result<()> {
+    if config_path.exists() && !options.force {
+        bail!(
+            "`{}` already exists. Pass --force to overwrite it",
+            config_path.display()
+        );
+    }
+    let mut proc_macros = BTreeSet::new();
+    let mut build_scripts = BTreeSet::new();
+    for pkg_id in crate_index.package_ids() {
+        let Some(info) = crate_index.package_info(pkg_id) else {
+            continue;
+        };
+        if info.is_proc_macro {
+            proc_macros.insert(pkg_id.name());
+        }
+        if info.has_build_script {
+            build_scripts.insert(pkg_id.name());
+        }
+    }
+    crate::fs::write_atomic(config_path, &initial_config(&proc_macros, &build_scripts))
+}
+
+fn initial_config(proc_macros: &BTreeSet<&str>, build_scripts: &BTreeSet<&str>) -> String {
+    let mut out = String::new();
+    let _ = writeln!(out, "[common]");
+    let _ = writeln!(out, "version = {MAX_VERSION}");
+    let _ = writeln!(out, "import_std = [");
+    for api in ["fs", "net", "process"] {
+        let _ = writeln!(out, "    \"{api}\",");
+    }
+    let _ = writeln!(out, "]");
+
+    let _ = writeln!(out);
+    if !build_scripts.is_empty() {
+        let _ = writeln!(
+            out,
+            "# {} package(s) have build scripts, which will be run during the build:",
+            build_scripts.len()
+        );
+        let names: Vec<&str> = build_scripts.iter().copied().collect();
+        let _ = writeln!(out, "#   {}", names.join(", "));
+    }
+    let _ = writeln!(
+        out,
+        "# Uncomment to run build scripts in a sandbox. This requires bwrap (bubblewrap)."
+    );
+    let _ = writeln!(out, "# [sandbox]");
+    let _ = writeln!(out, "# kind = \"Bubblewrap\"");
+
+    for pkg_name in proc_macros {
+        let _ = writeln!(out);
+        let _ = writeln!(
+            out,
+            "# `{pkg_name}` is a proc macro, so runs as part of the compiler."
+        );
+        let _ = writeln!(out, "# [pkg.{pkg_name}]");
+        let _ = writeln!(out, "# allow_proc_macro = true");
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crate_index::testing::index_with_package_names;
+
+    #[test]
+    fn initial_config_parses() {
+        let dir = tempfile::tempdir().unwrap();
+        let config_path = dir.path().join("cackle.toml");
+        let proc_macros = ["pmacro1"].into_iter().collect();
+        let build_scripts = ["crab1", "crab2"].into_iter().collect();
+        let toml = initial_config(&proc_macros, &build_scripts);
+        assert!(toml.contains("# [pkg.pmacro1]\n# allow_proc_macro = true"));
+        assert!(toml.contains("#   crab1, crab2\n"));
+        std::fs::write(&config_path, &toml).unwrap();
+        let crate_index = index_with_package_names(&["crab1", "crab2", "pmacro1"]);
+        let config = crate::config::parse_file(&config_path, &crate_index).unwrap();
+        assert!(config.apis.contains_key(&"fs".into()));
+        assert!(config.apis.contains_key(&"process".into()));
+    }
+
+    #[test]
+    fn refuses_to_overwrite() {
+        let dir = tempfile::tempdir().unwrap();
+        let config_path = dir.path().join("cackle.toml");
+        std::fs::write(&config_path, "existing").unwrap();
+        let crate_index = index_with_package_names(&[]);
+        let options = InitOptions { force: false };
+        assert!(write_initial_config(&config_path, &crate_index, &options).is_err());
+        assert_eq!(std::fs::read_to_string(&config_path).unwrap(), "existing");
+
+        let options = InitOptions { force: true };
+        write_initial_config(&config_path, &crate_index, &options).unwrap();
+        assert!(std::fs::read_to_string(&config_path)
+            .unwrap()
+            .starts_with("[common]"));
+    }
+}
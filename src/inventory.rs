@@ -0,0 +1,167 @@
+//! Collects every API usage that we find, whether or not it's allowed, so that we can write out a
+//! complete inventory of which crates use which APIs. Only done if requested with --inventory,
+//! since otherwise we only need to keep track of disallowed usages.
+
+use crate::config::CrateName;
+use crate::config::PermissionName;
+use crate::crate_index::CrateSel;
+use crate::crate_index::PackageId;
+use crate::location::SourceLocation;
+use crate::problem::ApiUsages;
+use anyhow::Result;
+use fxhash::FxHashMap;
+use fxhash::FxHashSet;
+use serde::Serialize;
+use std::path::Path;
+
+#[derive(Default)]
+pub(crate) struct Inventory {
+    /// For each crate and API, the distinct places where the crate uses the API and the name used
+    /// at each. The same code is often linked into several binaries and the usages of a binary can
+    /// be looked at more than once, so we dedup rather than just counting.
+    usages: FxHashMap<(CrateSel, PermissionName), FxHashSet<(SourceLocation, String)>>,
+    /// The number of bytes of code and data in the objects of each package. Only populated if
+    /// requested with --crate-sizes.
+    sizes: FxHashMap<PackageId, u64>,
+}
+
+#[derive(Serialize, Debug)]
+struct Output {
+    usages: Vec<Entry>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    sizes: Vec<SizeEntry>,
+}
+
+#[derive(Serialize, Debug, PartialEq, Eq, PartialOrd, Ord)]
+struct SizeEntry {
+    #[serde(rename = "crate")]
+    crate_name: String,
+    version: String,
+    bytes: u64,
+}
+
+#[derive(Serialize, Debug, PartialEq, Eq, PartialOrd, Ord)]
+struct Entry {
+    #[serde(rename = "crate")]
+    crate_name: String,
+    version: String,
+    permission: String,
+    count: usize,
+}
+
+impl Inventory {
+    pub(crate) fn record(&mut self, api_usages: &ApiUsages) {
+        for (permission, usages) in &api_usages.usages {
+            let seen = self
+                .usages
+                .entry((api_usages.crate_sel.clone(), permission.clone()))
+                .or_default();
+            for usage in usages {
+                seen.insert((usage.source_location.clone(), usage.to_name.to_string()));
+            }
+        }
+    }
+
+    /// Records the sizes of packages that were linked into a binary. The same package is generally
+    /// linked into several binaries, so we keep the largest size seen rather than adding them up.
+    pub(crate) fn record_sizes(&mut self, sizes: FxHashMap<PackageId, u64>) {
+        for (pkg_id, bytes) in sizes {
+            let size = self.sizes.entry(pkg_id).or_default();
+            *size = (*size).max(bytes);
+        }
+    }
+
+    fn output(&self) -> Output {
+        let mut usages: Vec<Entry> = self
+            .usages
+            .iter()
+            .map(|((crate_sel, permission), seen)| Entry {
+                crate_name: CrateName::from(crate_sel).to_string(),
+                version: crate_sel.pkg_id().version().to_string(),
+                permission: permission.to_string(),
+                count: seen.len(),
+            })
+            .collect();
+        usages.sort();
+        let mut sizes: Vec<SizeEntry> = self
+            .sizes
+            .iter()
+            .map(|(pkg_id, bytes)| SizeEntry {
+                crate_name: pkg_id.name().to_owned(),
+                version: pkg_id.version().to_string(),
+                bytes: *bytes,
+            })
+            .collect();
+        sizes.sort();
+        Output { usages, sizes }
+    }
+
+    pub(crate) fn write(&self, path: &Path) -> Result<()> {
+        let json = serde_json::to_string_pretty(&self.output())?;
+        crate::fs::write_atomic(path, &json)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crate_index::testing::pkg_id;
+    use crate::problem::testing::api_usage;
+    use std::collections::BTreeMap;
+
+    fn api_usages(crate_name: &'static str, permission: &'static str, lines: &[u32]) -> ApiUsages {
+        let usages = lines
+            .iter()
+            .map(|line| {
+                api_usage(
+                    "crab1::connect",
+                    "std::net::TcpStream::connect",
+                    SourceLocation::new(Path::new("src/lib.rs"), *line, None),
+                )
+            })
+            .collect();
+        crate::problem::testing::api_usages(
+            CrateSel::Primary(pkg_id(crate_name)),
+            BTreeMap::from([(PermissionName::from(permission), usages)]),
+        )
+    }
+
+    #[test]
+    fn counts_distinct_usages() {
+        let mut inventory = Inventory::default();
+        inventory.record(&api_usages("crab2", "net", &[10, 20]));
+        inventory.record(&api_usages("crab1", "net", &[10]));
+        // The same usages seen again, e.g. from another binary, aren't counted twice.
+        inventory.record(&api_usages("crab2", "net", &[20, 30]));
+        let json = serde_json::to_value(inventory.output()).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({
+                "usages": [
+                    {"crate": "crab1", "version": "0.0.0", "permission": "net", "count": 1},
+                    {"crate": "crab2", "version": "0.0.0", "permission": "net", "count": 3},
+                ]
+            })
+        );
+    }
+
+    #[test]
+    fn keeps_largest_size() {
+        let mut inventory = Inventory::default();
+        inventory.record_sizes([(pkg_id("crab1"), 100)].into_iter().collect());
+        inventory.record_sizes(
+            [(pkg_id("crab1"), 300), (pkg_id("crab2"), 50)]
+                .into_iter()
+                .collect(),
+        );
+        inventory.record_sizes([(pkg_id("crab1"), 200)].into_iter().collect());
+        let json = serde_json::to_value(inventory.output()).unwrap();
+        assert_eq!(
+            json["sizes"],
+            serde_json::json!([
+                {"crate": "crab1", "version": "0.0.0", "bytes": 300},
+                {"crate": "crab2", "version": "0.0.0", "bytes": 50},
+            ])
+        );
+    }
+}
@@ -0,0 +1,1080 @@
+//! Analyses rust crates and their dependent crates to see what categories of APIs and language
+//! features are used.
+//!
+//! Most users will want the `cackle` binary. This library allows running the same analysis from
+//! other programs. The items re-exported here are our stable API. Anything else that happens to be
+//! public isn't covered by semver.
+//!
+//! ```no_run
+//! fn main() -> anyhow::Result<()> {
+//!     // This must come first, since the build invokes the current executable.
+//!     cackle::handle_wrapped_binaries()?;
+//!     let problems = cackle::Cackle::new("cackle.toml", ".").analyze()?;
+//!     for problem in problems.iter() {
+//!         println!("{:?} {problem}", problem.severity());
+//!     }
+//!     Ok(())
+//! }
+//! ```
+
+#![forbid(unsafe_code)]
+#![cfg_attr(not(feature = "ui"), allow(dead_code, unused_variables))]
+
+mod build_script_checker;
+mod checker;
+mod colour;
+mod config;
+#[cfg(feature = "ui")]
+mod config_editor;
+mod config_validation;
+mod cowarc;
+mod crate_index;
+mod demangle;
+mod deps;
+mod embed;
+mod env_vars;
+pub(crate) mod events;
+pub(crate) mod fs;
+mod history;
+mod init;
+mod inventory;
+pub(crate) mod lazy;
+mod licenses;
+pub(crate) mod link_info;
+pub(crate) mod location;
+mod logging;
+mod names;
+mod outcome;
+pub(crate) mod problem;
+pub(crate) mod problem_store;
+mod proxy;
+mod reexports;
+mod resume;
+mod sandbox;
+mod summary;
+mod suppressions;
+pub(crate) mod symbol;
+mod symbol_graph;
+mod timing;
+mod tree;
+mod ui;
+mod unsafe_checker;
+#[cfg(feature = "ui")]
+mod watch;
+mod yanked;
+
+pub use embed::ApiUsage;
+pub use embed::Cackle;
+pub use embed::Problem;
+pub use embed::ProblemList;
+pub use embed::Severity;
+pub use location::SourceLocation;
+
+use anyhow::anyhow;
+use anyhow::bail;
+use anyhow::Context;
+use anyhow::Result;
+use checker::Checker;
+use clap::Parser;
+use clap::Subcommand;
+use crate_index::CrateIndex;
+use events::AppEvent;
+use log::info;
+use outcome::ExitCode;
+use outcome::Outcome;
+use problem_store::ProblemStoreRef;
+use proxy::cargo::CargoOptions;
+use proxy::rpc::Request;
+use resume::ResumeState;
+use std::path::Path;
+use std::path::PathBuf;
+use std::sync::mpsc::Receiver;
+use std::sync::mpsc::Sender;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::thread::JoinHandle;
+use summary::SummaryOptions;
+use symbol_graph::ScanOutputs;
+use tree::TreeOptions;
+
+#[derive(Parser, Debug, Clone, Default)]
+#[clap(version, about)]
+struct Args {
+    /// Directory containing crate to analyze. Defaults to current working
+    /// directory.
+    #[clap(short, long)]
+    path: Option<PathBuf>,
+
+    /// Path to cackle.toml. If not specified, looks in the directory containing
+    /// the crate to be analyzed. May be given more than once, in which case each
+    /// later file is merged into the config from the earlier ones. Edits are only
+    /// ever written to the first file.
+    #[clap(short, long, alias = "config")]
+    cackle_path: Vec<PathBuf>,
+
+    /// Print the mapping from paths to crate names. Useful for debugging.
+    #[clap(long, hide = true)]
+    print_path_to_crate_map: bool,
+
+    /// Print the names under each of the specified packages that could be used as API include
+    /// prefixes. These are derived from the symbols in the built binaries, so include both modules
+    /// and other top-level items. We can't tell whether they're public.
+    #[clap(long, value_name = "PACKAGE")]
+    print_exports: Vec<String>,
+
+    /// Print the APIs that each of the specified crates is allowed to use and whether each was
+    /// found to be used.
+    #[clap(long, value_name = "CRATE")]
+    print_permissions: Vec<String>,
+
+    /// Write to the specified file a JSON inventory of every API used by each crate, including
+    /// those that the crate is allowed to use, together with how many places use it. Always starts
+    /// from a clean build, since what was already built when resuming wouldn't be included.
+    #[clap(long, value_name = "FILE")]
+    inventory: Option<PathBuf>,
+
+    /// With --inventory, also include how many bytes of code and data each package contributes to
+    /// the objects that get linked. This requires reading each object an extra time.
+    #[clap(long, requires = "inventory")]
+    crate_sizes: bool,
+
+    /// Where to create the Unix socket over which cackle's rustc, linker and build script wrappers
+    /// talk to it. On Linux, a name starting with `@` selects an abstract socket. By default, a
+    /// socket is created in the temporary directory, or if its path would be too long, in a new
+    /// private directory under /tmp.
+    #[clap(long, value_name = "PATH")]
+    socket_path: Option<PathBuf>,
+
+    /// Instead of building, run the build script or rustc for the specified crate again, as it was
+    /// run when it last failed, but with the current config. Prints the command that was run and
+    /// its full output. Accepts a package name or a crate name such as `foo.build`. Useful for
+    /// debugging a build script failure.
+    #[clap(long, value_name = "CRATE")]
+    rerun_failed: Option<String>,
+
+    /// If set, warnings (e.g. due to unused permissions) are treated as errors, so cause an exit
+    /// code of 1 rather than 2.
+    #[clap(long)]
+    fail_on_warnings: bool,
+
+    /// If set, config that isn't needed (e.g. permissions that aren't used or packages that aren't
+    /// in the dependency tree) is treated as an error rather than a warning.
+    #[clap(long)]
+    deny_unused_config: bool,
+
+    /// Whether to use coloured output.
+    #[clap(long, alias = "color", default_value = "auto")]
+    colour: colour::Colour,
+
+    /// How to report problems when running non-interactively.
+    #[clap(long, default_value = "human")]
+    output_format: ui::OutputFormat,
+
+    /// When reporting problems non-interactively, also show the change to cackle.toml that each
+    /// available fix would make.
+    #[clap(long)]
+    show_fixes: bool,
+
+    /// When running non-interactively, automatically apply to cackle.toml the fix for each problem
+    /// that has only one possible fix. Problems with no fix or several possible fixes are reported
+    /// and cause a non-zero exit status.
+    #[cfg(feature = "ui")]
+    #[clap(long)]
+    fix: bool,
+
+    /// Like `--fix`, but rather than changing cackle.toml, prints a diff of the change that each fix
+    /// would make. Exits with a non-zero status if any change would be made.
+    #[cfg(feature = "ui")]
+    #[clap(long, conflicts_with = "fix")]
+    diff_config: bool,
+
+    /// The maximum number of usages to list for each API when reporting problems. -1 means no
+    /// limit, which is the default. Can be overridden for individual APIs with `report_cap`.
+    #[clap(long, value_name = "N", allow_negative_numbers = true)]
+    usage_report_cap: Option<i64>,
+
+    /// Symbol names longer than this many characters are truncated when reporting problems.
+    #[clap(long, default_value = "200", value_name = "N")]
+    max_symbol_length: usize,
+
+    /// For each disallowed API usage, show the chain of functions in the offending crate through
+    /// which the usage is reached, up to the nearest function that's called from outside the crate.
+    #[clap(long)]
+    show_backtrace: bool,
+
+    /// The maximum number of functions to show in each backtrace.
+    #[clap(long, default_value = "10", value_name = "N")]
+    backtrace_depth: usize,
+
+    /// Only run the specified categories of checks. Checks that don't fall into any category,
+    /// such as for proc macros and licenses, are then skipped. Checking only unsafe is much faster,
+    /// since the compiled binaries don't need to be analysed.
+    #[clap(long, value_delimiter = ',', value_name = "CATEGORY")]
+    only: Vec<checker::Category>,
+
+    /// When reporting problems non-interactively, mark problems that weren't reported by previous
+    /// runs as new and say when other problems were first seen. A history of reported problems is
+    /// kept in `target/cackle/history.toml`.
+    #[clap(long)]
+    show_age: bool,
+
+    /// Don't print anything on success. Cargo's output is only shown if the build fails.
+    #[clap(long)]
+    quiet: bool,
+
+    /// When running non-interactively, forward cargo's output to stderr a line at a time as it's
+    /// produced. Since cargo isn't writing to a terminal, it prints a line as each package starts
+    /// compiling rather than showing a progress bar, which is easier for other tools to follow.
+    #[clap(long, conflicts_with = "quiet")]
+    progress: bool,
+
+    /// Override the target used when compiling. e.g. specify "x86_64-apple-darwin" to compile for
+    /// x86 Mac. Note that build scripts and procedural macros will still be compiled for the host
+    /// target.
+    #[clap(long)]
+    target: Option<String>,
+
+    /// Only build the specified package, e.g. a single member of a large workspace, and only report
+    /// problems in that package. Its dependencies are still analysed as part of its binaries.
+    #[clap(long, value_name = "NAME")]
+    package: Option<String>,
+
+    /// Features to enable when building, separated by commas. If specified, these replace any
+    /// features listed in the config.
+    #[clap(long, value_delimiter = ',')]
+    features: Vec<String>,
+
+    /// Enable all features when building.
+    #[clap(long)]
+    all_features: bool,
+
+    /// Don't enable the default features when building.
+    #[clap(long)]
+    no_default_features: bool,
+
+    /// Number of parallel jobs. Passed to cargo and also used when analysing linked binaries.
+    /// Defaults to the number of CPUs.
+    #[clap(short, long, value_name = "N")]
+    jobs: Option<usize>,
+
+    /// Build profile to use. This is currently for testing purposes and isn't yet properly
+    /// supported. In particular, the selected profile needs to satisfy certain criteria and failure
+    /// to meet those criteria leads to surprising behaviour.
+    #[clap(long, default_value = proxy::cargo::DEFAULT_PROFILE_NAME, hide = true)]
+    profile: String,
+
+    /// Print how long various things take to run.
+    #[clap(long)]
+    print_timing: bool,
+
+    /// Print additional information that's probably only useful for debugging.
+    #[clap(long)]
+    debug: bool,
+
+    /// Output file for logs that might be useful for diagnosing problems.
+    #[clap(long)]
+    log_file: Option<PathBuf>,
+
+    /// How detailed the logs should be.
+    #[clap(long, default_value = "info")]
+    log_level: logging::LevelFilter,
+
+    /// When specified, writes all requests into a subdirectory of the target directory. For
+    /// debugging use.
+    #[clap(long, hide = true)]
+    save_requests: bool,
+
+    /// Instead of running `cargo build`, replay requests saved by a previous run where
+    /// --write-requests was specified. For debugging use.
+    #[clap(long, hide = true)]
+    replay_requests: bool,
+
+    /// Ignore anything left over from a previous interrupted run and check everything from a clean
+    /// build.
+    #[clap(long)]
+    fresh: bool,
+
+    /// Always run `cargo metadata`, rather than reusing its output from a previous run when no
+    /// `Cargo.toml` or `Cargo.lock` has changed.
+    #[clap(long)]
+    no_cache: bool,
+
+    /// Run cargo with `--offline`. Fails rather than accessing the network if any dependencies
+    /// haven't already been downloaded.
+    #[clap(long)]
+    offline: bool,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand, Debug, Clone, Default)]
+enum Command {
+    /// Non-interactive check of configuration.
+    #[default]
+    Check,
+
+    /// Interactive check of configuration.
+    #[cfg(feature = "ui")]
+    Ui(ui::UiArgs),
+
+    /// Print summary of permissions used.
+    Summary(SummaryOptions),
+
+    /// Run an arbitrary cargo command, analysing whatever gets built.
+    Cargo(CargoOptions),
+
+    /// Print which permissions each of the supplied paths would match with the current
+    /// configuration. Doesn't run a build.
+    MatchPath(MatchPathOptions),
+
+    /// Non-interactive check, then print the dependency tree annotated with what each package is
+    /// permitted to do and any problems found.
+    Tree(TreeOptions),
+
+    /// Rewrite cackle.toml, replacing anything deprecated with its current equivalent. Doesn't run
+    /// a build.
+    #[cfg(feature = "ui")]
+    MigrateConfig,
+
+    /// Check that the config is valid and consistent, then exit without building. Much faster
+    /// than a full check when only the config has changed.
+    ConfigCheck,
+
+    /// Write a starter cackle.toml, listing packages that have build scripts or are proc macros.
+    /// Doesn't run a build.
+    Init(init::InitOptions),
+
+    /// Check API usage in an already-built binary, without running a build. The binary must have
+    /// debug info. Only references recorded in the debug info are found, so this is less thorough
+    /// than a regular check.
+    Inspect(InspectOptions),
+}
+
+impl Command {
+    /// Returns whether this command builds the crate, in which case we start by cleaning.
+    fn builds(&self) -> bool {
+        match self {
+            Command::Cargo(..)
+            | Command::MatchPath(..)
+            | Command::ConfigCheck
+            | Command::Init(..)
+            | Command::Inspect(..) => false,
+            #[cfg(feature = "ui")]
+            Command::MigrateConfig => false,
+            _ => true,
+        }
+    }
+}
+
+#[derive(Parser, Debug, Clone)]
+struct MatchPathOptions {
+    /// Paths to check. e.g. `std::env::var`.
+    #[clap(required = true)]
+    paths: Vec<String>,
+}
+
+#[derive(Parser, Debug, Clone)]
+struct InspectOptions {
+    /// The executable or shared object to check.
+    bin: PathBuf,
+}
+
+/// Runs the command-line interface. This is what the `cackle` binary runs and isn't part of our
+/// stable API.
+#[doc(hidden)]
+pub fn run_cli() -> Result<()> {
+    handle_wrapped_binaries()?;
+
+    let mut args = Args::parse();
+    args.colour = args.colour.detect();
+    if let Some(log_file) = &args.log_file {
+        logging::init(log_file, args.log_level)?;
+    }
+    let (abort_send, abort_recv) = std::sync::mpsc::channel();
+    let app = App::new(args, abort_send, None)?;
+    let exit_code = app.run_and_report_errors(abort_recv);
+    info!("Shutdown with exit code {}", exit_code);
+    std::process::exit(exit_code.code());
+}
+
+/// Cackle runs builds with itself (i.e. the current executable) as a wrapper around rustc and the
+/// linker, and also uses itself to set up sandboxes. This function checks if we were invoked in
+/// one of these roles and if we were, does what's needed and exits. Programs that use [`Cackle`]
+/// must call this at the start of `main`, before doing anything else.
+pub fn handle_wrapped_binaries() -> Result<()> {
+    #[cfg(target_os = "linux")]
+    sandbox::handle_sandboxed_exec()?;
+    proxy::subprocess::handle_wrapped_binaries()
+}
+
+struct App {
+    problem_store: ProblemStoreRef,
+    root_path: PathBuf,
+    config_path: PathBuf,
+    checker: Arc<Mutex<Checker>>,
+    tmpdir: Arc<tempfile::TempDir>,
+    args: Arc<Args>,
+    event_sender: Sender<AppEvent>,
+    ui_join_handle: JoinHandle<Result<()>>,
+    crate_index: Arc<CrateIndex>,
+
+    /// Set if we're going to build. Contains what we learned from a previous interrupted run, if
+    /// any.
+    resume_state: Option<ResumeState>,
+}
+
+impl App {
+    /// Creates the app. Problems are reported via `ui` if supplied, otherwise via the user
+    /// interface selected by `args`.
+    fn new(
+        args: Args,
+        abort_sender: Sender<()>,
+        ui: Option<Box<dyn ui::UserInterface>>,
+    ) -> Result<Self> {
+        let args = Arc::new(args);
+        let root_path = args
+            .path
+            .clone()
+            .or_else(|| std::env::current_dir().ok())
+            .ok_or_else(|| anyhow!("Failed to get current working directory"))?;
+        let root_path = Path::new(&root_path)
+            .canonicalize()
+            .with_context(|| format!("Failed to read directory `{}`", root_path.display()))?;
+
+        let config_path = args
+            .cackle_path
+            .first()
+            .cloned()
+            .unwrap_or_else(|| root_path.join("cackle.toml"));
+
+        let crate_index = Arc::new(CrateIndex::new(
+            &root_path,
+            args.offline,
+            !args.no_cache,
+            &args.feature_options(&[]),
+        )?);
+        if let Some(package) = &args.package {
+            if crate_index
+                .newest_package_id_with_name(&package.as_str().into())
+                .is_none()
+            {
+                bail!("Package `{package}` passed to --package isn't in the dependency tree");
+            }
+        }
+
+        let mut resume_state = None;
+        if !args.replay_requests && args.rerun_failed.is_none() && args.command.builds() {
+            resume_state = Some(resume_state_or_clean(
+                &root_path,
+                &config_path,
+                &args,
+                &crate_index,
+            )?);
+        }
+        let target_dir = root_path.join("target");
+        let tmpdir = Arc::new(tempfile::TempDir::new()?);
+        let checker = Checker::new(
+            tmpdir.clone(),
+            target_dir.clone(),
+            args.clone(),
+            crate_index.clone(),
+            config_path.clone(),
+        );
+        let (event_sender, event_receiver) = std::sync::mpsc::channel();
+        let problem_store = crate::problem_store::create(event_sender.clone());
+        let ui = match ui {
+            Some(ui) => ui,
+            None => ui::create_ui(
+                &args,
+                &root_path,
+                &config_path,
+                crate_index.clone(),
+                abort_sender,
+            )?,
+        };
+        let ui_join_handle = ui::start_ui(ui, problem_store.clone(), event_receiver)?;
+        Ok(Self {
+            problem_store,
+            root_path,
+            config_path,
+            checker: Arc::new(Mutex::new(checker)),
+            args,
+            event_sender,
+            ui_join_handle,
+            crate_index,
+            tmpdir,
+            resume_state,
+        })
+    }
+
+    /// Runs, reports any error and returns the exit code. Takes self by value so that it's dropped
+    /// before we return. That way the user interface will be cleaned up before we exit.
+    fn run_and_report_errors(mut self, abort_recv: Receiver<()>) -> ExitCode {
+        if let Command::ConfigCheck = &self.args.command {
+            return self.check_config();
+        }
+        if let Command::Summary(options) = &self.args.command {
+            return self.print_summary(options);
+        }
+        if let Command::MatchPath(options) = &self.args.command {
+            return self.print_path_matches(options);
+        }
+        if let Command::Init(options) = &self.args.command {
+            return self.init_config(options);
+        }
+        #[cfg(feature = "ui")]
+        if let Command::MigrateConfig = &self.args.command {
+            return self.migrate_config();
+        }
+        let mut error = None;
+        let exit_code = match self.run_or_watch(&abort_recv) {
+            Err(e) => {
+                error = Some(e);
+                let _ = self.event_sender.send(AppEvent::CheckFailed);
+                outcome::INTERNAL_ERROR
+            }
+            Ok(exit_code) => exit_code,
+        };
+        let _ = self.event_sender.send(AppEvent::Shutdown);
+        if let Ok(Err(error)) = self.ui_join_handle.join() {
+            eprintln!("UI error: {error}");
+            return outcome::INTERNAL_ERROR;
+        }
+        // Now that the UI (if any) has shut down, print any errors. These go to stderr, so that
+        // they don't get mixed up with a JSON or SARIF document on stdout.
+        if let Some(error) = error {
+            eprintln!("{error:#}");
+        }
+
+        let checker = self.checker.lock().unwrap();
+        if let Some(version) = &checker.subprocess_version_mismatch {
+            eprintln!(
+                "Warning: cackle version {} invoked a subprocess running version {version}. \
+                 Check that only one version of cackle is installed.",
+                proxy::rpc::CACKLE_VERSION
+            );
+        }
+        if self.args.print_path_to_crate_map {
+            checker.print_path_to_crate_map();
+        }
+        for pkg_name in &self.args.print_exports {
+            checker.print_exports(pkg_name);
+        }
+        for crate_name in &self.args.print_permissions {
+            checker.print_permissions(crate_name);
+        }
+        if self.args.print_timing {
+            checker.print_timing();
+        }
+        if let Some(crate_name) = &self.args.rerun_failed {
+            if checker.reruns.is_empty() && exit_code != outcome::INTERNAL_ERROR {
+                eprintln!("The saved invocation for `{crate_name}` didn't report its output");
+            }
+            for rerun in &checker.reruns {
+                eprintln!("{rerun}");
+            }
+        }
+        if let Some(path) = &self.args.inventory {
+            if let Err(error) = checker.write_inventory(path) {
+                eprintln!("{error:#}");
+                return outcome::INTERNAL_ERROR;
+            }
+        }
+        if let Command::Tree(options) = &self.args.command {
+            let tree = tree::Tree::new(
+                &self.crate_index,
+                &checker.config,
+                &checker.package_problems,
+                options,
+            );
+            print!("{}", tree.text());
+            return exit_code;
+        }
+        if (exit_code == outcome::SUCCESS || exit_code == outcome::WARNINGS)
+            && !self.args.quiet
+            && !self.args.output_format.is_document()
+            && !matches!(self.args.command, Command::Cargo(..))
+            && self.args.rerun_failed.is_none()
+        {
+            println!(
+                "Completed successfully for configuration {}",
+                self.config_path.display()
+            );
+            let summary = summary::Summary::new(&self.crate_index, &checker.config);
+            println!("{summary}");
+        }
+        exit_code
+    }
+
+    /// Runs the check, then shuts down the user interface. Doesn't print anything. Used when
+    /// we're being run as a library.
+    fn run_without_reporting(mut self, abort_recv: Receiver<()>) -> Result<()> {
+        let result = self.run(&abort_recv);
+        let _ = self.event_sender.send(AppEvent::Shutdown);
+        self.ui_join_handle
+            .join()
+            .map_err(|_| anyhow!("User interface thread panicked"))??;
+        result.map(|_| ())
+    }
+
+    fn print_summary(&self, options: &SummaryOptions) -> ExitCode {
+        let mut checker = self.checker.lock().unwrap();
+        if let Err(error) = checker.load_config() {
+            println!("{error:#}");
+            return outcome::INTERNAL_ERROR;
+        }
+        let summary = summary::Summary::new(&self.crate_index, &checker.config);
+        summary.print(options);
+        outcome::SUCCESS
+    }
+
+    fn check_config(&self) -> ExitCode {
+        let mut checker = self.checker.lock().unwrap();
+        if let Err(error) = checker.load_config() {
+            println!("{error:#}");
+            return outcome::INTERNAL_ERROR;
+        }
+        let problems = checker.config_problems();
+        if problems.is_empty() {
+            if !self.args.quiet {
+                println!("`{}` is valid", self.config_path.display());
+            }
+            return outcome::SUCCESS;
+        }
+        for problem in &problems {
+            println!("{problem}");
+        }
+        outcome::FAILURE
+    }
+
+    fn print_path_matches(&self, options: &MatchPathOptions) -> ExitCode {
+        let mut checker = self.checker.lock().unwrap();
+        if let Err(error) = checker.load_config() {
+            println!("{error:#}");
+            return outcome::INTERNAL_ERROR;
+        }
+        for path in &options.paths {
+            let name = names::split_simple(path);
+            let apis = checker.apis_for_name_iterator(name.parts());
+            let mut apis: Vec<&str> = apis.iter().map(AsRef::as_ref).collect();
+            apis.sort();
+            if apis.is_empty() {
+                println!("{path}: (no permissions)");
+            } else {
+                println!("{path}: {}", apis.join(", "));
+            }
+        }
+        outcome::SUCCESS
+    }
+
+    fn init_config(&self, options: &init::InitOptions) -> ExitCode {
+        match init::write_initial_config(&self.config_path, &self.crate_index, options) {
+            Ok(()) => {
+                if !self.args.quiet && !self.args.output_format.is_document() {
+                    println!("Wrote `{}`", self.config_path.display());
+                }
+                outcome::SUCCESS
+            }
+            Err(error) => {
+                eprintln!("{error:#}");
+                outcome::INTERNAL_ERROR
+            }
+        }
+    }
+
+    #[cfg(feature = "ui")]
+    fn migrate_config(&self) -> ExitCode {
+        let result =
+            config_editor::ConfigEditor::from_file(&self.config_path).and_then(|mut editor| {
+                let changes = editor.migrate()?;
+                if !changes.is_empty() {
+                    editor.write(&self.config_path)?;
+                }
+                Ok(changes)
+            });
+        match result {
+            Ok(changes) if changes.is_empty() => {
+                println!("`{}` is already up to date", self.config_path.display());
+                outcome::SUCCESS
+            }
+            Ok(changes) => {
+                println!("Updated `{}`:", self.config_path.display());
+                for change in changes {
+                    println!("  {change}");
+                }
+                outcome::SUCCESS
+            }
+            Err(error) => {
+                println!("{error:#}");
+                outcome::INTERNAL_ERROR
+            }
+        }
+    }
+
+    /// Runs the check. If `--watch` was passed, checks again each time something changes, until
+    /// the user quits.
+    fn run_or_watch(&mut self, abort_recv: &Receiver<()>) -> Result<ExitCode> {
+        #[cfg(feature = "ui")]
+        if let Command::Ui(ui_args) = &self.args.command {
+            if ui_args.watch {
+                return self.watch(abort_recv);
+            }
+        }
+        self.run(abort_recv)
+    }
+
+    #[cfg(feature = "ui")]
+    fn watch(&mut self, abort_recv: &Receiver<()>) -> Result<ExitCode> {
+        let watcher = watch::Watcher::new(
+            &self.config_path,
+            &self.crate_index,
+            &self.root_path.join("target"),
+        )?;
+        loop {
+            let (exit_code, error) = match self.run(abort_recv) {
+                Ok(exit_code) => (exit_code, None),
+                // Errors such as a failed build are likely to be fixed by whatever change we're
+                // about to wait for, so we show them rather than stopping.
+                Err(error) => (outcome::INTERNAL_ERROR, Some(format!("{error:#}"))),
+            };
+            if self.problem_store.lock().has_aborted {
+                return Ok(exit_code);
+            }
+            let _ = self.event_sender.send(AppEvent::WaitingForChanges(error));
+            if watcher.wait_for_change(abort_recv) == Outcome::GiveUp {
+                return Ok(exit_code);
+            }
+            let _ = self.event_sender.send(AppEvent::Rescan);
+            // A manifest may have changed, adding or removing dependencies. If not, the metadata
+            // cache means that this is cheap.
+            self.crate_index = Arc::new(CrateIndex::new(
+                &self.root_path,
+                self.args.offline,
+                !self.args.no_cache,
+                &self.args.feature_options(&[]),
+            )?);
+            self.resume_state = Some(resume_state_or_clean(
+                &self.root_path,
+                &self.config_path,
+                &self.args,
+                &self.crate_index,
+            )?);
+            *self.checker.lock().unwrap() = Checker::new(
+                self.tmpdir.clone(),
+                self.root_path.join("target"),
+                self.args.clone(),
+                self.crate_index.clone(),
+                self.config_path.clone(),
+            );
+        }
+    }
+
+    fn run(&mut self, abort_recv: &Receiver<()>) -> Result<ExitCode> {
+        self.problem_store.lock().has_warnings = false;
+        if self.maybe_create_config()? == Outcome::GiveUp {
+            info!("Gave up creating initial configuration");
+            return Ok(outcome::FAILURE);
+        }
+        self.checker.lock().unwrap().load_config()?;
+        if let Some(crate_name) = self.args.rerun_failed.clone() {
+            return self.rerun_failed(&crate_name, abort_recv);
+        }
+        if let Some(resume_state) = self.resume_state.take() {
+            let log_path = resume::log_path(&self.root_path, &self.args);
+            self.checker
+                .lock()
+                .unwrap()
+                .resume(&log_path, resume_state)?;
+        }
+
+        let mut initial_outcome = self.new_request_handler(None).handle_request()?;
+        let config = self.checker.lock().unwrap().config.clone();
+        let crate_index = self.checker.lock().unwrap().crate_index.clone();
+        initial_outcome = initial_outcome.and(
+            self.problem_store
+                .fix_problems(config.unused_imports(&crate_index)),
+        );
+
+        {
+            let mut checker = self.checker.lock().unwrap();
+
+            // The following call to load_config is only really necessary if we fixed unused-import
+            // problems above. It might be worthwhile at some point refactoring so that we don't do an
+            // unnecessary reload here.
+            checker.load_config()?;
+        }
+
+        let root_path = self.root_path.clone();
+        let args = self.args.clone();
+        let build_result = if initial_outcome == Outcome::Continue {
+            if let Command::Inspect(options) = &self.args.command {
+                let bin_path = options.bin.clone();
+                if self.inspect_binary(&bin_path)? == Outcome::GiveUp {
+                    return Ok(outcome::FAILURE);
+                }
+                Ok(())
+            } else if self.args.replay_requests {
+                self.replay_requests()
+            } else {
+                proxy::invoke_cargo_build(
+                    &root_path,
+                    &self.tmpdir,
+                    &config,
+                    &args,
+                    abort_recv,
+                    &crate_index,
+                    |request| {
+                        if self.args.save_requests {
+                            if let Err(error) = self.save_request(&request) {
+                                println!("Failed to save request: {error}");
+                            }
+                        }
+                        self.new_request_handler(Some(request))
+                    },
+                )
+            }
+        } else {
+            // We've already detected problems before running cargo, don't run cargo.
+            Ok(())
+        };
+
+        if self.problem_store.lock().has_aborted {
+            return Ok(outcome::FAILURE);
+        }
+
+        // We only check if the build failed if there were no ACL check errors.
+        build_result?;
+
+        // A prebuilt binary generally only contains some of our dependencies, so we'd report
+        // config for the rest as unused.
+        if matches!(self.args.command, Command::Inspect(..)) {
+            return Ok(self.success_exit_code());
+        }
+
+        let unused_problems = self.checker.lock().unwrap().check_unused();
+        let resolution = self.problem_store.fix_problems(unused_problems);
+        if resolution != Outcome::Continue {
+            return Ok(outcome::FAILURE);
+        }
+
+        self.checker.lock().unwrap().finish_resume_log()?;
+        Ok(self.success_exit_code())
+    }
+
+    /// Runs the build script or rustc invocation that last failed for `crate_name` again with the
+    /// current config. Succeeds if it now succeeds.
+    fn rerun_failed(&self, crate_name: &str, abort_recv: &Receiver<()>) -> Result<ExitCode> {
+        proxy::rerun_failed(
+            &self.root_path,
+            &self.tmpdir,
+            &self.args,
+            crate_name,
+            abort_recv,
+            |request| self.new_request_handler(Some(request)),
+        )?;
+        let checker = self.checker.lock().unwrap();
+        if checker.reruns.iter().all(|rerun| rerun.exit_code == 0) {
+            Ok(outcome::SUCCESS)
+        } else {
+            Ok(outcome::FAILURE)
+        }
+    }
+
+    /// Returns the exit code for a check that completed without errors.
+    fn success_exit_code(&self) -> ExitCode {
+        if self.problem_store.lock().has_warnings {
+            outcome::WARNINGS
+        } else {
+            outcome::SUCCESS
+        }
+    }
+
+    fn new_request_handler(&self, request: Option<Request>) -> RequestHandler {
+        RequestHandler {
+            check_state: CheckState::default(),
+            checker: self.checker.clone(),
+            problem_store: self.problem_store.clone(),
+            request,
+        }
+    }
+
+    fn maybe_create_config(&mut self) -> Result<Outcome> {
+        if !self.config_path.exists() {
+            return Ok(self.problem_store.fix_problems(
+                problem::Problem::MissingConfiguration(self.config_path.clone()).into(),
+            ));
+        }
+        Ok(Outcome::Continue)
+    }
+
+    fn saved_request_path(&self) -> PathBuf {
+        self.root_path
+            .join("target")
+            .join(&self.args.profile)
+            .join("saved-cackle-rpcs")
+    }
+
+    /// Checks `bin_path` until there are no problems or the user gives up. This is like handling a
+    /// linker request, except that we don't have the object files that went into the binary.
+    fn inspect_binary(&mut self, bin_path: &Path) -> Result<Outcome> {
+        let mut check_state = CheckState::default();
+        loop {
+            let problems = self
+                .checker
+                .lock()
+                .unwrap()
+                .check_prebuilt_binary(bin_path, &mut check_state)?;
+            if problems.is_empty() {
+                return Ok(Outcome::Continue);
+            }
+            let only_warnings = (&problems)
+                .into_iter()
+                .all(|problem| problem.severity() == Severity::Warning);
+            match self.problem_store.fix_problems(problems) {
+                Outcome::Continue => {
+                    self.checker.lock().unwrap().load_config()?;
+                    // As when handling a request, checking again would just report the same
+                    // warnings again.
+                    if only_warnings {
+                        return Ok(Outcome::Continue);
+                    }
+                }
+                Outcome::GiveUp => return Ok(Outcome::GiveUp),
+            }
+        }
+    }
+
+    fn replay_requests(&self) -> Result<()> {
+        let rpcs_dir = &self.saved_request_path();
+        let mut rpc_paths: Vec<PathBuf> = rpcs_dir
+            .read_dir()
+            .with_context(|| format!("Failed to read saved RPCs dir `{}`", rpcs_dir.display()))?
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .collect();
+        rpc_paths.sort();
+        for path in rpc_paths {
+            info!("Replaying RPC `{}`", path.display());
+            let request_str = crate::fs::read_to_string(&path)?;
+            let request: Request = serde_json::from_str(&request_str)?;
+            let mut handler = self.new_request_handler(Some(request));
+            if handler
+                .handle_request()
+                .with_context(|| format!("Replay of request `{}` failed", path.display()))?
+                == Outcome::GiveUp
+            {
+                bail!("Request gave error");
+            }
+        }
+        Ok(())
+    }
+
+    fn save_request(&self, request: &Request) -> Result<()> {
+        let rpcs_dir = self.saved_request_path();
+        std::fs::create_dir_all(&rpcs_dir)?;
+        let num_entries = rpcs_dir.read_dir()?.count();
+        let serialized = serde_json::to_string(request)?;
+        std::fs::write(
+            rpcs_dir.join(format!("{num_entries:03}.cackle-rpc")),
+            serialized,
+        )?;
+        Ok(())
+    }
+}
+
+/// Returns what we learned from a previous interrupted run, if it's still applicable. If it isn't,
+/// cleans the build, so that everything gets checked.
+fn resume_state_or_clean(
+    root_path: &Path,
+    config_path: &Path,
+    args: &Args,
+    crate_index: &CrateIndex,
+) -> Result<ResumeState> {
+    let state = if args.fresh || args.inventory.is_some() {
+        None
+    } else {
+        load_resume_state(root_path, config_path, args, crate_index)?
+    };
+    if state.is_none() {
+        crate_index.preserving_metadata_cache(|| proxy::clean(root_path, args))?;
+    }
+    Ok(state.unwrap_or_default())
+}
+
+/// Returns what we learned from a previous interrupted run, provided the configuration hasn't
+/// changed since then.
+fn load_resume_state(
+    root_path: &Path,
+    config_path: &Path,
+    args: &Args,
+    crate_index: &CrateIndex,
+) -> Result<Option<ResumeState>> {
+    // If the config is missing or invalid, then we'll report that later.
+    let Ok(config) = config::parse_files(config_path, args.config_overrides(), crate_index) else {
+        return Ok(None);
+    };
+    resume::load(
+        &resume::log_path(root_path, args),
+        &config,
+        &args.only,
+        args.package.as_deref(),
+    )
+}
+
+#[derive(Default)]
+struct CheckState {
+    graph_outputs: Option<ScanOutputs>,
+}
+
+struct RequestHandler {
+    check_state: CheckState,
+    checker: Arc<Mutex<Checker>>,
+    problem_store: ProblemStoreRef,
+    request: Option<proxy::rpc::Request>,
+}
+
+impl RequestHandler {
+    fn handle_request(&mut self) -> Result<Outcome> {
+        loop {
+            let problems = self
+                .checker
+                .lock()
+                .unwrap()
+                .problems(&self.request, &mut self.check_state)?;
+            let return_on_retry = problems.should_send_retry_to_subprocess();
+            if problems.is_empty() {
+                return Ok(Outcome::Continue);
+            }
+            let only_warnings = (&problems)
+                .into_iter()
+                .all(|problem| problem.severity() == Severity::Warning);
+            match self.problem_store.fix_problems(problems) {
+                Outcome::Continue => {
+                    self.checker.lock().unwrap().load_config()?;
+                    if return_on_retry {
+                        // If the only problem is that something in a subprocess failed, we return
+                        // an empty error set. This signals the subprocess that it should proceed,
+                        // which since something failed means that it should reload the config and
+                        // retry whatever failed.
+                        return Ok(Outcome::Continue);
+                    }
+                    if only_warnings {
+                        // Warnings don't need to be fixed in order to proceed. Checking again would
+                        // just report the same warnings again.
+                        return Ok(Outcome::Continue);
+                    }
+                }
+                Outcome::GiveUp => {
+                    return Ok(Outcome::GiveUp);
+                }
+            }
+        }
+    }
+}
+
+const _CHECK_OS: () = if cfg!(all(
+    not(target_os = "linux"),
+    not(feature = "unsupported-os")
+)) {
+    panic!("Sorry, only Linux is currently supported. See PORTING.md");
+};
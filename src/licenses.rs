@@ -0,0 +1,150 @@
+//! Checks package licenses against a list of allowed licenses. Package licenses are SPDX
+//! expressions, e.g. "MIT OR Apache-2.0", so a package is permitted if its expression can be
+//! satisfied using only allowed licenses.
+
+use std::iter::Peekable;
+
+/// Returns whether the SPDX license `expression` is satisfied by `allowed`. Malformed expressions
+/// are never satisfied.
+pub(crate) fn is_allowed<S: AsRef<str>>(expression: &str, allowed: &[S]) -> bool {
+    let mut tokens = tokenize(expression).peekable();
+    let result = parse_or(&mut tokens, allowed);
+    result.unwrap_or(false) && tokens.next().is_none()
+}
+
+/// Returns the license identifiers referenced by `expression`, excluding exceptions.
+pub(crate) fn license_ids(expression: &str) -> Vec<&str> {
+    let mut ids = Vec::new();
+    let mut after_with = false;
+    for token in tokenize(expression) {
+        match token {
+            "(" | ")" | "AND" | "OR" => {}
+            "WITH" => {
+                after_with = true;
+                continue;
+            }
+            id if !after_with && !ids.contains(&id) => ids.push(id),
+            _ => {}
+        }
+        after_with = false;
+    }
+    ids
+}
+
+/// Splits `expression` into tokens. The obsolete `/` separator, still used by some older crates,
+/// is treated as `OR`.
+fn tokenize(expression: &str) -> impl Iterator<Item = &str> {
+    expression
+        .split_whitespace()
+        .flat_map(|word| word.split_inclusive(['(', ')', '/']))
+        .flat_map(|part| {
+            let (text, separator) = match part.chars().last() {
+                Some(c @ ('(' | ')' | '/')) => (&part[..part.len() - 1], Some(c)),
+                _ => (part, None),
+            };
+            let separator = separator.map(|c| match c {
+                '(' => "(",
+                ')' => ")",
+                _ => "OR",
+            });
+            std::iter::once(text).chain(separator)
+        })
+        .filter(|token| !token.is_empty())
+}
+
+fn parse_or<'a, S: AsRef<str>>(
+    tokens: &mut Peekable<impl Iterator<Item = &'a str>>,
+    allowed: &[S],
+) -> Option<bool> {
+    let mut result = parse_and(tokens, allowed)?;
+    while tokens.next_if_eq(&"OR").is_some() {
+        result |= parse_and(tokens, allowed)?;
+    }
+    Some(result)
+}
+
+fn parse_and<'a, S: AsRef<str>>(
+    tokens: &mut Peekable<impl Iterator<Item = &'a str>>,
+    allowed: &[S],
+) -> Option<bool> {
+    let mut result = parse_primary(tokens, allowed)?;
+    while tokens.next_if_eq(&"AND").is_some() {
+        result &= parse_primary(tokens, allowed)?;
+    }
+    Some(result)
+}
+
+fn parse_primary<'a, S: AsRef<str>>(
+    tokens: &mut Peekable<impl Iterator<Item = &'a str>>,
+    allowed: &[S],
+) -> Option<bool> {
+    match tokens.next()? {
+        "(" => {
+            let result = parse_or(tokens, allowed)?;
+            (tokens.next()? == ")").then_some(result)
+        }
+        ")" | "AND" | "OR" | "WITH" => None,
+        id => {
+            let mut result = id_allowed(id, allowed);
+            if tokens.next_if_eq(&"WITH").is_some() {
+                // An exception only grants additional permissions, so if the license is allowed
+                // without the exception, it's allowed with it.
+                let exception = tokens.next()?;
+                result |= id_allowed(&format!("{id} WITH {exception}"), allowed);
+            }
+            Some(result)
+        }
+    }
+}
+
+fn id_allowed<S: AsRef<str>>(id: &str, allowed: &[S]) -> bool {
+    let matches = |id: &str| {
+        allowed
+            .iter()
+            .any(|allowed| allowed.as_ref().eq_ignore_ascii_case(id))
+    };
+    // A trailing `+` means "this version or later", so allowing the base license suffices.
+    matches(id) || id.strip_suffix('+').map(matches).unwrap_or(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn allowed_licenses() {
+        let allowed = ["MIT", "Apache-2.0"];
+        assert!(is_allowed("MIT", &allowed));
+        assert!(is_allowed("mit", &allowed));
+        assert!(is_allowed("MIT OR Apache-2.0", &allowed));
+        assert!(is_allowed("MIT OR GPL-3.0", &allowed));
+        assert!(is_allowed("MIT/GPL-3.0", &allowed));
+        assert!(is_allowed("MIT AND Apache-2.0", &allowed));
+        assert!(is_allowed("(MIT OR GPL-3.0) AND Apache-2.0", &allowed));
+        assert!(is_allowed("Apache-2.0 WITH LLVM-exception", &allowed));
+        assert!(is_allowed("Apache-2.0+", &allowed));
+    }
+
+    #[test]
+    fn disallowed_licenses() {
+        let allowed = ["MIT", "Apache-2.0"];
+        assert!(!is_allowed("GPL-3.0", &allowed));
+        assert!(!is_allowed("MIT AND GPL-3.0", &allowed));
+        assert!(!is_allowed("(MIT OR Apache-2.0) AND GPL-3.0", &allowed));
+        assert!(!is_allowed(
+            "GPL-2.0 WITH Classpath-exception-2.0",
+            &allowed
+        ));
+        assert!(!is_allowed("MIT OR", &allowed));
+        assert!(!is_allowed("(MIT", &allowed));
+        assert!(!is_allowed("", &allowed));
+    }
+
+    #[test]
+    fn ids() {
+        assert_eq!(
+            license_ids("(MIT OR Apache-2.0 WITH LLVM-exception) AND MIT/Zlib"),
+            vec!["MIT", "Apache-2.0", "Zlib"]
+        );
+    }
+}
@@ -1,4 +1,5 @@
 use anyhow::bail;
+use anyhow::Context;
 use anyhow::Result;
 use serde::Deserialize;
 use serde::Serialize;
@@ -17,16 +18,22 @@ pub(crate) struct LinkInfo {
 
 impl LinkInfo {
     pub(crate) fn from_env() -> Result<Self> {
-        let crate_sel = CrateSel::from_env()?;
-        let object_paths = std::env::args()
-            .skip(1)
+        Self::from_args(CrateSel::from_env()?, std::env::args().skip(1))
+    }
+
+    /// Builds a `LinkInfo` from the arguments passed to the linker, excluding the name of the
+    /// linker itself. Any response files (`@path`) are expanded.
+    fn from_args(crate_sel: CrateSel, args: impl Iterator<Item = String>) -> Result<Self> {
+        let args = expand_response_files(args, 0)?;
+        let object_paths = args
+            .iter()
             .map(PathBuf::from)
             .filter(|path| has_supported_extension(path))
             .collect();
         Ok(LinkInfo {
             crate_sel,
             object_paths,
-            output_file: get_output_file()?,
+            output_file: get_output_file(&args)?,
         })
     }
 
@@ -44,8 +51,74 @@ impl LinkInfo {
     }
 }
 
-fn get_output_file() -> Result<PathBuf> {
-    let mut args = std::env::args();
+/// Response files can reference other response files. This limits how deep we'll go, so that a
+/// response file that references itself doesn't recurse forever.
+const MAX_RESPONSE_FILE_DEPTH: usize = 10;
+
+/// Replaces each argument of the form `@path` with the arguments contained in the file at `path`.
+fn expand_response_files(args: impl Iterator<Item = String>, depth: usize) -> Result<Vec<String>> {
+    let mut expanded = Vec::new();
+    for arg in args {
+        let Some(path) = arg.strip_prefix('@') else {
+            expanded.push(arg);
+            continue;
+        };
+        if depth >= MAX_RESPONSE_FILE_DEPTH {
+            bail!("Response files nested too deeply at `{path}`");
+        }
+        let contents = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read linker response file `{path}`"))?;
+        expanded.extend(expand_response_files(
+            split_response_file(&contents).into_iter(),
+            depth + 1,
+        )?);
+    }
+    Ok(expanded)
+}
+
+/// Splits the contents of a response file into arguments. Arguments are separated by whitespace.
+/// Single or double quotes can be used to include whitespace in an argument and a backslash escapes
+/// the following character. This is what GCC, Clang and GNU ld accept.
+fn split_response_file(contents: &str) -> Vec<String> {
+    let mut args = Vec::new();
+    let mut current = String::new();
+    let mut in_arg = false;
+    let mut quote = None;
+    let mut chars = contents.chars();
+    while let Some(ch) = chars.next() {
+        match (ch, quote) {
+            ('\\', _) => {
+                if let Some(escaped) = chars.next() {
+                    current.push(escaped);
+                }
+                in_arg = true;
+            }
+            (ch, Some(q)) if ch == q => quote = None,
+            (ch, Some(_)) => current.push(ch),
+            ('"' | '\'', None) => {
+                quote = Some(ch);
+                in_arg = true;
+            }
+            (ch, None) if ch.is_whitespace() => {
+                if in_arg {
+                    args.push(std::mem::take(&mut current));
+                    in_arg = false;
+                }
+            }
+            (ch, None) => {
+                current.push(ch);
+                in_arg = true;
+            }
+        }
+    }
+    if in_arg {
+        args.push(current);
+    }
+    args
+}
+
+fn get_output_file(args: &[String]) -> Result<PathBuf> {
+    let mut args = args.iter();
     while let Some(arg) = args.next() {
         if arg == "-o" {
             if let Some(output) = args.next() {
@@ -56,6 +129,16 @@ fn get_output_file() -> Result<PathBuf> {
     bail!("Failed to find output file in linker command line");
 }
 
+/// Returns whether `path` looks like a shared library (e.g. output from linking a `cdylib` or `dylib`
+/// crate), as opposed to an executable.
+pub(crate) fn is_shared_library(path: &Path) -> bool {
+    const EXTENSIONS: &[&str] = &["so", "dylib", "dll"];
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .map(|ext| EXTENSIONS.contains(&ext))
+        .unwrap_or(false)
+}
+
 fn has_supported_extension(path: &Path) -> bool {
     const EXTENSIONS: &[&str] = &["rlib", "o"];
     path.extension()
@@ -63,3 +146,64 @@ fn has_supported_extension(path: &Path) -> bool {
         .map(|ext| EXTENSIONS.contains(&ext))
         .unwrap_or(false)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::is_shared_library;
+    use super::split_response_file;
+    use super::LinkInfo;
+    use crate::crate_index::testing::pkg_id;
+    use crate::crate_index::CrateSel;
+    use std::path::Path;
+    use std::path::PathBuf;
+
+    #[test]
+    fn response_file_splitting() {
+        assert_eq!(
+            split_response_file("-o out\n'a b.o' \"c d.rlib\" e\\ f.o \"\""),
+            vec!["-o", "out", "a b.o", "c d.rlib", "e f.o", ""]
+        );
+    }
+
+    #[test]
+    fn response_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let inner = dir.path().join("inner.txt");
+        std::fs::write(&inner, "\"dir with space/b.rlib\"\n-lc\n").unwrap();
+        let outer = dir.path().join("args.txt");
+        std::fs::write(
+            &outer,
+            format!("a.o\n-o\n\"out dir/bin\"\n@{}\n", inner.display()),
+        )
+        .unwrap();
+        let link_info = LinkInfo::from_args(
+            CrateSel::Primary(pkg_id("crab1")),
+            ["-m64".to_owned(), format!("@{}", outer.display())].into_iter(),
+        )
+        .unwrap();
+        assert_eq!(
+            link_info.object_paths,
+            vec![PathBuf::from("a.o"), PathBuf::from("dir with space/b.rlib")]
+        );
+        assert_eq!(link_info.output_file, PathBuf::from("out dir/bin"));
+    }
+
+    #[test]
+    fn shared_library_extensions() {
+        assert!(is_shared_library(Path::new(
+            "target/debug/deps/libshared1.so"
+        )));
+        assert!(is_shared_library(Path::new(
+            "target/debug/deps/libshared1.dylib"
+        )));
+        assert!(is_shared_library(Path::new(
+            "target/debug/deps/shared1.dll"
+        )));
+        assert!(!is_shared_library(Path::new(
+            "target/debug/deps/crab_bin-1234"
+        )));
+        assert!(!is_shared_library(Path::new(
+            "target/debug/deps/crab_bin.exe"
+        )));
+    }
+}
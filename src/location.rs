@@ -2,10 +2,13 @@ use serde::Deserialize;
 use serde::Serialize;
 use std::fmt::Display;
 use std::path::Path;
+use std::path::PathBuf;
 use std::sync::Arc;
 
+/// A position in a source file. Lines and columns start at 1. A line of 0 means that all we know is
+/// the object file that the code came from.
 #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
-pub(crate) struct SourceLocation {
+pub struct SourceLocation {
     filename: Arc<Path>,
     line: u32,
     column: Option<u32>,
@@ -20,25 +23,129 @@ impl SourceLocation {
         }
     }
 
-    pub(crate) fn filename(&self) -> &Path {
+    /// Returns a location for code in `object_path` for which there's no debug info, e.g. C code
+    /// that was compiled without `-g`, so we can't tell which source file it came from.
+    pub(crate) fn unknown<P: Into<Arc<Path>>>(object_path: P) -> Self {
+        Self::new(object_path, 0, None)
+    }
+
+    pub fn is_unknown(&self) -> bool {
+        self.line == 0
+    }
+
+    pub fn filename(&self) -> &Path {
         &self.filename
     }
 
-    pub(crate) fn line(&self) -> u32 {
+    pub fn line(&self) -> u32 {
         self.line
     }
 
-    pub(crate) fn column(&self) -> Option<u32> {
+    pub fn column(&self) -> Option<u32> {
         self.column
     }
 }
 
 impl Display for SourceLocation {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{} [{}", self.filename.display(), self.line)?;
+        let filename = to_relative_path(&self.filename);
+        if self.is_unknown() {
+            return write!(f, "{} [unknown location]", filename.display());
+        }
+        write!(f, "{} [{}", filename.display(), self.line)?;
         if let Some(column) = self.column {
             write!(f, ":{}", column)?;
         }
         write!(f, "]")
     }
 }
+
+/// Returns a shorter form of `path` for display. Sources in cargo's registry are shown as
+/// `<registry>/crate-1.2.3/...`. Other absolute paths are made relative to the current directory,
+/// using `..` if necessary, provided that they have more in common with it than just the root.
+/// Failing that, `path` is returned unchanged.
+pub(crate) fn to_relative_path(path: &Path) -> PathBuf {
+    shorten_path(
+        path,
+        crate::fs::cargo_home().as_deref(),
+        std::env::current_dir().ok().as_deref(),
+    )
+}
+
+fn shorten_path(path: &Path, cargo_home: Option<&Path>, current_dir: Option<&Path>) -> PathBuf {
+    if !path.is_absolute() {
+        return path.to_owned();
+    }
+    if let Some(cargo_home) = cargo_home {
+        if let Ok(rest) = path.strip_prefix(cargo_home.join("registry").join("src")) {
+            // The first component names the registry, e.g. `index.crates.io-6f17d22bba15001f`.
+            let mut components = rest.components();
+            if components.next().is_some() {
+                return Path::new("<registry>").join(components.as_path());
+            }
+        }
+    }
+    current_dir
+        .and_then(|current_dir| relative_to(path, current_dir))
+        .unwrap_or_else(|| path.to_owned())
+}
+
+/// Returns `path` relative to `base`. Both should be absolute. Returns None if they have nothing in
+/// common other than the root.
+fn relative_to(path: &Path, base: &Path) -> Option<PathBuf> {
+    let common = path
+        .components()
+        .zip(base.components())
+        .take_while(|(a, b)| a == b)
+        .count();
+    // The first component is the root.
+    if common <= 1 {
+        return None;
+    }
+    let mut relative = PathBuf::new();
+    for _ in base.components().skip(common) {
+        relative.push("..");
+    }
+    relative.extend(path.components().skip(common));
+    Some(relative)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::shorten_path;
+    use super::SourceLocation;
+    use std::path::Path;
+
+    #[test]
+    fn unknown_location() {
+        let location = SourceLocation::unknown(Path::new("libfoo.a"));
+        assert!(location.is_unknown());
+        assert_eq!(location.to_string(), "libfoo.a [unknown location]");
+        assert!(!SourceLocation::new(Path::new("lib.rs"), 1, None).is_unknown());
+    }
+
+    #[test]
+    fn shortened_paths() {
+        let check = |path: &str, expected: &str| {
+            assert_eq!(
+                shorten_path(
+                    Path::new(path),
+                    Some(Path::new("/home/u/.cargo")),
+                    Some(Path::new("/home/u/ws"))
+                ),
+                Path::new(expected)
+            );
+        };
+        check("/home/u/ws/src/main.rs", "src/main.rs");
+        check("/home/u/other/src/lib.rs", "../other/src/lib.rs");
+        check(
+            "/home/u/.cargo/registry/src/index.crates.io-6f17d22bba15001f/foo-1.2.3/src/lib.rs",
+            "<registry>/foo-1.2.3/src/lib.rs",
+        );
+        check(
+            "/rustc/abc/library/std/src/fs.rs",
+            "/rustc/abc/library/std/src/fs.rs",
+        );
+        check("src/lib.rs", "src/lib.rs");
+    }
+}
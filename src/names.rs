@@ -238,7 +238,7 @@ impl<'data, I: Clone + Iterator<Item = DemangleToken<'data>>> Iterator
                     if self.as_final == Some(text)
                         && self
                             .as_final
-                            .map(|t| t.as_ptr() as usize == text.as_ptr() as usize)
+                            .map(|t| std::ptr::eq(t.as_ptr(), text.as_ptr()))
                             .unwrap_or(false)
                     {
                         // This text was already output as the final part of an as-name. Ignore it.
@@ -293,24 +293,20 @@ impl<'data, I: Clone + Iterator<Item = DemangleToken<'data>>> Iterator
                         NamesIteratorState::AsSkip {
                             gt_depth,
                             return_point,
-                        } => {
-                            if *gt_depth == 0 {
-                                match self.it.next() {
-                                    Some(DemangleToken::Text(text)) => {
-                                        self.it = return_point.clone();
-                                        self.as_final = Some(text);
-                                        self.state = NamesIteratorState::OutputtingName;
-                                        return Some(NameToken::Part(text));
-                                    }
-                                    _ => {
-                                        self.it = return_point.clone();
-                                        self.as_final = None;
-                                        self.state = NamesIteratorState::Inactive;
-                                        return Some(NameToken::EndName);
-                                    }
-                                }
+                        } if *gt_depth == 0 => match self.it.next() {
+                            Some(DemangleToken::Text(text)) => {
+                                self.it = return_point.clone();
+                                self.as_final = Some(text);
+                                self.state = NamesIteratorState::OutputtingName;
+                                return Some(NameToken::Part(text));
                             }
-                        }
+                            _ => {
+                                self.it = return_point.clone();
+                                self.as_final = None;
+                                self.state = NamesIteratorState::Inactive;
+                                return Some(NameToken::EndName);
+                            }
+                        },
                         _ => {}
                     }
                 }
@@ -336,12 +332,53 @@ impl<'input> DebugName<'input> {
         }
     }
 
-    pub(crate) fn new(namespace: Namespace, name: &str) -> DebugName {
+    pub(crate) fn new(namespace: Namespace, name: &str) -> DebugName<'_> {
         DebugName {
             namespace,
             name: Utf8Bytes::Borrowed(name),
         }
     }
+
+    /// If this names code that the compiler generated for a closure, async function or async
+    /// block, returns the name of the function that the user wrote that contains it. DWARF nests
+    /// such code within a namespace named after the enclosing function, so we drop generated names
+    /// from the end until we get to a function.
+    pub(crate) fn enclosing_function(&self) -> DebugName<'static> {
+        let mut parts: &[Box<str>] = &self.namespace.parts;
+        let mut name: &str = &self.name;
+        while is_generated_name(name) {
+            let Some((last, rest)) = parts.split_last() else {
+                break;
+            };
+            name = last;
+            parts = rest;
+        }
+        DebugName {
+            namespace: Namespace {
+                parts: Arc::from(parts),
+            },
+            name: Utf8Bytes::Heap(Arc::from(name)),
+        }
+    }
+}
+
+/// Returns whether `name` is a name that the compiler gives to generated code, e.g.
+/// `{closure#0}`, `{async_fn#0}` or `{async_block#1}`.
+fn is_generated_name(name: &str) -> bool {
+    (name.starts_with("{closure") || name.starts_with("{async")) && name.ends_with('}')
+}
+
+impl SymbolOrDebugName {
+    /// See `DebugName::enclosing_function`. Symbols are returned unchanged, since without debug
+    /// info we don't know where generated code came from.
+    pub(crate) fn enclosing_function(self) -> SymbolOrDebugName {
+        match self {
+            SymbolOrDebugName::DebugName(debug_name) => {
+                SymbolOrDebugName::DebugName(debug_name.enclosing_function())
+            }
+            symbol => symbol,
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -362,6 +399,79 @@ enum NamesIteratorState<I> {
     },
 }
 
+/// The crates that make up the standard library.
+const STD_CRATES: &[&str] = &["std", "core", "alloc"];
+
+/// Wraps an iterator of tokens, dropping the generic arguments of paths in the standard library.
+/// When a crate calls `core::ptr::drop_in_place<std::net::TcpListener>`, the type argument just
+/// says what's being dropped. It doesn't mean that the caller is using the networking API. Whatever
+/// the monomorphized code calls shows up as separate references, which are attributed to the crate
+/// that defines the code doing the calling. The `<` and `>` are dropped too, so a path continues
+/// across the generic arguments, e.g. `core::result::Result<T, E>::unwrap` becomes the single name
+/// `core::result::Result::unwrap`, which is what API definitions such as `panic` include.
+#[derive(Clone)]
+pub(crate) struct WithoutStdGenericArgs<I> {
+    it: I,
+    /// Whether we're in a path. A `<` immediately after a path opens the generic arguments of that
+    /// path, whereas one elsewhere, e.g. in `<Foo as Bar>::baz`, starts a qualified path.
+    in_path: bool,
+    /// Whether the path that we're in starts with the name of a standard library crate.
+    path_is_std: bool,
+    /// While we're skipping generic arguments, the number of `<` that haven't yet been closed.
+    skip_depth: u32,
+}
+
+impl<I> WithoutStdGenericArgs<I> {
+    pub(crate) fn new(it: I) -> Self {
+        Self {
+            it,
+            in_path: false,
+            path_is_std: false,
+            skip_depth: 0,
+        }
+    }
+}
+
+impl<'data, I: Iterator<Item = DemangleToken<'data>>> Iterator for WithoutStdGenericArgs<I> {
+    type Item = DemangleToken<'data>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let token = self.it.next()?;
+            if self.skip_depth > 0 {
+                match token {
+                    DemangleToken::Char('<') => self.skip_depth += 1,
+                    DemangleToken::Char('>') => {
+                        self.skip_depth -= 1;
+                        if self.skip_depth == 0 {
+                            // The path may continue after its generic arguments, e.g.
+                            // `Option<T>::map`. Since we don't emit the brackets, this is then
+                            // the single name `core::option::Option::map`.
+                            self.in_path = true;
+                        }
+                    }
+                    _ => {}
+                }
+                continue;
+            }
+            match token {
+                DemangleToken::Text(text) => {
+                    if !self.in_path {
+                        self.path_is_std = STD_CRATES.contains(&text);
+                    }
+                    self.in_path = true;
+                }
+                DemangleToken::Char('<') if self.in_path && self.path_is_std => {
+                    self.skip_depth = 1;
+                    continue;
+                }
+                _ => self.in_path = false,
+            }
+            return Some(token);
+        }
+    }
+}
+
 impl<'input> SymbolAndName<'input> {
     pub(crate) fn symbol_or_debug_name(&self) -> Result<SymbolOrDebugName> {
         if let Some(debug_name) = self.debug_name.as_ref() {
@@ -432,6 +542,21 @@ impl Display for SymbolOrDebugName {
     }
 }
 
+/// Returns the display text of `name`, truncated with an ellipsis if it's longer than `max_len`
+/// characters. Monomorphised generic symbols can be thousands of characters long, which makes
+/// reports hard to read.
+pub(crate) fn display_truncated(name: &impl Display, max_len: Option<usize>) -> String {
+    let text = name.to_string();
+    match max_len {
+        Some(max_len) if text.chars().count() > max_len => {
+            let mut truncated: String = text.chars().take(max_len).collect();
+            truncated.push('…');
+            truncated
+        }
+        _ => text,
+    }
+}
+
 impl Debug for Name {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(f, "Name({})", self)
@@ -523,6 +648,57 @@ mod tests {
         );
     }
 
+    #[track_caller]
+    fn check_without_std_generic_args(input: &str, expected: &[&[&str]]) {
+        let mut names = NamesIterator::new(WithoutStdGenericArgs::new(NonMangledIterator::new(
+            &[],
+            input,
+        )));
+        let mut out = Vec::new();
+        while let Some((parts, _)) = names.next_name().unwrap() {
+            let parts: Vec<&str> = parts.collect();
+            if !parts.is_empty() {
+                out.push(parts);
+            }
+        }
+        assert_eq!(out, expected);
+    }
+
+    #[test]
+    fn test_without_std_generic_args() {
+        check_without_std_generic_args(
+            "core::ptr::drop_in_place<std::net::tcp::TcpListener>",
+            &[&["core", "ptr", "drop_in_place"]],
+        );
+        check_without_std_generic_args(
+            "core::result::Result<std::net::tcp::TcpListener, std::io::error::Error>::is_ok<std::net::tcp::TcpListener>",
+            &[&["core", "result", "Result", "is_ok"]],
+        );
+        check_without_std_generic_args(
+            "<alloc::vec::Vec<std::fs::File> as core::ops::drop::Drop>::drop",
+            &[
+                &["alloc", "vec", "Vec"],
+                &["core", "ops", "drop", "Drop", "drop"],
+            ],
+        );
+        check_without_std_generic_args(
+            "<std::fs::File as std::io::Read>::read",
+            &[&["std", "fs", "File"], &["std", "io", "Read", "read"]],
+        );
+        check_without_std_generic_args(
+            "core::intrinsics::transmute<crab1::Foo, u64>",
+            &[&["core", "intrinsics", "transmute"]],
+        );
+        check_without_std_generic_args(
+            "crab1::wrap<alloc::vec::Vec<std::fs::File>, std::net::tcp::TcpListener>",
+            &[
+                &["crab1", "wrap"],
+                &["alloc", "vec", "Vec"],
+                &["std", "net", "tcp", "TcpListener"],
+            ],
+        );
+    }
+
     #[test]
     fn test_debug_name_display() {
         let name = DebugName::new(
@@ -531,4 +707,27 @@ mod tests {
         );
         assert_eq!(name.to_string(), "std::collections::HashMap<String, u32>");
     }
+
+    #[test]
+    fn test_enclosing_function() {
+        let check = |namespace: &[&str], name: &str, expected: &str| {
+            let namespace = namespace
+                .iter()
+                .fold(Namespace::empty(), |namespace, part| namespace.plus(part));
+            let debug_name = DebugName::new(namespace, name);
+            assert_eq!(debug_name.enclosing_function().to_string(), expected);
+        };
+        check(
+            &["crab1", "read_config"],
+            "{async_fn#0}",
+            "crab1::read_config",
+        );
+        check(
+            &["crab1", "{impl#0}", "run", "{closure#0}"],
+            "{async_block#1}",
+            "crab1::{impl#0}::run",
+        );
+        check(&["crab1"], "read_config", "crab1::read_config");
+        check(&[], "{closure#0}", "{closure#0}");
+    }
 }
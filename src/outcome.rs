@@ -2,8 +2,15 @@ use serde::Deserialize;
 use serde::Serialize;
 use std::fmt::Display;
 
+/// The check completed and found no problems.
 pub(crate) const SUCCESS: ExitCode = ExitCode(0);
-pub(crate) const FAILURE: ExitCode = ExitCode(-1);
+/// The check found errors, or the user gave up before all problems were resolved.
+pub(crate) const FAILURE: ExitCode = ExitCode(1);
+/// The check completed and the only problems found were warnings.
+pub(crate) const WARNINGS: ExitCode = ExitCode(2);
+/// We weren't able to complete the check, e.g. because the config couldn't be parsed or the build
+/// failed. This matches the exit code used when a Rust program panics.
+pub(crate) const INTERNAL_ERROR: ExitCode = ExitCode(101);
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub(crate) enum Outcome {
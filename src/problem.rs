@@ -3,6 +3,7 @@
 
 use fxhash::FxHashMap;
 
+use crate::build_script_checker::BuildInstruction;
 use crate::checker::ApiUsage;
 use crate::config::ApiPath;
 use crate::config::CrateName;
@@ -10,15 +11,20 @@ use crate::config::PermConfig;
 use crate::config::PermissionName;
 use crate::crate_index::BuildScriptId;
 use crate::crate_index::CrateSel;
+use crate::crate_index::DependencyPath;
 use crate::crate_index::PackageId;
 use crate::location::SourceLocation;
+use crate::names::display_truncated;
 use crate::names::SymbolOrDebugName;
 use crate::proxy::rpc::BuildScriptOutput;
 use crate::proxy::rpc::UnsafeUsage;
+use crate::suppressions::Suppression;
 use crate::symbol::Symbol;
 use std::borrow::Cow;
 use std::collections::hash_map::Entry;
 use std::collections::BTreeMap;
+use std::collections::BTreeSet;
+use std::collections::HashSet;
 use std::fmt::Display;
 use std::path::Path;
 use std::path::PathBuf;
@@ -42,16 +48,18 @@ pub(crate) enum Problem {
     DisallowedBuildInstruction(DisallowedBuildInstruction),
     UnusedPackageConfig(CrateName),
     UnusedAllowApi(UnusedAllowApi),
+    UnusedAllowSymbols(UnusedAllowSymbols),
+    UnusedSuppression(UnusedSuppression),
+    UnusedExclude(UnusedExclude),
     SelectSandbox,
     ImportStdApi(PermissionName),
     AvailableApi(AvailableApi),
     PossibleExportedApi(PossibleExportedApi),
-}
-
-#[derive(Debug, Clone, PartialEq, Eq, Hash)]
-pub(crate) struct ErrorDetails {
-    pub(crate) short: String,
-    pub(crate) detail: String,
+    UndecidedPackages(Vec<PackageId>),
+    IncludedAndExcluded(IncludedAndExcluded),
+    ShellInvocation(ShellInvocation),
+    DisallowedLicense(DisallowedLicense),
+    YankedVersion(PackageId),
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
@@ -64,6 +72,21 @@ pub(crate) struct BuildScriptFailed {
 pub(crate) struct ApiUsages {
     pub(crate) crate_sel: CrateSel,
     pub(crate) usages: BTreeMap<PermissionName, Vec<ApiUsage>>,
+    /// Descriptions from the config of any of the used APIs that have one.
+    pub(crate) descriptions: BTreeMap<PermissionName, String>,
+    /// Used APIs that the config says should only produce warnings. If all used APIs are in here,
+    /// the problem is a warning rather than an error.
+    pub(crate) warning_apis: BTreeSet<PermissionName>,
+    /// The maximum number of usages to list for APIs whose usages are capped.
+    pub(crate) report_caps: BTreeMap<PermissionName, usize>,
+    /// The chain of dependencies via which the package ends up in the dependency tree.
+    pub(crate) dependency_path: DependencyPath,
+    /// Whether the crate is a proc macro. Its usages then happen on the build machine whenever code
+    /// that uses the proc macro is compiled, rather than when the resulting program runs.
+    pub(crate) build_time: bool,
+    /// If this was produced by `grouped_by_type_and_crate_ignoring_version`, the `ApiUsages` that
+    /// were merged to produce it. Empty otherwise.
+    pub(crate) merged_from: Vec<ApiUsages>,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
@@ -72,10 +95,31 @@ pub(crate) struct UnusedAllowApi {
     pub(crate) permissions: Vec<PermissionName>,
 }
 
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub(crate) struct UnusedAllowSymbols {
+    pub(crate) crate_name: CrateName,
+    pub(crate) symbols: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub(crate) struct UnusedSuppression {
+    pub(crate) suppression: Suppression,
+}
+
+/// An `exclude` entry in an API definition that never stopped anything from matching the API.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub(crate) struct UnusedExclude {
+    pub(crate) api: PermissionName,
+    pub(crate) path: ApiPath,
+}
+
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub(crate) struct DisallowedBuildInstruction {
     pub(crate) build_script_id: BuildScriptId,
     pub(crate) instruction: String,
+    /// All the instructions that the build script emitted, whether allowed or not, so that they
+    /// can be reviewed together.
+    pub(crate) emitted: Vec<BuildInstruction>,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
@@ -85,6 +129,30 @@ pub(crate) struct AvailableApi {
     pub(crate) config: PermConfig,
 }
 
+/// A path that an API both includes and excludes. The exclude takes precedence.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub(crate) struct IncludedAndExcluded {
+    pub(crate) api: PermissionName,
+    pub(crate) path: ApiPath,
+}
+
+/// A place where a crate appears to run a shell, e.g. via `Command::new("sh")`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub(crate) struct ShellInvocation {
+    pub(crate) crate_sel: CrateSel,
+    /// The name of the shell, as it appeared in the string constant.
+    pub(crate) shell: String,
+    pub(crate) location: SourceLocation,
+}
+
+/// A package whose license isn't permitted by the config.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub(crate) struct DisallowedLicense {
+    pub(crate) pkg_id: PackageId,
+    /// The package's license expression, or None if it doesn't specify one.
+    pub(crate) license: Option<String>,
+}
+
 /// The name of a top-level module in a crate that matches the name of a restricted API. For
 /// example, if there's an API named "fs" and we find a crate with a module named "fs".
 #[derive(Clone, Hash, PartialEq, Eq, Debug)]
@@ -110,6 +178,10 @@ impl ProblemList {
         self.problems.append(&mut other.problems);
     }
 
+    pub(crate) fn retain(&mut self, f: impl FnMut(&Problem) -> bool) {
+        self.problems.retain(f);
+    }
+
     pub(crate) fn get(&self, index: usize) -> Option<&Problem> {
         self.problems.get(index)
     }
@@ -124,7 +196,7 @@ impl ProblemList {
 
     pub(crate) fn replace(&mut self, index: usize, replacement: ProblemList) -> Problem {
         self.problems
-            .splice(index..index + 1, replacement.problems.into_iter())
+            .splice(index..index + 1, replacement.problems)
             .next()
             .unwrap()
     }
@@ -150,6 +222,55 @@ impl ProblemList {
         })
     }
 
+    /// Combines all disallowed API usages for a package, even if they come from different versions
+    /// of that package. Build scripts are still kept separate from the rest of the package. The
+    /// merge can be undone with `split_versions`.
+    #[must_use]
+    pub(crate) fn grouped_by_type_and_crate_ignoring_version(mut self) -> ProblemList {
+        let mut merged = ProblemList::default();
+        let mut index_by_crate_name: FxHashMap<CrateName, usize> = FxHashMap::default();
+        for problem in self.problems.drain(..) {
+            match problem {
+                Problem::DisallowedApiUsage(usage) => {
+                    match index_by_crate_name.entry(CrateName::from(&usage.crate_sel)) {
+                        Entry::Occupied(entry) => {
+                            let Problem::DisallowedApiUsage(existing) =
+                                &mut merged.problems[*entry.get()]
+                            else {
+                                panic!("Problems::condense internal error");
+                            };
+                            existing.merge(usage);
+                        }
+                        Entry::Vacant(entry) => {
+                            entry.insert(merged.problems.len());
+                            merged.push(Problem::DisallowedApiUsage(usage));
+                        }
+                    }
+                }
+                other => merged.push(other),
+            }
+        }
+        merged
+    }
+
+    /// Undoes `grouped_by_type_and_crate_ignoring_version`, replacing each merged problem with the
+    /// problems that it was merged from.
+    #[must_use]
+    pub(crate) fn split_versions(mut self) -> ProblemList {
+        let mut split = ProblemList::default();
+        for problem in self.problems.drain(..) {
+            match problem {
+                Problem::DisallowedApiUsage(usage) if !usage.merged_from.is_empty() => {
+                    for original in usage.merged_from {
+                        split.push(Problem::DisallowedApiUsage(original));
+                    }
+                }
+                other => split.push(other),
+            }
+        }
+        split
+    }
+
     /// Combines disallowed API usages by whatever the supplied `group_fn` returns.
     #[must_use]
     fn grouped_by(mut self, group_fn: impl Fn(&ApiUsages) -> String) -> ProblemList {
@@ -168,6 +289,10 @@ impl ProblemList {
                             for (k, mut v) in usage.usages {
                                 existing.usages.entry(k).or_default().append(&mut v);
                             }
+                            existing.descriptions.extend(usage.descriptions);
+                            existing.warning_apis.extend(usage.warning_apis);
+                            existing.report_caps.extend(usage.report_caps);
+                            existing.build_time |= usage.build_time;
                         }
                         Entry::Vacant(entry) => {
                             let index = merged.problems.len();
@@ -201,9 +326,14 @@ impl<'a> IntoIterator for &'a ProblemList {
     }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-pub(crate) enum Severity {
+/// How serious a problem is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, serde::Deserialize, serde::Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    /// Something that's probably worth looking at, but that doesn't fail a check unless
+    /// `--fail-on-warnings` is given.
     Warning,
+    /// Something that fails a check, such as a package using an API that it isn't permitted to use.
     Error,
 }
 
@@ -215,13 +345,54 @@ impl Problem {
     pub(crate) fn severity(&self) -> Severity {
         match self {
             Problem::UnusedAllowApi(..)
+            | Problem::UnusedAllowSymbols(..)
+            | Problem::UnusedSuppression(..)
+            | Problem::UnusedExclude(..)
             | Problem::UnusedPackageConfig(..)
             | Problem::PossibleExportedApi(..)
-            | Problem::AvailableApi(..) => Severity::Warning,
+            | Problem::AvailableApi(..)
+            | Problem::IncludedAndExcluded(..)
+            | Problem::YankedVersion(..) => Severity::Warning,
+            // An API's severity is about what a program does when it runs, so doesn't apply to
+            // proc macros, which run on the build machine.
+            Problem::DisallowedApiUsage(usages)
+                if !usages.build_time
+                    && !usages.usages.is_empty()
+                    && usages
+                        .usages
+                        .keys()
+                        .all(|api| usages.warning_apis.contains(api)) =>
+            {
+                Severity::Warning
+            }
             _ => Severity::Error,
         }
     }
 
+    /// Returns whether this problem is about config that isn't needed and could be removed.
+    pub(crate) fn is_unused_config(&self) -> bool {
+        matches!(
+            self,
+            Problem::UnusedPackageConfig(..)
+                | Problem::UnusedAllowApi(..)
+                | Problem::UnusedAllowSymbols(..)
+                | Problem::UnusedSuppression(..)
+                | Problem::UnusedExclude(..)
+        )
+    }
+
+    /// Returns whether `self` and `other` are the same kind of problem, such that an edit that fixes
+    /// one would, if applied to the other's crate, fix the other. For API usages, this requires
+    /// that both use the same set of APIs.
+    pub(crate) fn is_similar_to(&self, other: &Problem) -> bool {
+        match (self, other) {
+            (Problem::DisallowedApiUsage(a), Problem::DisallowedApiUsage(b)) => {
+                a.usages.keys().eq(b.usages.keys())
+            }
+            _ => std::mem::discriminant(self) == std::mem::discriminant(other),
+        }
+    }
+
     /// Returns whether a retry on this problem needs to be sent to a subprocess.
     fn should_send_retry_to_subprocess(&self) -> bool {
         matches!(
@@ -230,24 +401,29 @@ impl Problem {
         )
     }
 
+    /// Returns an identifier for this problem that stays the same between runs. This is the
+    /// single-line summary of the problem, so doesn't depend on details like source locations.
+    pub(crate) fn stable_id(&self) -> String {
+        self.to_string()
+    }
+
     /// Returns `self` or a clone of `self` with any bits that aren't relevant for deduplication
     /// removed.
-    pub(crate) fn deduplication_key(&self) -> Cow<Problem> {
+    pub(crate) fn deduplication_key(&self) -> Cow<'_, Problem> {
         match self {
-            Problem::DisallowedApiUsage(api_usage) => {
+            Problem::DisallowedApiUsage(api_usage)
                 if api_usage
                     .usages
                     .values()
-                    .any(|usages| usages.iter().any(|usage| usage.debug_data.is_some()))
-                {
-                    let mut api_usage = api_usage.clone();
-                    for usages in api_usage.usages.values_mut() {
-                        for usage in usages {
-                            usage.debug_data = None;
-                        }
+                    .any(|usages| usages.iter().any(|usage| usage.debug_data.is_some())) =>
+            {
+                let mut api_usage = api_usage.clone();
+                for usages in api_usage.usages.values_mut() {
+                    for usage in usages {
+                        usage.debug_data = None;
                     }
-                    return Cow::Owned(Problem::DisallowedApiUsage(api_usage));
                 }
+                return Cow::Owned(Problem::DisallowedApiUsage(api_usage));
             }
             Problem::PossibleExportedApi(info) => {
                 return Cow::Owned(Problem::PossibleExportedApi(PossibleExportedApi {
@@ -260,6 +436,26 @@ impl Problem {
         Cow::Borrowed(self)
     }
 
+    /// Returns the source locations at which this problem occurs, if it's associated with any.
+    pub(crate) fn source_locations(&self) -> Vec<&SourceLocation> {
+        let mut locations: Vec<&SourceLocation> = match self {
+            Problem::DisallowedUnsafe(usage) => usage.locations.iter().collect(),
+            Problem::ShellInvocation(info) => vec![&info.location],
+            Problem::UnusedSuppression(info) => vec![&info.suppression.location],
+            Problem::DisallowedApiUsage(info) => info
+                .usages
+                .values()
+                .flatten()
+                .map(|usage| &usage.source_location)
+                .filter(|location| !location.is_unknown())
+                .collect(),
+            _ => Vec::new(),
+        };
+        locations.sort();
+        locations.dedup();
+        locations
+    }
+
     pub(crate) fn pkg_id(&self) -> Option<&PackageId> {
         match self {
             Problem::Message(_) => None,
@@ -272,10 +468,18 @@ impl Problem {
             Problem::DisallowedBuildInstruction(d) => Some(&d.build_script_id.pkg_id),
             Problem::UnusedPackageConfig(_) => None,
             Problem::UnusedAllowApi(_) => None,
+            Problem::UnusedAllowSymbols(_) => None,
+            Problem::UnusedSuppression(_) => None,
+            Problem::UnusedExclude(_) => None,
             Problem::SelectSandbox => None,
             Problem::ImportStdApi(_) => None,
             Problem::AvailableApi(d) => Some(&d.pkg_id),
             Problem::PossibleExportedApi(d) => Some(&d.pkg_id),
+            Problem::UndecidedPackages(_) => None,
+            Problem::IncludedAndExcluded(_) => None,
+            Problem::ShellInvocation(d) => Some(d.crate_sel.pkg_id()),
+            Problem::DisallowedLicense(d) => Some(&d.pkg_id),
+            Problem::YankedVersion(pkg_id) => Some(pkg_id),
         }
     }
 }
@@ -313,14 +517,7 @@ impl Display for Problem {
             )?,
             Problem::DisallowedApiUsage(info) => info.fmt(f)?,
             Problem::BuildScriptFailed(info) => info.fmt(f)?,
-            Problem::DisallowedBuildInstruction(info) => {
-                write!(
-                    f,
-                    "{}'s build script emitted disallowed instruction `{}`",
-                    CrateSel::Primary(info.build_script_id.pkg_id.clone()),
-                    info.instruction
-                )?;
-            }
+            Problem::DisallowedBuildInstruction(info) => info.fmt(f)?,
             Problem::UnusedPackageConfig(pkg_name) => {
                 write!(
                     f,
@@ -328,6 +525,9 @@ impl Display for Problem {
                 )?;
             }
             Problem::UnusedAllowApi(info) => info.fmt(f)?,
+            Problem::UnusedAllowSymbols(info) => info.fmt(f)?,
+            Problem::UnusedSuppression(info) => info.fmt(f)?,
+            Problem::UnusedExclude(info) => info.fmt(f)?,
             Problem::MissingConfiguration(path) => {
                 write!(f, "Config file `{}` not found", path.display())?;
             }
@@ -358,24 +558,82 @@ impl Display for Problem {
                     )?;
                 }
             }
+            Problem::ShellInvocation(info) => {
+                write!(f, "`{}` runs shell `{}`", info.crate_sel, info.shell)?;
+                if f.alternate() {
+                    write!(f, "\n{}", info.location)?;
+                }
+            }
+            Problem::DisallowedLicense(info) => match &info.license {
+                Some(license) => write!(
+                    f,
+                    "Package `{}` has license `{license}`, which isn't allowed",
+                    info.pkg_id
+                )?,
+                None => write!(f, "Package `{}` doesn't specify a license", info.pkg_id)?,
+            },
+            Problem::YankedVersion(pkg_id) => write!(
+                f,
+                "Version {} of package `{}` has been yanked from crates.io",
+                pkg_id.version(),
+                pkg_id.name()
+            )?,
+            Problem::IncludedAndExcluded(info) => write!(
+                f,
+                "API `{}` both includes and excludes `{}`. The exclude takes precedence",
+                info.api, info.path
+            )?,
+            Problem::UndecidedPackages(pkg_ids) => {
+                write!(f, "Packages had problems, but have no configuration: ")?;
+                let mut first = true;
+                for pkg_id in pkg_ids {
+                    if first {
+                        first = false;
+                    } else {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{pkg_id}")?;
+                }
+            }
         }
         Ok(())
     }
 }
 
+/// The alternate form lists each usage. If a precision is given, e.g. `{:#.200}`, then symbol names
+/// in that list are truncated to that many characters.
 impl Display for ApiUsages {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        // When usages from several versions have been merged, we show the package name without a
+        // version, followed by how many versions there were.
+        let num_versions = self.num_versions();
+        let (crate_label, versions) = if num_versions > 1 {
+            (
+                CrateName::from(&self.crate_sel).to_string(),
+                format!(" ({num_versions} versions)"),
+            )
+        } else {
+            (self.crate_sel.to_string(), String::new())
+        };
+        let when = if self.build_time {
+            " at compile time"
+        } else {
+            ""
+        };
         if f.alternate() {
-            writeln!(f, "'{}' uses disallowed APIs:", self.crate_sel)?;
+            writeln!(f, "'{crate_label}'{versions} uses disallowed APIs{when}:")?;
+            if !self.dependency_path.steps.is_empty() {
+                writeln!(f, "  pulled in by {}", self.dependency_path)?;
+            }
             for (perm_name, usages) in &self.usages {
                 writeln!(f, "  {perm_name}:")?;
-                display_usages(f, usages)?;
+                display_usages(f, usages, self.report_caps.get(perm_name).copied())?;
             }
         } else if self.usages.len() == 1 {
             let (perm, _) = self.usages.first_key_value().unwrap();
-            write!(f, "`{}` uses API `{perm}`", self.crate_sel)?;
+            write!(f, "`{crate_label}`{versions} uses API `{perm}`{when}")?;
         } else {
-            write!(f, "'{}' uses disallowed APIs: ", self.crate_sel)?;
+            write!(f, "'{crate_label}'{versions} uses disallowed APIs{when}: ")?;
             let mut first = true;
             for perm_name in self.usages.keys() {
                 if first {
@@ -404,13 +662,87 @@ impl Display for UnusedAllowApi {
     }
 }
 
+impl Display for UnusedSuppression {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "`cackle:allow({})` at {} doesn't suppress any API usages",
+            self.suppression.permission, self.suppression.location
+        )
+    }
+}
+
+impl Display for UnusedExclude {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "API `{}` excludes `{}`, but that exclude didn't affect anything",
+            self.api, self.path
+        )
+    }
+}
+
+impl Display for UnusedAllowSymbols {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if f.alternate() {
+            writeln!(
+                f,
+                "`pkg.{}` allows symbols that aren't used:",
+                self.crate_name
+            )?;
+            for symbol in &self.symbols {
+                writeln!(f, "    {symbol}")?;
+            }
+        } else {
+            write!(
+                f,
+                "`pkg.{}` allows symbols that aren't used",
+                self.crate_name
+            )?;
+        }
+        Ok(())
+    }
+}
+
+impl Display for DisallowedBuildInstruction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}'s build script emitted disallowed instruction `{}`",
+            CrateSel::Primary(self.build_script_id.pkg_id.clone()),
+            self.instruction
+        )?;
+        if f.alternate() {
+            let mut by_kind: BTreeMap<&str, Vec<&str>> = BTreeMap::new();
+            for instruction in &self.emitted {
+                by_kind
+                    .entry(instruction.kind.as_str())
+                    .or_default()
+                    .push(instruction.value.as_str());
+            }
+            writeln!(f, "\nAll instructions emitted by the build script:")?;
+            for (kind, values) in by_kind {
+                writeln!(f, "  {kind}")?;
+                for value in values {
+                    writeln!(f, "    {value}")?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
 impl Display for BuildScriptFailed {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(
             f,
-            "Build script for package `{}` failed",
+            "Build script for package `{}` ",
             self.output.build_script_id.pkg_id
         )?;
+        match self.output.timed_out_after {
+            Some(timeout) => write!(f, "timed out after {}s", timeout.as_secs())?,
+            None => write!(f, "failed")?,
+        }
         if f.alternate() {
             write!(
                 f,
@@ -430,10 +762,22 @@ impl Display for BuildScriptFailed {
     }
 }
 
+/// Writes `usages`, grouped by file and by the function they're in. If `cap` is supplied, only that
+/// many usages are written, taken in source order.
 fn display_usages(
     f: &mut std::fmt::Formatter,
-    usages: &Vec<ApiUsage>,
+    usages: &[ApiUsage],
+    cap: Option<usize>,
 ) -> Result<(), std::fmt::Error> {
+    let mut usages: Vec<&ApiUsage> = usages.iter().collect();
+    let mut num_omitted = 0;
+    if let Some(cap) = cap {
+        if usages.len() > cap {
+            usages.sort_by_key(|u| &u.source_location);
+            num_omitted = usages.len() - cap;
+            usages.truncate(cap);
+        }
+    }
     let mut by_source_filename: BTreeMap<&Path, Vec<&ApiUsage>> = BTreeMap::new();
     for u in usages {
         by_source_filename
@@ -441,29 +785,68 @@ fn display_usages(
             .or_default()
             .push(u);
     }
+    let max_len = f.precision();
     let mut by_from: BTreeMap<&SymbolOrDebugName, Vec<&ApiUsage>> = BTreeMap::new();
     for (filename, usages_for_location) in by_source_filename {
-        writeln!(f, "    {}", filename.display())?;
+        writeln!(
+            f,
+            "    {}",
+            crate::location::to_relative_path(filename).display()
+        )?;
         by_from.clear();
         for usage in usages_for_location {
             by_from.entry(&usage.from).or_default().push(usage);
         }
-        for (from, local_usages) in &by_from {
-            writeln!(f, "      {from}")?;
+        for (from, local_usages) in &mut by_from {
+            // Show usages in the order that they appear in the source. Several usages on the same
+            // line are distinguished by their columns.
+            local_usages.sort_by_key(|u| {
+                (
+                    u.source_location.line(),
+                    u.source_location.column(),
+                    u.to_source.to_string(),
+                )
+            });
+            writeln!(f, "      {}", display_truncated(from, max_len))?;
+            // The backtrace depends only on `from`, so it's the same for all of these usages.
+            if let Some(first) = local_usages.first() {
+                for caller in &first.backtrace {
+                    writeln!(
+                        f,
+                        "        called from {}",
+                        display_truncated(caller, max_len)
+                    )?;
+                }
+            }
             for u in local_usages {
+                if u.source_location.is_unknown() {
+                    writeln!(
+                        f,
+                        "        -> {} [unknown location]",
+                        display_truncated(&u.to_source, max_len),
+                    )?;
+                    continue;
+                }
                 write!(
                     f,
                     "        -> {} [{}",
-                    u.to_source,
+                    display_truncated(&u.to_source, max_len),
                     u.source_location.line(),
                 )?;
                 if let Some(column) = u.source_location.column() {
                     write!(f, ":{}", column)?;
                 }
-                writeln!(f, "]")?;
+                write!(f, "]")?;
+                if let Some(env_var) = &u.env_var {
+                    write!(f, " {env_var}")?;
+                }
+                writeln!(f)?;
             }
         }
     }
+    if num_omitted > 0 {
+        writeln!(f, "    ... and {num_omitted} more")?;
+    }
     Ok(())
 }
 
@@ -512,22 +895,112 @@ impl ApiUsages {
     }
 
     pub(crate) fn first_usage(&self) -> Option<&ApiUsage> {
-        self.usages.values().next().and_then(|u| u.get(0))
+        self.usages.values().next().and_then(|u| u.first())
+    }
+
+    /// Returns the number of distinct versions of the package that these usages came from.
+    pub(crate) fn num_versions(&self) -> usize {
+        self.merged_from
+            .iter()
+            .map(|usages| usages.crate_sel.pkg_id())
+            .collect::<HashSet<_>>()
+            .len()
+            .max(1)
+    }
+
+    /// Adds the usages from `other` into `self`, remembering the originals so that they can be
+    /// split apart again.
+    fn merge(&mut self, other: ApiUsages) {
+        if self.merged_from.is_empty() {
+            self.merged_from.push(self.clone());
+        }
+        for (perm, usages) in &other.usages {
+            self.usages
+                .entry(perm.clone())
+                .or_default()
+                .extend(usages.iter().cloned());
+        }
+        self.descriptions.extend(
+            other
+                .descriptions
+                .iter()
+                .map(|(perm, description)| (perm.clone(), description.clone())),
+        );
+        self.warning_apis.extend(other.warning_apis.iter().cloned());
+        self.report_caps.extend(
+            other
+                .report_caps
+                .iter()
+                .map(|(perm, cap)| (perm.clone(), *cap)),
+        );
+        if other.merged_from.is_empty() {
+            self.merged_from.push(other);
+        } else {
+            self.merged_from.extend(other.merged_from);
+        }
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod testing {
+    use super::ApiUsage;
+    use super::ApiUsages;
+    use crate::config::PermissionName;
+    use crate::crate_index::CrateSel;
+    use crate::location::SourceLocation;
+    use crate::names::SymbolOrDebugName;
+    use crate::symbol::Symbol;
+    use crate::symbol_graph::NameSource;
+    use std::collections::BTreeMap;
+
+    /// Returns a usage of the function `to` from the function `from`.
+    pub(crate) fn api_usage(from: &str, to: &str, source_location: SourceLocation) -> ApiUsage {
+        let to_symbol = Symbol::borrowed(to.as_bytes()).to_heap();
+        ApiUsage {
+            source_location,
+            from: SymbolOrDebugName::Symbol(Symbol::borrowed(from.as_bytes()).to_heap()),
+            to: SymbolOrDebugName::Symbol(to_symbol.clone()),
+            to_name: crate::names::split_simple(to),
+            to_source: NameSource::Symbol(to_symbol),
+            debug_data: None,
+            backtrace: Vec::new(),
+            env_var: None,
+        }
+    }
+
+    pub(crate) fn api_usages(
+        crate_sel: CrateSel,
+        usages: BTreeMap<PermissionName, Vec<ApiUsage>>,
+    ) -> ApiUsages {
+        ApiUsages {
+            crate_sel,
+            usages,
+            descriptions: BTreeMap::new(),
+            warning_apis: Default::default(),
+            report_caps: BTreeMap::new(),
+            dependency_path: Default::default(),
+            build_time: false,
+            merged_from: Vec::new(),
+        }
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use super::testing::api_usage;
+    use super::testing::api_usages;
     use super::Problem;
     use super::ProblemList;
+    use super::Severity;
     use crate::checker::ApiUsage;
     use crate::config::PermissionName;
     use crate::crate_index::testing::pkg_id;
+    use crate::crate_index::testing::pkg_id_with_version;
     use crate::crate_index::CrateSel;
+    use crate::crate_index::DependencyPath;
+    use crate::crate_index::DependencyStep;
     use crate::location::SourceLocation;
-    use crate::names::SymbolOrDebugName;
     use crate::symbol::Symbol;
-    use crate::symbol_graph::NameSource;
     use std::collections::BTreeMap;
     use std::path::Path;
     use std::sync::Arc;
@@ -564,6 +1037,178 @@ mod tests {
         assert_eq!(package_names, vec!["foo1", "foo2"]);
     }
 
+    #[test]
+    fn group_ignoring_version() {
+        let versioned = |major, from| {
+            let Problem::DisallowedApiUsage(mut usages) =
+                create_problem("foo", &[("net", &[create_usage(from, "net_stuff")])])
+            else {
+                unreachable!();
+            };
+            usages.crate_sel = CrateSel::Primary(pkg_id_with_version("foo", major));
+            Problem::DisallowedApiUsage(usages)
+        };
+        let mut problems = ProblemList::default();
+        problems.push(versioned(1, "aaa"));
+        problems.push(create_problem(
+            "bar",
+            &[("net", &[create_usage("ccc", "net_stuff")])],
+        ));
+        problems.push(versioned(2, "bbb"));
+        let original = problems.clone();
+        assert_eq!(original[0].to_string(), "`foo[1.0.0]` uses API `net`");
+
+        let merged = problems.grouped_by_type_and_crate_ignoring_version();
+        assert_eq!(merged.len(), 2);
+        assert_eq!(merged[0].to_string(), "`foo` (2 versions) uses API `net`");
+        assert_eq!(merged[1].to_string(), "`bar` uses API `net`");
+        let Problem::DisallowedApiUsage(usages) = &merged[0] else {
+            panic!("Unexpected problem {}", merged[0]);
+        };
+        assert_eq!(usages.usages[&PermissionName::from("net")].len(), 2);
+
+        let mut split = merged.split_versions();
+        split.problems.sort_by_key(|problem| problem.to_string());
+        let mut expected = original;
+        expected.problems.sort_by_key(|problem| problem.to_string());
+        assert_eq!(split, expected);
+    }
+
+    #[test]
+    fn similar_problems() {
+        let fs1 = create_problem("foo1", &[("fs", &[create_usage("aaa", "fs_stuff")])]);
+        let fs2 = create_problem("foo2", &[("fs", &[create_usage("bbb", "fs_stuff")])]);
+        let net = create_problem("foo1", &[("net", &[create_usage("aaa", "net_stuff")])]);
+        assert!(fs1.is_similar_to(&fs2));
+        assert!(!fs1.is_similar_to(&net));
+        assert!(!fs1.is_similar_to(&Problem::IsProcMacro(pkg_id("foo1"))));
+        assert!(Problem::IsProcMacro(pkg_id("foo1"))
+            .is_similar_to(&Problem::IsProcMacro(pkg_id("foo2"))));
+    }
+
+    #[test]
+    fn usages_capped() {
+        let mut problem = create_problem(
+            "foo1",
+            &[(
+                "fs",
+                &[
+                    create_usage("aaa", "fs_stuff"),
+                    create_usage("bbb", "fs_stuff"),
+                    create_usage("ccc", "fs_stuff"),
+                ],
+            )],
+        );
+        assert!(!format!("{problem:#}").contains("more"));
+        let Problem::DisallowedApiUsage(usages) = &mut problem else {
+            unreachable!();
+        };
+        usages.report_caps.insert(PermissionName::from("fs"), 1);
+        let capped = format!("{problem:#}");
+        assert!(capped.contains(
+            "      aaa
+"
+        ));
+        assert!(!capped.contains("bbb"));
+        assert!(capped.contains("    ... and 2 more\n"));
+    }
+
+    #[test]
+    fn long_symbols_truncated() {
+        let long_name = format!("foo::<{}>", "u8, ".repeat(1000));
+        let problem = create_problem("foo1", &[("fs", &[create_usage(&long_name, "fs_stuff")])]);
+        let truncated = format!("{problem:#.20}");
+        assert!(truncated.contains("      foo::<u8, u8, u8, u8…\n"));
+        assert!(truncated.contains("        -> fs_stuff [1]\n"));
+        assert!(!truncated.contains(&long_name));
+        assert!(format!("{problem:#}").contains(&long_name));
+    }
+
+    #[test]
+    fn backtrace_displayed() {
+        let mut usage = create_usage("helper", "net_stuff");
+        usage.backtrace = vec![
+            Symbol::borrowed(b"middle").to_heap(),
+            Symbol::borrowed(b"entry").to_heap(),
+        ];
+        let problem = create_problem("foo1", &[("net", &[usage])]);
+        assert!(format!("{problem:#}").contains(
+            "      helper\n        called from middle\n        called from entry\n        -> net_stuff [1]\n"
+        ));
+    }
+
+    #[test]
+    fn dependency_path_displayed() {
+        let mut problem = create_problem("openssl", &[("net", &[create_usage("a", "b")])]);
+        let Problem::DisallowedApiUsage(usages) = &mut problem else {
+            unreachable!();
+        };
+        usages.dependency_path = DependencyPath {
+            steps: vec![
+                DependencyStep {
+                    pkg_id: pkg_id("foo"),
+                    feature: None,
+                },
+                DependencyStep {
+                    pkg_id: pkg_id("bar"),
+                    feature: Some("tls".to_owned()),
+                },
+                DependencyStep {
+                    pkg_id: pkg_id("openssl"),
+                    feature: None,
+                },
+            ],
+        };
+        assert!(
+            format!("{problem:#}").contains("  pulled in by foo → bar (feature `tls`) → openssl\n")
+        );
+        assert!(!format!("{problem}").contains("pulled in by"));
+    }
+
+    #[test]
+    fn usages_sorted_by_line_and_column() {
+        let usage_at = |to: &str, line, column| {
+            let mut usage = create_usage("helper", to);
+            usage.source_location = SourceLocation::new(Path::new("lib.rs"), line, column);
+            usage
+        };
+        let problem = create_problem(
+            "foo1",
+            &[(
+                "net",
+                &[
+                    usage_at("connect", 2, Some(30)),
+                    usage_at("bind", 2, Some(5)),
+                    usage_at("listen", 1, None),
+                ],
+            )],
+        );
+        assert!(format!("{problem:#}").contains(
+            "      helper\n        -> listen [1]\n        -> bind [2:5]\n        -> connect [2:30]\n"
+        ));
+    }
+
+    #[test]
+    fn proc_macro_usage() {
+        let Problem::DisallowedApiUsage(mut usages) =
+            create_problem("pmacro", &[("fs", &[create_usage("expand", "read")])])
+        else {
+            unreachable!();
+        };
+        usages.warning_apis.insert(PermissionName::new("fs"));
+        assert_eq!(
+            Problem::DisallowedApiUsage(usages.clone()).severity(),
+            Severity::Warning
+        );
+        usages.build_time = true;
+        let problem = Problem::DisallowedApiUsage(usages);
+        assert_eq!(problem.severity(), Severity::Error);
+        assert_eq!(
+            problem.to_string(),
+            "`pmacro` uses API `fs` at compile time"
+        );
+    }
+
     fn create_problem(package: &str, permissions_and_usage: &[(&str, &[ApiUsage])]) -> Problem {
         let mut usages = BTreeMap::new();
         for (perm_name, usage) in permissions_and_usage {
@@ -574,21 +1219,10 @@ mod tests {
                 usage.to_vec(),
             );
         }
-        Problem::DisallowedApiUsage(super::ApiUsages {
-            crate_sel: CrateSel::Primary(pkg_id(package)),
-            usages,
-        })
+        Problem::DisallowedApiUsage(api_usages(CrateSel::Primary(pkg_id(package)), usages))
     }
 
     fn create_usage(from: &str, to: &str) -> ApiUsage {
-        let to_symbol = Symbol::borrowed(to.as_bytes()).to_heap();
-        ApiUsage {
-            source_location: SourceLocation::new(Path::new("lib.rs"), 1, None),
-            from: SymbolOrDebugName::Symbol(Symbol::borrowed(from.as_bytes()).to_heap()),
-            to: SymbolOrDebugName::Symbol(to_symbol.clone()),
-            to_name: crate::names::split_simple("foo:bar"),
-            to_source: NameSource::Symbol(to_symbol.clone()),
-            debug_data: None,
-        }
+        api_usage(from, to, SourceLocation::new(Path::new("lib.rs"), 1, None))
     }
 }
@@ -22,6 +22,10 @@ pub(crate) struct ProblemStore {
     entries: Vec<Entry>,
     event_sender: Sender<AppEvent>,
     pub(crate) has_aborted: bool,
+    /// Whether any problems were reported as warnings and then resolved without being fixed.
+    pub(crate) has_warnings: bool,
+    /// Whether disallowed API usages from different versions of the same package are merged.
+    merge_versions: bool,
 }
 
 #[derive(Debug, Clone, Copy, Default)]
@@ -45,7 +49,7 @@ impl ProblemStoreRef {
         outcome.recv().unwrap_or(Outcome::GiveUp)
     }
 
-    pub(crate) fn lock(&self) -> MutexGuard<ProblemStore> {
+    pub(crate) fn lock(&self) -> MutexGuard<'_, ProblemStore> {
         self.inner.lock().unwrap()
     }
 }
@@ -56,17 +60,22 @@ impl ProblemStore {
             entries: Vec::new(),
             event_sender,
             has_aborted: false,
+            has_warnings: false,
+            merge_versions: false,
         }
     }
 
     /// Adds `problems` to this store. The returned receiver will receive a single value once all
     /// problems in the supplied list have been resolved, or abort has been called. The supplied
     /// problem list must not be empty.
-    fn add(&mut self, problems: ProblemList) -> Receiver<Outcome> {
+    fn add(&mut self, mut problems: ProblemList) -> Receiver<Outcome> {
         for problem in &problems {
             info!("Reported problem: {problem}");
         }
         assert!(!problems.is_empty());
+        if self.merge_versions {
+            problems = problems.grouped_by_type_and_crate_ignoring_version();
+        }
         let (sender, receiver) = std::sync::mpsc::channel();
         self.entries.push(Entry {
             problems,
@@ -136,12 +145,29 @@ impl ProblemStore {
         }
     }
 
-    pub(crate) fn is_empty(&self) -> bool {
-        self.entries.iter().all(|entry| entry.problems.is_empty())
+    pub(crate) fn merge_versions(&self) -> bool {
+        self.merge_versions
     }
 
-    pub(crate) fn len(&self) -> usize {
-        self.entries.iter().map(|entry| entry.problems.len()).sum()
+    /// Sets whether disallowed API usages from different versions of the same package are merged.
+    /// Applies to problems already in the store as well as those added later.
+    pub(crate) fn set_merge_versions(&mut self, merge_versions: bool) {
+        if merge_versions == self.merge_versions {
+            return;
+        }
+        self.merge_versions = merge_versions;
+        for plist in &mut self.entries {
+            let problems = std::mem::take(&mut plist.problems);
+            plist.problems = if merge_versions {
+                problems.grouped_by_type_and_crate_ignoring_version()
+            } else {
+                problems.split_versions()
+            };
+        }
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.entries.iter().all(|entry| entry.problems.is_empty())
     }
 
     pub(crate) fn resolve(&mut self, index: ProblemStoreIndex) {
@@ -226,7 +252,7 @@ mod tests {
         store.add(create_problems());
         store.add(create_problems());
 
-        assert_eq!(store.len(), 4);
+        assert_eq!(store.iterate_with_duplicates().count(), 4);
 
         let mut iter = store.iterate_with_duplicates();
         assert_eq!(
@@ -20,15 +20,20 @@
 //! * We can run them inside a sandbox if the config says to do so.
 //! * We can capture their output and check for any directives to cargo that haven't been permitted.
 
+use crate::checker::Category;
 use crate::config::Config;
 use crate::crate_index::CrateIndex;
+use crate::crate_index::PackageId;
 use crate::outcome::ExitCode;
 use crate::outcome::Outcome;
+use crate::ui::CargoOutput;
 use crate::Args;
 use crate::RequestHandler;
 use anyhow::Context;
 use anyhow::Result;
 use std::fmt::Display;
+use std::io::BufRead;
+use std::io::BufReader;
 use std::os::unix::net::UnixListener;
 use std::os::unix::net::UnixStream;
 use std::path::Path;
@@ -37,34 +42,84 @@ use std::process::Command;
 use std::process::Stdio;
 use std::sync::mpsc::channel;
 use std::sync::mpsc::Receiver;
+use std::sync::mpsc::Sender;
 use std::thread::JoinHandle;
 use std::time::Duration;
 use tempfile::TempDir;
 
 use self::rpc::Request;
+use self::socket::SocketAddress;
 
 pub(crate) mod cargo;
 pub(crate) mod errors;
+pub(crate) mod rerun;
 pub(crate) mod rpc;
+mod socket;
 pub(crate) mod subprocess;
+pub(crate) mod target;
 
 const SOCKET_ENV: &str = "CACKLE_SOCKET_PATH";
 const CONFIG_PATH_ENV: &str = "CACKLE_CONFIG_PATH";
 const ORIG_LINKER_ENV: &str = "CACKLE_ORIG_LINKER";
+const SKIP_UNSAFE_ENV: &str = "CACKLE_SKIP_UNSAFE";
+const IGNORED_PACKAGES_ENV: &str = "CACKLE_IGNORED_PACKAGES";
+const RERUN_FAILED_ENV: &str = "CACKLE_RERUN_FAILED";
+const FAILED_INVOCATIONS_DIR_ENV: &str = "CACKLE_FAILED_INVOCATIONS_DIR";
 
 #[derive(Debug)]
 pub(crate) struct CargoBuildFailure {
     stdout: Vec<u8>,
     stderr: Vec<u8>,
+    offline: bool,
 }
 
 pub(crate) fn clean(dir: &Path, args: &Args) -> Result<()> {
-    // For now, we always clean before we build. It might be possible to not do this, but we'd need
+    // We clean before we build unless we're resuming an interrupted run, since otherwise we'd need
     // to carefully track changes to things we care about, like cackle.toml.
-    run_command(&mut cargo::command("clean", dir, args))?;
+    let mut command = cargo::command("clean", dir, args);
+    // When a user interface is running, e.g. when we're checking again with `--watch`, anything
+    // that cargo printed would mess up the display.
+    if args.quiet || args.cargo_output() == CargoOutput::Capture {
+        command.arg("--quiet");
+    }
+    run_command(&mut command)?;
     Ok(())
 }
 
+/// Returns the `cargo build` command to run in `dir`, without any of the environment variables needed
+/// for us to act as a proxy.
+fn build_command(dir: &Path, config: &Config, args: &Args) -> Command {
+    let mut command = cargo::command("build", dir, args);
+    // Tests and benchmarks need the standard library, so by default, we don't try to build them for
+    // bare-metal targets, which generally don't have it.
+    let all_targets = ["--all-targets".to_owned()];
+    let default_build_flags: &[String] =
+        if args.target.as_deref().is_some_and(target::is_bare_metal) {
+            &[]
+        } else {
+            &all_targets
+        };
+    for flag in config
+        .common
+        .build_flags
+        .as_deref()
+        .unwrap_or(default_build_flags)
+    {
+        command.arg(flag);
+    }
+    if let Some(target) = &args.target {
+        command.arg("--target").arg(target);
+    }
+    if let Some(package) = &args.package {
+        command.arg("--package").arg(package);
+    }
+    command.args(args.feature_options(&config.common.features).cargo_args());
+    if let Some(jobs) = args.jobs {
+        command.arg("--jobs").arg(jobs.to_string());
+    }
+    command
+}
+
 /// Invokes `cargo build` in the specified directory with us acting as proxy versions of rustc and
 /// the linker. If calling this, you must call handle_wrapped_binaries from the start of main.
 pub(crate) fn invoke_cargo_build(
@@ -72,7 +127,7 @@ pub(crate) fn invoke_cargo_build(
     tmpdir: &TempDir,
     config: &Config,
     args: &Args,
-    abort_recv: Receiver<()>,
+    abort_recv: &Receiver<()>,
     crate_index: &CrateIndex,
     request_creator: impl Fn(Request) -> RequestHandler,
 ) -> Result<()> {
@@ -80,43 +135,55 @@ pub(crate) fn invoke_cargo_build(
         panic!("{SOCKET_ENV} is already set. Missing call to handle_wrapped_binarie?");
     }
 
-    let ipc_path = tmpdir.path().join("cackle.socket");
-    let _ = std::fs::remove_file(&ipc_path);
-    let listener = UnixListener::bind(&ipc_path)
-        .with_context(|| format!("Failed to create Unix socket `{}`", ipc_path.display()))?;
+    let (socket_address, _socket_dir) =
+        SocketAddress::choose(args.socket_path.as_deref(), tmpdir.path())?;
+    let listener = socket_address.bind()?;
 
-    let mut command = cargo::command("build", dir, args);
-    let default_build_flags = ["--all-targets".to_owned()];
-    for flag in config
-        .common
-        .build_flags
-        .as_deref()
-        .unwrap_or(default_build_flags.as_slice())
-    {
-        command.arg(flag);
-    }
-    if let Some(target) = &args.target {
-        command.arg("--target").arg(target);
-    }
-    if !config.common.features.is_empty() {
-        command.arg("--features");
-        command.arg(config.common.features.join(","));
-    }
+    let mut command = build_command(dir, config, args);
     let config_path = crate::config::flattened_config_path(tmpdir.path());
     command
-        .env(SOCKET_ENV, &ipc_path)
+        .env(SOCKET_ENV, socket_address.env_value())
         .env(CONFIG_PATH_ENV, config_path)
         .env("RUSTC_WRAPPER", cackle_exe()?);
+    if !args.should_check(Category::Unsafe) {
+        command.env(SKIP_UNSAFE_ENV, "1");
+    }
 
     crate_index.add_internal_env(&mut command);
+    let only_reachable_via_ignored = crate_index.packages_only_reachable_via(
+        config.common.ignore_dev_dependencies,
+        config.common.ignore_build_dependencies,
+    );
+    let ignored_packages: Vec<String> = crate_index
+        .package_ids()
+        .filter(|pkg_id| {
+            only_reachable_via_ignored.contains(pkg_id)
+                || args
+                    .package
+                    .as_deref()
+                    .is_some_and(|package| pkg_id.name() != package)
+        })
+        .map(ignored_package_key)
+        .collect();
+    command.env(IGNORED_PACKAGES_ENV, ignored_packages.join(","));
+    command.env(
+        FAILED_INVOCATIONS_DIR_ENV,
+        rerun::invocations_dir(dir, args),
+    );
 
     // Don't pass through environment variables that might have been set by `cargo run`. If we do,
     // then they might still be set in our subprocesses, which might then get confused and think
     // they're proxying the build of "cackle" itself.
     command.env_remove("CARGO_PKG_NAME");
-    let capture_output = args.should_capture_cargo_output();
-    if capture_output {
-        command.stdout(Stdio::piped()).stderr(Stdio::piped());
+    let cargo_output = args.cargo_output();
+    match cargo_output {
+        CargoOutput::Inherit => {}
+        CargoOutput::Capture => {
+            command.stdout(Stdio::piped()).stderr(Stdio::piped());
+        }
+        CargoOutput::Stream => {
+            command.stderr(Stdio::piped());
+        }
     }
     let mut cargo_process = command
         .spawn()
@@ -124,15 +191,24 @@ pub(crate) fn invoke_cargo_build(
 
     let mut stdout_thread = None;
     let mut stderr_thread = None;
-    if capture_output {
-        stdout_thread = Some(start_output_collecting_thread(
-            "cargo-stdout-reader",
-            cargo_process.stdout.take().unwrap(),
-        )?);
-        stderr_thread = Some(start_output_collecting_thread(
-            "cargo-stderr-reader",
-            cargo_process.stderr.take().unwrap(),
-        )?);
+    match cargo_output {
+        CargoOutput::Inherit => {}
+        CargoOutput::Capture => {
+            stdout_thread = Some(start_output_collecting_thread(
+                "cargo-stdout-reader",
+                cargo_process.stdout.take().unwrap(),
+            )?);
+            stderr_thread = Some(start_output_collecting_thread(
+                "cargo-stderr-reader",
+                cargo_process.stderr.take().unwrap(),
+            )?);
+        }
+        CargoOutput::Stream => {
+            stderr_thread = Some(start_output_streaming_thread(
+                "cargo-stderr-reader",
+                cargo_process.stderr.take().unwrap(),
+            )?);
+        }
     }
 
     listener
@@ -151,10 +227,14 @@ pub(crate) fn invoke_cargo_build(
                 .map(|thread| thread.join().unwrap())
                 .unwrap_or_default();
             drop(listener);
-            // Deleting the socket is best-effort only, so we don't report an error if we can't.
-            let _ = std::fs::remove_file(&ipc_path);
+            socket_address.remove();
             if status.code() != Some(0) {
-                return Err(CargoBuildFailure { stdout, stderr }.into());
+                return Err(CargoBuildFailure {
+                    stdout,
+                    stderr,
+                    offline: args.offline,
+                }
+                .into());
             }
             break;
         }
@@ -167,24 +247,84 @@ pub(crate) fn invoke_cargo_build(
         // We need to concurrently accept connections from our proxy subprocesses and also check to
         // see if our main subprocess has terminated. It should be possible to do this without
         // polling... but it's so much simpler to just poll.
-        if let Ok((mut connection, _)) = listener.accept() {
-            let request: rpc::Request = rpc::read_from_stream(&mut connection)
-                .context("Malformed request from subprocess")?;
-            let request_handler = (request_creator)(request);
-            let error_send = error_send.clone();
-            std::thread::Builder::new()
-                .name("Request handler".to_owned())
-                .spawn(move || {
-                    if let Err(error) = process_request(request_handler, connection) {
-                        let _ = error_send.send(error);
-                    }
-                })?;
-        } else {
-            // Avoid using too much CPU with our polling.
-            std::thread::sleep(Duration::from_millis(10));
+        accept_request(&listener, &request_creator, &error_send)?;
+    }
+
+    Ok(())
+}
+
+/// Runs the build script or rustc invocation that was saved when it failed for `crate_name` again
+/// with the current config. The subprocess reports what it ran and its output via an RPC request,
+/// which is handled like any other.
+pub(crate) fn rerun_failed(
+    dir: &Path,
+    tmpdir: &TempDir,
+    args: &Args,
+    crate_name: &str,
+    abort_recv: &Receiver<()>,
+    request_creator: impl Fn(Request) -> RequestHandler,
+) -> Result<()> {
+    let invocation = rerun::Invocation::load(&rerun::invocations_dir(dir, args), crate_name)?;
+    let (socket_address, _socket_dir) =
+        SocketAddress::choose(args.socket_path.as_deref(), tmpdir.path())?;
+    let listener = socket_address.bind()?;
+    let mut command = invocation.command()?;
+    command
+        .env(SOCKET_ENV, socket_address.env_value())
+        .env(
+            CONFIG_PATH_ENV,
+            crate::config::flattened_config_path(tmpdir.path()),
+        )
+        .env(RERUN_FAILED_ENV, crate_name)
+        .env_remove(FAILED_INVOCATIONS_DIR_ENV)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null());
+    let mut child = command
+        .spawn()
+        .with_context(|| format!("Failed to run {command:?}"))?;
+    listener
+        .set_nonblocking(true)
+        .context("Failed to set socket to non-blocking")?;
+    let (error_send, error_recv) = channel();
+    while child.try_wait()?.is_none() {
+        if let Ok(error) = error_recv.try_recv() {
+            return Err(error);
+        }
+        if abort_recv.try_recv().is_ok() {
+            let _ = child.kill();
         }
+        accept_request(&listener, &request_creator, &error_send)?;
     }
+    drop(listener);
+    socket_address.remove();
+    Ok(())
+}
 
+/// Accepts a connection from a subprocess, if there is one, and handles its request on a separate
+/// thread. Any error from handling the request is sent to `error_send`. If there's no connection
+/// waiting, sleeps for a short while, since callers poll.
+fn accept_request(
+    listener: &UnixListener,
+    request_creator: &impl Fn(Request) -> RequestHandler,
+    error_send: &Sender<anyhow::Error>,
+) -> Result<()> {
+    if let Ok((mut connection, _)) = listener.accept() {
+        let request: rpc::Request =
+            rpc::read_from_stream(&mut connection).context("Malformed request from subprocess")?;
+        let request_handler = (request_creator)(request);
+        let error_send = error_send.clone();
+        std::thread::Builder::new()
+            .name("Request handler".to_owned())
+            .spawn(move || {
+                if let Err(error) = process_request(request_handler, connection) {
+                    let _ = error_send.send(error);
+                }
+            })?;
+    } else {
+        // Avoid using too much CPU with our polling.
+        std::thread::sleep(Duration::from_millis(10));
+    }
     Ok(())
 }
 
@@ -201,6 +341,33 @@ fn start_output_collecting_thread(
         })?)
 }
 
+/// Copies each line from `reader` to our stderr as soon as it's available. Since the output has
+/// already been shown, the thread returns an empty buffer.
+fn start_output_streaming_thread(
+    thread_name: &str,
+    reader: impl std::io::Read + Send + 'static,
+) -> Result<JoinHandle<Vec<u8>>> {
+    Ok(std::thread::Builder::new()
+        .name(thread_name.to_owned())
+        .spawn(move || -> Vec<u8> {
+            let _ = stream_lines(reader, std::io::stderr());
+            Vec::new()
+        })?)
+}
+
+fn stream_lines(reader: impl std::io::Read, mut out: impl std::io::Write) -> std::io::Result<()> {
+    let mut reader = BufReader::new(reader);
+    let mut line = Vec::new();
+    loop {
+        line.clear();
+        if reader.read_until(b'\n', &mut line)? == 0 {
+            return Ok(());
+        }
+        out.write_all(&line)?;
+        out.flush()?;
+    }
+}
+
 fn process_request(mut request_handler: RequestHandler, mut connection: UnixStream) -> Result<()> {
     let response = request_handler.handle_request();
     let can_continue = response.as_ref().unwrap_or(&Outcome::GiveUp);
@@ -221,16 +388,123 @@ fn run_command(command: &mut Command) -> Result<ExitCode> {
         .into())
 }
 
-fn cackle_exe() -> Result<PathBuf> {
+fn ignored_package_key(pkg_id: &PackageId) -> String {
+    format!("{}@{}", pkg_id.name(), pkg_id.version())
+}
+
+/// Returns whether the parent cackle process told us that problems in `pkg_id` aren't reported,
+/// either because it's only a dev or build dependency that the config says to ignore, or because a
+/// different package was selected with --package.
+fn is_ignored_package(pkg_id: &PackageId) -> bool {
+    let key = ignored_package_key(pkg_id);
+    std::env::var(IGNORED_PACKAGES_ENV)
+        .unwrap_or_default()
+        .split(',')
+        .any(|ignored| ignored == key)
+}
+
+pub(crate) fn cackle_exe() -> Result<PathBuf> {
     std::env::current_exe().context("Failed to get current exe")
 }
 
 impl Display for CargoBuildFailure {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(f, "{}", String::from_utf8_lossy(&self.stdout))?;
-        write!(f, "{}", String::from_utf8_lossy(&self.stderr))?;
+        let stderr = String::from_utf8_lossy(&self.stderr);
+        write!(f, "{stderr}")?;
+        if self.offline && cargo::is_offline_failure(&stderr) {
+            write!(f, "\n{}", cargo::OFFLINE_FAILURE_MESSAGE)?;
+        }
         Ok(())
     }
 }
 
 impl std::error::Error for CargoBuildFailure {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::testing::parse;
+
+    fn build_args(config: &str, args: Args) -> Vec<String> {
+        let config = parse(config).unwrap();
+        let args = Args {
+            colour: crate::colour::Colour::Never,
+            ..args
+        };
+        build_command(Path::new("."), &config, &args)
+            .get_args()
+            .map(|arg| arg.to_string_lossy().into_owned())
+            .collect()
+    }
+
+    fn contains_sequence(args: &[String], sequence: &[&str]) -> bool {
+        args.windows(sequence.len())
+            .any(|window| window == sequence)
+    }
+
+    #[test]
+    fn features_from_config() {
+        let args = build_args(r#"features = ["a", "b"]"#, Args::default());
+        assert!(contains_sequence(&args, &["--features", "a,b"]));
+        assert!(!args.contains(&"--all-features".to_owned()));
+    }
+
+    #[test]
+    fn command_line_features_override_config() {
+        let args = build_args(
+            r#"features = ["a", "b"]"#,
+            Args {
+                features: vec!["c".to_owned()],
+                no_default_features: true,
+                ..Args::default()
+            },
+        );
+        assert!(contains_sequence(&args, &["--features", "c"]));
+        assert!(!args.contains(&"a,b".to_owned()));
+        assert!(args.contains(&"--no-default-features".to_owned()));
+
+        let args = build_args(
+            r#"features = ["a", "b"]"#,
+            Args {
+                all_features: true,
+                ..Args::default()
+            },
+        );
+        assert!(args.contains(&"--all-features".to_owned()));
+        assert!(!args.contains(&"--features".to_owned()));
+    }
+
+    #[test]
+    fn offline_passed_to_cargo() {
+        let args = build_args("", Args::default());
+        assert!(!args.contains(&"--offline".to_owned()));
+        let args = build_args(
+            "",
+            Args {
+                offline: true,
+                ..Args::default()
+            },
+        );
+        assert!(args.contains(&"--offline".to_owned()));
+    }
+
+    #[test]
+    fn package_passed_to_cargo() {
+        let args = build_args(
+            "",
+            Args {
+                package: Some("crab1".to_owned()),
+                ..Args::default()
+            },
+        );
+        assert!(contains_sequence(&args, &["--package", "crab1"]));
+    }
+
+    #[test]
+    fn stream_lines_copies_everything() {
+        let mut out = Vec::new();
+        stream_lines(&b"   Compiling a\n   Compiling b\nno newline"[..], &mut out).unwrap();
+        assert_eq!(out, b"   Compiling a\n   Compiling b\nno newline");
+    }
+}
@@ -6,6 +6,12 @@ use std::process::Command;
 /// The name of the default cargo profile that we use.
 pub(crate) const DEFAULT_PROFILE_NAME: &str = "cackle";
 
+/// Reported when cargo fails because it needed to access the network while running with
+/// `--offline`.
+pub(crate) const OFFLINE_FAILURE_MESSAGE: &str =
+    "Cargo needed to access the network, but --offline was specified. \
+     Run `cargo fetch` to download dependencies first.";
+
 #[derive(Parser, Debug, Clone)]
 pub(crate) struct CargoOptions {
     subcommand: String,
@@ -20,6 +26,9 @@ pub(crate) fn command(base_command: &str, dir: &Path, args: &Args) -> Command {
     if args.colour.should_use_colour() {
         command.arg("--color=always");
     }
+    if args.offline {
+        command.arg("--offline");
+    }
     let extra_args;
     if let crate::Command::Cargo(cargo_options) = &args.command {
         command.arg(&cargo_options.subcommand);
@@ -35,13 +44,24 @@ pub(crate) fn command(base_command: &str, dir: &Path, args: &Args) -> Command {
     command
         .arg("--config")
         .arg(format!("profile.{DEFAULT_PROFILE_NAME}.opt-level=0"));
-    // We currently always clean before we build, so incremental compilation would just be a waste.
+    // We generally clean before we build, so incremental compilation would just be a waste.
     command
         .arg("--config")
         .arg(format!("profile.{DEFAULT_PROFILE_NAME}.incremental=false"));
+    // Build scripts and proc macros are analysed in the same way as other code, which requires
+    // debug info. Cargo doesn't produce debug info for them by default.
+    command.arg("--config").arg(format!(
+        "profile.{DEFAULT_PROFILE_NAME}.build-override.debug=true"
+    ));
     // We don't currently support split debug info.
     command.arg("--config").arg("split-debuginfo=\"off\"");
     command.arg("--profile").arg(&args.profile);
     command.args(extra_args);
     command
 }
+
+/// Returns whether `stderr` from cargo indicates that it failed because something wasn't available
+/// locally and it wasn't permitted to access the network.
+pub(crate) fn is_offline_failure(stderr: &str) -> bool {
+    stderr.contains("--offline was specified") || stderr.contains("using offline mode")
+}
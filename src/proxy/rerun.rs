@@ -0,0 +1,124 @@
+//! Support for `--rerun-failed`. When a build script or rustc fails, the subprocess that was
+//! proxying it saves how it was invoked. `--rerun-failed` then runs just that invocation again,
+//! with whatever config is now in place, rather than running the whole build. The subprocess
+//! reports the command that it ran and its output back to us via RPC.
+
+use crate::config::CrateName;
+use crate::crate_index::CrateSel;
+use crate::Args;
+use anyhow::anyhow;
+use anyhow::Context;
+use anyhow::Result;
+use serde::Deserialize;
+use serde::Serialize;
+use std::path::Path;
+use std::path::PathBuf;
+use std::process::Command;
+
+/// How a proxy subprocess was invoked by cargo.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
+pub(crate) struct Invocation {
+    /// The arguments, starting with the binary.
+    args: Vec<String>,
+    env: Vec<(String, String)>,
+    dir: PathBuf,
+}
+
+/// Returns the directory in which the invocations of failed subprocesses are saved. Like the rest of
+/// the build output, they're removed when we clean.
+pub(crate) fn invocations_dir(root_path: &Path, args: &Args) -> PathBuf {
+    root_path
+        .join("target")
+        .join(&args.profile)
+        .join("failed-invocations")
+}
+
+impl Invocation {
+    fn current() -> Result<Self> {
+        Ok(Self {
+            args: std::env::args().collect(),
+            // Variables that aren't valid UTF-8 are very unlikely to matter to a build script or
+            // rustc, so we don't go to the trouble of preserving them.
+            env: std::env::vars_os()
+                .filter_map(|(name, value)| {
+                    Some((name.into_string().ok()?, value.into_string().ok()?))
+                })
+                .collect(),
+            dir: std::env::current_dir().context("Failed to get current directory")?,
+        })
+    }
+
+    /// Returns the invocation saved for `crate_name`, which is either a crate name such as
+    /// `foo.build`, or a package name, in which case the package's build script is used if its
+    /// primary crate didn't fail.
+    pub(crate) fn load(dir: &Path, crate_name: &str) -> Result<Self> {
+        let mut path = dir.join(format!("{crate_name}.json"));
+        if !path.exists() && !crate_name.ends_with(".build") {
+            path = dir.join(format!("{crate_name}.build.json"));
+        }
+        if !path.exists() {
+            return Err(anyhow!(
+                "No failed build script or rustc invocation for `{crate_name}` was found. Run a \
+                 check in which it fails first."
+            ));
+        }
+        Ok(serde_json::from_str(&crate::fs::read_to_string(&path)?)?)
+    }
+
+    /// Returns a command that repeats the invocation. The caller still needs to set the
+    /// environment variables that tell the subprocess how to reach us and where the config is.
+    pub(crate) fn command(&self) -> Result<Command> {
+        let (binary, args) = self
+            .args
+            .split_first()
+            .ok_or_else(|| anyhow!("Saved invocation has no arguments"))?;
+        let mut command = Command::new(binary);
+        command
+            .args(args)
+            .env_clear()
+            .envs(self.env.iter().map(|(name, value)| (name, value)))
+            .current_dir(&self.dir);
+        Ok(command)
+    }
+}
+
+/// Saves how the current subprocess was invoked, so that it can be run again with
+/// `--rerun-failed`.
+pub(super) fn save_current(crate_sel: &CrateSel) -> Result<()> {
+    let Some(dir) = std::env::var_os(super::FAILED_INVOCATIONS_DIR_ENV) else {
+        return Ok(());
+    };
+    let dir = PathBuf::from(dir);
+    std::fs::create_dir_all(&dir)
+        .with_context(|| format!("Failed to create directory `{}`", dir.display()))?;
+    let path = dir.join(format!("{}.json", CrateName::from(crate_sel)));
+    crate::fs::write_atomic(&path, &serde_json::to_string(&Invocation::current()?)?)
+}
+
+/// Returns whether we're a subprocess being run again by `--rerun-failed`.
+pub(super) fn is_rerun() -> bool {
+    std::env::var_os(super::RERUN_FAILED_ENV).is_some()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn load_falls_back_to_build_script() {
+        let dir = tempfile::tempdir().unwrap();
+        let invocation = Invocation {
+            args: vec!["build-script-build".to_owned()],
+            env: vec![("OUT_DIR".to_owned(), "/out".to_owned())],
+            dir: PathBuf::from("/src"),
+        };
+        let json = serde_json::to_string(&invocation).unwrap();
+        std::fs::write(dir.path().join("foo.build.json"), json).unwrap();
+        assert_eq!(Invocation::load(dir.path(), "foo").unwrap(), invocation);
+        assert_eq!(
+            Invocation::load(dir.path(), "foo.build").unwrap(),
+            invocation
+        );
+        assert!(Invocation::load(dir.path(), "bar").is_err());
+    }
+}
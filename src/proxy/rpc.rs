@@ -1,29 +1,37 @@
 //! Defines the communication protocol between the proxy subprocesses and the parent process.
 
+use crate::build_script_checker::BuildInstruction;
 use crate::config::SandboxConfig;
 use crate::crate_index::BuildScriptId;
 use crate::crate_index::CrateSel;
 use crate::link_info::LinkInfo;
 use crate::location::SourceLocation;
 use crate::outcome::Outcome;
+use crate::proxy::socket::SocketAddress;
 use anyhow::Context;
 use anyhow::Result;
 use serde::de::DeserializeOwned;
 use serde::Deserialize;
 use serde::Serialize;
+use std::fmt::Display;
 use std::io::Read;
 use std::io::Write;
 use std::os::unix::net::UnixStream;
 use std::path::PathBuf;
+use std::time::Duration;
+
+/// The version of this binary. Subprocesses report their version so that the parent process can
+/// detect if it and its subprocesses are running different versions.
+pub(crate) const CACKLE_VERSION: &str = env!("CARGO_PKG_VERSION");
 
 /// A communication channel to the main Cackle process.
 pub(crate) struct RpcClient {
-    socket_path: PathBuf,
+    socket_address: SocketAddress,
 }
 
 impl RpcClient {
-    pub(crate) fn new(socket_path: PathBuf) -> Self {
-        RpcClient { socket_path }
+    pub(crate) fn new(socket_address: SocketAddress) -> Self {
+        RpcClient { socket_address }
     }
 
     /// Advises the parent process that the specified crate uses unsafe.
@@ -43,7 +51,10 @@ impl RpcClient {
 
     pub(crate) fn rustc_started(&self, crate_sel: &CrateSel) -> Result<Outcome> {
         let mut ipc = self.connect()?;
-        let request = Request::RustcStarted(crate_sel.clone());
+        let request = Request::RustcStarted(RustcStartInfo {
+            crate_sel: crate_sel.clone(),
+            cackle_version: CACKLE_VERSION.to_owned(),
+        });
         write_to_stream(&request, &mut ipc)?;
         read_from_stream(&mut ipc)
     }
@@ -66,16 +77,19 @@ impl RpcClient {
         read_from_stream(&mut ipc)
     }
 
+    /// Reports the output of a build script or rustc that was run again after it failed. Only
+    /// called for the crate selected with --rerun-failed.
+    pub(crate) fn rerun_complete(&self, info: RerunOutput) -> Result<Outcome> {
+        let mut ipc = self.connect()?;
+        write_to_stream(&Request::RerunComplete(info), &mut ipc)?;
+        read_from_stream(&mut ipc)
+    }
+
     /// Creates a new connection to the socket. We only send a single request/response on each
     /// connection because it makes things simpler. In general a single request/response is all we
     /// need anyway.
     fn connect(&self) -> Result<UnixStream> {
-        UnixStream::connect(&self.socket_path).with_context(|| {
-            format!(
-                "Failed to connect to socket `{}`",
-                self.socket_path.display()
-            )
-        })
+        self.socket_address.connect()
     }
 }
 
@@ -85,8 +99,9 @@ pub(crate) enum Request {
     CrateUsesUnsafe(UnsafeUsage),
     LinkerInvoked(LinkInfo),
     BuildScriptComplete(BuildScriptOutput),
-    RustcStarted(CrateSel),
+    RustcStarted(RustcStartInfo),
     RustcComplete(RustcOutput),
+    RerunComplete(RerunOutput),
 }
 
 #[derive(Serialize, Deserialize, PartialEq, Eq, Debug, Clone, Hash)]
@@ -97,6 +112,19 @@ pub(crate) struct BuildScriptOutput {
     pub(crate) build_script_id: BuildScriptId,
     pub(crate) sandbox_config: SandboxConfig,
     pub(crate) build_script: PathBuf,
+    pub(crate) out_dir: PathBuf,
+    /// Set if the build script was killed because it ran for longer than this.
+    pub(crate) timed_out_after: Option<Duration>,
+}
+
+impl BuildScriptOutput {
+    /// Returns all the `cargo:` instructions that the build script emitted, in order.
+    pub(crate) fn instructions(&self) -> Vec<BuildInstruction> {
+        String::from_utf8_lossy(&self.stdout)
+            .lines()
+            .filter_map(BuildInstruction::parse)
+            .collect()
+    }
 }
 
 #[derive(Serialize, Deserialize, PartialEq, Eq, Debug, Clone, Hash)]
@@ -105,6 +133,38 @@ pub(crate) struct RustcOutput {
     pub(crate) source_paths: Vec<PathBuf>,
 }
 
+/// The result of running a build script or rustc again after it failed and the config was changed.
+#[derive(Serialize, Deserialize, PartialEq, Eq, Debug, Clone, Hash)]
+pub(crate) struct RerunOutput {
+    pub(crate) crate_sel: CrateSel,
+    /// The command that was run, including any sandbox.
+    pub(crate) command: String,
+    pub(crate) exit_code: i32,
+    pub(crate) stdout: Vec<u8>,
+    pub(crate) stderr: Vec<u8>,
+}
+
+impl Display for RerunOutput {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(
+            f,
+            "Rerun of `{}` exited with status {}",
+            self.crate_sel, self.exit_code
+        )?;
+        writeln!(f, "Command:\n{}", self.command)?;
+        writeln!(f, "Stdout:\n{}", String::from_utf8_lossy(&self.stdout))?;
+        write!(f, "Stderr:\n{}", String::from_utf8_lossy(&self.stderr))
+    }
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Eq, Debug, Clone, Hash)]
+pub(crate) struct RustcStartInfo {
+    pub(crate) crate_sel: CrateSel,
+    /// The version of the cackle binary that was invoked as the rustc wrapper. This might differ
+    /// from the version of the parent process if multiple versions are installed.
+    pub(crate) cackle_version: String,
+}
+
 #[derive(Serialize, Deserialize, PartialEq, Eq, Debug, Clone, Hash)]
 pub(crate) struct UnsafeUsage {
     pub(crate) crate_sel: CrateSel,
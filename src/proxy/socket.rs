@@ -0,0 +1,260 @@
+//! The Unix socket over which our subprocesses talk to the parent process. A Unix socket address
+//! can only hold a path of up to 107 bytes, which the path of our temporary directory can exceed,
+//! e.g. in deeply nested CI workspaces. When it would, we create a private directory with a short
+//! path directly under `/tmp` and put the socket there instead. Abstract sockets, which on Linux
+//! have a name, but no presence in the filesystem, are only used if explicitly requested, since
+//! any process on the machine can connect to them.
+
+use anyhow::bail;
+use anyhow::Context;
+use anyhow::Result;
+use std::ffi::OsString;
+use std::fmt::Display;
+use std::os::unix::fs::FileTypeExt;
+use std::os::unix::net::UnixListener;
+use std::os::unix::net::UnixStream;
+use std::path::Path;
+use std::path::PathBuf;
+use tempfile::TempDir;
+
+/// The maximum length of a socket path. `sun_path` in `sockaddr_un` is 108 bytes, but needs to
+/// include a nul terminator.
+const MAX_PATH_LEN: usize = 107;
+
+/// When passing the socket address to subprocesses, abstract socket names are prefixed with this.
+/// It's the same notation used by tools such as `ss`.
+const ABSTRACT_PREFIX: &str = "@";
+
+const SOCKET_NAME: &str = "cackle.socket";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum SocketAddress {
+    Path(PathBuf),
+    Abstract(String),
+}
+
+impl SocketAddress {
+    /// Picks where to listen. If the user requested a particular address with `--socket-path`, we
+    /// use that, otherwise we use a socket in `tmpdir` if its path fits, or failing that, in a new
+    /// directory under `/tmp`. In the last case, the directory is also returned and should be kept
+    /// until we're done with the socket.
+    pub(crate) fn choose(
+        requested: Option<&Path>,
+        tmpdir: &Path,
+    ) -> Result<(Self, Option<TempDir>)> {
+        if let Some(requested) = requested {
+            let address = Self::from_path(requested);
+            if let SocketAddress::Path(path) = &address {
+                if !fits(path) {
+                    bail!(
+                        "Socket path `{}` is longer than the maximum of {MAX_PATH_LEN} bytes",
+                        path.display()
+                    );
+                }
+            }
+            return Ok((address, None));
+        }
+        let path = tmpdir.join(SOCKET_NAME);
+        if fits(&path) {
+            return Ok((SocketAddress::Path(path), None));
+        }
+        // The directory is only accessible by us, so other users can't connect to the socket.
+        let short_dir = tempfile::Builder::new()
+            .prefix("cackle")
+            .tempdir_in("/tmp")
+            .context("Failed to create a directory for our socket in /tmp")?;
+        let path = short_dir.path().join(SOCKET_NAME);
+        if !fits(&path) {
+            bail!(
+                "Socket path `{}` is longer than the maximum of {MAX_PATH_LEN} bytes. \
+                 Try passing --socket-path",
+                path.display()
+            );
+        }
+        Ok((SocketAddress::Path(path), Some(short_dir)))
+    }
+
+    /// Parses the value that we pass to subprocesses via SOCKET_ENV.
+    pub(crate) fn from_env_value(value: &str) -> Self {
+        Self::from_path(Path::new(value))
+    }
+
+    fn from_path(path: &Path) -> Self {
+        match path.to_str().and_then(|p| p.strip_prefix(ABSTRACT_PREFIX)) {
+            Some(name) => SocketAddress::Abstract(name.to_owned()),
+            None => SocketAddress::Path(path.to_owned()),
+        }
+    }
+
+    /// Returns the value that should be passed to subprocesses via SOCKET_ENV.
+    pub(crate) fn env_value(&self) -> OsString {
+        match self {
+            SocketAddress::Path(path) => path.as_os_str().to_owned(),
+            SocketAddress::Abstract(name) => format!("{ABSTRACT_PREFIX}{name}").into(),
+        }
+    }
+
+    /// Starts listening. We never delete an existing file in order to do so, since it might not be
+    /// ours. A stale socket left by a previous run needs to be removed by the user.
+    pub(crate) fn bind(&self) -> Result<UnixListener> {
+        let listener = match self {
+            SocketAddress::Path(path) => {
+                if let Ok(metadata) = std::fs::symlink_metadata(path) {
+                    if !metadata.file_type().is_socket() {
+                        bail!(
+                            "Failed to create Unix socket `{}`: a file that isn't a socket \
+                             already exists there",
+                            path.display()
+                        );
+                    }
+                }
+                UnixListener::bind(path)
+            }
+            SocketAddress::Abstract(name) => abstract_socket::bind(name),
+        };
+        listener.with_context(|| format!("Failed to create Unix socket `{self}`"))
+    }
+
+    pub(crate) fn connect(&self) -> Result<UnixStream> {
+        let stream = match self {
+            SocketAddress::Path(path) => UnixStream::connect(path),
+            SocketAddress::Abstract(name) => abstract_socket::connect(name),
+        };
+        stream.with_context(|| format!("Failed to connect to socket `{self}`"))
+    }
+
+    /// Deletes the socket file if there is one. Must only be called after a successful call to
+    /// `bind`, so that we only delete a file that we created. This is best-effort only, so we don't
+    /// report an error if we can't.
+    pub(crate) fn remove(&self) {
+        if let SocketAddress::Path(path) = self {
+            let _ = std::fs::remove_file(path);
+        }
+    }
+}
+
+fn fits(path: &Path) -> bool {
+    path.as_os_str().len() <= MAX_PATH_LEN
+}
+
+#[cfg(target_os = "linux")]
+mod abstract_socket {
+    use std::os::linux::net::SocketAddrExt;
+    use std::os::unix::net::SocketAddr;
+    use std::os::unix::net::UnixListener;
+    use std::os::unix::net::UnixStream;
+
+    pub(super) fn bind(name: &str) -> std::io::Result<UnixListener> {
+        UnixListener::bind_addr(&SocketAddr::from_abstract_name(name)?)
+    }
+
+    pub(super) fn connect(name: &str) -> std::io::Result<UnixStream> {
+        UnixStream::connect_addr(&SocketAddr::from_abstract_name(name)?)
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+mod abstract_socket {
+    use std::io::Error;
+    use std::io::ErrorKind;
+    use std::os::unix::net::UnixListener;
+    use std::os::unix::net::UnixStream;
+
+    pub(super) fn bind(_name: &str) -> std::io::Result<UnixListener> {
+        Err(unsupported())
+    }
+
+    pub(super) fn connect(_name: &str) -> std::io::Result<UnixStream> {
+        Err(unsupported())
+    }
+
+    fn unsupported() -> Error {
+        Error::new(
+            ErrorKind::Unsupported,
+            "Abstract sockets are only supported on Linux",
+        )
+    }
+}
+
+impl Display for SocketAddress {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SocketAddress::Path(path) => path.display().fmt(f),
+            SocketAddress::Abstract(name) => write!(f, "{ABSTRACT_PREFIX}{name}"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn short_tmpdir_uses_path() {
+        let (address, dir) = SocketAddress::choose(None, Path::new("/tmp/.tmpabc")).unwrap();
+        assert_eq!(
+            address,
+            SocketAddress::Path(PathBuf::from("/tmp/.tmpabc/cackle.socket"))
+        );
+        assert!(dir.is_none());
+    }
+
+    #[test]
+    fn long_tmpdir_uses_short_dir() {
+        let tmpdir = PathBuf::from(format!("/{}/.tmpabc", "x".repeat(100)));
+        let (address, dir) = SocketAddress::choose(None, &tmpdir).unwrap();
+        let dir = dir.unwrap();
+        assert!(dir.path().starts_with("/tmp"));
+        assert_eq!(address, SocketAddress::Path(dir.path().join(SOCKET_NAME)));
+        let _listener = address.bind().unwrap();
+        let _stream = address.connect().unwrap();
+    }
+
+    #[test]
+    fn bind_refuses_existing_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(SOCKET_NAME);
+        std::fs::write(&path, "not a socket").unwrap();
+        assert!(SocketAddress::Path(path.clone()).bind().is_err());
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "not a socket");
+    }
+
+    #[test]
+    fn requested_address() {
+        let tmpdir = Path::new("/tmp/.tmpabc");
+        assert_eq!(
+            SocketAddress::choose(Some(Path::new("/run/c.sock")), tmpdir)
+                .unwrap()
+                .0,
+            SocketAddress::Path(PathBuf::from("/run/c.sock"))
+        );
+        assert_eq!(
+            SocketAddress::choose(Some(Path::new("@cackle")), tmpdir)
+                .unwrap()
+                .0,
+            SocketAddress::Abstract("cackle".to_owned())
+        );
+        let too_long = PathBuf::from(format!("/{}", "x".repeat(MAX_PATH_LEN)));
+        assert!(SocketAddress::choose(Some(&too_long), tmpdir).is_err());
+    }
+
+    #[test]
+    fn env_value_round_trip() {
+        for address in [
+            SocketAddress::Path(PathBuf::from("/tmp/.tmpabc/cackle.socket")),
+            SocketAddress::Abstract("cackle-1.tmpabc".to_owned()),
+        ] {
+            let value = address.env_value().into_string().unwrap();
+            assert_eq!(SocketAddress::from_env_value(&value), address);
+        }
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn connect_to_abstract_socket() {
+        let address = SocketAddress::Abstract(format!("cackle-test-{}", std::process::id()));
+        let listener = address.bind().unwrap();
+        let _stream = address.connect().unwrap();
+        listener.accept().unwrap();
+    }
+}
@@ -4,12 +4,12 @@
 use super::cackle_exe;
 use super::errors::get_disallowed_unsafe_locations;
 use super::rpc::BuildScriptOutput;
+use super::rpc::RerunOutput;
 use super::rpc::RustcOutput;
 use super::run_command;
 use super::ExitCode;
 use super::CONFIG_PATH_ENV;
 use crate::config::Config;
-use crate::config::CrateName;
 use crate::crate_index::BuildScriptId;
 use crate::crate_index::CrateIndex;
 use crate::crate_index::CrateSel;
@@ -17,6 +17,7 @@ use crate::link_info::LinkInfo;
 use crate::location::SourceLocation;
 use crate::outcome::Outcome;
 use crate::proxy::rpc::RpcClient;
+use crate::proxy::socket::SocketAddress;
 use crate::unsafe_checker;
 use anyhow::anyhow;
 use anyhow::bail;
@@ -37,7 +38,7 @@ pub(crate) fn handle_wrapped_binaries() -> Result<()> {
     if socket_path.is_empty() {
         return Ok(());
     }
-    let rpc_client = RpcClient::new(socket_path.into());
+    let rpc_client = RpcClient::new(SocketAddress::from_env_value(&socket_path));
 
     let mut args = std::env::args().peekable();
     let binary_name = PathBuf::from(args.next().ok_or_else(|| anyhow!("Missing all args"))?);
@@ -112,25 +113,69 @@ fn orig_build_rs_bin_path(path: &Path) -> PathBuf {
 }
 
 fn proxy_build_script(orig_build_script: PathBuf, rpc_client: &RpcClient) -> Result<ExitCode> {
+    let is_rerun = super::rerun::is_rerun();
     loop {
         let config = get_config_from_env()?;
         let build_script_id = BuildScriptId::from_env()?;
         let sandbox_config = config.sandbox_config_for_build_script(&build_script_id);
-        let Some(mut sandbox) = crate::sandbox::from_config(&sandbox_config)? else {
-            // Config says to run without a sandbox.
-            return Ok(Command::new(&orig_build_script).status()?.into());
+        let timeout = sandbox_config.timeout();
+        let out_dir = PathBuf::from(get_env("OUT_DIR")?);
+        let command_display;
+        let run_output = match crate::sandbox::from_config(&sandbox_config)? {
+            Some(mut sandbox) => {
+                // Allow read access to the crate's root source directory.
+                sandbox.ro_bind(Path::new(&get_env("CARGO_MANIFEST_DIR")?));
+                // Allow read access to the directory containing the build script itself.
+                if let Some(build_script_dir) = orig_build_script.parent() {
+                    sandbox.ro_bind(build_script_dir);
+                }
+                // Allow write access to OUT_DIR.
+                sandbox.writable_bind(&out_dir);
+                sandbox.pass_cargo_env();
+                command_display = sandbox.display_to_run(&orig_build_script).to_string();
+                sandbox.run(&orig_build_script, timeout)?
+            }
+            None => {
+                // Config says to run without a sandbox. Without a sandbox, there's only a timeout
+                // if one was explicitly configured.
+                let timeout = sandbox_config.timeout_secs.and(timeout);
+                command_display = orig_build_script.display().to_string();
+                if is_rerun {
+                    crate::sandbox::run_with_timeout(
+                        &mut Command::new(&orig_build_script),
+                        timeout,
+                    )?
+                } else {
+                    // The build script talks to cargo directly, so we only get involved if it
+                    // times out.
+                    let Some(timeout) = timeout else {
+                        return Ok(Command::new(&orig_build_script).status()?.into());
+                    };
+                    let run_output = crate::sandbox::run_inherited_with_timeout(
+                        &mut Command::new(&orig_build_script),
+                        timeout,
+                    )?;
+                    if !run_output.timed_out {
+                        return Ok(run_output.output.status.into());
+                    }
+                    run_output
+                }
+            }
         };
-        // Allow read access to the crate's root source directory.
-        sandbox.ro_bind(Path::new(&get_env("CARGO_MANIFEST_DIR")?));
-        // Allow read access to the directory containing the build script itself.
-        if let Some(build_script_dir) = orig_build_script.parent() {
-            sandbox.ro_bind(build_script_dir);
+        let output = run_output.output;
+        if is_rerun {
+            rpc_client.rerun_complete(RerunOutput {
+                crate_sel: CrateSel::BuildScript(build_script_id),
+                command: command_display,
+                exit_code: output.status.code().unwrap_or(-1),
+                stdout: output.stdout,
+                stderr: output.stderr,
+            })?;
+            return Ok(output.status.into());
+        }
+        if !output.status.success() {
+            super::rerun::save_current(&CrateSel::BuildScript(build_script_id.clone()))?;
         }
-        // Allow write access to OUT_DIR.
-        sandbox.writable_bind(Path::new(&get_env("OUT_DIR")?));
-        sandbox.pass_cargo_env();
-
-        let output = sandbox.run(&orig_build_script)?;
         let rpc_response = rpc_client.build_script_complete({
             BuildScriptOutput {
                 exit_code: output.status.code().unwrap_or(-1),
@@ -139,6 +184,8 @@ fn proxy_build_script(orig_build_script: PathBuf, rpc_client: &RpcClient) -> Res
                 build_script_id,
                 sandbox_config,
                 build_script: orig_build_script.clone(),
+                out_dir,
+                timed_out_after: timeout.filter(|_| run_output.timed_out),
             }
         })?;
         match rpc_response {
@@ -167,6 +214,9 @@ fn proxy_rustc(rpc_client: &RpcClient) -> Result<ExitCode> {
     };
     let crate_sel = CrateSel::from_env()?;
     let mut runner = RustcRunner::new(crate_sel)?;
+    if super::rerun::is_rerun() {
+        return runner.rerun(rpc_client);
+    }
     rpc_client.rustc_started(&runner.crate_sel)?;
     loop {
         match runner.run(rpc_client)? {
@@ -205,6 +255,21 @@ impl RustcRunner {
         })
     }
 
+    /// Runs rustc once, as it was run when it failed, and reports the command and its output.
+    fn rerun(&self, rpc_client: &RpcClient) -> Result<ExitCode> {
+        let config = get_config_from_env()?;
+        let mut command = self.command_for_config(&config, false)?;
+        let output = command.output()?;
+        rpc_client.rerun_complete(RerunOutput {
+            crate_sel: self.crate_sel.clone(),
+            command: crate::sandbox::command_display(&command),
+            exit_code: output.status.code().unwrap_or(-1),
+            stdout: output.stdout,
+            stderr: output.stderr,
+        })?;
+        Ok(output.status.into())
+    }
+
     fn run(&mut self, rpc_client: &RpcClient) -> Result<RustcRunStatus> {
         // Until source_paths has been filled, we don't allow linking, since linking requires that
         // we have already given this information to the parent cackle process.
@@ -212,10 +277,8 @@ impl RustcRunner {
         // We need to parse the configuration each time, since it might have changed. Specifically
         // it might have been changed to allow unsafe.
         let config = get_config_from_env()?;
-        let crate_name = CrateName::from(&self.crate_sel);
-        let unsafe_permitted = config.unsafe_permitted_for_crate(&crate_name);
-        let mut command = self.get_command(allow_linking, unsafe_permitted)?;
-        let output = command.output()?;
+        let (unsafe_permitted, unsafe_modules) = self.unsafe_permissions(&config);
+        let output = self.command_for_config(&config, allow_linking)?.output()?;
         let mut unsafe_locations = Vec::new();
 
         if output.status.code() == Some(0) {
@@ -235,17 +298,23 @@ impl RustcRunner {
                 }
             }
         } else {
-            unsafe_locations.extend(get_disallowed_unsafe_locations(&output)?.into_iter());
+            unsafe_locations.extend(get_disallowed_unsafe_locations(&output)?);
         }
         if !unsafe_permitted {
-            unsafe_locations.extend(
-                find_unsafe_in_sources(self.source_paths.as_deref().unwrap_or_default())?
-                    .into_iter(),
-            );
+            unsafe_locations.extend(find_unsafe_in_sources(
+                self.source_paths.as_deref().unwrap_or_default(),
+            )?);
+        }
+        // The deps file written by rustc lists the crate root first.
+        if let Some(crate_root) = self.source_paths.as_ref().and_then(|paths| paths.first()) {
+            unsafe_locations.retain(|location| {
+                !unsafe_checker::is_in_modules(location.filename(), crate_root, unsafe_modules)
+            });
         }
         if !unsafe_locations.is_empty() {
             unsafe_locations.sort();
             unsafe_locations.dedup();
+            super::rerun::save_current(&self.crate_sel)?;
             let response = rpc_client.crate_uses_unsafe(&self.crate_sel, unsafe_locations)?;
             if response == Outcome::Continue {
                 return Ok(RustcRunStatus::Retry);
@@ -254,9 +323,38 @@ impl RustcRunner {
             }
         }
 
+        if !output.status.success() {
+            super::rerun::save_current(&self.crate_sel)?;
+        }
         Ok(RustcRunStatus::Done(output))
     }
 
+    /// Returns whether the config permits unsafe code throughout the crate and the modules in which
+    /// it's permitted.
+    fn unsafe_permissions<'a>(&self, config: &'a Config) -> (bool, &'a [String]) {
+        let crate_name = config.crate_name_for(&self.crate_sel);
+        let unsafe_permitted = std::env::var_os(super::SKIP_UNSAFE_ENV).is_some()
+            || super::is_ignored_package(self.crate_sel.pkg_id())
+            || config.unsafe_permitted_for_crate(&crate_name);
+        (
+            unsafe_permitted,
+            config.unsafe_permitted_modules(&crate_name),
+        )
+    }
+
+    /// Returns the rustc command to run with `config`.
+    fn command_for_config(&self, config: &Config, allow_linking: bool) -> Result<Command> {
+        let (unsafe_permitted, unsafe_modules) = self.unsafe_permissions(config);
+        // If unsafe is permitted in some modules, then we can't get rustc to forbid it, so we rely on
+        // scanning the sources and filter out locations in the permitted modules.
+        self.get_command(
+            std::env::args().skip(2),
+            allow_linking,
+            unsafe_permitted || !unsafe_modules.is_empty(),
+            config.common.real_linker.as_deref(),
+        )
+    }
+
     /// Returns whether rustc was asked to link as indicated by --emit=*,link,*.
     fn linking_requested() -> bool {
         std::env::args().any(|arg| {
@@ -266,33 +364,55 @@ impl RustcRunner {
         })
     }
 
-    fn get_command(&self, allow_linking: bool, unsafe_permitted: bool) -> Result<Command> {
-        let mut args = std::env::args().skip(2).peekable();
+    /// Returns the rustc command to run. `args` are the arguments that cargo passed to us. These
+    /// already include any flags from `RUSTFLAGS` or cargo's `rustflags` config, so we pass them
+    /// through in order and only add or replace what we need to.
+    fn get_command(
+        &self,
+        args: impl Iterator<Item = String>,
+        allow_linking: bool,
+        unsafe_permitted: bool,
+        real_linker: Option<&str>,
+    ) -> Result<Command> {
+        let mut args = args.peekable();
         let mut command = Command::new("rustc");
         let mut linker_arg = OsString::new();
         let mut orig_linker_arg = None;
+        let mut linker_flavor_specified = false;
+        let mut target = None;
         while let Some(arg) = args.next() {
-            // Look for `-C linker=...`. If we find it, note the value for later use and drop the
-            // argument.
-            if arg == "-C" {
-                if let Some(linker) = args
-                    .peek()
-                    .and_then(|arg| arg.strip_prefix("linker="))
-                    .map(ToOwned::to_owned)
-                {
-                    orig_linker_arg = Some(linker);
-                    args.next();
+            if arg == "--target" {
+                target = args.peek().cloned();
+            } else if let Some(value) = arg.strip_prefix("--target=") {
+                target = Some(value.to_owned());
+            }
+            // Codegen options can be given either as `-C opt` or as `-Copt`. Cargo uses the former,
+            // but the latter is common in RUSTFLAGS.
+            let (codegen_option, separate) = if arg == "-C" {
+                (args.peek().cloned(), true)
+            } else {
+                (arg.strip_prefix("-C").map(ToOwned::to_owned), false)
+            };
+            if let Some(option) = codegen_option {
+                // Look for `-C linker=...`. If we find it, note the value for later use and drop
+                // the argument.
+                if let Some(linker) = option.strip_prefix("linker=") {
+                    orig_linker_arg = Some(linker.to_owned());
+                    if separate {
+                        args.next();
+                    }
                     continue;
                 }
+                if option.starts_with("linker-flavor=") {
+                    linker_flavor_specified = true;
+                }
                 // Force-enable -C debuginfo=2. We need debug info in order to know where code
                 // originated.
-                if args
-                    .peek()
-                    .map(|arg| arg.starts_with("debuginfo="))
-                    .unwrap_or(false)
-                {
+                if option.starts_with("debuginfo=") {
                     command.arg("-C").arg("debuginfo=2");
-                    args.next();
+                    if separate {
+                        args.next();
+                    }
                     continue;
                 }
             }
@@ -314,9 +434,21 @@ impl RustcRunner {
             // For all other arguments, pass them through.
             command.arg(arg);
         }
-        if let Some(orig_linker) = orig_linker_arg {
-            command.env(super::ORIG_LINKER_ENV, orig_linker);
+        // Since we're replacing the linker, we need to tell our linker proxy what linker to invoke.
+        // The config takes precedence, then `-C linker`. If neither is given, it needs to be
+        // whatever rustc would have used for the target.
+        let orig_linker = real_linker
+            .map(ToOwned::to_owned)
+            .or(orig_linker_arg)
+            .unwrap_or_else(|| super::target::default_linker(target.as_deref()));
+        // Rustc picks the style of arguments to pass based on the name of the linker. It'll now see
+        // our name, so if the real linker isn't a compiler driver, we need to say what it is.
+        if !linker_flavor_specified {
+            if let Some(flavor) = super::target::linker_flavor(&orig_linker) {
+                command.arg("-C").arg(format!("linker-flavor={flavor}"));
+            }
         }
+        command.env(super::ORIG_LINKER_ENV, orig_linker);
         linker_arg.push("linker=");
         linker_arg.push(cackle_exe()?);
         command.arg("--error-format=json");
@@ -364,18 +496,14 @@ fn invoke_real_linker(
 ) -> Result<ExitCode, anyhow::Error> {
     let orig_linker = std::env::var(super::ORIG_LINKER_ENV)
         .ok()
-        .unwrap_or_else(default_linker);
+        .unwrap_or_else(|| super::target::default_linker(None));
     let mut command = Command::new(orig_linker);
+    // Arguments are passed through exactly as we got them, including response files (`@file`) and
+    // driver arguments like `-flavor gnu`.
     command.args(args);
     run_command(&mut command)
 }
 
-/// Returns our best guess as to the default linker.
-fn default_linker() -> String {
-    // Ideally we'd have a way to ask rustc what linker it wants to use, for now we just guess.
-    "cc".to_owned()
-}
-
 fn get_config_from_env() -> Result<Arc<Config>> {
     let Ok(config_path) = std::env::var(CONFIG_PATH_ENV) else {
         bail!("Internal env var `{}` not set", CONFIG_PATH_ENV);
@@ -391,3 +519,71 @@ fn get_env(var_name: &str) -> Result<String> {
     std::env::var(var_name)
         .with_context(|| format!("Failed to get environment variable `{var_name}`"))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::RustcRunner;
+    use crate::crate_index::testing::pkg_id;
+    use crate::crate_index::CrateSel;
+    use std::ffi::OsStr;
+
+    fn rustc_args(args: &[&str], unsafe_permitted: bool) -> (Vec<String>, Option<String>) {
+        let runner = RustcRunner {
+            crate_sel: CrateSel::Primary(pkg_id("crab1")),
+            linking_requested: false,
+            source_paths: None,
+        };
+        let command = runner
+            .get_command(
+                args.iter().map(|arg| (*arg).to_owned()),
+                false,
+                unsafe_permitted,
+                None,
+            )
+            .unwrap();
+        let orig_linker = command
+            .get_envs()
+            .find(|(key, _)| *key == OsStr::new(super::super::ORIG_LINKER_ENV))
+            .and_then(|(_, value)| value)
+            .map(|value| value.to_string_lossy().into_owned());
+        let args = command
+            .get_args()
+            .map(|arg| arg.to_string_lossy().into_owned())
+            .collect();
+        (args, orig_linker)
+    }
+
+    #[test]
+    fn rustflags_preserved() {
+        // Cargo appends flags from RUSTFLAGS after its own, split on whitespace.
+        let (args, orig_linker) = rustc_args(
+            &[
+                "--crate-name",
+                "crab1",
+                "src/lib.rs",
+                "-C",
+                "debuginfo=0",
+                "-C",
+                "target-cpu=native",
+                "-Clinker=clang",
+                "-Cdebuginfo=1",
+                "--cfg",
+                "foo",
+            ],
+            false,
+        );
+        let position = |arg: &str| args.iter().position(|a| a == arg);
+        assert!(position("target-cpu=native").unwrap() > position("src/lib.rs").unwrap());
+        assert_eq!(position("--cfg").unwrap() + 1, position("foo").unwrap());
+        assert!(args.contains(&"-Funsafe-code".to_owned()));
+        assert_eq!(args.iter().filter(|arg| *arg == "debuginfo=2").count(), 2);
+        assert!(!args.iter().any(|arg| arg.contains("debuginfo=0")
+            || arg.contains("debuginfo=1")
+            || arg.contains("clang")));
+        assert_eq!(orig_linker.as_deref(), Some("clang"));
+
+        let (args, _) = rustc_args(&["src/lib.rs", "-C", "target-cpu=native"], true);
+        assert!(args.contains(&"target-cpu=native".to_owned()));
+        assert!(!args.contains(&"-Funsafe-code".to_owned()));
+    }
+}
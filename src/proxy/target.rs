@@ -0,0 +1,263 @@
+//! Information about the target that we're compiling for, as specified by `--target`. This is
+//! either a target triple, e.g. `thumbv7em-none-eabihf`, or a path to a custom target JSON file, as
+//! is common for embedded firmware.
+
+use anyhow::bail;
+use anyhow::Context;
+use anyhow::Result;
+use cargo_platform::Cfg;
+use cargo_platform::Platform;
+use std::path::Path;
+use std::process::Command;
+use std::str::FromStr;
+
+/// The targets that crates get compiled for during a build.
+#[derive(Debug, Clone)]
+pub(crate) struct BuildTargets {
+    /// The target that regular crates are compiled for.
+    pub(crate) target: TargetCfg,
+    /// The target that build scripts are compiled for.
+    pub(crate) host: TargetCfg,
+}
+
+/// The name and `cfg` values of a target. Used to determine which target-specific parts of the
+/// config apply.
+#[derive(Debug, Clone)]
+pub(crate) struct TargetCfg {
+    name: String,
+    cfg: Vec<Cfg>,
+}
+
+impl BuildTargets {
+    /// Asks rustc about `target`, or just the host if `target` is None.
+    pub(crate) fn query(target: Option<&str>) -> Result<Self> {
+        let host = TargetCfg::query(None)?;
+        let target = match target {
+            Some(target) => TargetCfg::query(Some(target))?,
+            None => host.clone(),
+        };
+        Ok(Self { target, host })
+    }
+}
+
+impl TargetCfg {
+    fn query(target: Option<&str>) -> Result<Self> {
+        let name = match target {
+            // Cargo matches custom targets by the name of the JSON file.
+            Some(target) => custom_target_spec(target)
+                .and_then(|_| Path::new(target).file_stem())
+                .and_then(|stem| stem.to_str())
+                .unwrap_or(target)
+                .to_owned(),
+            None => host_triple()?,
+        };
+        let mut command = Command::new("rustc");
+        command.arg("--print").arg("cfg");
+        if let Some(target) = target {
+            command.arg("--target").arg(target);
+        }
+        let cfg = run_rustc(&mut command)?
+            .lines()
+            .map(|line| {
+                Cfg::from_str(line).with_context(|| format!("Invalid cfg from rustc `{line}`"))
+            })
+            .collect::<Result<_>>()?;
+        Ok(Self { name, cfg })
+    }
+
+    #[cfg(test)]
+    pub(crate) fn for_testing(name: &str, cfg: &[&str]) -> Self {
+        Self {
+            name: name.to_owned(),
+            cfg: cfg.iter().map(|cfg| Cfg::from_str(cfg).unwrap()).collect(),
+        }
+    }
+
+    /// Returns whether `spec`, which is either a target triple or a `cfg(...)` expression, applies
+    /// to this target.
+    pub(crate) fn matches(&self, spec: &str) -> Result<bool> {
+        self.matches_with_features(spec, &[])
+    }
+
+    /// Like `matches`, but `cfg(feature = "...")` is also true for each of `features`.
+    pub(crate) fn matches_with_features(&self, spec: &str, features: &[String]) -> Result<bool> {
+        let platform =
+            Platform::from_str(spec).with_context(|| format!("Invalid target `{spec}`"))?;
+        if features.is_empty() {
+            return Ok(platform.matches(&self.name, &self.cfg));
+        }
+        let mut cfg = self.cfg.clone();
+        cfg.extend(
+            features
+                .iter()
+                .map(|feature| Cfg::KeyPair("feature".to_owned(), feature.clone())),
+        );
+        Ok(platform.matches(&self.name, &cfg))
+    }
+}
+
+fn host_triple() -> Result<String> {
+    let version_info = run_rustc(Command::new("rustc").arg("-vV"))?;
+    version_info
+        .lines()
+        .find_map(|line| line.strip_prefix("host: "))
+        .map(ToOwned::to_owned)
+        .context("rustc -vV didn't report a host")
+}
+
+fn run_rustc(command: &mut Command) -> Result<String> {
+    let output = command
+        .output()
+        .with_context(|| format!("Failed to run {command:?}"))?;
+    if !output.status.success() {
+        bail!(
+            "{command:?} failed: {}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+    }
+    Ok(String::from_utf8(output.stdout)?)
+}
+
+/// Returns whether `target` is a bare-metal target. Such targets generally don't have the standard
+/// library, so crates built for them will be `no_std`.
+pub(super) fn is_bare_metal(target: &str) -> bool {
+    match custom_target_spec(target) {
+        // Custom target specs default to an OS of "none".
+        Some(spec) => spec
+            .get("os")
+            .and_then(|os| os.as_str())
+            .map_or(true, |os| os == "none"),
+        None => target.split('-').any(|part| part == "none"),
+    }
+}
+
+/// Returns the linker that rustc would use for `target` if it wasn't told to use us as the linker
+/// and no linker was specified on the command line.
+pub(super) fn default_linker(target: Option<&str>) -> String {
+    let Some(target) = target else {
+        // Ideally we'd have a way to ask rustc what linker it wants to use, for now we just guess.
+        return "cc".to_owned();
+    };
+    if let Some(linker) = custom_target_spec(target)
+        .as_ref()
+        .and_then(|spec| spec.get("linker"))
+        .and_then(|linker| linker.as_str())
+    {
+        return linker.to_owned();
+    }
+    // Bare-metal targets that are built into rustc generally use the copy of LLD that ships with
+    // rustc. When rustc invokes a linker, it adds the directory containing `rust-lld` to the path.
+    if is_bare_metal(target) {
+        return "rust-lld".to_owned();
+    }
+    "cc".to_owned()
+}
+
+/// Returns the value to pass to `-C linker-flavor` so that rustc passes arguments suitable for
+/// `linker`. Rustc normally infers the flavor from the name of the linker, but since it invokes us
+/// instead, it would pick the target's default flavor. Returns None if the default is fine.
+pub(super) fn linker_flavor(linker: &str) -> Option<&'static str> {
+    // More specific names come first, so that `wasm-ld` isn't treated as a cross-compiling `ld`.
+    const FLAVORS: &[&str] = &["ld.lld", "ld64.lld", "lld-link", "wasm-ld", "ld"];
+    let name = Path::new(linker).file_name()?.to_str()?;
+    let name = name.strip_suffix(".exe").unwrap_or(name);
+    FLAVORS.iter().copied().find(|flavor| {
+        name == *flavor
+            || name
+                .strip_suffix(flavor)
+                .is_some_and(|prefix| prefix.ends_with('-'))
+    })
+}
+
+/// If `target` is the path to a custom target JSON file, returns its contents.
+fn custom_target_spec(target: &str) -> Option<serde_json::Value> {
+    let path = Path::new(target);
+    if path.extension().map_or(true, |ext| ext != "json") {
+        return None;
+    }
+    let contents = std::fs::read_to_string(path).ok()?;
+    serde_json::from_str(&contents).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn target_triples() {
+        assert!(is_bare_metal("thumbv7em-none-eabihf"));
+        assert!(is_bare_metal("riscv32imac-unknown-none-elf"));
+        assert!(!is_bare_metal("x86_64-unknown-linux-gnu"));
+        assert!(!is_bare_metal("aarch64-apple-darwin"));
+        assert_eq!(default_linker(Some("thumbv7em-none-eabihf")), "rust-lld");
+        assert_eq!(default_linker(Some("x86_64-unknown-linux-gnu")), "cc");
+        assert_eq!(default_linker(None), "cc");
+    }
+
+    #[test]
+    fn linker_flavors() {
+        assert_eq!(linker_flavor("/usr/bin/ld.lld"), Some("ld.lld"));
+        assert_eq!(linker_flavor("ld64.lld"), Some("ld64.lld"));
+        assert_eq!(linker_flavor("arm-none-eabi-ld"), Some("ld"));
+        assert_eq!(linker_flavor("wasm-ld"), Some("wasm-ld"));
+        assert_eq!(linker_flavor("lld-link.exe"), Some("lld-link"));
+        assert_eq!(linker_flavor("cc"), None);
+        assert_eq!(linker_flavor("clang"), None);
+        assert_eq!(linker_flavor("rust-lld"), None);
+        assert_eq!(linker_flavor("mold"), None);
+    }
+
+    #[test]
+    fn target_cfg_matches() {
+        let target = TargetCfg::for_testing(
+            "x86_64-unknown-linux-gnu",
+            &["unix", "target_os=\"linux\"", "target_arch=\"x86_64\""],
+        );
+        assert!(target.matches("x86_64-unknown-linux-gnu").unwrap());
+        assert!(!target.matches("wasm32-unknown-unknown").unwrap());
+        assert!(target.matches("cfg(unix)").unwrap());
+        assert!(target
+            .matches("cfg(all(unix, target_arch = \"x86_64\"))")
+            .unwrap());
+        assert!(!target.matches("cfg(target_arch = \"wasm32\")").unwrap());
+        assert!(target.matches("cfg(unix").is_err());
+    }
+
+    #[test]
+    fn feature_cfg_matches() {
+        let target = TargetCfg::for_testing("x86_64-unknown-linux-gnu", &["unix"]);
+        let online = ["online".to_owned()];
+        assert!(!target.matches("cfg(feature = \"online\")").unwrap());
+        assert!(target
+            .matches_with_features("cfg(feature = \"online\")", &online)
+            .unwrap());
+        assert!(target
+            .matches_with_features("cfg(all(unix, feature = \"online\"))", &online)
+            .unwrap());
+        assert!(!target
+            .matches_with_features("cfg(feature = \"offline\")", &online)
+            .unwrap());
+    }
+
+    #[test]
+    fn custom_target() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("firmware.json");
+        std::fs::write(
+            &path,
+            r#"{"llvm-target": "thumbv7em-none-eabihf", "linker": "arm-none-eabi-gcc"}"#,
+        )
+        .unwrap();
+        let target = path.to_str().unwrap();
+        assert!(is_bare_metal(target));
+        assert_eq!(default_linker(Some(target)), "arm-none-eabi-gcc");
+
+        std::fs::write(
+            &path,
+            r#"{"llvm-target": "x86_64-unknown-linux-gnu", "os": "linux"}"#,
+        )
+        .unwrap();
+        assert!(!is_bare_metal(target));
+        assert_eq!(default_linker(Some(target)), "cc");
+    }
+}
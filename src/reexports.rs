@@ -0,0 +1,353 @@
+//! This module tokenises Rust code and looks for `pub use` re-exports. Symbols and debug info always
+//! name items by the path at which they're defined, so if an API is configured using a path that
+//! only exists as a re-export, we'd otherwise never see any usages of it.
+//!
+//! We don't resolve names the way the compiler does. Module paths are inferred from the names of
+//! source files and from inline `mod` blocks, which is enough for the common ways in which crates
+//! re-export items.
+
+use log::warn;
+use rustc_ap_rustc_lexer::TokenKind;
+use std::collections::BTreeSet;
+use std::path::Path;
+use std::path::PathBuf;
+
+/// An item or module that is publicly reachable via `alias`, but is defined at `target`. For glob
+/// re-exports, `alias` is the module containing the `pub use` and `target` is the module whose
+/// contents are re-exported.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub(crate) struct Reexport {
+    pub(crate) alias: String,
+    pub(crate) target: String,
+}
+
+/// A `pub use` as written in the source, before we've resolved its target.
+struct UseEntry {
+    /// The module containing the `pub use`.
+    module: Vec<String>,
+    target: Vec<String>,
+    /// The name under which the item is re-exported, or None for a glob re-export.
+    name: Option<String>,
+}
+
+/// Returns the re-exports found in the sources of the crate named `crate_name`. The first of
+/// `source_paths` should be the crate root, which is the order in which rustc writes them to the
+/// deps file.
+pub(crate) fn scan_crate(crate_name: &str, source_paths: &[PathBuf]) -> Vec<Reexport> {
+    let Some(root) = source_paths.first() else {
+        return Vec::new();
+    };
+    let root_dir = root.parent().unwrap_or(Path::new(""));
+    let mut modules = BTreeSet::new();
+    let mut entries = Vec::new();
+    for path in source_paths {
+        let Some(module) = module_for_file(root, root_dir, path) else {
+            continue;
+        };
+        // A file that we can't read only means that we might miss some re-exports, which isn't
+        // worth failing the check over.
+        let bytes = match std::fs::read(path) {
+            Ok(bytes) => bytes,
+            Err(error) => {
+                warn!("Failed to read `{}`: {error}", path.display());
+                continue;
+            }
+        };
+        let Ok(source) = std::str::from_utf8(&bytes) else {
+            continue;
+        };
+        scan_string(source, &module, &mut modules, &mut entries);
+        modules.insert(module);
+    }
+    resolve(crate_name, &modules, entries)
+}
+
+/// Returns the module path, relative to the crate root, that we expect `path` to define.
+fn module_for_file(root: &Path, root_dir: &Path, path: &Path) -> Option<Vec<String>> {
+    if path == root {
+        return Some(Vec::new());
+    }
+    if path.extension().map_or(true, |ext| ext != "rs") {
+        return None;
+    }
+    let relative = path.strip_prefix(root_dir).ok()?.with_extension("");
+    let mut module: Vec<String> = relative
+        .iter()
+        .map(|part| part.to_str().map(ToOwned::to_owned))
+        .collect::<Option<_>>()?;
+    if module.last().is_some_and(|last| last == "mod") {
+        module.pop();
+    }
+    Some(module)
+}
+
+/// Finds `pub use` declarations in `source`, which is the source for `file_module`. Inline modules
+/// that are encountered are added to `modules`.
+fn scan_string(
+    source: &str,
+    file_module: &[String],
+    modules: &mut BTreeSet<Vec<String>>,
+    entries: &mut Vec<UseEntry>,
+) {
+    let mut offset = 0;
+    let mut tokens = Vec::new();
+    for token in rustc_ap_rustc_lexer::tokenize(source) {
+        let new_offset = offset + token.len;
+        if !matches!(
+            token.kind,
+            TokenKind::Whitespace | TokenKind::LineComment { .. } | TokenKind::BlockComment { .. }
+        ) {
+            tokens.push(Token {
+                kind: token.kind,
+                text: &source[offset..new_offset],
+            });
+        }
+        offset = new_offset;
+    }
+
+    let mut module = file_module.to_owned();
+    // The brace depth at which each inline module that we're in was opened.
+    let mut module_depths = Vec::new();
+    let mut depth = 0;
+    let mut pending_module = None;
+    let mut i = 0;
+    while let Some(token) = tokens.get(i) {
+        i += 1;
+        match token.kind {
+            TokenKind::OpenBrace => {
+                depth += 1;
+                if let Some(name) = pending_module.take() {
+                    module.push(name);
+                    modules.insert(module.clone());
+                    module_depths.push(depth);
+                }
+            }
+            TokenKind::CloseBrace => {
+                if module_depths.last() == Some(&depth) {
+                    module_depths.pop();
+                    module.pop();
+                }
+                depth -= 1;
+            }
+            TokenKind::Ident if token.text == "mod" => {
+                if let (Some(name), Some(brace)) = (tokens.get(i), tokens.get(i + 1)) {
+                    if name.kind == TokenKind::Ident && brace.kind == TokenKind::OpenBrace {
+                        pending_module = Some(name.text.to_owned());
+                    }
+                }
+            }
+            // Only unrestricted `pub` makes something visible outside the crate, so we skip
+            // `pub(crate) use` and the like.
+            TokenKind::Ident
+                if token.text == "pub" && tokens.get(i).is_some_and(|next| next.text == "use") =>
+            {
+                i += 1;
+                parse_use_tree(&tokens, &mut i, &module, Vec::new(), entries);
+            }
+            _ => {}
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+struct Token<'a> {
+    kind: TokenKind,
+    text: &'a str,
+}
+
+/// Parses a use tree starting at `tokens[*i]`, adding an entry for each leaf of the tree.
+fn parse_use_tree(
+    tokens: &[Token],
+    i: &mut usize,
+    module: &[String],
+    mut path: Vec<String>,
+    entries: &mut Vec<UseEntry>,
+) {
+    let mut name = None;
+    while let Some(token) = tokens.get(*i) {
+        match token.kind {
+            TokenKind::Colon => *i += 1,
+            TokenKind::Ident if token.text == "as" => {
+                name = tokens.get(*i + 1).map(|t| t.text.to_owned());
+                *i += 2;
+                break;
+            }
+            TokenKind::Ident | TokenKind::RawIdent => {
+                path.push(token.text.trim_start_matches("r#").to_owned());
+                *i += 1;
+            }
+            TokenKind::Star => {
+                *i += 1;
+                entries.push(UseEntry {
+                    module: module.to_owned(),
+                    target: path,
+                    name: None,
+                });
+                return;
+            }
+            TokenKind::OpenBrace => {
+                *i += 1;
+                while let Some(token) = tokens.get(*i) {
+                    match token.kind {
+                        TokenKind::CloseBrace => break,
+                        TokenKind::Comma => *i += 1,
+                        TokenKind::Ident
+                        | TokenKind::RawIdent
+                        | TokenKind::Colon
+                        | TokenKind::Star
+                        | TokenKind::OpenBrace => {
+                            parse_use_tree(tokens, i, module, path.clone(), entries)
+                        }
+                        // Something we don't understand. Skip it rather than getting stuck.
+                        _ => *i += 1,
+                    }
+                }
+                *i += 1;
+                return;
+            }
+            _ => break,
+        }
+    }
+    // `use foo::{self}` re-exports `foo`.
+    if path.len() > 1 && path.last().is_some_and(|last| last == "self") {
+        path.pop();
+    }
+    let Some(name) = name.or_else(|| path.last().cloned()) else {
+        return;
+    };
+    // Items re-exported as `_` can't be referred to by name.
+    if name != "_" {
+        entries.push(UseEntry {
+            module: module.to_owned(),
+            target: path,
+            name: Some(name),
+        });
+    }
+}
+
+/// Converts `entries` into re-exports with absolute paths.
+fn resolve(
+    crate_name: &str,
+    modules: &BTreeSet<Vec<String>>,
+    entries: Vec<UseEntry>,
+) -> Vec<Reexport> {
+    let absolute = |module: &[String]| {
+        std::iter::once(crate_name)
+            .chain(module.iter().map(String::as_str))
+            .collect::<Vec<_>>()
+            .join("::")
+    };
+    let mut reexports = Vec::new();
+    for entry in entries {
+        let mut parts = entry.target.iter().peekable();
+        let mut target_module = entry.module.clone();
+        let mut relative = true;
+        match parts.peek().map(|part| part.as_str()) {
+            Some("crate") => {
+                target_module.clear();
+                parts.next();
+            }
+            Some("self") => {
+                parts.next();
+            }
+            Some("super") => {
+                while parts.next_if(|part| *part == "super").is_some() {
+                    target_module.pop();
+                }
+            }
+            Some(first) => {
+                let mut child = entry.module.clone();
+                child.push(first.to_owned());
+                // Anything that isn't a child module is assumed to be another crate.
+                relative = modules.contains(&child);
+            }
+            None => continue,
+        }
+        let rest: Vec<String> = parts.cloned().collect();
+        let target = if relative {
+            target_module.extend(rest);
+            absolute(&target_module)
+        } else {
+            rest.join("::")
+        };
+        let mut alias_module = entry.module;
+        if let Some(name) = entry.name {
+            alias_module.push(name);
+        }
+        let alias = absolute(&alias_module);
+        if alias != target {
+            reexports.push(Reexport { alias, target });
+        }
+    }
+    reexports.sort();
+    reexports.dedup();
+    reexports
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn reexports(source: &str, modules: &[&str]) -> Vec<(String, String)> {
+        let mut known = BTreeSet::new();
+        for module in modules {
+            known.insert(module.split("::").map(ToOwned::to_owned).collect());
+        }
+        let mut entries = Vec::new();
+        scan_string(source, &[], &mut known, &mut entries);
+        resolve("foo", &known, entries)
+            .into_iter()
+            .map(|r| (r.alias, r.target))
+            .collect()
+    }
+
+    fn pairs(expected: &[(&str, &str)]) -> Vec<(String, String)> {
+        expected
+            .iter()
+            .map(|(a, t)| (a.to_string(), t.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_scan_string() {
+        assert_eq!(
+            reexports(
+                indoc::indoc! {r#"
+                    mod imp;
+                    pub use crate::imp::a;
+                    pub use imp::{b as c, self};
+                    pub(crate) use imp::d;
+                    use imp::e;
+                    // pub use imp::f;
+                    pub mod api {
+                        pub use super::imp::g::*;
+                        fn x() {}
+                    }
+                    pub use std::env::var as get_env;
+                    pub use imp::h as _;
+                "#},
+                &["imp"]
+            ),
+            pairs(&[
+                ("foo::a", "foo::imp::a"),
+                ("foo::api", "foo::imp::g"),
+                ("foo::c", "foo::imp::b"),
+                ("foo::get_env", "std::env::var"),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_module_for_file() {
+        let root = Path::new("/src/lib.rs");
+        let module = |path: &str| module_for_file(root, Path::new("/src"), Path::new(path));
+        assert_eq!(module("/src/lib.rs"), Some(vec![]));
+        assert_eq!(module("/src/a.rs"), Some(vec!["a".to_owned()]));
+        assert_eq!(
+            module("/src/a/b/mod.rs"),
+            Some(vec!["a".to_owned(), "b".to_owned()])
+        );
+        assert_eq!(module("/src/data.bin"), None);
+        assert_eq!(module("/elsewhere/a.rs"), None);
+    }
+}
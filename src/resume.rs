@@ -0,0 +1,354 @@
+//! Support for resuming a check that was interrupted (e.g. by Ctrl-C or by the user giving up on a
+//! problem) without first running `cargo clean`. While a check runs, we append to a log in the
+//! target directory, recording each crate that rustc finished compiling and each permission,
+//! allowed symbol, inline suppression or API exclude that was found to be used. Anything that cargo
+//! finished building had already passed our checks, so provided the configuration hasn't changed,
+//! we can let cargo reuse it and just replay what we'd learned about it. Crates that cargo rebuilds
+//! go through the normal checks.
+//!
+//! The log is deleted when a check completes successfully, so that a subsequent run starts from a
+//! clean build. If the configuration changes during a run, we also delete the log, since we can no
+//! longer say which configuration the already built crates were checked against. Likewise, we only
+//! resume if the same categories of checks were selected with `--only` and the same package with
+//! `--package`.
+
+use crate::checker::Category;
+use crate::config::ApiPath;
+use crate::config::Config;
+use crate::config::CrateName;
+use crate::config::PermissionName;
+use crate::proxy::rpc::RustcOutput;
+use crate::proxy::rpc::CACKLE_VERSION;
+use crate::suppressions::Suppression;
+use crate::Args;
+use anyhow::Context;
+use anyhow::Result;
+use log::info;
+use serde::Deserialize;
+use serde::Serialize;
+use std::fs::File;
+use std::io::BufRead;
+use std::io::BufReader;
+use std::io::Write;
+use std::path::Path;
+use std::path::PathBuf;
+
+/// What we learned during a previous, interrupted run.
+#[derive(Default, Debug, Clone, PartialEq, Eq)]
+pub(crate) struct ResumeState {
+    pub(crate) crate_outputs: Vec<RustcOutput>,
+    pub(crate) used_permissions: Vec<(CrateName, PermissionName)>,
+    pub(crate) used_symbols: Vec<(CrateName, String)>,
+    pub(crate) used_suppressions: Vec<Suppression>,
+    pub(crate) used_excludes: Vec<(PermissionName, ApiPath)>,
+}
+
+/// An append-only log of what we've learned during the current run.
+pub(crate) struct ResumeLog {
+    path: PathBuf,
+    file: File,
+    flattened_config: String,
+}
+
+/// A single line of the log. The first line is always a `Header`.
+#[derive(Serialize, Deserialize)]
+enum Record {
+    Header {
+        cackle_version: String,
+        flattened_config: String,
+        only: Vec<Category>,
+        package: Option<String>,
+    },
+    CrateCompiled(RustcOutput),
+    PermissionUsed {
+        crate_name: CrateName,
+        permission: PermissionName,
+    },
+    SymbolUsed {
+        crate_name: CrateName,
+        symbol: String,
+    },
+    SuppressionUsed(Suppression),
+    ExcludeUsed {
+        api: PermissionName,
+        path: ApiPath,
+    },
+}
+
+pub(crate) fn log_path(root_path: &Path, args: &Args) -> PathBuf {
+    root_path
+        .join("target")
+        .join(&args.profile)
+        .join("cackle-resume-log")
+}
+
+/// Reads the log left by a previous run. Returns `None` if there isn't one, or if it was written by
+/// a different version of cackle, with a different configuration or with different categories of
+/// checks or a different package selected, in which case the caller needs to do a clean build.
+pub(crate) fn load(
+    path: &Path,
+    config: &Config,
+    only: &[Category],
+    package: Option<&str>,
+) -> Result<Option<ResumeState>> {
+    let Ok(file) = File::open(path) else {
+        return Ok(None);
+    };
+    let flattened_config = config.flattened_toml()?;
+    let mut lines = BufReader::new(file).lines();
+    let Some(Ok(Record::Header {
+        cackle_version,
+        flattened_config: previous_config,
+        only: previous_only,
+        package: previous_package,
+    })) = lines.next().map(|line| parse_record(line.ok()))
+    else {
+        return Ok(None);
+    };
+    if cackle_version != CACKLE_VERSION
+        || previous_config != flattened_config
+        || previous_only != only
+        || previous_package.as_deref() != package
+    {
+        info!("Configuration changed since interrupted run, not resuming");
+        return Ok(None);
+    }
+    let mut state = ResumeState::default();
+    for line in lines {
+        // If we were killed part way through writing a record, the last line may be incomplete, in
+        // which case we just ignore it.
+        let Ok(record) = parse_record(line.ok()) else {
+            break;
+        };
+        match record {
+            Record::Header { .. } => break,
+            Record::CrateCompiled(output) => state.crate_outputs.push(output),
+            Record::PermissionUsed {
+                crate_name,
+                permission,
+            } => state.used_permissions.push((crate_name, permission)),
+            Record::SymbolUsed { crate_name, symbol } => {
+                state.used_symbols.push((crate_name, symbol))
+            }
+            Record::SuppressionUsed(suppression) => state.used_suppressions.push(suppression),
+            Record::ExcludeUsed { api, path } => state.used_excludes.push((api, path)),
+        }
+    }
+    info!(
+        "Resuming interrupted run with {} crates already compiled",
+        state.crate_outputs.len()
+    );
+    Ok(Some(state))
+}
+
+fn parse_record(line: Option<String>) -> Result<Record> {
+    let line = line.context("Failed to read line")?;
+    Ok(serde_json::from_str(&line)?)
+}
+
+impl ResumeLog {
+    /// Creates a new log at `path`, replacing any existing log. Anything in `state` is carried over,
+    /// so that if we're interrupted again, we can still resume.
+    pub(crate) fn create(
+        path: &Path,
+        config: &Config,
+        only: &[Category],
+        package: Option<&str>,
+        state: &ResumeState,
+    ) -> Result<Self> {
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)
+                .with_context(|| format!("Failed to create directory `{}`", dir.display()))?;
+        }
+        let file =
+            File::create(path).with_context(|| format!("Failed to create `{}`", path.display()))?;
+        let mut log = Self {
+            path: path.to_owned(),
+            file,
+            flattened_config: config.flattened_toml()?,
+        };
+        log.append(&Record::Header {
+            cackle_version: CACKLE_VERSION.to_owned(),
+            flattened_config: log.flattened_config.clone(),
+            only: only.to_owned(),
+            package: package.map(str::to_owned),
+        })?;
+        for output in &state.crate_outputs {
+            log.crate_compiled(output)?;
+        }
+        for (crate_name, permission) in &state.used_permissions {
+            log.permission_used(crate_name, permission)?;
+        }
+        for (crate_name, symbol) in &state.used_symbols {
+            log.symbol_used(crate_name, symbol)?;
+        }
+        for suppression in &state.used_suppressions {
+            log.suppression_used(suppression)?;
+        }
+        for (api, path) in &state.used_excludes {
+            log.exclude_used(api, path)?;
+        }
+        Ok(log)
+    }
+
+    /// Returns whether the log is still valid for `config`. If it isn't, the log is deleted.
+    pub(crate) fn config_loaded(&mut self, config: &Config) -> Result<bool> {
+        if config.flattened_toml()? == self.flattened_config {
+            return Ok(true);
+        }
+        self.delete()?;
+        Ok(false)
+    }
+
+    pub(crate) fn crate_compiled(&mut self, output: &RustcOutput) -> Result<()> {
+        self.append(&Record::CrateCompiled(output.clone()))
+    }
+
+    pub(crate) fn permission_used(
+        &mut self,
+        crate_name: &CrateName,
+        permission: &PermissionName,
+    ) -> Result<()> {
+        self.append(&Record::PermissionUsed {
+            crate_name: crate_name.clone(),
+            permission: permission.clone(),
+        })
+    }
+
+    pub(crate) fn symbol_used(&mut self, crate_name: &CrateName, symbol: &str) -> Result<()> {
+        self.append(&Record::SymbolUsed {
+            crate_name: crate_name.clone(),
+            symbol: symbol.to_owned(),
+        })
+    }
+
+    pub(crate) fn suppression_used(&mut self, suppression: &Suppression) -> Result<()> {
+        self.append(&Record::SuppressionUsed(suppression.clone()))
+    }
+
+    pub(crate) fn exclude_used(&mut self, api: &PermissionName, path: &ApiPath) -> Result<()> {
+        self.append(&Record::ExcludeUsed {
+            api: api.clone(),
+            path: path.clone(),
+        })
+    }
+
+    pub(crate) fn delete(&self) -> Result<()> {
+        std::fs::remove_file(&self.path)
+            .with_context(|| format!("Failed to delete `{}`", self.path.display()))
+    }
+
+    fn append(&mut self, record: &Record) -> Result<()> {
+        let mut line = serde_json::to_string(record)?;
+        line.push('\n');
+        self.file
+            .write_all(line.as_bytes())
+            .with_context(|| format!("Failed to write to `{}`", self.path.display()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::testing::parse;
+    use crate::crate_index::testing::pkg_id;
+    use crate::crate_index::CrateSel;
+    use crate::location::SourceLocation;
+
+    fn state() -> ResumeState {
+        ResumeState {
+            crate_outputs: vec![RustcOutput {
+                crate_sel: CrateSel::Primary(pkg_id("crab1")),
+                source_paths: vec![PathBuf::from("/crab1/src/lib.rs")],
+            }],
+            used_permissions: vec![("crab1".into(), PermissionName::from("fs"))],
+            used_symbols: vec![("crab2".into(), "std::fs::metadata".to_owned())],
+            used_suppressions: vec![Suppression {
+                location: SourceLocation::new(Path::new("/crab1/src/lib.rs"), 10, Some(5)),
+                permission: PermissionName::from("net"),
+            }],
+            used_excludes: vec![(
+                PermissionName::from("env"),
+                ApiPath::from_str("std::env::var"),
+            )],
+        }
+    }
+
+    #[test]
+    fn resume_after_interruption() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("cackle").join("log");
+        let config = parse("").unwrap();
+        assert_eq!(load(&path, &config, &[], None).unwrap(), None);
+
+        let mut log =
+            ResumeLog::create(&path, &config, &[], None, &ResumeState::default()).unwrap();
+        let expected = state();
+        log.crate_compiled(&expected.crate_outputs[0]).unwrap();
+        log.permission_used(&"crab1".into(), &PermissionName::from("fs"))
+            .unwrap();
+        log.symbol_used(&"crab2".into(), "std::fs::metadata")
+            .unwrap();
+        log.suppression_used(&expected.used_suppressions[0])
+            .unwrap();
+        log.exclude_used(&expected.used_excludes[0].0, &expected.used_excludes[0].1)
+            .unwrap();
+        drop(log);
+        // Simulate having been killed part way through writing a record.
+        let mut file = std::fs::OpenOptions::new()
+            .append(true)
+            .open(&path)
+            .unwrap();
+        file.write_all(b"{\"CrateCompiled\":").unwrap();
+        assert_eq!(
+            load(&path, &config, &[], None).unwrap(),
+            Some(expected.clone())
+        );
+
+        // Records from the previous run should be carried over to the new log.
+        ResumeLog::create(&path, &config, &[], None, &expected).unwrap();
+        assert_eq!(load(&path, &config, &[], None).unwrap(), Some(expected));
+    }
+
+    #[test]
+    fn config_changed() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("log");
+        let config = parse("").unwrap();
+        let changed_config = parse("[pkg.crab1]\nallow_unsafe = true\n").unwrap();
+        ResumeLog::create(&path, &config, &[], None, &state()).unwrap();
+        assert_eq!(load(&path, &changed_config, &[], None).unwrap(), None);
+
+        let mut log = ResumeLog::create(&path, &config, &[], None, &state()).unwrap();
+        assert!(log.config_loaded(&config).unwrap());
+        assert!(!log.config_loaded(&changed_config).unwrap());
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn only_changed() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("log");
+        let config = parse("").unwrap();
+        ResumeLog::create(&path, &config, &[Category::Unsafe], None, &state()).unwrap();
+        assert_eq!(load(&path, &config, &[], None).unwrap(), None);
+        assert_eq!(
+            load(&path, &config, &[Category::Unsafe], None).unwrap(),
+            Some(state())
+        );
+    }
+
+    #[test]
+    fn package_changed() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("log");
+        let config = parse("").unwrap();
+        ResumeLog::create(&path, &config, &[], Some("crab1"), &state()).unwrap();
+        assert_eq!(load(&path, &config, &[], None).unwrap(), None);
+        assert_eq!(load(&path, &config, &[], Some("crab2")).unwrap(), None);
+        assert_eq!(
+            load(&path, &config, &[], Some("crab1")).unwrap(),
+            Some(state())
+        );
+    }
+}
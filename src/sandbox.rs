@@ -2,16 +2,37 @@ use crate::config::SandboxConfig;
 use crate::config::SandboxKind;
 use anyhow::Context;
 use anyhow::Result;
+#[cfg(target_os = "linux")]
+use once_cell::sync::Lazy;
 use std::ffi::OsStr;
 use std::fmt::Display;
+use std::io::Read;
 use std::path::Path;
 use std::path::PathBuf;
+use std::process::Child;
+use std::process::Command;
+use std::process::ExitStatus;
+use std::process::Output;
+use std::process::Stdio;
+#[cfg(target_os = "linux")]
+use std::sync::atomic::AtomicBool;
+#[cfg(target_os = "linux")]
+use std::sync::atomic::Ordering;
+#[cfg(target_os = "linux")]
+use std::sync::Arc;
+use std::time::Duration;
+use std::time::Instant;
 
 mod bubblewrap;
+#[cfg(target_os = "linux")]
+mod landlock;
+
+#[cfg(target_os = "linux")]
+pub(crate) use self::landlock::handle_sandboxed_exec;
 
 pub(crate) trait Sandbox {
-    /// Runs `binary` inside the sandbox.
-    fn run(&self, binary: &Path) -> Result<std::process::Output>;
+    /// Runs `binary` inside the sandbox. If it's still running after `timeout`, it's killed.
+    fn run(&self, binary: &Path, timeout: Option<Duration>) -> Result<RunOutput>;
 
     /// Bind a tmpfs at `dir`.
     fn tmpfs(&mut self, dir: &Path);
@@ -53,10 +74,204 @@ pub(crate) trait Sandbox {
     fn display_to_run(&self, binary: &Path) -> Box<dyn Display>;
 }
 
+/// Returns `command` as it might be typed into a shell, for showing to the user.
+pub(crate) fn command_display(command: &Command) -> String {
+    let mut out = command.get_program().to_string_lossy().into_owned();
+    for arg in command.get_args() {
+        let arg = arg.to_string_lossy();
+        if arg.contains(' ') {
+            // Use debug print, since that gives us quotes.
+            out.push_str(&format!(" {arg:?}"));
+        } else {
+            // Print without quotes, since it probably isn't necessary.
+            out.push(' ');
+            out.push_str(&arg);
+        }
+    }
+    out
+}
+
+/// The output of a binary that was run with a time limit.
+pub(crate) struct RunOutput {
+    pub(crate) output: Output,
+    /// Whether the binary was killed because it exceeded its time limit. If it was, `output` has
+    /// whatever it wrote before it was killed.
+    pub(crate) timed_out: bool,
+}
+
+/// How often we check whether a binary that has a time limit has finished.
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// Runs `command`, capturing its output. If it's still running after `timeout`, then it's killed,
+/// together with any processes that it started.
+pub(crate) fn run_with_timeout(
+    command: &mut Command,
+    timeout: Option<Duration>,
+) -> Result<RunOutput> {
+    let mut child = spawn_in_process_group(
+        command
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped()),
+    )?;
+    let stdout = read_in_background(child.stdout.take());
+    let stderr = read_in_background(child.stderr.take());
+    let (status, timed_out) = wait_with_timeout(&mut child, timeout)?;
+    Ok(RunOutput {
+        output: Output {
+            status,
+            stdout: stdout.join().unwrap_or_default(),
+            stderr: stderr.join().unwrap_or_default(),
+        },
+        timed_out,
+    })
+}
+
+/// Runs `command` with stdin, stdout and stderr inherited from us. If it's still running after
+/// `timeout`, then it's killed, together with any processes that it started. Since nothing is
+/// captured, the returned output is empty.
+pub(crate) fn run_inherited_with_timeout(
+    command: &mut Command,
+    timeout: Duration,
+) -> Result<RunOutput> {
+    let mut child = spawn_in_process_group(command)?;
+    let (status, timed_out) = wait_with_timeout(&mut child, Some(timeout))?;
+    Ok(RunOutput {
+        output: Output {
+            status,
+            stdout: Vec::new(),
+            stderr: Vec::new(),
+        },
+        timed_out,
+    })
+}
+
+/// Waits for `child` to exit, killing it and its process group if it's still running after
+/// `timeout`. Returns its exit status and whether it was killed.
+fn wait_with_timeout(child: &mut Child, timeout: Option<Duration>) -> Result<(ExitStatus, bool)> {
+    let deadline = timeout.map(|timeout| Instant::now() + timeout);
+    let interrupts = InterruptForwarder::start();
+    loop {
+        if let Some(status) = child.try_wait()? {
+            return Ok((status, false));
+        }
+        if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+            kill_process_group(child);
+            return Ok((child.wait()?, true));
+        }
+        interrupts.forward_to(child);
+        std::thread::sleep(POLL_INTERVAL);
+    }
+}
+
+fn read_in_background(
+    pipe: Option<impl Read + Send + 'static>,
+) -> std::thread::JoinHandle<Vec<u8>> {
+    std::thread::spawn(move || {
+        let mut buffer = Vec::new();
+        if let Some(mut pipe) = pipe {
+            // If reading fails part way through, we still want whatever we got.
+            let _ = pipe.read_to_end(&mut buffer);
+        }
+        buffer
+    })
+}
+
+/// Spawns `command` in its own process group, so that if we need to kill it, we can also kill
+/// anything that it has started, which would otherwise keep running and hold its output open.
+fn spawn_in_process_group(command: &mut Command) -> Result<Child> {
+    #[cfg(target_os = "linux")]
+    std::os::unix::process::CommandExt::process_group(command, 0);
+    Ok(command.spawn()?)
+}
+
+fn kill_process_group(child: &mut Child) {
+    // The standard library can only signal the child itself.
+    #[cfg(target_os = "linux")]
+    let _ = rustix::process::kill_process_group(
+        rustix::process::Pid::from_child(child),
+        rustix::process::Signal::Kill,
+    );
+    // In case the above failed, at least kill the child.
+    let _ = child.kill();
+}
+
+/// Since the binaries that we run are in their own process group, they don't get the SIGINT that
+/// the terminal sends to the foreground process group when the user presses Ctrl-C. So while we're
+/// waiting for one, we catch SIGINT and pass it on. The rest of the time, SIGINT has its default
+/// behaviour.
+struct InterruptForwarder {
+    #[cfg(target_os = "linux")]
+    flags: Option<&'static InterruptFlags>,
+}
+
+#[cfg(target_os = "linux")]
+struct InterruptFlags {
+    /// Whether we're not waiting for any binary, in which case SIGINT terminates us as usual.
+    idle: Arc<AtomicBool>,
+    /// Whether we've received a SIGINT that we haven't yet passed on.
+    received: Arc<AtomicBool>,
+}
+
+/// The flags set by our SIGINT handlers, or `None` if they couldn't be registered.
+#[cfg(target_os = "linux")]
+static INTERRUPT_FLAGS: Lazy<Option<InterruptFlags>> = Lazy::new(|| {
+    let flags = InterruptFlags {
+        idle: Arc::new(AtomicBool::new(true)),
+        received: Arc::new(AtomicBool::new(false)),
+    };
+    let signal = signal_hook::consts::SIGINT;
+    signal_hook::flag::register_conditional_default(signal, flags.idle.clone()).ok()?;
+    signal_hook::flag::register(signal, flags.received.clone()).ok()?;
+    Some(flags)
+});
+
+impl InterruptForwarder {
+    fn start() -> Self {
+        #[cfg(target_os = "linux")]
+        {
+            let flags = INTERRUPT_FLAGS.as_ref();
+            if let Some(flags) = flags {
+                flags.idle.store(false, Ordering::SeqCst);
+            }
+            Self { flags }
+        }
+        #[cfg(not(target_os = "linux"))]
+        Self {}
+    }
+
+    /// Passes on any SIGINT that we've received since we last checked to the process group of
+    /// `child`.
+    fn forward_to(&self, child: &Child) {
+        #[cfg(target_os = "linux")]
+        if let Some(flags) = self.flags {
+            if flags.received.swap(false, Ordering::SeqCst) {
+                let _ = rustix::process::kill_process_group(
+                    rustix::process::Pid::from_child(child),
+                    rustix::process::Signal::Int,
+                );
+            }
+        }
+    }
+}
+
+impl Drop for InterruptForwarder {
+    fn drop(&mut self) {
+        #[cfg(target_os = "linux")]
+        if let Some(flags) = self.flags {
+            flags.idle.store(true, Ordering::SeqCst);
+        }
+    }
+}
+
 pub(crate) fn from_config(config: &SandboxConfig) -> Result<Option<Box<dyn Sandbox>>> {
-    let mut sandbox = match &config.kind {
+    let mut sandbox: Box<dyn Sandbox> = match &config.kind {
         SandboxKind::Disabled | SandboxKind::Inherit => return Ok(None),
         SandboxKind::Bubblewrap => Box::<bubblewrap::Bubblewrap>::default(),
+        #[cfg(target_os = "linux")]
+        SandboxKind::Landlock => Box::<landlock::Landlock>::default(),
+        #[cfg(not(target_os = "linux"))]
+        SandboxKind::Landlock => anyhow::bail!("The Landlock sandbox is only supported on Linux"),
     };
     let home = PathBuf::from(std::env::var("HOME").context("Couldn't get HOME env var")?);
     // We allow access to the root of the filesystem, but only selected parts of the user's home
@@ -74,6 +289,14 @@ pub(crate) fn from_config(config: &SandboxConfig) -> Result<Option<Box<dyn Sandb
     sandbox.ro_bind(&cargo_home.join("git"));
     sandbox.ro_bind(&cargo_home.join("registry"));
     sandbox.ro_bind(&home.join(".rustup"));
+    for path in &config.extra_read_only_paths {
+        check_extra_path_exists(path)?;
+        sandbox.ro_bind(path);
+    }
+    for path in &config.extra_read_write_paths {
+        check_extra_path_exists(path)?;
+        sandbox.writable_bind(path);
+    }
     sandbox.set_env(OsStr::new("USER"), OsStr::new("user"));
     sandbox.pass_env("PATH");
     sandbox.pass_env("HOME");
@@ -91,22 +314,45 @@ pub(crate) fn from_config(config: &SandboxConfig) -> Result<Option<Box<dyn Sandb
     Ok(Some(sandbox))
 }
 
+/// Makes sure that a path from `extra_read_only_paths` or `extra_read_write_paths` exists. We check
+/// this rather than letting the sandbox fail, since a missing path most likely indicates a mistake in
+/// the config and the sandbox's error wouldn't point at the config.
+fn check_extra_path_exists(path: &Path) -> Result<()> {
+    if !path.exists() {
+        anyhow::bail!(
+            "Sandbox config lists path `{}`, which doesn't exist",
+            path.display()
+        );
+    }
+    Ok(())
+}
+
 pub(crate) fn available_kind() -> SandboxKind {
     if bubblewrap::has_bwrap() {
         SandboxKind::Bubblewrap
+    } else if verify_kind(SandboxKind::Landlock).is_ok() {
+        SandboxKind::Landlock
     } else {
         SandboxKind::Disabled
     }
 }
 
 pub(crate) fn verify_kind(kind: SandboxKind) -> Result<()> {
-    if kind == SandboxKind::Bubblewrap
-        && std::process::Command::new("bwrap")
-            .arg("--version")
-            .output()
-            .is_err()
-    {
-        anyhow::bail!("Failed to run `bwrap`, perhaps it needs to be installed? On systems with apt you can `sudo apt install bubblewrap`");
+    match kind {
+        SandboxKind::Bubblewrap => {
+            if std::process::Command::new("bwrap")
+                .arg("--version")
+                .output()
+                .is_err()
+            {
+                anyhow::bail!("Failed to run `bwrap`, perhaps it needs to be installed? On systems with apt you can `sudo apt install bubblewrap`");
+            }
+        }
+        #[cfg(target_os = "linux")]
+        SandboxKind::Landlock => landlock::check_supported()?,
+        #[cfg(not(target_os = "linux"))]
+        SandboxKind::Landlock => anyhow::bail!("The Landlock sandbox is only supported on Linux"),
+        SandboxKind::Disabled | SandboxKind::Inherit => {}
     }
     Ok(())
 }
@@ -128,3 +374,105 @@ fn is_cargo_env(var: &str) -> bool {
     ];
     PREFIXES.iter().any(|prefix| var.starts_with(prefix)) || ONE_OFFS.contains(&var)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::run_inherited_with_timeout;
+    use super::run_with_timeout;
+    use crate::config::SandboxConfig;
+    use crate::config::SandboxKind;
+    use std::path::Path;
+    use std::process::Command;
+    use std::time::Duration;
+    use std::time::Instant;
+
+    #[test]
+    #[cfg(unix)]
+    fn timeout() {
+        let start = Instant::now();
+        let result = run_with_timeout(
+            Command::new("sh").args(["-c", "echo started; sleep 30 & wait"]),
+            Some(Duration::from_millis(200)),
+        )
+        .unwrap();
+        assert!(result.timed_out);
+        assert!(!result.output.status.success());
+        // Output written before the timeout is kept. Getting it also requires that the background
+        // `sleep` was killed, since otherwise it would hold stdout open.
+        assert_eq!(result.output.stdout, b"started\n");
+        assert!(start.elapsed() < Duration::from_secs(20));
+
+        let result = run_with_timeout(
+            Command::new("sh").args(["-c", "echo done"]),
+            Some(Duration::from_secs(20)),
+        )
+        .unwrap();
+        assert!(!result.timed_out);
+        assert!(result.output.status.success());
+        assert_eq!(result.output.stdout, b"done\n");
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn inherited_timeout() {
+        let start = Instant::now();
+        let result = run_inherited_with_timeout(
+            Command::new("sh").args(["-c", "sleep 30 & wait"]),
+            Duration::from_millis(200),
+        )
+        .unwrap();
+        assert!(result.timed_out);
+        assert!(!result.output.status.success());
+        assert!(start.elapsed() < Duration::from_secs(20));
+
+        let result =
+            run_inherited_with_timeout(&mut Command::new("true"), Duration::from_secs(20)).unwrap();
+        assert!(!result.timed_out);
+        assert!(result.output.status.success());
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn interrupt_forwarded() {
+        use std::os::unix::process::ExitStatusExt;
+        use std::sync::atomic::Ordering;
+
+        let mut child = super::spawn_in_process_group(Command::new("sleep").arg("30")).unwrap();
+        let interrupts = super::InterruptForwarder::start();
+        // Pretend that the user pressed Ctrl-C.
+        interrupts
+            .flags
+            .unwrap()
+            .received
+            .store(true, Ordering::SeqCst);
+        interrupts.forward_to(&child);
+        let status = child.wait().unwrap();
+        assert_eq!(status.signal(), Some(signal_hook::consts::SIGINT));
+    }
+
+    #[test]
+    fn extra_paths() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let dir = tempdir.path();
+        let config = SandboxConfig {
+            kind: SandboxKind::Bubblewrap,
+            extra_read_only_paths: vec![dir.to_owned()],
+            extra_read_write_paths: vec![dir.to_owned()],
+            ..SandboxConfig::default()
+        };
+        let sandbox = super::from_config(&config).unwrap().unwrap();
+        let command = sandbox
+            .display_to_run(Path::new("build-script"))
+            .to_string();
+        let dir = dir.display();
+        assert!(command.contains(&format!("--ro-bind {dir} {dir}")));
+        assert!(command.contains(&format!("--bind-try {dir} {dir}")));
+
+        let config = SandboxConfig {
+            kind: SandboxKind::Bubblewrap,
+            extra_read_only_paths: vec![tempdir.path().join("does-not-exist")],
+            ..SandboxConfig::default()
+        };
+        assert!(super::from_config(&config).is_err());
+    }
+}
@@ -1,3 +1,4 @@
+use super::RunOutput;
 use super::Sandbox;
 use anyhow::Context;
 use anyhow::Result;
@@ -6,6 +7,7 @@ use std::ffi::OsString;
 use std::fmt::Display;
 use std::path::Path;
 use std::process::Command;
+use std::time::Duration;
 
 #[derive(Default)]
 pub(super) struct Bubblewrap {
@@ -25,6 +27,8 @@ impl Bubblewrap {
             .args(["--gid", "1000"])
             .args(["--hostname", "none"])
             .args(["--new-session"])
+            // If we kill bwrap because a build script timed out, make sure the build script dies.
+            .args(["--die-with-parent"])
             .args(["--clearenv"])
             .args(&self.args)
             .args(["--dev", "/dev"])
@@ -66,9 +70,9 @@ impl Sandbox for Bubblewrap {
         self.arg("--share-net");
     }
 
-    fn run(&self, binary: &Path) -> Result<std::process::Output> {
+    fn run(&self, binary: &Path, timeout: Option<Duration>) -> Result<RunOutput> {
         let mut command = self.command(binary);
-        command.output().with_context(|| {
+        super::run_with_timeout(&mut command, timeout).with_context(|| {
             format!(
                 "Failed to run sandbox command: {}",
                 Path::new(command.get_program()).display()
@@ -77,9 +81,7 @@ impl Sandbox for Bubblewrap {
     }
 
     fn display_to_run(&self, binary: &Path) -> Box<dyn Display> {
-        Box::new(CommandDisplay {
-            command: self.command(binary),
-        })
+        Box::new(super::command_display(&self.command(binary)))
     }
 }
 
@@ -90,24 +92,3 @@ pub(crate) fn has_bwrap() -> bool {
         .map(|output| output.status.success())
         .unwrap_or(false)
 }
-
-struct CommandDisplay {
-    command: Command,
-}
-
-impl Display for CommandDisplay {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{}", self.command.get_program().to_string_lossy())?;
-        for arg in self.command.get_args() {
-            let arg = arg.to_string_lossy();
-            if arg.contains(' ') {
-                // Use debug print, since that gives us quotes.
-                write!(f, " {:?}", arg)?;
-            } else {
-                // Print without quotes, since it probably isn't necessary.
-                write!(f, " {arg}")?
-            }
-        }
-        Ok(())
-    }
-}
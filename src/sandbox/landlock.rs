@@ -0,0 +1,337 @@
+//! A sandbox that uses Landlock to restrict filesystem access and a seccomp filter to deny network
+//! access. Unlike Bubblewrap, it doesn't need any external binaries or user namespaces, so works in
+//! minimal containers, provided the kernel is Linux 5.13 or later.
+//!
+//! A process can only apply Landlock and seccomp restrictions to itself. So to run a binary in the
+//! sandbox, we run a copy of ourselves with the filesystem and network rules in an environment
+//! variable. That process restricts itself then execs the binary, which inherits the
+//! restrictions.
+
+use super::RunOutput;
+use super::Sandbox;
+use ::landlock::path_beneath_rules;
+use ::landlock::Access;
+use ::landlock::AccessFs;
+use ::landlock::CompatLevel;
+use ::landlock::Compatible;
+use ::landlock::Ruleset;
+use ::landlock::RulesetAttr;
+use ::landlock::RulesetCreatedAttr;
+use ::landlock::RulesetStatus;
+use ::landlock::ABI;
+use anyhow::anyhow;
+use anyhow::bail;
+use anyhow::Context;
+use anyhow::Result;
+use seccompiler::BpfProgram;
+use seccompiler::SeccompAction;
+use seccompiler::SeccompCmpArgLen;
+use seccompiler::SeccompCmpOp;
+use seccompiler::SeccompCondition;
+use seccompiler::SeccompFilter;
+use seccompiler::SeccompRule;
+use serde::Deserialize;
+use serde::Serialize;
+use std::ffi::OsStr;
+use std::ffi::OsString;
+use std::fmt::Display;
+use std::os::unix::process::CommandExt;
+use std::path::Path;
+use std::path::PathBuf;
+use std::process::Command;
+use std::time::Duration;
+
+/// If set, we're running as the sandbox helper. The value is the JSON-encoded `Rules`.
+const RULES_ENV: &str = "CACKLE_LANDLOCK_RULES";
+
+/// The Landlock ABI that we require. Version 1 is what Linux 5.13 provides.
+const REQUIRED_ABI: ABI = ABI::V1;
+
+const UNSUPPORTED_MESSAGE: &str = "The Landlock sandbox requires a Linux kernel with Landlock \
+    enabled (5.13 or later). Either use a different sandbox kind or upgrade the kernel.";
+
+#[derive(Default)]
+pub(super) struct Landlock {
+    rules: Rules,
+    env: Vec<(OsString, OsString)>,
+    extra_args: Vec<OsString>,
+}
+
+/// What the sandboxed process is permitted to access.
+#[derive(Serialize, Deserialize, Default, Debug, Clone, PartialEq, Eq)]
+struct Rules {
+    read_only: Vec<PathBuf>,
+    writable: Vec<PathBuf>,
+    /// Directories that shouldn't be accessible, even if they're inside a read-only directory.
+    /// Landlock can only grant access, so rather than granting access to a read-only directory that
+    /// contains a hidden directory, we grant access to each of its entries other than the hidden
+    /// directory.
+    hidden: Vec<PathBuf>,
+    allow_network: bool,
+}
+
+impl Landlock {
+    /// Returns a command that will run `binary` in the sandbox. Bubblewrap gives the binary an
+    /// empty, writable /tmp. We can't do that, so instead we make `tmpdir` writable and point
+    /// TMPDIR at it.
+    fn command(&self, binary: &Path, tmpdir: &Path) -> Result<Command> {
+        let mut rules = self.rules.clone();
+        rules.writable.push(tmpdir.to_owned());
+        let mut command = Command::new(crate::proxy::cackle_exe()?);
+        command
+            .env_clear()
+            .envs(self.env.iter().map(|(var, value)| (var, value)))
+            .env("TMPDIR", tmpdir)
+            .env(RULES_ENV, serde_json::to_string(&rules)?)
+            .arg(binary);
+        Ok(command)
+    }
+}
+
+impl Sandbox for Landlock {
+    fn raw_arg(&mut self, arg: &OsStr) {
+        self.extra_args.push(arg.to_owned());
+    }
+
+    fn tmpfs(&mut self, dir: &Path) {
+        self.rules.hidden.push(dir.to_owned());
+    }
+
+    fn ro_bind(&mut self, dir: &Path) {
+        self.rules.read_only.push(dir.to_owned());
+    }
+
+    fn writable_bind(&mut self, dir: &Path) {
+        self.rules.writable.push(dir.to_owned());
+    }
+
+    fn set_env(&mut self, var: &OsStr, value: &OsStr) {
+        self.env.push((var.to_owned(), value.to_owned()));
+    }
+
+    fn allow_network(&mut self) {
+        self.rules.allow_network = true;
+    }
+
+    fn run(&self, binary: &Path, timeout: Option<Duration>) -> Result<RunOutput> {
+        if !self.extra_args.is_empty() {
+            bail!("The Landlock sandbox doesn't support `extra_args`");
+        }
+        let tmpdir = tempfile::tempdir()?;
+        super::run_with_timeout(&mut self.command(binary, tmpdir.path())?, timeout)
+            .context("Failed to run Landlock sandbox")
+    }
+
+    fn display_to_run(&self, binary: &Path) -> Box<dyn Display> {
+        Box::new(RulesDisplay {
+            rules: self.rules.clone(),
+            binary: binary.to_owned(),
+        })
+    }
+}
+
+/// Checks that the running kernel supports the Landlock features that we need.
+pub(super) fn check_supported() -> Result<()> {
+    Ruleset::default()
+        .set_compatibility(CompatLevel::HardRequirement)
+        .handle_access(AccessFs::from_all(REQUIRED_ABI))
+        .and_then(|ruleset| ruleset.create())
+        .map(|_| ())
+        .map_err(|_| anyhow!(UNSUPPORTED_MESSAGE))
+}
+
+/// Checks if we've been invoked to run a binary inside the sandbox. If we have, then we restrict
+/// ourselves and exec the binary - i.e. we don't return. Otherwise we just return.
+pub(crate) fn handle_sandboxed_exec() -> Result<()> {
+    let Some(rules) = std::env::var_os(RULES_ENV) else {
+        return Ok(());
+    };
+    let rules: Rules = serde_json::from_str(
+        rules
+            .to_str()
+            .ok_or_else(|| anyhow!("{RULES_ENV} isn't valid UTF-8"))?,
+    )?;
+    let mut args = std::env::args_os().skip(1);
+    let binary = args
+        .next()
+        .ok_or_else(|| anyhow!("Landlock sandbox invoked without a binary to run"))?;
+    restrict(&rules)?;
+    let error = Command::new(&binary)
+        .args(args)
+        .env_remove(RULES_ENV)
+        .exec();
+    Err(error).with_context(|| format!("Failed to run `{}`", Path::new(&binary).display()))
+}
+
+fn restrict(rules: &Rules) -> Result<()> {
+    let mut writable = rules.writable.clone();
+    // Lots of things write to /dev/null, which should be harmless.
+    writable.push(PathBuf::from("/dev/null"));
+    let status = Ruleset::default()
+        .handle_access(AccessFs::from_all(REQUIRED_ABI))?
+        .create()?
+        .add_rules(path_beneath_rules(
+            readable_paths(&rules.read_only, &rules.hidden),
+            AccessFs::from_read(REQUIRED_ABI),
+        ))?
+        .add_rules(path_beneath_rules(
+            writable,
+            AccessFs::from_all(REQUIRED_ABI),
+        ))?
+        .restrict_self()?;
+    if status.ruleset == RulesetStatus::NotEnforced {
+        bail!(UNSUPPORTED_MESSAGE);
+    }
+    if !rules.allow_network {
+        deny_network()?;
+    }
+    Ok(())
+}
+
+/// Returns the paths to which read access should be granted. This is `read_only`, except that any
+/// directory containing one of the `hidden` directories is replaced by its entries. Directories in
+/// `read_only` are always included, even if they're inside a hidden directory.
+fn readable_paths(read_only: &[PathBuf], hidden: &[PathBuf]) -> Vec<PathBuf> {
+    fn add(dir: &Path, hidden: &[PathBuf], out: &mut Vec<PathBuf>) {
+        if !hidden
+            .iter()
+            .any(|hidden| hidden.starts_with(dir) && hidden != dir)
+        {
+            out.push(dir.to_owned());
+            return;
+        }
+        let Ok(entries) = std::fs::read_dir(dir) else {
+            return;
+        };
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if !hidden.contains(&path) {
+                add(&path, hidden, out);
+            }
+        }
+    }
+
+    let mut out = Vec::new();
+    for dir in read_only {
+        add(dir, hidden, &mut out);
+    }
+    out
+}
+
+/// Installs a seccomp filter that prevents creation of IP sockets. Unix domain sockets are still
+/// permitted.
+fn deny_network() -> Result<()> {
+    let mut socket_rules = Vec::new();
+    for family in [libc::AF_INET, libc::AF_INET6] {
+        socket_rules.push(SeccompRule::new(vec![SeccompCondition::new(
+            0,
+            SeccompCmpArgLen::Dword,
+            SeccompCmpOp::Eq,
+            family as u64,
+        )?])?);
+    }
+    let filter = SeccompFilter::new(
+        [
+            (libc::SYS_socket, socket_rules),
+            // io_uring can create sockets without going via the socket syscall.
+            (libc::SYS_io_uring_setup, Vec::new()),
+        ]
+        .into_iter()
+        .collect(),
+        SeccompAction::Allow,
+        SeccompAction::Errno(libc::EACCES as u32),
+        std::env::consts::ARCH.try_into()?,
+    )?;
+    let program: BpfProgram = filter.try_into()?;
+    seccompiler::apply_filter(&program)?;
+    Ok(())
+}
+
+struct RulesDisplay {
+    rules: Rules,
+    binary: PathBuf,
+}
+
+impl Display for RulesDisplay {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "Landlock sandbox running `{}`", self.binary.display())?;
+        let lists = [
+            ("Read-only", &self.rules.read_only),
+            ("Writable", &self.rules.writable),
+            ("Hidden", &self.rules.hidden),
+        ];
+        for (label, paths) in lists {
+            let paths: Vec<String> = paths
+                .iter()
+                .map(|path| path.display().to_string())
+                .collect();
+            writeln!(f, "  {label}: {}", paths.join(", "))?;
+        }
+        let network = if self.rules.allow_network {
+            "allowed"
+        } else {
+            "denied"
+        };
+        write!(f, "  Network: {network}")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn write_outside_out_dir_is_blocked() {
+        if check_supported().is_err() {
+            return;
+        }
+        let dir = tempfile::tempdir().unwrap();
+        let out_dir = dir.path().join("out");
+        std::fs::create_dir(&out_dir).unwrap();
+        let rules = Rules {
+            read_only: vec![dir.path().to_owned()],
+            writable: vec![out_dir.clone()],
+            ..Rules::default()
+        };
+        // Landlock restrictions apply to the thread that adds them, so we restrict a new thread
+        // rather than the test runner.
+        let root = dir.path().to_owned();
+        let (inside, outside) = std::thread::spawn(move || {
+            restrict(&rules).unwrap();
+            (
+                std::fs::write(out_dir.join("inside"), "ok"),
+                std::fs::write(root.join("outside"), "not ok"),
+            )
+        })
+        .join()
+        .unwrap();
+        assert!(inside.is_ok());
+        assert_eq!(
+            outside.unwrap_err().kind(),
+            std::io::ErrorKind::PermissionDenied
+        );
+        assert!(!dir.path().join("outside").exists());
+    }
+
+    #[test]
+    fn readable_paths_exclude_hidden() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        for subdir in ["a/secret", "a/public", "b"] {
+            std::fs::create_dir_all(root.join(subdir)).unwrap();
+        }
+        let mut readable = readable_paths(
+            &[root.to_owned(), root.join("a/secret/granted")],
+            &[root.join("a/secret")],
+        );
+        readable.sort();
+        assert_eq!(
+            readable,
+            vec![
+                root.join("a/public"),
+                root.join("a/secret/granted"),
+                root.join("b"),
+            ]
+        );
+    }
+}
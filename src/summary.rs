@@ -1,11 +1,10 @@
 use crate::config::Config;
 use crate::config::CrateName;
-use crate::config::PackageConfig;
 use crate::crate_index::BuildScriptId;
 use crate::crate_index::CrateIndex;
 use crate::crate_index::CrateSel;
+use crate::crate_index::PackageId;
 use clap::Parser;
-use fxhash::FxHashMap;
 use std::collections::BTreeMap;
 use std::fmt::Display;
 
@@ -46,33 +45,11 @@ struct PackageSummary {
 
 impl Summary {
     pub(crate) fn new(crate_index: &CrateIndex, config: &Config) -> Self {
-        let pkg_configs: FxHashMap<&CrateName, &PackageConfig> =
-            config.packages.iter().map(|(k, v)| (k, v)).collect();
         let mut packages: Vec<PackageSummary> = crate_index
             .package_ids()
-            .map(|pkg_id| {
-                let mut permissions = Vec::new();
-                let pkg_name = CrateName::from(&CrateSel::Primary(pkg_id.clone()));
-                let build_script_name = CrateName::from(&CrateSel::BuildScript(BuildScriptId {
-                    pkg_id: pkg_id.clone(),
-                }));
-                for (crate_name, suffix) in [(&pkg_name, ""), (&build_script_name, "[build]")] {
-                    if let Some(pkg_config) = pkg_configs.get(&crate_name) {
-                        if pkg_config.allow_proc_macro {
-                            permissions.push(format!("proc_macro{suffix}"));
-                        }
-                        if pkg_config.allow_unsafe {
-                            permissions.push(format!("unsafe{suffix}"));
-                        }
-                        for api in &pkg_config.allow_apis {
-                            permissions.push(format!("{api}{suffix}"));
-                        }
-                    }
-                }
-                PackageSummary {
-                    name: pkg_name,
-                    permissions,
-                }
+            .map(|pkg_id| PackageSummary {
+                name: CrateName::from(&CrateSel::Primary(pkg_id.clone())),
+                permissions: package_permissions(pkg_id, config),
             })
             .collect();
         packages.sort_by(|a, b| a.name.cmp(&b.name));
@@ -81,6 +58,45 @@ impl Summary {
     }
 }
 
+/// Returns the permissions that `config` grants to the package `pkg_id`. Permissions granted to the
+/// package's build script have a `[build]` suffix.
+pub(crate) fn package_permissions(pkg_id: &PackageId, config: &Config) -> Vec<String> {
+    let mut permissions = Vec::new();
+    let pkg_name = config.crate_name_for(&CrateSel::Primary(pkg_id.clone()));
+    let build_script_name = config.crate_name_for(&CrateSel::BuildScript(BuildScriptId {
+        pkg_id: pkg_id.clone(),
+    }));
+    for (crate_name, suffix) in [(&pkg_name, ""), (&build_script_name, "[build]")] {
+        if let Some(pkg_config) = config.packages.get(crate_name) {
+            if pkg_config.allow_proc_macro {
+                permissions.push(format!("proc_macro{suffix}"));
+            }
+            if pkg_config.allow_unsafe {
+                permissions.push(format!("unsafe{suffix}"));
+            }
+            for module in &pkg_config.allow_unsafe_in {
+                permissions.push(format!("unsafe({module}){suffix}"));
+            }
+            if pkg_config.allow_shell {
+                permissions.push(format!("shell{suffix}"));
+            }
+            if pkg_config.allow_unknown_locations {
+                permissions.push(format!("unknown_locations{suffix}"));
+            }
+            for api in &pkg_config.allow_apis {
+                permissions.push(format!("{api}{suffix}"));
+            }
+            for symbol in &pkg_config.allow_symbols {
+                permissions.push(format!("symbol({symbol}){suffix}"));
+            }
+            for var in &pkg_config.allow_env_vars {
+                permissions.push(format!("env_var({var}){suffix}"));
+            }
+        }
+    }
+    permissions
+}
+
 impl Summary {
     pub(crate) fn print(&self, options: &SummaryOptions) {
         let options = options.with_defaults();
@@ -0,0 +1,143 @@
+//! This module tokenises Rust code and looks for annotations that suppress individual API usages.
+//! An annotation is a line comment like `// cackle:allow(net)`. It applies to usages of the listed
+//! APIs on the line immediately after the comment, which is useful for one-off cases that can't be
+//! avoided, but where allowing the API for the whole package would be too broad.
+
+use crate::config::PermissionName;
+use crate::location::SourceLocation;
+use anyhow::Context;
+use anyhow::Result;
+use rustc_ap_rustc_lexer::TokenKind;
+use serde::Deserialize;
+use serde::Serialize;
+use std::path::Path;
+
+const ANNOTATION_PREFIX: &str = "cackle:allow(";
+
+/// Permission to use an API, granted by an annotation in the source.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub(crate) struct Suppression {
+    /// Where the annotation is written. Usages on the following line are suppressed.
+    pub(crate) location: SourceLocation,
+    pub(crate) permission: PermissionName,
+}
+
+impl Suppression {
+    /// Returns whether this suppresses usages of `permission` at `location`.
+    pub(crate) fn applies_to(
+        &self,
+        location: &SourceLocation,
+        permission: &PermissionName,
+    ) -> bool {
+        &self.permission == permission
+            && self.location.filename() == location.filename()
+            && self.location.line() + 1 == location.line()
+    }
+}
+
+/// Returns all suppressions found in `path`.
+pub(crate) fn scan_path(path: &Path) -> Result<Vec<Suppression>> {
+    let bytes =
+        std::fs::read(path).with_context(|| format!("Failed to read `{}`", path.display()))?;
+    let Ok(source) = std::str::from_utf8(&bytes) else {
+        return Ok(Vec::new());
+    };
+    Ok(scan_string(source, path))
+}
+
+fn scan_string(source: &str, path: &Path) -> Vec<Suppression> {
+    let mut offset = 0;
+    let mut suppressions = Vec::new();
+    for token in rustc_ap_rustc_lexer::tokenize(source) {
+        let new_offset = offset + token.len;
+        if matches!(token.kind, TokenKind::LineComment { .. }) {
+            let comment = source[offset..new_offset].trim_start_matches('/').trim();
+            if let Some(apis) = comment
+                .strip_prefix(ANNOTATION_PREFIX)
+                .and_then(|rest| rest.split_once(')'))
+                .map(|(apis, _)| apis)
+            {
+                let line_start = source[..offset].rfind('\n').map_or(0, |pos| pos + 1);
+                let column = (offset - line_start + 1) as u32;
+                let line = 1.max(source[..new_offset].lines().count() as u32);
+                let location = SourceLocation::new(path, line, Some(column));
+                for api in apis.split(',').map(str::trim).filter(|api| !api.is_empty()) {
+                    suppressions.push(Suppression {
+                        location: location.clone(),
+                        permission: PermissionName::new(api),
+                    });
+                }
+            }
+        }
+        offset = new_offset;
+    }
+    suppressions
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn annotations(source: &str) -> Vec<(u32, String)> {
+        scan_string(source, Path::new("test.rs"))
+            .into_iter()
+            .map(|s| (s.location.line(), s.permission.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_scan_string() {
+        assert_eq!(
+            annotations("// cackle:allow(net)"),
+            vec![(1, "net".to_owned())]
+        );
+        assert_eq!(
+            annotations(indoc::indoc! {r#"
+                fn foo() {
+                    // cackle:allow(fs, net)
+                    bar();
+                }"#
+            }),
+            vec![(2, "fs".to_owned()), (2, "net".to_owned())]
+        );
+        assert_eq!(
+            annotations("/// cackle:allow(net)"),
+            vec![(1, "net".to_owned())]
+        );
+        // Only line comments are annotations.
+        assert_eq!(annotations(r#"let s = "// cackle:allow(net)";"#), vec![]);
+        assert_eq!(annotations("/* cackle:allow(net) */"), vec![]);
+        assert_eq!(annotations("// cackle:allow(net"), vec![]);
+        assert_eq!(annotations("// See cackle:allow(net)"), vec![]);
+    }
+
+    #[test]
+    fn test_column() {
+        let columns = |source| {
+            scan_string(source, Path::new("test.rs"))
+                .into_iter()
+                .map(|s| (s.location.line(), s.location.column()))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(columns("// cackle:allow(net)"), vec![(1, Some(1))]);
+        assert_eq!(columns("foo();\n// cackle:allow(net)"), vec![(2, Some(1))]);
+        assert_eq!(
+            columns("foo();\n    // cackle:allow(net)"),
+            vec![(2, Some(5))]
+        );
+        assert_eq!(columns("foo(); // cackle:allow(net)"), vec![(1, Some(8))]);
+    }
+
+    #[test]
+    fn test_applies_to() {
+        let suppression = &scan_string("\n// cackle:allow(net)", Path::new("a.rs"))[0];
+        let net = PermissionName::new("net");
+        assert!(suppression.applies_to(&SourceLocation::new(Path::new("a.rs"), 3, None), &net));
+        assert!(!suppression.applies_to(&SourceLocation::new(Path::new("a.rs"), 2, None), &net));
+        assert!(!suppression.applies_to(&SourceLocation::new(Path::new("b.rs"), 3, None), &net));
+        assert!(!suppression.applies_to(
+            &SourceLocation::new(Path::new("a.rs"), 3, None),
+            &PermissionName::new("fs")
+        ));
+    }
+}
@@ -2,6 +2,7 @@ use crate::cowarc::Bytes;
 use crate::demangle::DemangleIterator;
 use crate::demangle::DemangleToken;
 use crate::names::NamesIterator;
+use crate::names::WithoutStdGenericArgs;
 use anyhow::Result;
 use rustc_demangle::demangle;
 use std::fmt::Debug;
@@ -16,7 +17,7 @@ pub(crate) struct Symbol<'data> {
 }
 
 impl<'data> Symbol<'data> {
-    pub(crate) fn borrowed(data: &[u8]) -> Symbol {
+    pub(crate) fn borrowed(data: &[u8]) -> Symbol<'_> {
         Symbol {
             bytes: Bytes::Borrowed(data),
         }
@@ -40,10 +41,20 @@ impl<'data> Symbol<'data> {
     }
 
     /// Splits the name of this symbol into names. See `crate::names::split_names` for details.
-    pub(crate) fn names(&self) -> Result<NamesIterator<DemangleIterator>> {
+    pub(crate) fn names(&self) -> Result<NamesIterator<'_, DemangleIterator<'_>>> {
         Ok(NamesIterator::new(DemangleIterator::new(self.to_str()?)))
     }
 
+    /// Like `names`, but ignores the generic arguments of items in the standard library, which is
+    /// what we want when determining what APIs the symbol refers to.
+    pub(crate) fn names_without_std_generic_args(
+        &self,
+    ) -> Result<NamesIterator<'_, WithoutStdGenericArgs<DemangleIterator<'_>>>> {
+        Ok(NamesIterator::new(WithoutStdGenericArgs::new(
+            DemangleIterator::new(self.to_str()?),
+        )))
+    }
+
     pub(crate) fn len(&self) -> usize {
         self.data().len()
     }
@@ -70,6 +81,18 @@ impl<'data> Symbol<'data> {
         }
     }
 
+    /// Returns the name of this symbol if it isn't a mangled Rust symbol. Such symbols are generally
+    /// functions in C libraries, e.g. `system` or `fopen`, or functions declared `extern "C"`. They
+    /// have no namespace, so the whole name is used when matching APIs.
+    pub(crate) fn extern_name(&self) -> Option<&str> {
+        let name = self.to_str().ok()?;
+        if name.is_empty() || name.starts_with("_ZN") || name.starts_with("_R") {
+            return None;
+        }
+        // Symbols from shared libraries may have a version suffix, e.g. `system@GLIBC_2.2.5`.
+        Some(name.split('@').next().unwrap_or(name))
+    }
+
     /// Returns whether this symbol is one that we should "look through". Such symbols are ones
     /// where we pretend they don't exist and treat any outgoing references from the symbol as
     /// originating from whatever referenced the look-through symbol. So for example, if
@@ -167,6 +190,20 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_extern_name() {
+        assert_eq!(Symbol::borrowed(b"system").extern_name(), Some("system"));
+        assert_eq!(
+            Symbol::borrowed(b"getenv@GLIBC_2.2.5").extern_name(),
+            Some("getenv")
+        );
+        assert_eq!(
+            Symbol::borrowed(b"_ZN3std2fs5write17h0f72782372833d23E").extern_name(),
+            None
+        );
+        assert_eq!(Symbol::borrowed(b"").extern_name(), None);
+    }
+
     #[test]
     fn test_display() {
         let symbol = Symbol::borrowed(b"_ZN4core3ptr85drop_in_place$LT$std..rt..lang_start$LT$$LP$$RP$$GT$..$u7b$$u7b$closure$u7d$$u7d$$GT$17h0bb7e9fe967fc41cE");
@@ -12,6 +12,7 @@ use crate::checker::Checker;
 use crate::config::CrateName;
 use crate::config::PermissionName;
 use crate::crate_index::CrateSel;
+use crate::crate_index::PackageId;
 use crate::demangle::NonMangledIterator;
 use crate::lazy::Lazy;
 use crate::location::SourceLocation;
@@ -19,10 +20,13 @@ use crate::names::DebugName;
 use crate::names::Name;
 use crate::names::NamesIterator;
 use crate::names::SymbolAndName;
+use crate::names::WithoutStdGenericArgs;
 use crate::problem::ApiUsageGroupKey;
 use crate::problem::ApiUsages;
 use crate::problem::PossibleExportedApi;
 use crate::problem::ProblemList;
+use crate::problem::ShellInvocation;
+use crate::suppressions::Suppression;
 use crate::symbol::Symbol;
 use anyhow::anyhow;
 use anyhow::bail;
@@ -37,22 +41,36 @@ use gimli::LittleEndian;
 use log::debug;
 use log::trace;
 use object::Object;
+use object::ObjectKind;
 use object::ObjectSection;
 use object::ObjectSymbol;
+use object::RelocationKind;
 use object::RelocationTarget;
 use object::SectionIndex;
+use object::SectionKind;
+use object::SymbolKind;
+use rayon::prelude::*;
 use std::borrow::Cow;
 use std::collections::BTreeMap;
+use std::collections::BTreeSet;
 use std::fmt::Display;
 use std::fs::File;
 use std::io::Read;
 use std::path::Path;
 use std::path::PathBuf;
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::Ordering;
 use std::sync::Arc;
+use std::sync::Mutex;
 use std::time::Instant;
 
+mod backtrace;
 mod dwarf;
 pub(crate) mod object_file_path;
+mod progress;
+mod reachability;
+mod shell;
+pub(crate) mod sizes;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 enum Filetype {
@@ -66,6 +84,15 @@ struct ApiUsageCollector<'input> {
     bin: BinInfo<'input>,
     debug_enabled: bool,
     new_api_usages: FxHashMap<ApiUsageGroupKey, Vec<ApiUsages>>,
+
+    /// Names under each package that could be used as API prefixes. Only collected if requested.
+    exports: Vec<(PackageId, String)>,
+
+    /// If set, only usages from these symbols will be reported.
+    reachable: Option<FxHashSet<Symbol<'static>>>,
+
+    /// If set, each usage records the chain of functions through which it's reached.
+    callers: Option<backtrace::Callers>,
 }
 
 /// Information derived from a linked binary. Generally an executable, but could also be shared
@@ -73,14 +100,27 @@ struct ApiUsageCollector<'input> {
 struct BinInfo<'input> {
     filename: Arc<Path>,
     symbol_addresses: FxHashMap<Symbol<'input>, u64>,
+    /// Names of symbols that the binary imports from shared libraries, e.g. `system` from libc.
+    imported_symbols: FxHashSet<Symbol<'static>>,
     /// Symbols that we've already determined have no APIs. This is an optimisation that lets us
-    /// skip these symbols when we see them again.
-    symbol_has_no_apis: FxHashMap<Symbol<'input>, bool>,
+    /// skip these symbols when we see them again. The keys are fixed up front, so the values can be
+    /// updated while object files are being processed in parallel.
+    symbol_has_no_apis: FxHashMap<Symbol<'input>, AtomicBool>,
 
     /// Information about each symbol obtained from the debug info.
     symbol_debug_info: FxHashMap<Symbol<'input>, SymbolDebugInfo<'input>>,
 }
 
+/// What we find while processing some of the references in a binary. Object files are processed
+/// independently, possibly on different threads, each producing one of these. They're then merged
+/// in a fixed order so that our output doesn't depend on thread scheduling.
+#[derive(Default)]
+struct PartialOutputs {
+    new_api_usages: FxHashMap<ApiUsageGroupKey, Vec<ApiUsages>>,
+    shell_invocations: Vec<ShellInvocation>,
+    used_suppressions: Vec<Suppression>,
+}
+
 #[derive(Default)]
 pub(crate) struct ScanOutputs {
     api_usages: Vec<ApiUsages>,
@@ -90,12 +130,24 @@ pub(crate) struct ScanOutputs {
     base_problems: ProblemList,
 
     possible_exported_apis: Vec<PossibleExportedApi>,
+
+    shell_invocations: Vec<ShellInvocation>,
+
+    /// Inline annotations that suppressed at least one API usage.
+    used_suppressions: Vec<Suppression>,
 }
 
 struct ObjectIndex<'obj, 'data> {
     obj: &'obj object::File<'data>,
 
     section_infos: Vec<SectionInfo<'data>>,
+
+    /// Whether this is a linked binary rather than an object file, e.g. because we're inspecting a
+    /// binary that we didn't see get linked.
+    is_linked: bool,
+
+    /// The symbol defined at each address. Only populated for linked binaries.
+    symbols_by_address: FxHashMap<u64, Symbol<'data>>,
 }
 
 #[derive(Clone, Default)]
@@ -121,8 +173,15 @@ pub(crate) fn scan_objects(
     let start = Instant::now();
     let file_bytes = std::fs::read(bin_path)
         .with_context(|| format!("Failed to read `{}`", bin_path.display()))?;
-    let obj = object::File::parse(file_bytes.as_slice())
-        .with_context(|| format!("Failed to parse {}", bin_path.display()))?;
+    let obj = object::File::parse(file_bytes.as_slice()).with_context(|| {
+        format!(
+            "Failed to parse {}. Only linker output in an object file format (e.g. ELF) can be \
+             analysed. If a linker script is producing a raw binary, produce an ELF file instead \
+             and convert it afterwards",
+            bin_path.display()
+        )
+    })?;
+    check_bin_supported(&obj, bin_path)?;
     let owned_dwarf = Dwarf::load(|id| load_section(&obj, id))?;
     let dwarf = owned_dwarf.borrow(|section| gimli::EndianSlice::new(section, gimli::LittleEndian));
     let start = checker.timings.add_timing(start, "Parse bin");
@@ -135,22 +194,57 @@ pub(crate) fn scan_objects(
     let no_api_symbol_hashes = debug_artifacts
         .symbol_debug_info
         .keys()
-        .map(|symbol| (symbol.clone(), false))
+        .map(|symbol| (symbol.clone(), AtomicBool::new(false)))
         .collect();
     let mut collector = ApiUsageCollector {
         outputs: Default::default(),
         bin: BinInfo {
             filename: Arc::from(bin_path),
             symbol_addresses: Default::default(),
+            imported_symbols: Default::default(),
             symbol_debug_info: debug_artifacts.symbol_debug_info,
             symbol_has_no_apis: no_api_symbol_hashes,
         },
         debug_enabled: checker.args.debug,
         new_api_usages: FxHashMap::default(),
+        exports: Vec::new(),
+        reachable: None,
+        callers: None,
     };
     collector.bin.load_symbols(&obj)?;
-    let start = checker.timings.add_timing(start, "Load symbols from bin");
+    let mut start = checker.timings.add_timing(start, "Load symbols from bin");
+    // If we don't have any object files other than the binary itself, e.g. when inspecting a
+    // prebuilt binary, then we likely don't have enough references to determine reachability, so we
+    // treat everything as reachable.
+    if paths.iter().any(|path| path != bin_path)
+        && (checker.config.common.reachable_code_only || checker.args.show_backtrace)
+    {
+        let mut graph = reachability::ReferenceGraph::default();
+        if crate::link_info::is_shared_library(bin_path) {
+            graph
+                .add_exports_as_roots(&obj)
+                .with_context(|| format!("Failed to read exports from `{}`", bin_path.display()))?;
+        }
+        for path in paths {
+            collector
+                .add_references_from_file(path, &mut graph)
+                .with_context(|| format!("Failed to process `{}`", path.display()))?;
+        }
+        if checker.config.common.reachable_code_only {
+            collector.reachable = Some(graph.reachable());
+        }
+        if checker.args.show_backtrace {
+            collector.callers = Some(backtrace::Callers::new(&graph));
+        }
+        start = checker.timings.add_timing(start, "Build reference graph");
+    }
+    let mut inlined_outputs = PartialOutputs::default();
     for f in debug_artifacts.inlined_functions {
+        if let Some(from_symbol) = f.from.symbol.as_ref() {
+            if !collector.is_reachable(from_symbol) {
+                continue;
+            }
+        }
         let mut lazy_location = crate::lazy::lazy(|| f.location());
         let debug_data = if checker.args.debug {
             Some(UsageDebugData::Inlined(InlinedDebugData::from_offset(
@@ -165,17 +259,56 @@ pub(crate) fn scan_objects(
             checker,
             &mut lazy_location,
             debug_data.as_ref(),
+            &mut inlined_outputs,
         )?;
     }
+    collector.merge(inlined_outputs);
     let start = checker
         .timings
         .add_timing(start, "Process inlined references");
     collector.find_possible_exports(checker);
+    for (pkg_id, prefix) in std::mem::take(&mut collector.exports) {
+        checker.record_export(&pkg_id, prefix);
+    }
     let start = checker.timings.add_timing(start, "Find possible exports");
-    for path in paths {
-        collector
-            .process_file(path, checker, &ctx)
-            .with_context(|| format!("Failed to process `{}`", path.display()))?;
+    let pool = rayon::ThreadPoolBuilder::new()
+        .num_threads(checker.args.jobs.unwrap_or(0))
+        .build()?;
+    // An addr2line context caches what it parses, so can't be shared between threads. We give each
+    // thread its own. The first thread reuses the context we already have and the others create
+    // theirs when they first need it.
+    let mut initial_ctx = Some(ctx);
+    let thread_contexts: Vec<Mutex<Option<addr2line::Context<_>>>> = (0..pool
+        .current_num_threads())
+        .map(|_| Mutex::new(initial_ctx.take()))
+        .collect();
+    let progress = progress::ProgressBar::new(paths.len(), checker.args.show_progress_bars());
+    let file_outputs: Vec<Result<PartialOutputs>> = pool.install(|| {
+        paths
+            .par_iter()
+            .map(|path| {
+                let thread_index = rayon::current_thread_index().unwrap_or(0);
+                let mut thread_ctx = thread_contexts[thread_index].lock().unwrap();
+                let ctx = match &mut *thread_ctx {
+                    Some(ctx) => ctx,
+                    None => thread_ctx.insert(
+                        addr2line::Context::from_dwarf(owned_dwarf.borrow(|section| {
+                            gimli::EndianSlice::new(section, gimli::LittleEndian)
+                        }))
+                        .with_context(|| format!("Failed to process {}", bin_path.display()))?,
+                    ),
+                };
+                let outputs = collector
+                    .process_file(path, checker, ctx)
+                    .with_context(|| format!("Failed to process `{}`", path.display()));
+                progress.inc();
+                outputs
+            })
+            .collect()
+    });
+    progress.finish();
+    for outputs in file_outputs {
+        collector.merge(outputs?);
     }
     collector.emit_shortest_api_usages();
     checker.timings.add_timing(start, "Process object files");
@@ -190,6 +323,9 @@ impl ScanOutputs {
             checker.permission_used(api_usage, &mut problems);
         }
         checker.possible_exported_api_problems(&self.possible_exported_apis, &mut problems);
+        checker.shell_invocation_problems(&self.shell_invocations, &mut problems);
+        checker.suppressions_used(&self.used_suppressions);
+        checker.record_used_excludes();
 
         Ok(problems)
     }
@@ -197,56 +333,137 @@ impl ScanOutputs {
 
 impl<'input> ApiUsageCollector<'input> {
     fn process_file(
-        &mut self,
+        &self,
         filename: &Path,
         checker: &Checker,
         ctx: &addr2line::Context<EndianSlice<'input, LittleEndian>>,
+    ) -> Result<PartialOutputs> {
+        let mut outputs = PartialOutputs::default();
+        for_each_object_file(filename, |object_file_path, file_bytes| {
+            self.process_object_file_bytes(object_file_path, file_bytes, checker, ctx, &mut outputs)
+        })?;
+        Ok(outputs)
+    }
+
+    /// Adds outputs from processing part of the binary to our combined outputs.
+    fn merge(&mut self, partial: PartialOutputs) {
+        for (key, api_usages) in partial.new_api_usages {
+            self.new_api_usages
+                .entry(key)
+                .or_default()
+                .extend(api_usages);
+        }
+        self.outputs
+            .shell_invocations
+            .extend(partial.shell_invocations);
+        self.outputs
+            .used_suppressions
+            .extend(partial.used_suppressions);
+    }
+
+    /// Adds references between symbols in the object files in `filename` to `graph`.
+    fn add_references_from_file(
+        &self,
+        filename: &Path,
+        graph: &mut reachability::ReferenceGraph,
     ) -> Result<()> {
-        let mut buffer = Vec::new();
-        match Filetype::from_filename(filename) {
-            Filetype::Archive => {
-                let mut archive = Archive::new(File::open(filename)?);
-                while let Some(entry_result) = archive.next_entry() {
-                    let Ok(mut entry) = entry_result else {
-                        continue;
-                    };
-                    buffer.clear();
-                    entry.read_to_end(&mut buffer)?;
-                    let object_file_path = ObjectFilePath::in_archive(filename, &entry)?;
-                    self.process_object_file_bytes(&object_file_path, &buffer, checker, ctx)
-                        .with_context(|| format!("Failed to process {object_file_path}"))?;
+        for_each_object_file(filename, |_, file_bytes| {
+            self.add_object_references(file_bytes, graph)
+        })
+    }
+
+    fn add_object_references(
+        &self,
+        file_bytes: &[u8],
+        graph: &mut reachability::ReferenceGraph,
+    ) -> Result<()> {
+        let obj = object::File::parse(file_bytes).context("Failed to parse object file")?;
+        let object_index = ObjectIndex::new(&obj);
+        for obj_symbol in obj.symbols() {
+            let name = obj_symbol.name_bytes().unwrap_or_default();
+            if !obj_symbol.is_definition() || name.is_empty() {
+                continue;
+            }
+            let symbol = Symbol::borrowed(name);
+            if obj_symbol.is_global() && reachability::is_entry_point(name) {
+                graph.add_root(&symbol);
+            }
+            // References are recorded against the first symbol in each section, so make any other
+            // symbols in the section reference the first.
+            let first_symbol = obj_symbol
+                .section_index()
+                .and_then(|index| object_index.section_infos.get(index.0))
+                .and_then(|section_info| section_info.first_symbol.as_ref());
+            if let Some(first_symbol) = first_symbol {
+                if first_symbol.symbol != symbol {
+                    graph.add_reference(&symbol, &first_symbol.symbol);
                 }
             }
-            Filetype::Other => {
-                let file_bytes = std::fs::read(filename)
-                    .with_context(|| format!("Failed to read `{}`", filename.display()))?;
-                let object_file_path = ObjectFilePath::non_archive(filename);
-                self.process_object_file_bytes(&object_file_path, &file_bytes, checker, ctx)
-                    .with_context(|| format!("Failed to process {object_file_path}"))?;
+        }
+        for section in obj.sections() {
+            let section_name = section.name().unwrap_or("");
+            let from = if reachability::is_constructor_section(section_name) {
+                None
+            } else {
+                let Some(first_sym_info) = object_index.first_symbol(&section) else {
+                    continue;
+                };
+                Some(&first_sym_info.symbol)
+            };
+            for (_, rel) in section.relocations() {
+                let mut target_symbols = Vec::new();
+                object_index.add_target_symbols(
+                    &rel,
+                    &mut target_symbols,
+                    &mut FxHashSet::default(),
+                    &self.bin.symbol_addresses,
+                )?;
+                for target in &target_symbols {
+                    match from {
+                        Some(from) => graph.add_reference(from, target),
+                        // Everything referenced from a constructor section is an entry point.
+                        None => graph.add_root(target),
+                    }
+                }
             }
         }
         Ok(())
     }
 
+    fn is_reachable(&self, symbol: &Symbol) -> bool {
+        let Some(reachable) = self.reachable.as_ref() else {
+            return true;
+        };
+        let reachable: &FxHashSet<Symbol> = reachable;
+        reachable.contains(symbol)
+    }
+
     /// Processes an unlinked object file - as opposed to an executable or a shared object, which
     /// has been linked.
     fn process_object_file_bytes(
-        &mut self,
+        &self,
         filename: &ObjectFilePath,
         file_bytes: &[u8],
         checker: &Checker,
         ctx: &addr2line::Context<EndianSlice<'input, LittleEndian>>,
+        outputs: &mut PartialOutputs,
     ) -> Result<()> {
         debug!("Processing object file {}", filename);
 
         let obj = object::File::parse(file_bytes).context("Failed to parse object file")?;
         let object_index = ObjectIndex::new(&obj);
+        // Whether this object is in the rlib of one of our packages. Only determined if needed.
+        let mut object_in_package = None;
         for section in obj.sections() {
             let section_name = section.name().unwrap_or("");
             let Some(first_sym_info) = object_index.first_symbol(&section) else {
                 debug!("Skipping section `{section_name}` due to lack of debug info");
                 continue;
             };
+            if !self.is_reachable(&first_sym_info.symbol) {
+                debug!("Skipping section `{section_name}` because it isn't reachable");
+                continue;
+            }
             let Some(symbol_address_in_bin) = self
                 .bin
                 .symbol_addresses
@@ -259,10 +476,42 @@ impl<'input> ApiUsageCollector<'input> {
                 );
                 continue;
             };
-            let Some(debug_info) = self.bin.symbol_debug_info.get(&first_sym_info.symbol) else {
+            // In a linked binary, each section contains many functions, so the debug info for the
+            // first symbol in the section doesn't tell us where references come from. We instead
+            // rely on the debug info for each reference.
+            let fallback_source_location =
+                match self.bin.symbol_debug_info.get(&first_sym_info.symbol) {
+                    _ if object_index.is_linked => None,
+                    Some(debug_info) => Some(debug_info.source_location()),
+                    // Code without debug info, e.g. C code compiled without `-g`, can still be
+                    // attributed to a package if it's in the package's rlib. We just don't know
+                    // where in the package's source it came from.
+                    None => {
+                        let in_package = *object_in_package.get_or_insert_with(|| {
+                            checker
+                                .crate_index
+                                .package_id_for_output(&filename.outer)
+                                .is_some()
+                        });
+                        if !in_package {
+                            continue;
+                        }
+                        Some(SourceLocation::unknown(filename.outer.as_path()))
+                    }
+                };
+            if fallback_source_location
+                .as_ref()
+                .is_some_and(|location| checker.is_ignored_path(location.filename()))
+            {
+                debug!("Skipping section `{section_name}` because its source path is ignored");
                 continue;
-            };
-            let fallback_source_location = debug_info.source_location();
+            }
+            // Only bother checking for process spawning if the section references a shell name,
+            // since most sections won't.
+            let shell = section
+                .relocations()
+                .find_map(|(_, rel)| object_index.shell_name_for_relocation(&rel));
+            let mut shell_invocation_found = false;
             let debug_data = self.debug_enabled.then(|| {
                 UsageDebugData::Relocation(RelocationDebugData {
                     bin_path: self.bin.filename.clone(),
@@ -271,7 +520,15 @@ impl<'input> ApiUsageCollector<'input> {
                 })
             });
 
+            // We look at all relocations, not just those for call instructions. That way, taking the
+            // address of a function, e.g. to store it in a function pointer or in the vtable of a
+            // trait object, counts as using it, even if it's never called directly.
             for (offset, rel) in section.relocations() {
+                // Relocations without a target, such as the relative relocations in a linked
+                // binary, don't reference anything that could be an API.
+                if rel.target() == RelocationTarget::Absolute {
+                    continue;
+                }
                 let mut target_symbols = Vec::new();
                 let rel = &rel;
                 object_index.add_target_symbols(
@@ -288,10 +545,14 @@ impl<'input> ApiUsageCollector<'input> {
                     .next()?
                     .map(|frame| (frame.function, frame.location))
                     .unwrap_or((None, None));
+                if frame_location.is_none() && fallback_source_location.is_none() {
+                    continue;
+                }
                 let mut lazy_location = crate::lazy::lazy(|| {
-                    Ok(frame_location
+                    frame_location
                         .and_then(|l| l.try_into().ok())
-                        .unwrap_or_else(|| fallback_source_location.clone()))
+                        .or_else(|| fallback_source_location.clone())
+                        .ok_or_else(|| anyhow!("Reference has no source location"))
                 });
                 let frame_symbol = frame_fn_name
                     .as_ref()
@@ -300,6 +561,17 @@ impl<'input> ApiUsageCollector<'input> {
                 let from_symbol = frame_symbol.as_ref().unwrap_or(&first_sym_info.symbol);
                 let from = self.bin.get_symbol_and_name(from_symbol);
                 for target_symbol in target_symbols {
+                    if let Some(shell) = shell {
+                        if !shell_invocation_found && shell::is_process_spawn(&target_symbol)? {
+                            shell_invocation_found = true;
+                            self.record_shell_invocation(
+                                shell,
+                                lazy_location.get()?,
+                                checker,
+                                outputs,
+                            );
+                        }
+                    }
                     let target = self.bin.get_symbol_and_name(&target_symbol);
                     self.process_reference(
                         &from,
@@ -307,6 +579,7 @@ impl<'input> ApiUsageCollector<'input> {
                         checker,
                         &mut lazy_location,
                         debug_data.as_ref(),
+                        outputs,
                     )?;
                 }
             }
@@ -314,27 +587,55 @@ impl<'input> ApiUsageCollector<'input> {
         Ok(())
     }
 
+    fn record_shell_invocation(
+        &self,
+        shell: &str,
+        location: &SourceLocation,
+        checker: &Checker,
+        outputs: &mut PartialOutputs,
+    ) {
+        if checker.is_ignored_path(location.filename()) {
+            return;
+        }
+        let Some(crate_names) = checker.opt_crate_names_from_source_path(location.filename())
+        else {
+            return;
+        };
+        for crate_sel in crate_names.as_ref() {
+            outputs.shell_invocations.push(ShellInvocation {
+                crate_sel: crate_sel.clone(),
+                shell: shell.to_owned(),
+                location: location.clone(),
+            });
+        }
+    }
+
     fn process_reference(
-        &mut self,
+        &self,
         from: &SymbolAndName,
         target: &SymbolAndName,
         checker: &Checker,
         lazy_location: &mut impl Lazy<SourceLocation>,
         debug_data: Option<&UsageDebugData>,
+        outputs: &mut PartialOutputs,
     ) -> Result<(), anyhow::Error> {
         trace!("{from} -> {target}");
 
         let mut from_apis = FxHashSet::default();
         self.bin.names_and_apis_do(from, checker, |_, _, apis| {
-            from_apis.extend(apis.iter());
+            from_apis.extend(apis.iter().cloned());
             Ok(())
         })?;
         let mut lazy_crate_names = None;
+        let mut lazy_backtrace = None;
         self.bin
             .names_and_apis_do(target, checker, |name, name_source, apis| {
                 // For the majority of references we expect no APIs to match. We defer computation
                 // of a source location and crate names until we know that an API matched.
                 let location = lazy_location.get()?;
+                if checker.is_ignored_path(location.filename()) {
+                    return Ok(());
+                }
                 if lazy_crate_names.is_none() {
                     lazy_crate_names =
                         Some(checker.crate_names_from_source_path(location.filename())?);
@@ -354,26 +655,48 @@ impl<'input> ApiUsageCollector<'input> {
                         continue;
                     }
                     for permission in apis {
-                        if from_apis.contains(&permission) {
+                        if from_apis.contains(permission) {
                             continue;
                         }
+                        if let Some(suppression) = checker.suppression_for(location, permission) {
+                            outputs.used_suppressions.push(suppression.clone());
+                            continue;
+                        }
+                        let backtrace: &Vec<Symbol<'static>> =
+                            lazy_backtrace.get_or_insert_with(|| {
+                                match (&self.callers, &from.symbol) {
+                                    (Some(callers), Some(symbol)) => {
+                                        callers.backtrace(symbol, checker.args.backtrace_depth)
+                                    }
+                                    _ => Vec::new(),
+                                }
+                            });
                         let mut usages = BTreeMap::new();
                         usages.insert(
                             permission.clone(),
                             vec![ApiUsage {
                                 source_location: location.clone(),
-                                from: from.symbol_or_debug_name()?,
+                                from: from.symbol_or_debug_name()?.enclosing_function(),
                                 to: target.symbol_or_debug_name()?,
                                 to_name: name.clone(),
                                 to_source: name_source.to_owned(),
                                 debug_data: debug_data.cloned(),
+                                backtrace: backtrace.clone(),
+                                env_var: None,
                             }],
                         );
                         let api_usage = ApiUsages {
                             crate_sel: crate_sel.clone(),
                             usages,
+                            descriptions: BTreeMap::new(),
+                            warning_apis: BTreeSet::new(),
+                            report_caps: BTreeMap::new(),
+                            dependency_path: Default::default(),
+                            build_time: false,
+                            merged_from: Vec::new(),
                         };
-                        self.new_api_usages
+                        outputs
+                            .new_api_usages
                             .entry(api_usage.deduplication_key())
                             .or_default()
                             .push(api_usage);
@@ -409,10 +732,14 @@ impl<'input> ApiUsageCollector<'input> {
             .map(|n| (n.name.as_ref(), n))
             .collect();
         let mut found = FxHashSet::default();
+        let collect_exports = !checker.args.print_exports.is_empty();
         for (symbol, debug_info) in &self.bin.symbol_debug_info {
             let Some(module_name) = symbol.module_name() else {
                 continue;
             };
+            if collect_exports {
+                collect_export(&mut self.exports, symbol, module_name, debug_info, checker);
+            }
             let Some(permission_name) = api_names.get(module_name) else {
                 continue;
             };
@@ -446,15 +773,55 @@ impl<'input> ApiUsageCollector<'input> {
     }
 }
 
+/// Records `module_name` as a possible export of the package that defines `symbol` if that package
+/// was requested via --print-exports.
+fn collect_export(
+    exports: &mut Vec<(PackageId, String)>,
+    symbol: &Symbol,
+    module_name: &str,
+    debug_info: &SymbolDebugInfo,
+    checker: &Checker,
+) {
+    let location = debug_info.source_location();
+    let Some(crate_sels) = checker.opt_crate_names_from_source_path(location.filename()) else {
+        return;
+    };
+    for crate_sel in crate_sels.as_ref() {
+        let CrateSel::Primary(pkg_id) = crate_sel else {
+            continue;
+        };
+        if !checker
+            .args
+            .print_exports
+            .iter()
+            .any(|p| p == pkg_id.name())
+            || symbol.crate_name() != Some(pkg_id.crate_name().as_ref())
+        {
+            continue;
+        }
+        exports.push((
+            pkg_id.clone(),
+            format!("{}::{module_name}", pkg_id.crate_name()),
+        ));
+    }
+}
+
 impl<'obj, 'data> ObjectIndex<'obj, 'data> {
     fn new(obj: &'obj object::File<'data>) -> Self {
         let max_section_index = obj.sections().map(|s| s.index().0).max().unwrap_or(0);
         let mut section_infos = vec![SectionInfo::default(); max_section_index + 1];
+        let is_linked = matches!(obj.kind(), ObjectKind::Executable | ObjectKind::Dynamic);
+        let mut symbols_by_address = FxHashMap::default();
         for obj_symbol in obj.symbols() {
             let name = obj_symbol.name_bytes().unwrap_or_default();
             if name.is_empty() || !obj_symbol.is_definition() {
                 continue;
             }
+            if is_linked {
+                symbols_by_address
+                    .entry(obj_symbol.address())
+                    .or_insert_with(|| Symbol::borrowed(name));
+            }
             let Some(section_index) = obj_symbol.section_index() else {
                 continue;
             };
@@ -471,7 +838,12 @@ impl<'obj, 'data> ObjectIndex<'obj, 'data> {
                 });
             }
         }
-        Self { obj, section_infos }
+        Self {
+            obj,
+            section_infos,
+            is_linked,
+            symbols_by_address,
+        }
     }
 
     /// Adds the symbol or symbols that `rel` refers to into `symbols_out`. If `rel` refers to a
@@ -484,6 +856,10 @@ impl<'obj, 'data> ObjectIndex<'obj, 'data> {
         visited: &mut FxHashSet<SectionIndex>,
         bin_symbols: &FxHashMap<Symbol, u64>,
     ) -> Result<()> {
+        if let Some(symbol) = self.linked_target_symbol(rel) {
+            symbols_out.push(symbol);
+            return Ok(());
+        }
         match self.get_symbol_or_section(rel.target(), bin_symbols)? {
             SymbolOrSection::Symbol(symbol) => {
                 symbols_out.push(symbol);
@@ -502,6 +878,33 @@ impl<'obj, 'data> ObjectIndex<'obj, 'data> {
         Ok(())
     }
 
+    /// Returns the symbol at the address that `rel` refers to if this is a linked binary and `rel`
+    /// is relative to a section. In a linked binary, a section contains many functions, so
+    /// references to local symbols are generally to the section plus an offset.
+    fn linked_target_symbol(&self, rel: &object::Relocation) -> Option<Symbol<'data>> {
+        if !self.is_linked {
+            return None;
+        }
+        let RelocationTarget::Symbol(symbol_index) = rel.target() else {
+            return None;
+        };
+        let symbol = self.obj.symbol_by_index(symbol_index).ok()?;
+        if symbol.kind() != SymbolKind::Section {
+            return None;
+        }
+        let mut address = symbol.address() as i64 + rel.addend();
+        if matches!(
+            rel.kind(),
+            RelocationKind::Relative | RelocationKind::PltRelative
+        ) {
+            // PC-relative addends are relative to the end of the instruction operand.
+            address += i64::from(rel.size() / 8);
+        }
+        self.symbols_by_address
+            .get(&u64::try_from(address).ok()?)
+            .cloned()
+    }
+
     /// Returns either symbol or the section index for a relocation target, giving preference to the
     /// symbol.
     fn get_symbol_or_section(
@@ -539,6 +942,28 @@ impl<'obj, 'data> ObjectIndex<'obj, 'data> {
         Ok(SymbolOrSection::Section(section_index))
     }
 
+    /// Returns the name of a shell if `rel` refers to read-only data that starts with one.
+    fn shell_name_for_relocation(&self, rel: &object::Relocation) -> Option<&'static str> {
+        let RelocationTarget::Symbol(symbol_index) = rel.target() else {
+            return None;
+        };
+        let symbol = self.obj.symbol_by_index(symbol_index).ok()?;
+        let section = self.obj.section_by_index(symbol.section_index()?).ok()?;
+        if !matches!(
+            section.kind(),
+            SectionKind::ReadOnlyData | SectionKind::ReadOnlyString
+        ) {
+            return None;
+        }
+        let mut offset = symbol.address() as i64 + rel.addend();
+        if rel.kind() == RelocationKind::Relative {
+            // PC-relative addends are relative to the end of the instruction operand.
+            offset += i64::from(rel.size() / 8);
+        }
+        let data = section.data().ok()?;
+        shell::shell_name(data.get(usize::try_from(offset).ok()?..)?)
+    }
+
     /// Returns information about the first symbol in the section.
     fn first_symbol(&self, section: &object::Section) -> Option<&SymbolInfo<'data>> {
         self.section_infos
@@ -556,6 +981,12 @@ impl<'symbol, 'input: 'symbol> BinInfo<'input> {
     fn load_symbols(&mut self, obj: &object::File) -> Result<()> {
         for sym in obj.symbols() {
             let symbol = &Symbol::borrowed(sym.name_bytes()?);
+            if sym.is_undefined() {
+                if let Some(name) = symbol.extern_name() {
+                    self.imported_symbols
+                        .insert(Symbol::borrowed(name.as_bytes()).to_heap());
+                }
+            }
             if !symbol.is_look_through() {
                 self.symbol_addresses
                     .insert(symbol.to_heap(), sym.address());
@@ -595,11 +1026,11 @@ impl<'input> BinInfo<'input> {
     /// Runs `callback` for each name in `symbol` or in the name obtained for the debug information
     /// for `symbol`. Also supplies information about the name source and a set of APIs that match
     /// the name.
-    fn names_and_apis_do<'checker>(
-        &mut self,
+    fn names_and_apis_do(
+        &self,
         symbol_and_name: &SymbolAndName,
-        checker: &'checker Checker,
-        mut callback: impl FnMut(Name, NameSource, &'checker FxHashSet<PermissionName>) -> Result<()>,
+        checker: &Checker,
+        mut callback: impl FnMut(Name, NameSource, &FxHashSet<PermissionName>) -> Result<()>,
     ) -> Result<()> {
         // If we've previously observed that this symbol has no APIs associated with it, then skip
         // it.
@@ -607,17 +1038,16 @@ impl<'input> BinInfo<'input> {
             .symbol
             .as_ref()
             .and_then(|symbol| self.symbol_has_no_apis.get(symbol))
-            .cloned()
-            .unwrap_or(false)
+            .is_some_and(|no_apis| no_apis.load(Ordering::Relaxed))
         {
             return Ok(());
         }
         let mut got_apis = false;
         if let Some(debug_name) = symbol_and_name.debug_name.as_ref() {
-            let mut it = NamesIterator::new(NonMangledIterator::new(
+            let mut it = NamesIterator::new(WithoutStdGenericArgs::new(NonMangledIterator::new(
                 &debug_name.namespace.parts,
                 debug_name.name.as_ref(),
-            ));
+            )));
             while let Some((parts, name)) = it
                 .next_name()
                 .with_context(|| format!("Failed to parse debug name `{debug_name}`"))?
@@ -628,12 +1058,12 @@ impl<'input> BinInfo<'input> {
                     (callback)(
                         name.create_name()?,
                         NameSource::DebugName(debug_name.to_heap()),
-                        apis,
+                        &apis,
                     )?;
                 }
             }
         } else if let Some(symbol) = symbol_and_name.symbol.as_ref() {
-            let mut symbol_it = symbol.names()?;
+            let mut symbol_it = symbol.names_without_std_generic_args()?;
             while let Some((parts, name)) = symbol_it.next_name()? {
                 let apis = checker.apis_for_name_iterator(parts);
                 if !apis.is_empty() {
@@ -641,7 +1071,26 @@ impl<'input> BinInfo<'input> {
                     (callback)(
                         name.create_name()?,
                         NameSource::Symbol(symbol.clone()),
-                        apis,
+                        &apis,
+                    )?;
+                }
+            }
+            // Functions that the binary imports from a C library, such as `system`, have no
+            // namespace to match against, so we match on the whole name. This lets an API (e.g.
+            // `ffi`) include them, even when they're called from a C shim that a crate compiles. We
+            // only do this for imports, since a function that's defined in the binary, e.g. a
+            // `#[no_mangle]` function in some crate, might just happen to have the same name.
+            if let Some(name) = symbol.extern_name().filter(|name| {
+                self.imported_symbols
+                    .contains(&Symbol::borrowed(name.as_bytes()))
+            }) {
+                let apis = checker.apis_for_name_iterator(std::iter::once(name));
+                if !apis.is_empty() {
+                    got_apis = true;
+                    (callback)(
+                        crate::names::split_simple(name),
+                        NameSource::Symbol(symbol.clone()),
+                        &apis,
                     )?;
                 }
             }
@@ -651,8 +1100,8 @@ impl<'input> BinInfo<'input> {
                 // The need to call `to_heap` here is just to get past an annoying variance issue.
                 // Fortunately it doesn't seem to affect performance significantly, so probably the
                 // optimiser is able to get rid of the allocation.
-                if let Some(x) = self.symbol_has_no_apis.get_mut(&symbol.to_heap()) {
-                    *x = true;
+                if let Some(no_apis) = self.symbol_has_no_apis.get(&symbol.to_heap()) {
+                    no_apis.store(true, Ordering::Relaxed);
                 }
             }
         }
@@ -684,6 +1133,57 @@ impl<'symbol> Display for NameSource<'symbol> {
     }
 }
 
+/// Calls `callback` for each object file in `filename`, which may be either an archive or a single
+/// object file.
+fn for_each_object_file(
+    filename: &Path,
+    mut callback: impl FnMut(&ObjectFilePath, &[u8]) -> Result<()>,
+) -> Result<()> {
+    match Filetype::from_filename(filename) {
+        Filetype::Archive => {
+            let mut buffer = Vec::new();
+            let mut archive = Archive::new(File::open(filename)?);
+            while let Some(entry_result) = archive.next_entry() {
+                let Ok(mut entry) = entry_result else {
+                    continue;
+                };
+                buffer.clear();
+                entry.read_to_end(&mut buffer)?;
+                let object_file_path = ObjectFilePath::in_archive(filename, &entry)?;
+                (callback)(&object_file_path, &buffer)
+                    .with_context(|| format!("Failed to process {object_file_path}"))?;
+            }
+        }
+        Filetype::Other => {
+            let file_bytes = std::fs::read(filename)
+                .with_context(|| format!("Failed to read `{}`", filename.display()))?;
+            let object_file_path = ObjectFilePath::non_archive(filename);
+            (callback)(&object_file_path, &file_bytes)
+                .with_context(|| format!("Failed to process {object_file_path}"))?;
+        }
+    }
+    Ok(())
+}
+
+/// Checks that the binary produced by the linker is something that we can analyse. Firmware for
+/// embedded targets in particular may not be a conventional executable.
+fn check_bin_supported(obj: &object::File, bin_path: &Path) -> Result<()> {
+    if !obj.is_little_endian() {
+        bail!(
+            "`{}` is big-endian, which isn't yet supported",
+            bin_path.display()
+        );
+    }
+    if obj.section_by_name(".debug_info").is_none() {
+        bail!(
+            "`{}` has no debug info, which is required in order to attribute code to crates. \
+             If you're using a linker script, check that it doesn't discard `.debug_*` sections",
+            bin_path.display()
+        );
+    }
+    Ok(())
+}
+
 /// Loads section `id` from `obj`.
 fn load_section<'data>(
     obj: &object::File<'data>,
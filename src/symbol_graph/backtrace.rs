@@ -0,0 +1,117 @@
+//! Works out how code in a crate reaches the function that uses an API. We follow references
+//! backwards from the function that made the API call until we reach a function that's referenced
+//! from outside the crate. That function is what the rest of the program calls, so is generally
+//! the one that the user needs to look at.
+
+use super::reachability::ReferenceGraph;
+use crate::symbol::Symbol;
+use fxhash::FxHashMap;
+use fxhash::FxHashSet;
+
+/// For each symbol, the symbols that reference it.
+pub(super) struct Callers {
+    callers: FxHashMap<Symbol<'static>, Vec<Symbol<'static>>>,
+}
+
+impl Callers {
+    pub(super) fn new(graph: &ReferenceGraph) -> Self {
+        let mut callers: FxHashMap<Symbol<'static>, Vec<Symbol<'static>>> = FxHashMap::default();
+        for (from, targets) in graph.references() {
+            for to in targets {
+                if to != from {
+                    callers.entry(to.clone()).or_default().push(from.clone());
+                }
+            }
+        }
+        // Sort so that which caller we pick doesn't depend on hash map iteration order.
+        for symbols in callers.values_mut() {
+            symbols.sort();
+            symbols.dedup();
+        }
+        Self { callers }
+    }
+
+    /// Returns the chain of functions through which `from` is called, starting with its immediate
+    /// caller. The chain stops once it reaches a function that is referenced from another crate or
+    /// that has no callers, or when it contains `max_depth` functions.
+    pub(super) fn backtrace(&self, from: &Symbol, max_depth: usize) -> Vec<Symbol<'static>> {
+        let mut chain = Vec::new();
+        let Some(crate_name) = from.crate_name() else {
+            return chain;
+        };
+        let mut visited = FxHashSet::default();
+        let mut current = from.to_heap();
+        while chain.len() < max_depth {
+            let Some(callers) = self.callers.get(&current) else {
+                break;
+            };
+            if callers
+                .iter()
+                .any(|caller| caller.crate_name() != Some(crate_name))
+            {
+                break;
+            }
+            visited.insert(current);
+            let Some(next) = callers.iter().find(|caller| !visited.contains(*caller)) else {
+                break;
+            };
+            chain.push(next.clone());
+            current = next.clone();
+        }
+        chain
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MAIN: &[u8] = b"main";
+    const BIN_MAIN: &[u8] = b"_ZN7crabbin4main17h0000000000000000E";
+    const ENTRY: &[u8] = b"_ZN5crab15entry17h0000000000000000E";
+    const HELPER: &[u8] = b"_ZN5crab16helper17h0000000000000000E";
+    const CONNECT: &[u8] = b"_ZN5crab17connect17h0000000000000000E";
+    const LOOP_A: &[u8] = b"_ZN5crab16loop_a17h0000000000000000E";
+    const LOOP_B: &[u8] = b"_ZN5crab16loop_b17h0000000000000000E";
+
+    fn callers(references: &[(&[u8], &[u8])]) -> Callers {
+        let mut graph = ReferenceGraph::default();
+        for (from, to) in references {
+            graph.add_reference(&Symbol::borrowed(from), &Symbol::borrowed(to));
+        }
+        Callers::new(&graph)
+    }
+
+    fn backtrace(callers: &Callers, from: &[u8], max_depth: usize) -> Vec<String> {
+        callers
+            .backtrace(&Symbol::borrowed(from), max_depth)
+            .iter()
+            .map(|symbol| symbol.to_string())
+            .collect()
+    }
+
+    #[test]
+    fn stops_at_crate_boundary() {
+        let callers = callers(&[
+            (MAIN, BIN_MAIN),
+            (BIN_MAIN, ENTRY),
+            (ENTRY, HELPER),
+            (HELPER, CONNECT),
+        ]);
+        assert_eq!(
+            backtrace(&callers, CONNECT, 10),
+            vec!["crab1::helper", "crab1::entry"]
+        );
+        assert_eq!(backtrace(&callers, CONNECT, 1), vec!["crab1::helper"]);
+        assert!(backtrace(&callers, ENTRY, 10).is_empty());
+    }
+
+    #[test]
+    fn cycles() {
+        let callers = callers(&[(LOOP_A, LOOP_B), (LOOP_B, LOOP_A), (LOOP_B, CONNECT)]);
+        assert_eq!(
+            backtrace(&callers, CONNECT, 10),
+            vec!["crab1::loop_b", "crab1::loop_a"]
+        );
+    }
+}
@@ -375,7 +375,7 @@ impl<'input> SymbolDebugInfoScanner<'input> {
                 self.line = attr.udata_value().map(|v| v as u32);
             }
             gimli::DW_AT_decl_column => {
-                self.column = attr.udata_value().map(|v| v as u32);
+                self.column = column_value(&attr);
             }
             gimli::DW_AT_decl_file => {
                 self.file_index = Some(attr.value());
@@ -430,7 +430,7 @@ impl<'input> SymbolDebugInfoScanner<'input> {
     }
 }
 
-fn path_from_opt_slice(slice: Option<gimli::EndianSlice<gimli::LittleEndian>>) -> &Path {
+fn path_from_opt_slice(slice: Option<gimli::EndianSlice<'_, gimli::LittleEndian>>) -> &Path {
     slice
         .map(|dir| Path::new(OsStr::from_bytes(dir.slice())))
         .unwrap_or_else(|| Path::new(""))
@@ -486,7 +486,7 @@ impl<'input> InlinedFunctionScanner<'input> {
                 self.call_location.line = attr.udata_value().map(|v| v as u32);
             }
             gimli::DW_AT_call_column => {
-                self.call_location.column = attr.udata_value().map(|v| v as u32);
+                self.call_location.column = column_value(&attr);
             }
             gimli::DW_AT_low_pc => {
                 self.low_pc = unit_state
@@ -549,3 +549,11 @@ impl<'input> InlinedFunctionScanner<'input> {
         None
     }
 }
+
+/// Returns the column from a column attribute. DWARF uses 0 to mean that the column is unknown,
+/// which we represent as `None`, so that we don't report a bogus column.
+fn column_value<R: gimli::Reader>(attr: &gimli::Attribute<R>) -> Option<u32> {
+    attr.udata_value()
+        .filter(|column| *column != 0)
+        .map(|column| column as u32)
+}
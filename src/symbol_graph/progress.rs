@@ -0,0 +1,98 @@
+//! A progress bar shown on stderr while we analyse object files when there's no full terminal UI to
+//! show progress instead.
+
+use std::io::Write;
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering;
+use std::sync::Mutex;
+use std::time::Duration;
+use std::time::Instant;
+
+/// The minimum time between redraws. This also means that if analysis completes quickly, we never
+/// draw anything.
+const REDRAW_INTERVAL: Duration = Duration::from_millis(100);
+
+/// The width of the bar in characters, excluding the surrounding text.
+const BAR_WIDTH: usize = 30;
+
+pub(super) struct ProgressBar {
+    total: usize,
+    done: AtomicUsize,
+    /// When we last drew the bar, or if we haven't yet, when we started. `None` if disabled.
+    last_draw: Option<Mutex<Instant>>,
+    /// Whether we've drawn anything that we need to clear when we finish.
+    drawn: AtomicBool,
+}
+
+impl ProgressBar {
+    pub(super) fn new(total: usize, enabled: bool) -> Self {
+        Self {
+            total,
+            done: AtomicUsize::new(0),
+            last_draw: enabled.then(|| Mutex::new(Instant::now())),
+            drawn: AtomicBool::new(false),
+        }
+    }
+
+    /// Records that another object has been analysed. May be called from any thread.
+    pub(super) fn inc(&self) {
+        let done = self.done.fetch_add(1, Ordering::Relaxed) + 1;
+        let Some(last_draw) = &self.last_draw else {
+            return;
+        };
+        // If another thread is drawing, then we skip drawing. It'll have drawn something recent.
+        let Ok(mut last_draw) = last_draw.try_lock() else {
+            return;
+        };
+        if last_draw.elapsed() < REDRAW_INTERVAL {
+            return;
+        }
+        *last_draw = Instant::now();
+        self.drawn.store(true, Ordering::Relaxed);
+        let mut stderr = std::io::stderr().lock();
+        let _ = write!(stderr, "\r{}", line(done, self.total));
+        let _ = stderr.flush();
+    }
+
+    /// Clears the bar, if we drew it.
+    pub(super) fn finish(&self) {
+        if !self.drawn.load(Ordering::Relaxed) {
+            return;
+        }
+        let width = line(self.total, self.total).len();
+        let mut stderr = std::io::stderr().lock();
+        let _ = write!(stderr, "\r{:width$}\r", "");
+        let _ = stderr.flush();
+    }
+}
+
+fn line(done: usize, total: usize) -> String {
+    let filled = (done * BAR_WIDTH).checked_div(total).unwrap_or(BAR_WIDTH);
+    format!(
+        "Analysing objects [{}{}] {done}/{total}",
+        "=".repeat(filled),
+        " ".repeat(BAR_WIDTH - filled)
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::line;
+
+    #[test]
+    fn line_text() {
+        assert_eq!(
+            line(0, 4),
+            "Analysing objects [                              ] 0/4"
+        );
+        assert_eq!(
+            line(2, 4),
+            "Analysing objects [===============               ] 2/4"
+        );
+        assert_eq!(
+            line(4, 4),
+            "Analysing objects [==============================] 4/4"
+        );
+    }
+}
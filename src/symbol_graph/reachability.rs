@@ -0,0 +1,143 @@
+//! Determines which symbols in a binary are reachable from its entry points. This lets us ignore API
+//! usages in code that was linked, but can never run.
+
+use crate::symbol::Symbol;
+use anyhow::Result;
+use fxhash::FxHashMap;
+use fxhash::FxHashSet;
+
+/// Names of sections containing pointers to functions that are run before or after `main`.
+const CONSTRUCTOR_SECTION_PREFIXES: &[&str] = &[
+    ".init_array",
+    ".fini_array",
+    ".ctors",
+    ".dtors",
+    ".preinit_array",
+];
+
+/// A graph of references between symbols.
+#[derive(Default)]
+pub(super) struct ReferenceGraph {
+    roots: Vec<Symbol<'static>>,
+    references: FxHashMap<Symbol<'static>, Vec<Symbol<'static>>>,
+}
+
+impl ReferenceGraph {
+    /// Records that `symbol` is an entry point, so is reachable regardless of whether anything
+    /// references it.
+    pub(super) fn add_root(&mut self, symbol: &Symbol) {
+        self.roots.push(symbol.to_heap());
+    }
+
+    /// Records each symbol exported by the shared library `obj` as an entry point. Exported symbols
+    /// may be called by whatever loads the library, even if nothing in the library references them.
+    /// For a `cdylib` these are the `#[no_mangle]` functions, but for a `dylib` they include mangled
+    /// Rust symbols, which `is_entry_point` wouldn't pick up.
+    pub(super) fn add_exports_as_roots(&mut self, obj: &object::File) -> Result<()> {
+        use object::Object;
+
+        for export in obj.exports()? {
+            self.add_root(&Symbol::borrowed(export.name()));
+        }
+        Ok(())
+    }
+
+    pub(super) fn add_reference(&mut self, from: &Symbol, to: &Symbol) {
+        self.references
+            .entry(from.to_heap())
+            .or_default()
+            .push(to.to_heap());
+    }
+
+    /// Returns each symbol that references other symbols, together with the symbols it references.
+    pub(super) fn references(
+        &self,
+    ) -> impl Iterator<Item = (&Symbol<'static>, &[Symbol<'static>])> {
+        self.references
+            .iter()
+            .map(|(from, targets)| (from, targets.as_slice()))
+    }
+
+    /// Returns all symbols that can be reached by following references from the roots.
+    pub(super) fn reachable(&self) -> FxHashSet<Symbol<'static>> {
+        let mut reachable = FxHashSet::default();
+        let mut pending: Vec<&Symbol<'static>> = self.roots.iter().collect();
+        while let Some(symbol) = pending.pop() {
+            if !reachable.insert(symbol.clone()) {
+                continue;
+            }
+            if let Some(targets) = self.references.get(symbol) {
+                pending.extend(targets.iter());
+            }
+        }
+        reachable
+    }
+}
+
+/// Returns whether a global symbol named `name` should be treated as an entry point. We don't scan
+/// the standard library, so we can't see references from it into our code. Such references are
+/// generally via unmangled names (e.g. `main`, `__rust_alloc` or `#[no_mangle]` functions that may
+/// be called from C or exported from a shared object), so we treat all unmangled global symbols as
+/// entry points.
+pub(super) fn is_entry_point(name: &[u8]) -> bool {
+    !name.is_empty() && !name.starts_with(b"_ZN") && !name.starts_with(b"_R")
+}
+
+/// Returns whether `section_name` is a section containing constructors or destructors. All
+/// symbols referenced from such sections are entry points.
+pub(super) fn is_constructor_section(section_name: &str) -> bool {
+    CONSTRUCTOR_SECTION_PREFIXES
+        .iter()
+        .any(|prefix| section_name.starts_with(prefix))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MAIN: &[u8] = b"main";
+    const CRAB1_ENTRY: &[u8] = b"crab1_entry";
+    const CALL_READ_FILE: &[u8] = b"_ZN5crab114call_read_file17h0000000000000000E";
+    const READ_FILE: &[u8] = b"_ZN5crab19read_file17h0000000000000000E";
+    const DO_NETWORK_STUFF: &[u8] = b"_ZN5crab116do_network_stuff17h0000000000000000E";
+    const ENV_VAR: &[u8] = b"_ZN3std3env3var17h0000000000000000E";
+    const TCP_BIND: &[u8] = b"_ZN3std3net3tcp11TcpListener4bind17h0000000000000000E";
+
+    #[test]
+    fn unreachable_code_is_excluded() {
+        let mut graph = ReferenceGraph::default();
+        for name in [
+            MAIN,
+            CRAB1_ENTRY,
+            CALL_READ_FILE,
+            READ_FILE,
+            DO_NETWORK_STUFF,
+        ] {
+            if is_entry_point(name) {
+                graph.add_root(&Symbol::borrowed(name));
+            }
+        }
+        let mut reference = |from, to| {
+            graph.add_reference(&Symbol::borrowed(from), &Symbol::borrowed(to));
+        };
+        reference(MAIN, CALL_READ_FILE);
+        reference(CALL_READ_FILE, READ_FILE);
+        reference(CRAB1_ENTRY, ENV_VAR);
+        reference(DO_NETWORK_STUFF, TCP_BIND);
+
+        let reachable = graph.reachable();
+        for name in [MAIN, CRAB1_ENTRY, CALL_READ_FILE, READ_FILE, ENV_VAR] {
+            assert!(reachable.contains(&Symbol::borrowed(name)));
+        }
+        for name in [DO_NETWORK_STUFF, TCP_BIND] {
+            assert!(!reachable.contains(&Symbol::borrowed(name)));
+        }
+    }
+
+    #[test]
+    fn constructor_sections() {
+        assert!(is_constructor_section(".init_array"));
+        assert!(is_constructor_section(".init_array.00099"));
+        assert!(!is_constructor_section(".text.main"));
+    }
+}
@@ -0,0 +1,78 @@
+//! Heuristic detection of code that runs a shell. We look for functions that reference both
+//! `std::process::Command::new` and a string constant that is the name of a shell. This doesn't
+//! catch everything, e.g. if the name of the shell is computed at runtime, but it does catch the
+//! common `Command::new("sh").arg("-c")` pattern.
+
+use crate::symbol::Symbol;
+use anyhow::Result;
+
+/// Names of programs that, when spawned, will interpret their arguments as a script.
+const SHELLS: &[&str] = &[
+    "sh",
+    "bash",
+    "dash",
+    "zsh",
+    "cmd",
+    "cmd.exe",
+    "powershell",
+    "/bin/sh",
+    "/bin/bash",
+];
+
+const SPAWN_PREFIX: &[&str] = &["std", "process", "Command", "new"];
+
+/// Returns the name of the shell if `data` starts with the name of a shell. String constants may be
+/// stored adjacent to other data, so we accept any byte after the name that couldn't be part of a
+/// program name.
+pub(super) fn shell_name(data: &[u8]) -> Option<&'static str> {
+    SHELLS.iter().copied().find(|shell| {
+        let Some(rest) = data.strip_prefix(shell.as_bytes()) else {
+            return false;
+        };
+        rest.first()
+            .map(|b| !(b.is_ascii_alphanumeric() || b"_-./".contains(b)))
+            .unwrap_or(true)
+    })
+}
+
+/// Returns whether `symbol` is `std::process::Command::new`.
+pub(super) fn is_process_spawn(symbol: &Symbol) -> Result<bool> {
+    let mut it = symbol.names()?;
+    while let Some((parts, _)) = it.next_name()? {
+        if parts
+            .take(SPAWN_PREFIX.len())
+            .eq(SPAWN_PREFIX.iter().copied())
+        {
+            return Ok(true);
+        }
+    }
+    Ok(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shell_names() {
+        assert_eq!(shell_name(b"sh"), Some("sh"));
+        assert_eq!(shell_name(b"sh\0"), Some("sh"));
+        assert_eq!(shell_name(b"bash-c"), None);
+        assert_eq!(shell_name(b"cmd.exe"), Some("cmd.exe"));
+        assert_eq!(shell_name(b"shared"), None);
+        assert_eq!(shell_name(b"/bin/sh\xff"), Some("/bin/sh"));
+        assert_eq!(shell_name(b"echo"), None);
+    }
+
+    #[test]
+    fn process_spawn() {
+        assert!(is_process_spawn(&Symbol::borrowed(
+            b"_ZN3std7process7Command3new17h0000000000000000E"
+        ))
+        .unwrap());
+        assert!(!is_process_spawn(&Symbol::borrowed(
+            b"_ZN3std7process7Command3arg17h0000000000000000E"
+        ))
+        .unwrap());
+    }
+}
@@ -0,0 +1,55 @@
+//! Attributes the code and data in the objects that get linked to the packages that they were
+//! compiled from. Only done if requested with --crate-sizes, since it means parsing every object an
+//! extra time.
+
+use super::for_each_object_file;
+use crate::crate_index::CrateIndex;
+use crate::crate_index::PackageId;
+use anyhow::Result;
+use fxhash::FxHashMap;
+use object::Object;
+use object::ObjectSection;
+use object::SectionKind;
+use std::path::PathBuf;
+
+/// Returns the total size in bytes of the code and data sections in `paths`, which are objects or
+/// rlibs, by package. Paths for which we can't determine a package are skipped. Sizes are from
+/// before linking, so include code that the linker might discard.
+pub(crate) fn package_sizes(
+    paths: &[PathBuf],
+    crate_index: &CrateIndex,
+) -> Result<FxHashMap<PackageId, u64>> {
+    let mut sizes = FxHashMap::default();
+    for path in paths {
+        let Some(pkg_id) = crate_index.package_id_for_output(path) else {
+            continue;
+        };
+        let mut size = 0;
+        for_each_object_file(path, |_, file_bytes| {
+            size += section_sizes(file_bytes);
+            Ok(())
+        })?;
+        *sizes.entry(pkg_id.clone()).or_default() += size;
+    }
+    Ok(sizes)
+}
+
+/// Returns the combined size of the code and data sections in an object file. Archive members that
+/// aren't objects, such as the metadata in an rlib on some platforms, have a size of 0.
+fn section_sizes(file_bytes: &[u8]) -> u64 {
+    let Ok(obj) = object::File::parse(file_bytes) else {
+        return 0;
+    };
+    obj.sections()
+        .filter(|section| {
+            matches!(
+                section.kind(),
+                SectionKind::Text
+                    | SectionKind::Data
+                    | SectionKind::ReadOnlyData
+                    | SectionKind::ReadOnlyString
+            )
+        })
+        .map(|section| section.size())
+        .sum()
+}
@@ -0,0 +1,201 @@
+//! Prints the dependency tree, annotating each package with what it's permitted to do and any
+//! problems that were found with it.
+
+use crate::config::Config;
+use crate::crate_index::CrateIndex;
+use crate::crate_index::PackageId;
+use crate::summary::package_permissions;
+use clap::Parser;
+use fxhash::FxHashMap;
+use fxhash::FxHashSet;
+use std::collections::BTreeSet;
+use std::fmt::Write;
+
+#[derive(Parser, Debug, Clone, Default)]
+pub(crate) struct TreeOptions {
+    /// Maximum depth of the tree to print. Workspace members are at depth 0.
+    #[clap(long)]
+    depth: Option<usize>,
+
+    /// Only print packages that had problems and the packages through which they're depended on.
+    #[clap(long)]
+    problems_only: bool,
+}
+
+pub(crate) struct Tree<'a> {
+    crate_index: &'a CrateIndex,
+    config: &'a Config,
+    package_problems: &'a FxHashMap<PackageId, BTreeSet<String>>,
+    options: &'a TreeOptions,
+}
+
+/// State used while producing the tree text.
+struct TreeBuilder {
+    out: String,
+    /// Packages whose dependencies we've already output.
+    expanded: FxHashSet<PackageId>,
+    /// If set, only packages in this set will be output.
+    filter: Option<FxHashSet<PackageId>>,
+}
+
+const INDENT: &str = "    ";
+
+impl<'a> Tree<'a> {
+    pub(crate) fn new(
+        crate_index: &'a CrateIndex,
+        config: &'a Config,
+        package_problems: &'a FxHashMap<PackageId, BTreeSet<String>>,
+        options: &'a TreeOptions,
+    ) -> Self {
+        Self {
+            crate_index,
+            config,
+            package_problems,
+            options,
+        }
+    }
+
+    pub(crate) fn text(&self) -> String {
+        let mut builder = TreeBuilder {
+            out: String::new(),
+            expanded: FxHashSet::default(),
+            filter: self
+                .options
+                .problems_only
+                .then(|| self.packages_leading_to_problems()),
+        };
+        for root in &self.crate_index.roots {
+            self.add_package(root, 0, &mut builder);
+        }
+        builder.out
+    }
+
+    fn add_package(&self, pkg_id: &PackageId, depth: usize, builder: &mut TreeBuilder) {
+        if let Some(filter) = &builder.filter {
+            if !filter.contains(pkg_id) {
+                return;
+            }
+        }
+        let Some(info) = self.crate_index.package_info(pkg_id) else {
+            return;
+        };
+        let indent = INDENT.repeat(depth);
+        let _ = write!(
+            builder.out,
+            "{indent}{} v{}",
+            pkg_id.name(),
+            pkg_id.version()
+        );
+        let mut tags = Vec::new();
+        if info.is_proc_macro {
+            tags.push("proc-macro".to_owned());
+        }
+        if info.has_build_script {
+            tags.push("build script".to_owned());
+        }
+        let permissions = package_permissions(pkg_id, self.config);
+        if !permissions.is_empty() {
+            tags.push(format!("allowed: {}", permissions.join(", ")));
+        }
+        if !tags.is_empty() {
+            let _ = write!(builder.out, " [{}]", tags.join("; "));
+        }
+        let first_visit = builder.expanded.insert(pkg_id.clone());
+        if !first_visit && !info.dependencies.is_empty() {
+            // Like `cargo tree`, we mark packages whose dependencies have already been shown.
+            builder.out.push_str(" (*)");
+        }
+        builder.out.push('\n');
+        if !first_visit {
+            return;
+        }
+        for problem in self.package_problems.get(pkg_id).into_iter().flatten() {
+            let _ = writeln!(builder.out, "{indent}  ! {problem}");
+        }
+        if self.options.depth.is_some_and(|max| depth >= max) {
+            return;
+        }
+        for dep in &info.dependencies {
+            self.add_package(dep, depth + 1, builder);
+        }
+    }
+
+    /// Returns the packages that either had problems, or that transitively depend on a package that
+    /// had problems.
+    fn packages_leading_to_problems(&self) -> FxHashSet<PackageId> {
+        let mut dependents: FxHashMap<&PackageId, Vec<&PackageId>> = FxHashMap::default();
+        for (pkg_id, info) in &self.crate_index.package_infos {
+            for dep in &info.dependencies {
+                dependents.entry(dep).or_default().push(pkg_id);
+            }
+        }
+        let mut result = FxHashSet::default();
+        let mut pending: Vec<&PackageId> = self.package_problems.keys().collect();
+        while let Some(pkg_id) = pending.pop() {
+            if result.insert(pkg_id.clone()) {
+                pending.extend(dependents.get(pkg_id).into_iter().flatten());
+            }
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    fn tree_text(options: &TreeOptions, problems: &[(&str, &str)]) -> String {
+        let crate_root = PathBuf::from(std::env::var_os("CARGO_MANIFEST_DIR").unwrap());
+        let test_crates_dir = crate_root.join("test_crates");
+        let crate_index =
+            CrateIndex::new(&test_crates_dir, false, false, &Default::default()).unwrap();
+        let config =
+            crate::config::parse_file(&test_crates_dir.join("cackle.toml"), &crate_index).unwrap();
+        let mut package_problems: FxHashMap<PackageId, BTreeSet<String>> = FxHashMap::default();
+        for (pkg_name, problem) in problems {
+            let pkg_id = crate_index
+                .newest_package_id_with_name(&(*pkg_name).into())
+                .unwrap();
+            package_problems
+                .entry(pkg_id.clone())
+                .or_default()
+                .insert(problem.to_string());
+        }
+        Tree::new(&crate_index, &config, &package_problems, options).text()
+    }
+
+    #[test]
+    fn problems_only() {
+        let options = TreeOptions {
+            depth: None,
+            problems_only: true,
+        };
+        assert_eq!(
+            tree_text(&options, &[("crab6", "`crab6` uses API `fs`")]),
+            indoc::indoc! {"
+                crab-bin v0.1.0 [build script; allowed: unsafe, env, fs, res1, secret, fs[build]]
+                    crab6 v0.1.0
+                      ! `crab6` uses API `fs`
+                    crab8 v0.1.0 [build script; allowed: fs]
+                        crab6 v0.1.0
+                    res1 v0.1.0
+                        crab6 v0.1.0
+                crab6 v0.1.0
+                crab8 v0.1.0 [build script; allowed: fs] (*)
+                res1 v0.1.0 (*)
+            "}
+        );
+    }
+
+    #[test]
+    fn depth() {
+        let options = TreeOptions {
+            depth: Some(0),
+            problems_only: false,
+        };
+        let text = tree_text(&options, &[]);
+        assert!(text.lines().all(|line| !line.starts_with(' ')));
+        assert!(text.contains("pmacro1 v0.1.0 [proc-macro; allowed: proc_macro, env]\n"));
+    }
+}
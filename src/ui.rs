@@ -9,6 +9,7 @@ use crate::Command;
 use anyhow::Result;
 use clap::Parser;
 use clap::ValueEnum;
+use is_terminal::IsTerminal;
 use log::info;
 use std::path::Path;
 use std::sync::mpsc::Receiver;
@@ -18,15 +19,31 @@ use std::thread::JoinHandle;
 
 #[cfg(feature = "ui")]
 mod basic_term;
+pub(crate) mod collecting;
+#[cfg(feature = "ui")]
+mod diff;
 #[cfg(feature = "ui")]
 mod full_term;
+pub(crate) mod json;
 mod null_ui;
+mod sarif;
+mod summary;
 
 #[derive(Parser, Debug, Clone)]
 pub(crate) struct UiArgs {
     /// What kind of user interface to use.
     #[clap(long, default_value = "full")]
     ui: Kind,
+
+    /// Once the check finishes, keep watching cackle.toml and the sources of the workspace's
+    /// packages, checking again whenever they change. Quit from the user interface to stop.
+    #[clap(long)]
+    pub(crate) watch: bool,
+
+    /// Whether to automatically accept the edit for problems that have only one. Defaults to
+    /// whatever was last chosen with the `a` key in this workspace.
+    #[clap(long, value_name = "BOOL")]
+    auto_accept: Option<bool>,
 }
 
 #[derive(ValueEnum, Debug, Clone, Copy)]
@@ -38,7 +55,29 @@ enum Kind {
     Full,
 }
 
-trait UserInterface: Send {
+/// How problems should be reported by the non-interactive user interface.
+#[derive(ValueEnum, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub(crate) enum OutputFormat {
+    /// Human-readable output.
+    #[default]
+    Human,
+    /// GitHub Actions workflow commands, which show up as annotations on pull requests.
+    GithubActions,
+    /// A single JSON document listing all problems, printed once the check finishes.
+    Json,
+    /// A SARIF 2.1.0 log, as accepted by GitHub code scanning, printed once the check finishes.
+    Sarif,
+}
+
+impl OutputFormat {
+    /// Returns whether problems are collected and printed as a single document once the check
+    /// finishes, in which case nothing else should be written to stdout.
+    pub(crate) fn is_document(self) -> bool {
+        matches!(self, OutputFormat::Json | OutputFormat::Sarif)
+    }
+}
+
+pub(crate) trait UserInterface: Send {
     fn run(
         &mut self,
         problem_store: ProblemStoreRef,
@@ -46,18 +85,24 @@ trait UserInterface: Send {
     ) -> Result<()>;
 }
 
-pub(crate) fn start_ui(
+/// Creates the user interface selected by `args`.
+pub(crate) fn create_ui(
     args: &Arc<Args>,
+    root_path: &Path,
     config_path: &Path,
-    problem_store: ProblemStoreRef,
     crate_index: Arc<CrateIndex>,
-    event_receiver: Receiver<AppEvent>,
     abort_sender: Sender<()>,
-) -> Result<JoinHandle<Result<()>>> {
-    let mut ui: Box<dyn UserInterface> = match args.ui_kind() {
+) -> Result<Box<dyn UserInterface>> {
+    Ok(match args.ui_kind() {
         Kind::None => {
             info!("Starting null UI");
-            Box::new(null_ui::NullUi::new(args, abort_sender))
+            Box::new(null_ui::NullUi::new(
+                args,
+                config_path.to_owned(),
+                crate::history::history_path(root_path),
+                crate_index,
+                abort_sender,
+            ))
         }
         #[cfg(feature = "ui")]
         Kind::Basic => {
@@ -67,21 +112,72 @@ pub(crate) fn start_ui(
         #[cfg(feature = "ui")]
         Kind::Full => {
             info!("Starting full terminal UI");
+            let auto_accept = match &args.command {
+                Command::Ui(ui_args) => ui_args.auto_accept,
+                _ => None,
+            };
             Box::new(full_term::FullTermUi::new(
                 config_path.to_owned(),
+                args.config_overrides().to_owned(),
+                root_path,
+                auto_accept,
                 crate_index,
                 abort_sender,
+                args.max_symbol_length,
             )?)
         }
-    };
+    })
+}
+
+/// Runs `ui` on a separate thread.
+pub(crate) fn start_ui(
+    mut ui: Box<dyn UserInterface>,
+    problem_store: ProblemStoreRef,
+    event_receiver: Receiver<AppEvent>,
+) -> Result<JoinHandle<Result<()>>> {
     Ok(std::thread::Builder::new()
         .name("UI".to_owned())
         .spawn(move || ui.run(problem_store, event_receiver))?)
 }
 
+/// What to do with the output of the cargo build that we run.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum CargoOutput {
+    /// Let cargo write directly to our stdout and stderr.
+    Inherit,
+    /// Collect the output and only show it if the build fails.
+    Capture,
+    /// Forward cargo's stderr to our stderr a line at a time as it's produced.
+    Stream,
+}
+
 impl Args {
-    pub(crate) fn should_capture_cargo_output(&self) -> bool {
-        !matches!(self.ui_kind(), Kind::None)
+    pub(crate) fn cargo_output(&self) -> CargoOutput {
+        if !matches!(self.ui_kind(), Kind::None) || self.quiet {
+            CargoOutput::Capture
+        } else if self.progress {
+            CargoOutput::Stream
+        } else {
+            CargoOutput::Inherit
+        }
+    }
+
+    /// Returns whether to draw progress bars on stderr during slow steps, such as analysing object
+    /// files. The full terminal UI owns the terminal, so isn't compatible with them.
+    pub(crate) fn show_progress_bars(&self) -> bool {
+        #[cfg(feature = "ui")]
+        if matches!(self.ui_kind(), Kind::Full) {
+            return false;
+        }
+        !self.quiet && std::io::stderr().is_terminal()
+    }
+
+    /// Returns whether `--fix` or `--diff-config` was specified.
+    pub(crate) fn fix_enabled(&self) -> bool {
+        #[cfg(feature = "ui")]
+        return self.fix || self.diff_config;
+        #[cfg(not(feature = "ui"))]
+        false
     }
 
     fn ui_kind(&self) -> Kind {
@@ -91,6 +187,13 @@ impl Args {
             Command::Ui(ui_args) => ui_args.ui,
             Command::Summary(..) => Kind::None,
             Command::Cargo(..) => Kind::None,
+            Command::MatchPath(..) => Kind::None,
+            Command::Tree(..) => Kind::None,
+            #[cfg(feature = "ui")]
+            Command::MigrateConfig => Kind::None,
+            Command::ConfigCheck => Kind::None,
+            Command::Init(..) => Kind::None,
+            Command::Inspect(..) => Kind::None,
         }
     }
 }
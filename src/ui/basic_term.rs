@@ -44,6 +44,18 @@ impl super::UserInterface for BasicTermUi {
             match event {
                 AppEvent::Shutdown => return Ok(()),
                 AppEvent::ProblemsAdded => {}
+                AppEvent::CheckFailed => continue,
+                AppEvent::WaitingForChanges(error) => {
+                    if let Some(error) = error {
+                        println!("{error}");
+                    }
+                    println!("Waiting for changes...");
+                    continue;
+                }
+                AppEvent::Rescan => {
+                    println!("Change detected, checking again");
+                    continue;
+                }
             }
             loop {
                 let pstore_lock = problem_store.lock();
@@ -105,9 +117,10 @@ impl BasicTermUi {
         let sandbox_kind = sandbox::available_kind();
         if sandbox_kind == SandboxKind::Disabled {
             println!(indoc! {r#"
-                bwrap (bubblewrap) doesn't seem to be installed, so sandboxing will be disabled.
-                If you'd like to sandbox execution of build scripts, press control-c, install
-                bubble wrap, then try again. On system with apt, you can run:
+                bwrap (bubblewrap) doesn't seem to be installed and the kernel doesn't support
+                Landlock, so sandboxing will be disabled. If you'd like to sandbox execution of
+                build scripts, press control-c, install bubble wrap, then try again. On system
+                with apt, you can run:
                 sudo apt install bubblewrap
             "#});
         }
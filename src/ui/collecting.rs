@@ -0,0 +1,76 @@
+//! A user-interface that doesn't show anything, but instead records the problems found, so that
+//! they can be returned to a program that's using us as a library.
+
+use crate::events::AppEvent;
+use crate::problem::Problem;
+use crate::problem::Severity;
+use crate::problem_store::ProblemStoreRef;
+use anyhow::Result;
+use std::sync::mpsc::Receiver;
+use std::sync::mpsc::Sender;
+use std::sync::Arc;
+use std::sync::Mutex;
+
+pub(crate) struct CollectingUi {
+    problems: Arc<Mutex<Vec<(Problem, Severity)>>>,
+    abort_sender: Sender<()>,
+}
+
+impl CollectingUi {
+    pub(crate) fn new(
+        problems: Arc<Mutex<Vec<(Problem, Severity)>>>,
+        abort_sender: Sender<()>,
+    ) -> Self {
+        Self {
+            problems,
+            abort_sender,
+        }
+    }
+}
+
+impl super::UserInterface for CollectingUi {
+    fn run(
+        &mut self,
+        problem_store: ProblemStoreRef,
+        event_receiver: Receiver<AppEvent>,
+    ) -> Result<()> {
+        while let Ok(event) = event_receiver.recv() {
+            match event {
+                AppEvent::Shutdown => break,
+                AppEvent::WaitingForChanges(..) | AppEvent::Rescan | AppEvent::CheckFailed => {}
+                AppEvent::ProblemsAdded => {
+                    let mut pstore = problem_store.lock();
+                    pstore.group_by_crate();
+                    let mut has_errors = false;
+                    {
+                        let mut problems = self.problems.lock().unwrap();
+                        for (_, problem) in pstore.deduplicated_into_iter() {
+                            let severity = problem.severity();
+                            has_errors |= severity == Severity::Error;
+                            problems.push((problem.clone(), severity));
+                        }
+                    }
+                    // As with a non-interactive check, we stop at the first errors, since we can't
+                    // fix them. Warnings are just recorded.
+                    if has_errors {
+                        let _ = self.abort_sender.send(());
+                        pstore.abort();
+                    } else {
+                        loop {
+                            let maybe_index = pstore
+                                .iterate_with_duplicates()
+                                .next()
+                                .map(|(index, _)| index);
+                            if let Some(index) = maybe_index {
+                                pstore.resolve(index);
+                            } else {
+                                break;
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,251 @@
+use ratatui::style::Color;
+use ratatui::style::Style;
+use ratatui::text::Line;
+use ratatui::text::Span;
+use std::collections::VecDeque;
+
+/// A contiguous group of changed lines together with surrounding context.
+struct Hunk<'a> {
+    /// The 1-based line number in the original of the first line of the hunk.
+    original_start: usize,
+    /// The 1-based line number in the updated text of the first line of the hunk.
+    updated_start: usize,
+    lines: Vec<diff::Result<&'a str>>,
+}
+
+/// Splits the diff from `original` to `updated` into hunks. Each hunk shows common context from
+/// the start to the end of the current section.
+fn hunks<'a>(original: &'a str, updated: &'a str) -> Vec<Hunk<'a>> {
+    fn is_section_start(line: &str) -> bool {
+        line.starts_with('[')
+    }
+
+    let mut hunks: Vec<Hunk> = Vec::new();
+    // Common lines that we may need as context, together with their line numbers in the original
+    // and updated text.
+    let mut common = VecDeque::new();
+    let mut after_context = false;
+    let mut original_line = 1;
+    let mut updated_line = 1;
+    for diff in diff::lines(original, updated) {
+        match diff {
+            diff::Result::Both(s, _) => {
+                if after_context && is_section_start(s) {
+                    after_context = false;
+                }
+                if after_context {
+                    if let Some(hunk) = hunks.last_mut() {
+                        hunk.lines.push(diff);
+                    }
+                } else {
+                    if is_section_start(s) {
+                        common.clear();
+                    }
+                    common.push_back((original_line, updated_line, s));
+                }
+                original_line += 1;
+                updated_line += 1;
+            }
+            diff::Result::Left(_) | diff::Result::Right(_) => {
+                if !after_context {
+                    let (original_start, updated_start) = common
+                        .front()
+                        .map(|(o, u, _)| (*o, *u))
+                        .unwrap_or((original_line, updated_line));
+                    hunks.push(Hunk {
+                        original_start,
+                        updated_start,
+                        lines: Vec::new(),
+                    });
+                    after_context = true;
+                }
+                if matches!(diff, diff::Result::Left(_)) {
+                    original_line += 1;
+                } else {
+                    updated_line += 1;
+                }
+                if let Some(hunk) = hunks.last_mut() {
+                    for (_, _, line) in common.drain(..) {
+                        hunk.lines.push(diff::Result::Both(line, line));
+                    }
+                    hunk.lines.push(diff);
+                }
+            }
+        }
+    }
+    hunks
+}
+
+/// Builds the styled lines of a diff from `original` to `updated`. Shows common context from the
+/// start to the end of the current section.
+pub(super) fn diff_lines(original: &str, updated: &str) -> Vec<Line<'static>> {
+    let mut lines = Vec::new();
+    for hunk in hunks(original, updated) {
+        for diff in hunk.lines {
+            lines.push(match diff {
+                diff::Result::Both(s, _) => Line::from(format!(" {s}")),
+                diff::Result::Left(s) => Line::from(vec![Span::styled(
+                    format!("-{s}"),
+                    Style::default().fg(Color::Red),
+                )]),
+                diff::Result::Right(s) => Line::from(vec![Span::styled(
+                    format!("+{s}"),
+                    Style::default().fg(Color::Green),
+                )]),
+            });
+        }
+    }
+    lines
+}
+
+/// Returns a unified diff from `original` to `updated`, both of which are versions of the file
+/// `path`.
+pub(super) fn unified_diff(path: &str, original: &str, updated: &str) -> String {
+    let mut out = String::new();
+    let hunks = hunks(original, updated);
+    if hunks.is_empty() {
+        return out;
+    }
+    out.push_str(&format!("--- {path}\n+++ {path}\n"));
+    for hunk in hunks {
+        let original_len = hunk
+            .lines
+            .iter()
+            .filter(|diff| !matches!(diff, diff::Result::Right(_)))
+            .count();
+        let updated_len = hunk
+            .lines
+            .iter()
+            .filter(|diff| !matches!(diff, diff::Result::Left(_)))
+            .count();
+        out.push_str(&format!(
+            "@@ -{},{original_len} +{},{updated_len} @@\n",
+            hunk.original_start, hunk.updated_start
+        ));
+        for diff in hunk.lines {
+            let (prefix, line) = match diff {
+                diff::Result::Both(s, _) => (' ', s),
+                diff::Result::Left(s) => ('-', s),
+                diff::Result::Right(s) => ('+', s),
+            };
+            out.push(prefix);
+            out.push_str(line);
+            out.push('\n');
+        }
+    }
+    out
+}
+
+#[test]
+fn test_diff_lines() {
+    fn line_to_string(line: &Line) -> String {
+        line.spans
+            .iter()
+            .map(|span| span.content.as_ref())
+            .collect::<Vec<_>>()
+            .join("")
+    }
+    let lines = diff_lines(
+        indoc::indoc! { r#"
+            a = 1
+            [section1]
+            b = 2
+            x = [
+                "x1",
+                "x2",
+                "x3",
+            ]
+            [section2]
+            c = 3
+            d = 4
+            e = 5
+            f = 6
+            g = 7
+            h = 8
+        "# },
+        indoc::indoc! { r#"
+            a = 1
+            [section1]
+            b = 2
+            x = [
+                "x1",
+                "x2",
+                "x3",
+            ]
+            [section2]
+            c = 3
+            d = 4
+            e = 5
+            f = 6
+            g2 = 7.5
+            h = 8
+        "# },
+    );
+    let lines: Vec<_> = lines.iter().map(line_to_string).collect();
+    let expected = vec![
+        " [section2]",
+        " c = 3",
+        " d = 4",
+        " e = 5",
+        " f = 6",
+        "-g = 7",
+        "+g2 = 7.5",
+        " h = 8",
+        " ",
+    ];
+    assert_eq!(lines, expected);
+}
+
+#[test]
+fn test_unified_diff() {
+    let original = indoc::indoc! { r#"
+        [common]
+        version = 1
+
+        [pkg.crab1]
+        allow_apis = [
+            "fs",
+        ]
+
+        [pkg.crab2]
+        allow_unsafe = true
+    "# };
+    let updated = indoc::indoc! { r#"
+        [common]
+        version = 1
+
+        [pkg.crab1]
+        allow_apis = [
+            "fs",
+            "net",
+        ]
+
+        [pkg.crab2]
+        allow_unsafe = true
+
+        [pkg.crab3]
+        allow_unsafe = true
+    "# };
+    assert_eq!(
+        unified_diff("cackle.toml", original, updated),
+        indoc::indoc! { r#"
+            --- cackle.toml
+            +++ cackle.toml
+            @@ -4,5 +4,6 @@
+             [pkg.crab1]
+             allow_apis = [
+                 "fs",
+            +    "net",
+             ]
+             
+            @@ -9,3 +10,6 @@
+             [pkg.crab2]
+            +allow_unsafe = true
+            +
+            +[pkg.crab3]
+             allow_unsafe = true
+             
+        "# }
+    );
+    assert_eq!(unified_diff("cackle.toml", original, original), "");
+}
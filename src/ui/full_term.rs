@@ -2,10 +2,15 @@
 
 use crate::crate_index::CrateIndex;
 use crate::events::AppEvent;
+use crate::location::SourceLocation;
 use crate::problem_store::ProblemStoreRef;
+use anyhow::anyhow;
+use anyhow::bail;
+use anyhow::Context;
 use anyhow::Result;
 use crossterm::event::Event;
 use crossterm::event::KeyCode;
+use preferences::Preferences;
 use ratatui::backend::CrosstermBackend;
 use ratatui::layout::Constraint;
 use ratatui::layout::Direction;
@@ -26,6 +31,7 @@ use ratatui::widgets::Wrap;
 use ratatui::Frame;
 use ratatui::Terminal;
 use std::io::Stdout;
+use std::path::Path;
 use std::path::PathBuf;
 use std::sync::mpsc::Receiver;
 use std::sync::mpsc::Sender;
@@ -33,20 +39,30 @@ use std::sync::mpsc::TryRecvError;
 use std::sync::Arc;
 use std::time::Duration;
 
+mod preferences;
 mod problems_ui;
 
 pub(crate) struct FullTermUi {
     config_path: PathBuf,
+    config_overrides: Vec<PathBuf>,
+    preferences_path: PathBuf,
+    /// Overrides the remembered preference for auto-accepting single edits.
+    auto_accept: Option<bool>,
     terminal: Terminal<CrosstermBackend<Stdout>>,
     abort_sender: Sender<()>,
     crate_index: Arc<CrateIndex>,
+    max_symbol_length: usize,
 }
 
 impl FullTermUi {
     pub(crate) fn new(
         config_path: PathBuf,
+        config_overrides: Vec<PathBuf>,
+        root_path: &Path,
+        auto_accept: Option<bool>,
         crate_index: Arc<CrateIndex>,
         abort_sender: Sender<()>,
+        max_symbol_length: usize,
     ) -> Result<Self> {
         crossterm::terminal::enable_raw_mode()?;
         let mut stdout = std::io::stdout();
@@ -55,13 +71,69 @@ impl FullTermUi {
         let terminal = ratatui::Terminal::new(backend)?;
         Ok(Self {
             config_path,
+            config_overrides,
+            preferences_path: preferences::preferences_path(root_path),
+            auto_accept,
             terminal,
             abort_sender,
             crate_index,
+            max_symbol_length,
         })
     }
 }
 
+impl FullTermUi {
+    /// Opens `location` in the user's editor. We leave the alternate screen while the editor runs,
+    /// then restore it once the editor exits.
+    fn open_in_editor(&mut self, location: &SourceLocation) -> Result<()> {
+        if location.is_unknown() {
+            bail!("The source location of this usage is unknown");
+        }
+        let editor = std::env::var("EDITOR").unwrap_or_default();
+        // EDITOR may include arguments, e.g. `code --wait`.
+        let mut editor_parts = editor.split_whitespace();
+        let Some(program) = editor_parts.next() else {
+            bail!("Set the EDITOR environment variable to open source files");
+        };
+        let mut command = std::process::Command::new(program);
+        command
+            .args(editor_parts)
+            .arg(format!("+{}", location.line()))
+            .arg(location.filename());
+
+        self.suspend()?;
+        let status = command.status();
+        // Resume regardless of whether the editor succeeded, so that the UI remains usable.
+        self.resume()?;
+        let status = status.with_context(|| format!("Failed to run editor `{program}`"))?;
+        if !status.success() {
+            bail!("Editor `{program}` exited with {status}");
+        }
+        Ok(())
+    }
+
+    fn suspend(&mut self) -> Result<()> {
+        crossterm::terminal::disable_raw_mode()?;
+        crossterm::execute!(
+            self.terminal.backend_mut(),
+            crossterm::terminal::LeaveAlternateScreen
+        )?;
+        Ok(())
+    }
+
+    fn resume(&mut self) -> Result<()> {
+        crossterm::terminal::enable_raw_mode()?;
+        crossterm::execute!(
+            self.terminal.backend_mut(),
+            crossterm::terminal::EnterAlternateScreen
+        )?;
+        // Whatever the editor left on the screen isn't what ratatui thinks is there, so force a full
+        // redraw.
+        self.terminal.clear()?;
+        Ok(())
+    }
+}
+
 impl super::UserInterface for FullTermUi {
     fn run(
         &mut self,
@@ -72,7 +144,15 @@ impl super::UserInterface for FullTermUi {
             problem_store.clone(),
             self.crate_index.clone(),
             self.config_path.clone(),
+            self.config_overrides.clone(),
+            self.max_symbol_length,
         );
+        let mut preferences = Preferences::load(&self.preferences_path);
+        match self.auto_accept {
+            Some(enabled) => screen.set_accept_single_enabled(enabled),
+            None if preferences.accept_single_enabled => screen.restore_accept_single_enabled(),
+            None => {}
+        }
         let mut needs_redraw = true;
         let mut error = None;
         loop {
@@ -100,6 +180,20 @@ impl super::UserInterface for FullTermUi {
                         error = Some(e);
                     }
                 }
+                Ok(AppEvent::WaitingForChanges(check_error)) => {
+                    needs_redraw = true;
+                    screen.set_waiting_for_changes(true);
+                    if let Some(check_error) = check_error {
+                        error = Some(anyhow!(check_error));
+                    }
+                }
+                Ok(AppEvent::Rescan) => {
+                    needs_redraw = true;
+                    screen.set_waiting_for_changes(false);
+                    error = None;
+                }
+                // The error gets printed once we've shut down and left the alternate screen.
+                Ok(AppEvent::CheckFailed) => {}
                 Ok(AppEvent::Shutdown) => {
                     return Ok(());
                 }
@@ -125,6 +219,19 @@ impl super::UserInterface for FullTermUi {
                         if let Err(e) = screen.handle_key(key) {
                             error = Some(e);
                         }
+                        // We only remember choices that the user makes in the UI, not ones made
+                        // with --auto-accept.
+                        if screen.take_auto_accept_toggled() {
+                            preferences.accept_single_enabled = screen.accept_single_enabled();
+                            if let Err(e) = preferences.save(&self.preferences_path) {
+                                error = Some(e);
+                            }
+                        }
+                        if let Some(location) = screen.take_source_to_open() {
+                            if let Err(e) = self.open_in_editor(&location) {
+                                error = Some(e);
+                            }
+                        }
                     }
                 }
             }
@@ -154,6 +261,20 @@ fn render_build_progress(f: &mut Frame<CrosstermBackend<Stdout>>, area: Rect) {
     f.render_widget(paragraph, area);
 }
 
+fn render_waiting_for_changes(f: &mut Frame<CrosstermBackend<Stdout>>, area: Rect) {
+    let block = Block::default()
+        .title("Watching")
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(Color::Green));
+    let paragraph = Paragraph::new(
+        "Check complete. Waiting for cackle.toml or sources to change. Press q to quit.",
+    )
+    .block(block)
+    .wrap(Wrap { trim: false });
+    f.render_widget(Clear, area);
+    f.render_widget(paragraph, area);
+}
+
 fn render_error(f: &mut Frame<CrosstermBackend<Stdout>>, error: &anyhow::Error) {
     let area = message_area(f.size());
     let block = Block::default()
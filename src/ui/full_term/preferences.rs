@@ -0,0 +1,63 @@
+//! Choices made in the full terminal UI that we remember for next time. These are stored per
+//! workspace, so different projects have independent preferences.
+
+use anyhow::Context;
+use anyhow::Result;
+use serde::Deserialize;
+use serde::Serialize;
+use std::path::Path;
+use std::path::PathBuf;
+
+#[derive(Serialize, Deserialize, Default, Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) struct Preferences {
+    /// Whether edits are automatically accepted for problems that only have a single edit.
+    #[serde(default)]
+    pub(super) accept_single_enabled: bool,
+}
+
+/// Returns where preferences for the workspace in `root_path` are stored. As with the metadata
+/// cache, this isn't under `target/cackle`, since that's the output directory of our default
+/// profile, which we clean before most builds.
+pub(super) fn preferences_path(root_path: &Path) -> PathBuf {
+    root_path.join("target").join("cackle-ui-preferences.json")
+}
+
+impl Preferences {
+    /// Loads preferences from `path`. If there aren't any, or they can't be read, we use the
+    /// defaults, since preferences aren't important enough to stop the user from continuing.
+    pub(super) fn load(path: &Path) -> Self {
+        std::fs::read_to_string(path)
+            .ok()
+            .and_then(|json| serde_json::from_str(&json).ok())
+            .unwrap_or_default()
+    }
+
+    pub(super) fn save(&self, path: &Path) -> Result<()> {
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)
+                .with_context(|| format!("Failed to create directory `{}`", dir.display()))?;
+        }
+        crate::fs::write_atomic(path, &serde_json::to_string(self)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::preferences_path;
+    use super::Preferences;
+
+    #[test]
+    fn round_trip() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = preferences_path(dir.path());
+        assert_eq!(Preferences::load(&path), Preferences::default());
+        let preferences = Preferences {
+            accept_single_enabled: true,
+        };
+        preferences.save(&path).unwrap();
+        assert_eq!(Preferences::load(&path), preferences);
+
+        std::fs::write(&path, "not json").unwrap();
+        assert_eq!(Preferences::load(&path), Preferences::default());
+    }
+}
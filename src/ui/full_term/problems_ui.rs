@@ -6,15 +6,18 @@ use super::update_counter;
 use crate::checker::ApiUsage;
 use crate::config::CrateName;
 use crate::config_editor;
+use crate::config_editor::AutoFixPolicy;
 use crate::config_editor::ConfigEditor;
 use crate::config_editor::Edit;
 use crate::crate_index::CrateIndex;
 use crate::crate_index::PackageId;
 use crate::location::SourceLocation;
+use crate::names::display_truncated;
 use crate::problem::Problem;
 use crate::problem_store::ProblemStore;
 use crate::problem_store::ProblemStoreIndex;
 use crate::problem_store::ProblemStoreRef;
+use crate::ui::diff;
 use anyhow::anyhow;
 use anyhow::bail;
 use anyhow::Context;
@@ -47,18 +50,41 @@ use std::rc::Rc;
 use std::sync::Arc;
 use std::sync::MutexGuard;
 
-mod diff;
+/// Problems shown in the breadcrumb are truncated to this many characters.
+const MAX_BREADCRUMB_PROBLEM_LENGTH: usize = 60;
 
 pub(super) struct ProblemsUi {
     problem_store: ProblemStoreRef,
     crate_index: Arc<CrateIndex>,
     modes: Vec<Mode>,
     problem_index: usize,
+    /// The deduplication key of the selected problem. Used to keep the selection on the same
+    /// problem when other problems are added or resolved.
+    selected_problem: Option<Problem>,
     edit_index: usize,
     usage_index: usize,
     config_path: PathBuf,
+    /// Config files that are merged into `config_path`. We only ever edit `config_path`.
+    config_overrides: Vec<PathBuf>,
     accept_single_enabled: bool,
+    /// Set when the user turns auto-accept on or off, so that our caller can remember the choice.
+    auto_accept_toggled: bool,
     show_package_details: bool,
+    /// Symbol names in the list of usages are truncated to this many characters unless
+    /// `show_full_symbols` is set.
+    max_symbol_length: usize,
+    show_full_symbols: bool,
+    /// Edits that auto-accept didn't apply because they'd grant a permission listed in
+    /// `never_auto_grant` or edit a table that an override file replaces.
+    skipped_auto_edits: Vec<String>,
+    /// Only problems whose description or package name contains this text are shown.
+    /// `problem_index` indexes into the problems that match.
+    filter: String,
+    /// A source location that the user has asked to open in their editor. Running the editor needs
+    /// control of the terminal, so it's up to our caller to take this and open it.
+    source_to_open: Option<SourceLocation>,
+    /// Set with `--watch` while we're waiting for something to change before checking again.
+    waiting_for_changes: bool,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -67,7 +93,11 @@ enum Mode {
     SelectEdit,
     SelectUsage,
     PromptAutoAccept,
+    PromptApplyToSimilar,
     ShowPackageTree,
+    ShowSkippedAutoEdits,
+    ShowAutoAcceptRestored,
+    EditFilter,
     Help,
 }
 
@@ -77,10 +107,16 @@ impl ProblemsUi {
     }
 
     pub(super) fn render(&self, f: &mut Frame<CrosstermBackend<Stdout>>) {
+        let outer = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Length(1), Constraint::Min(0)])
+            .split(f.size());
+        f.render_widget(Paragraph::new(self.breadcrumb()), outer[0]);
+
         let chunks = if self.show_package_details {
-            split_vertial(f.size(), &[30, 50, 20])
+            split_vertial(outer[1], &[30, 50, 20])
         } else {
-            split_vertial(f.size(), &[35, 65])
+            split_vertial(outer[1], &[35, 65])
         };
         let (top, middle) = (chunks[0], chunks[1]);
 
@@ -110,25 +146,83 @@ impl ProblemsUi {
                     self.render_usage_details(f, middle);
                 }
                 Mode::PromptAutoAccept => render_auto_accept(f),
+                Mode::PromptApplyToSimilar => self.render_apply_to_similar(f),
                 Mode::ShowPackageTree => self.render_package_tree(f),
+                Mode::ShowSkippedAutoEdits => self.render_skipped_auto_edits(f),
+                Mode::ShowAutoAcceptRestored => render_auto_accept_restored(f),
+                Mode::EditFilter => {}
                 Mode::Help => render_help(f, previous_mode),
             }
             previous_mode = Some(mode);
         }
     }
 
+    /// Returns a line showing where we are in the stack of modes, e.g. "Problems > Edits > Help".
+    fn breadcrumb(&self) -> String {
+        self.modes
+            .iter()
+            .map(|mode| match mode {
+                Mode::SelectProblem => "Problems".to_owned(),
+                Mode::SelectEdit => {
+                    let pstore = self.problem_store.lock();
+                    let label =
+                        match visible_problems(&pstore, &self.filter).nth(self.problem_index) {
+                            Some((_, problem)) => format!(
+                                "Edits for {}",
+                                display_truncated(problem, Some(MAX_BREADCRUMB_PROBLEM_LENGTH))
+                            ),
+                            None => "Edits".to_owned(),
+                        };
+                    label
+                }
+                Mode::SelectUsage => "Usages".to_owned(),
+                Mode::PromptAutoAccept | Mode::ShowAutoAcceptRestored => "Auto-accept".to_owned(),
+                Mode::PromptApplyToSimilar => "Apply to similar".to_owned(),
+                Mode::ShowPackageTree => "Package tree".to_owned(),
+                Mode::ShowSkippedAutoEdits => "Skipped edits".to_owned(),
+                Mode::EditFilter => "Filter".to_owned(),
+                Mode::Help => "Help".to_owned(),
+            })
+            .collect::<Vec<_>>()
+            .join(" > ")
+    }
+
     pub(super) fn handle_key(&mut self, key: KeyEvent) -> Result<()> {
         let Some(mode) = self.modes.last() else {
             return Ok(());
         };
         match (mode, key.code) {
+            (Mode::EditFilter, KeyCode::Char(c)) => {
+                self.filter.push(c);
+                self.filter_changed();
+            }
+            (Mode::EditFilter, KeyCode::Backspace) => {
+                self.filter.pop();
+                self.filter_changed();
+            }
+            (Mode::EditFilter, KeyCode::Enter) => {
+                self.modes.pop();
+            }
+            (Mode::EditFilter, KeyCode::Esc)
+            | (Mode::SelectProblem, KeyCode::Esc | KeyCode::Backspace)
+                if !self.filter.is_empty() =>
+            {
+                let was_editing = *mode == Mode::EditFilter;
+                self.filter.clear();
+                self.filter_changed();
+                if was_editing {
+                    self.modes.pop();
+                }
+            }
             (_, KeyCode::Char('q')) => self.modes.clear(),
             (Mode::SelectProblem, KeyCode::Up | KeyCode::Down) => {
-                update_counter(
-                    &mut self.problem_index,
-                    key.code,
-                    self.problem_store.lock().len(),
-                );
+                let num_problems = self.num_visible_problems();
+                if num_problems > 0 {
+                    update_counter(&mut self.problem_index, key.code, num_problems);
+                }
+            }
+            (Mode::SelectProblem, KeyCode::Char('/')) => {
+                self.modes.push(Mode::EditFilter);
             }
             (Mode::SelectEdit, KeyCode::Up | KeyCode::Down) => {
                 let num_edits = self.edits().len();
@@ -150,16 +244,28 @@ impl ProblemsUi {
                 }
                 self.enter_usage_mode();
             }
+            (Mode::SelectProblem, KeyCode::Char('e')) => {
+                let Some(usage) = self.usages().into_iter().next() else {
+                    bail!("Sorry. No source location available for this problem");
+                };
+                self.source_to_open = Some(usage.source_location().clone());
+            }
             (Mode::SelectProblem, KeyCode::Char('t')) => {
                 self.modes.push(Mode::ShowPackageTree);
             }
-            (Mode::ShowPackageTree, _) => {
+            (
+                Mode::ShowPackageTree | Mode::ShowSkippedAutoEdits | Mode::ShowAutoAcceptRestored,
+                _,
+            ) => {
                 self.modes.pop();
             }
             (Mode::SelectUsage, KeyCode::Char('d')) => {
                 // We're already in details mode, drop back out to the problems list.
                 self.modes.pop();
             }
+            (Mode::SelectUsage, KeyCode::Char('n')) => {
+                self.show_full_symbols = !self.show_full_symbols;
+            }
             (Mode::SelectUsage, KeyCode::Char('f')) => {
                 // We're showing details, jump over to showing edits.
                 self.modes.pop();
@@ -167,20 +273,45 @@ impl ProblemsUi {
             }
             (Mode::SelectEdit, KeyCode::Char(' ' | 'f') | KeyCode::Enter) => {
                 self.apply_selected_edit()?;
-                if self.problem_index >= self.problem_store.lock().len() {
+                if self.problem_index >= self.num_visible_problems() {
+                    self.problem_index = 0;
+                }
+                self.modes.pop();
+            }
+            (Mode::SelectEdit, KeyCode::Char('A'))
+                if self.edits().get(self.edit_index).is_some() =>
+            {
+                self.modes.push(Mode::PromptApplyToSimilar);
+            }
+            (Mode::PromptApplyToSimilar, KeyCode::Enter) => {
+                self.apply_selected_edit_to_similar()?;
+                if self.problem_index >= self.num_visible_problems() {
                     self.problem_index = 0;
                 }
+                // Pop both the prompt and the select-edit mode.
+                self.modes.pop();
                 self.modes.pop();
             }
             (Mode::SelectProblem, KeyCode::Char('a')) => {
-                if !self.accept_single_enabled {
+                if self.accept_single_enabled {
+                    self.accept_single_enabled = false;
+                    self.auto_accept_toggled = true;
+                } else {
                     self.modes.push(Mode::PromptAutoAccept);
                 }
             }
             (Mode::PromptAutoAccept, KeyCode::Enter) => {
                 self.accept_single_enabled = true;
-                self.accept_all_single_edits()?;
+                self.auto_accept_toggled = true;
                 self.modes.pop();
+                self.accept_all_single_edits()?;
+            }
+            (Mode::SelectProblem, KeyCode::Char('v')) => {
+                let mut pstore = self.problem_store.lock();
+                let merge_versions = !pstore.merge_versions();
+                pstore.set_merge_versions(merge_versions);
+                drop(pstore);
+                self.restore_selected_problem();
             }
             (_, KeyCode::Char('p')) => {
                 self.show_package_details = !self.show_package_details;
@@ -189,13 +320,12 @@ impl ProblemsUi {
                 self.modes.pop();
             }
             (_, KeyCode::Char('h' | '?')) => self.modes.push(Mode::Help),
-            (_, KeyCode::Esc) => {
-                if self.modes.len() >= 2 {
-                    self.modes.pop();
-                }
+            (_, KeyCode::Esc | KeyCode::Backspace) if self.modes.len() >= 2 => {
+                self.modes.pop();
             }
             _ => {}
         }
+        self.remember_selected_problem();
         Ok(())
     }
 
@@ -216,53 +346,132 @@ impl ProblemsUi {
         problem_store: ProblemStoreRef,
         crate_index: Arc<CrateIndex>,
         config_path: PathBuf,
+        config_overrides: Vec<PathBuf>,
+        max_symbol_length: usize,
     ) -> Self {
         Self {
             problem_store,
             crate_index,
             modes: vec![Mode::SelectProblem],
             problem_index: 0,
+            selected_problem: None,
             edit_index: 0,
             usage_index: 0,
             config_path,
+            config_overrides,
             accept_single_enabled: false,
+            auto_accept_toggled: false,
+            skipped_auto_edits: Vec::new(),
+            filter: String::new(),
             show_package_details: true,
+            max_symbol_length,
+            show_full_symbols: false,
+            source_to_open: None,
+            waiting_for_changes: false,
         }
     }
 
+    pub(super) fn accept_single_enabled(&self) -> bool {
+        self.accept_single_enabled
+    }
+
+    /// Sets whether edits are automatically accepted for problems that only have a single edit.
+    /// Takes effect when problems are next added.
+    pub(super) fn set_accept_single_enabled(&mut self, enabled: bool) {
+        self.accept_single_enabled = enabled;
+    }
+
+    /// Turns on auto-accept because the user turned it on when they last used the UI. Since
+    /// edits will then be applied without being asked, we tell the user that this is the case.
+    pub(super) fn restore_accept_single_enabled(&mut self) {
+        self.accept_single_enabled = true;
+        self.modes.push(Mode::ShowAutoAcceptRestored);
+    }
+
+    /// Returns whether the user has turned auto-accept on or off since we last asked.
+    pub(super) fn take_auto_accept_toggled(&mut self) -> bool {
+        std::mem::take(&mut self.auto_accept_toggled)
+    }
+
+    pub(super) fn set_waiting_for_changes(&mut self, waiting: bool) {
+        self.waiting_for_changes = waiting;
+    }
+
+    /// Returns the source location, if any, that the user has asked to open in their editor.
+    pub(super) fn take_source_to_open(&mut self) -> Option<SourceLocation> {
+        self.source_to_open.take()
+    }
+
     pub(super) fn problems_added(&mut self) -> Result<()> {
         if self.accept_single_enabled {
             self.accept_all_single_edits()?;
         }
+        self.restore_selected_problem();
         Ok(())
     }
 
-    fn accept_all_single_edits(&mut self) -> Result<()> {
-        fn first_single_edit(
-            pstore: &MutexGuard<ProblemStore>,
-        ) -> Option<(ProblemStoreIndex, Box<dyn Edit>)> {
-            pstore
-                .iterate_with_duplicates()
-                .find_map(|(index, problem)| {
-                    let mut edits = config_editor::fixes_for_problem(problem);
-                    if edits.len() == 1 {
-                        Some((index, edits.pop().unwrap()))
-                    } else {
-                        None
-                    }
-                })
+    fn remember_selected_problem(&mut self) {
+        self.selected_problem = visible_problems(&self.problem_store.lock(), &self.filter)
+            .nth(self.problem_index)
+            .map(|(_, problem)| problem.deduplication_key().into_owned());
+    }
+
+    /// Updates `problem_index` to point at the previously selected problem, which may have moved
+    /// if problems before it were resolved. If the selected problem is gone, we leave the index
+    /// where it was, provided it's still in range.
+    fn restore_selected_problem(&mut self) {
+        {
+            let pstore = self.problem_store.lock();
+            if let Some(selected) = &self.selected_problem {
+                if let Some(index) = visible_problems(&pstore, &self.filter)
+                    .position(|(_, problem)| *problem.deduplication_key() == *selected)
+                {
+                    self.problem_index = index;
+                }
+            }
+            if self.problem_index >= visible_problems(&pstore, &self.filter).count() {
+                self.problem_index = 0;
+            }
         }
+        self.remember_selected_problem();
+    }
+
+    fn num_visible_problems(&self) -> usize {
+        visible_problems(&self.problem_store.lock(), &self.filter).count()
+    }
 
+    /// Called after the filter is changed. Keeps the selection on the same problem if it still
+    /// matches the filter, otherwise selects the first problem that does.
+    fn filter_changed(&mut self) {
+        self.problem_index = 0;
+        self.restore_selected_problem();
+    }
+
+    fn accept_all_single_edits(&mut self) -> Result<()> {
+        let policy = self.auto_fix_policy()?;
+        let num_previously_skipped = self.skipped_auto_edits.len();
         let mut pstore = self.problem_store.lock();
         let mut editor = ConfigEditor::from_file(&self.config_path)?;
-        while let Some((index, edit)) = first_single_edit(&pstore) {
+        while let Some((index, edit)) =
+            config_editor::first_single_fix(&pstore, &editor, &policy, &mut self.skipped_auto_edits)
+        {
             edit.apply(&mut editor)?;
             pstore.resolve(index);
         }
+        drop(pstore);
         self.write_config(&editor)?;
+        if self.skipped_auto_edits.len() > num_previously_skipped
+            && self.modes.last() != Some(&Mode::ShowSkippedAutoEdits)
+        {
+            self.modes.push(Mode::ShowSkippedAutoEdits);
+        }
         Ok(())
     }
 
+    fn auto_fix_policy(&self) -> Result<AutoFixPolicy> {
+        AutoFixPolicy::load(&self.config_path, &self.config_overrides, &self.crate_index)
+    }
+
     fn write_config(&self, editor: &ConfigEditor) -> Result<(), anyhow::Error> {
         crate::fs::write_atomic(&self.config_path, &editor.to_toml())
     }
@@ -270,52 +479,71 @@ impl ProblemsUi {
     fn render_problems(&self, f: &mut Frame<CrosstermBackend<Stdout>>, area: Rect) {
         let pstore_lock = &self.problem_store.lock();
         if pstore_lock.is_empty() {
-            super::render_build_progress(f, area);
+            if self.waiting_for_changes {
+                super::render_waiting_for_changes(f, area);
+            } else {
+                super::render_build_progress(f, area);
+            }
             return;
         }
         let mut items = Vec::new();
         let is_edit_mode = self.modes.contains(&Mode::SelectEdit);
         let is_usage_mode = self.modes.contains(&Mode::SelectUsage);
-        for (index, (_, problem)) in pstore_lock.deduplicated_into_iter().enumerate() {
+        for (index, (_, problem)) in visible_problems(pstore_lock, &self.filter).enumerate() {
             items.push(ListItem::new(format!("{problem}")));
             if index == self.problem_index {
                 if is_edit_mode {
-                    let edits = edits_for_problem(pstore_lock, self.problem_index);
+                    let edits = edits_for_problem(pstore_lock, &self.filter, self.problem_index);
                     items.extend(
                         edits
                             .iter()
                             .map(|fix| ListItem::new(format!("  {}", fix.title()))),
                     );
                 } else if is_usage_mode {
-                    let usages =
-                        usages_for_problem(pstore_lock, self.problem_index, &self.crate_index);
+                    let usages = usages_for_problem(
+                        pstore_lock,
+                        &self.filter,
+                        self.problem_index,
+                        &self.crate_index,
+                    );
+                    let max_len = (!self.show_full_symbols).then_some(self.max_symbol_length);
                     items.extend(
-                        usages
-                            .iter()
-                            .map(|usage| ListItem::new(format!("  {}", usage.list_display()))),
+                        usages.iter().map(|usage| {
+                            ListItem::new(format!("  {}", usage.list_display(max_len)))
+                        }),
                     );
                 }
             }
         }
         let mut index = self.problem_index;
-        let title;
+        let mut title;
         if is_edit_mode {
-            title = "Select edit";
+            title = "Select edit".to_owned();
             index += self.edit_index + 1
         } else if is_usage_mode {
-            title = "Select usage";
+            title = "Select usage".to_owned();
             index += self.usage_index + 1
         } else {
-            title = "Problems";
+            title = "Problems".to_owned();
+        }
+        if self.modes.last() == Some(&Mode::EditFilter) {
+            title = format!("{title} (filter: {}_)", self.filter);
+        } else if !self.filter.is_empty() {
+            title = format!("{title} (filter: {})", self.filter);
         }
 
         render_list(
             f,
-            title,
+            &title,
             items.into_iter(),
             matches!(
                 self.modes.last(),
-                Some(&Mode::SelectProblem | &Mode::SelectEdit | &Mode::SelectUsage)
+                Some(
+                    &Mode::SelectProblem
+                        | &Mode::SelectEdit
+                        | &Mode::SelectUsage
+                        | &Mode::EditFilter
+                )
             ),
             area,
             index,
@@ -325,8 +553,7 @@ impl ProblemsUi {
     fn render_details(&self, f: &mut Frame<CrosstermBackend<Stdout>>, area: Rect) {
         let block = Block::default().title("Details").borders(Borders::ALL);
         let pstore_lock = &self.problem_store.lock();
-        let problem = pstore_lock
-            .deduplicated_into_iter()
+        let problem = visible_problems(pstore_lock, &self.filter)
             .nth(self.problem_index)
             .map(|(_, problem)| problem);
         let mut details = problem.map(problem_details).unwrap_or_default();
@@ -345,12 +572,13 @@ impl ProblemsUi {
     }
 
     fn edits(&self) -> Vec<Box<dyn Edit>> {
-        edits_for_problem(&self.problem_store.lock(), self.problem_index)
+        edits_for_problem(&self.problem_store.lock(), &self.filter, self.problem_index)
     }
 
     fn usages(&self) -> Vec<Box<dyn DisplayUsage>> {
         usages_for_problem(
             &self.problem_store.lock(),
+            &self.filter,
             self.problem_index,
             &self.crate_index,
         )
@@ -379,6 +607,15 @@ impl ProblemsUi {
 
         let mut lines = usage_source_lines(&**usage).unwrap_or_else(error_lines);
 
+        let backtrace = usage.backtrace();
+        if !backtrace.is_empty() {
+            lines.push(Line::from(""));
+            lines.push(Line::from("Called via:"));
+            for caller in backtrace {
+                lines.push(Line::from(format!("  {caller}")));
+            }
+        }
+
         if let Some(debug_data) = usage.debug_data() {
             lines.push(Line::from(""));
             for line in debug_data.lines() {
@@ -398,17 +635,19 @@ impl ProblemsUi {
     /// Applies the currently selected edit and resolves the problem that produced that edit.
     fn apply_selected_edit(&self) -> Result<()> {
         let mut pstore_lock = self.problem_store.lock();
-        let edits = edits_for_problem(&pstore_lock, self.problem_index);
+        let edits = edits_for_problem(&pstore_lock, &self.filter, self.problem_index);
         let Some(edit) = edits.get(self.edit_index) else {
             return Ok(());
         };
         let mut editor = ConfigEditor::from_file(&self.config_path)?;
+        self.auto_fix_policy()?
+            .check_not_shadowed(&editor, &**edit)
+            .context("Edit would have no effect")?;
         edit.apply(&mut editor)?;
         self.write_config(&editor)?;
 
         // Resolve the currently selected problem.
-        let maybe_index = pstore_lock
-            .deduplicated_into_iter()
+        let maybe_index = visible_problems(&pstore_lock, &self.filter)
             .nth(self.problem_index)
             .map(|(index, _)| index);
         if let Some(index) = maybe_index {
@@ -420,6 +659,79 @@ impl ProblemsUi {
         Ok(())
     }
 
+    /// Applies the currently selected edit to the selected problem as well as to all other problems
+    /// that are similar to it. e.g. if the selected problem is that some crate uses the `fs` API,
+    /// then all crates that use the `fs` API will be allowed to do so. All edits are applied to the
+    /// config as a single write.
+    fn apply_selected_edit_to_similar(&self) -> Result<()> {
+        let mut pstore_lock = self.problem_store.lock();
+        let mut edits = similar_problem_edits(
+            &pstore_lock,
+            &self.filter,
+            self.problem_index,
+            self.edit_index,
+        );
+        if edits.is_empty() {
+            return Ok(());
+        }
+        let mut editor = ConfigEditor::from_file(&self.config_path)?;
+        let policy = self.auto_fix_policy()?;
+        for (_, edit) in &edits {
+            policy
+                .check_not_shadowed(&editor, &**edit)
+                .context("Edit would have no effect")?;
+            edit.apply(&mut editor)?;
+        }
+        self.write_config(&editor)?;
+
+        // Resolving a problem invalidates the indexes of problems after it, so we resolve from the
+        // end.
+        edits.reverse();
+        for (index, edit) in edits {
+            pstore_lock.replace(index, edit.replacement_problems());
+        }
+        pstore_lock.resolve_problems_with_empty_diff(&editor);
+        Ok(())
+    }
+
+    fn render_apply_to_similar(&self, f: &mut Frame<CrosstermBackend<Stdout>>) {
+        let edits = similar_problem_edits(
+            &self.problem_store.lock(),
+            &self.filter,
+            self.problem_index,
+            self.edit_index,
+        );
+        let mut lines = vec![
+            format!(
+                "Apply the selected edit to {} similar problem(s)?",
+                edits.len()
+            ),
+            String::new(),
+        ];
+        lines.extend(edits.iter().map(|(_, edit)| format!("  {}", edit.title())));
+        lines.push(String::new());
+        lines.push("Press enter to accept, or escape to cancel.".to_owned());
+        render_message(f, None, &lines);
+    }
+
+    fn render_skipped_auto_edits(&self, f: &mut Frame<CrosstermBackend<Stdout>>) {
+        let mut lines = vec![
+            "The following edits weren't auto-accepted because they grant permissions listed in \
+             never_auto_grant or edit a table that an override file replaces. They need to be \
+             reviewed manually."
+                .to_owned(),
+            String::new(),
+        ];
+        lines.extend(
+            self.skipped_auto_edits
+                .iter()
+                .map(|description| format!("  {description}")),
+        );
+        lines.push(String::new());
+        lines.push("Press any key to continue.".to_owned());
+        render_message(f, Some("Edits not auto-accepted"), &lines);
+    }
+
     fn render_package_details(&self, f: &mut Frame<CrosstermBackend<Stdout>>, area: Rect) {
         use std::fmt::Write;
 
@@ -476,9 +788,7 @@ impl ProblemsUi {
 
     fn current_package_id(&self) -> Option<PackageId> {
         let pstore = &self.problem_store.lock();
-        let Some((_, problem)) = pstore.deduplicated_into_iter().nth(self.problem_index) else {
-            return None;
-        };
+        let (_, problem) = visible_problems(pstore, &self.filter).nth(self.problem_index)?;
         problem.pkg_id().cloned()
     }
 }
@@ -492,7 +802,9 @@ fn error_lines(error: anyhow::Error) -> Vec<Line<'static>> {
 
 fn config_diff_lines(config_path: &Path, edit: &dyn Edit) -> Result<Vec<Line<'static>>> {
     let mut lines = Vec::new();
-    lines.push(Line::from(edit.help().to_string()));
+    for line in edit.help().lines() {
+        lines.push(Line::from(line.to_owned()));
+    }
     let original = std::fs::read_to_string(config_path).unwrap_or_default();
     let mut editor = ConfigEditor::from_toml_string(&original)?;
     if let Err(error) = edit.apply(&mut editor) {
@@ -517,8 +829,14 @@ fn usage_source_lines(usage: &dyn DisplayUsage) -> Result<Vec<Line<'static>>> {
     let source_location = usage.source_location();
     lines.push(Line::from(format!(
         "{}",
-        source_location.filename().display()
+        crate::location::to_relative_path(source_location.filename()).display()
     )));
+    if source_location.is_unknown() {
+        lines.push(Line::from(
+            "No debug info, so the source location is unknown".to_owned(),
+        ));
+        return Ok(lines);
+    }
 
     let source = crate::fs::read_to_string(source_location.filename())?;
     let target_line = source_location.line() as i32;
@@ -567,56 +885,53 @@ fn render_help(f: &mut Frame<CrosstermBackend<Stdout>>, mode: Option<&Mode>) {
     match mode {
         Some(Mode::SelectProblem) => {
             title = "Help for select-problem";
-            keys.extend(
-                [
-                    ("f", "Show available automatic fixes for this problem"),
-                    (
-                        "d",
-                        "Select and show details of each usage (API/unsafe only)",
-                    ),
-                    ("t", "Show tree of crate dependencies to this crate"),
-                    ("up", "Select previous problem"),
-                    ("down", "Select next problem"),
-                    ("a", "Enable auto-apply for problems with only one edit"),
-                ]
-                .into_iter(),
-            );
+            keys.extend([
+                ("f", "Show available automatic fixes for this problem"),
+                (
+                    "d",
+                    "Select and show details of each usage (API/unsafe only)",
+                ),
+                ("t", "Show tree of crate dependencies to this crate"),
+                ("e", "Open the first usage in $EDITOR (API/unsafe only)"),
+                ("up", "Select previous problem"),
+                ("down", "Select next problem"),
+                ("/", "Filter problems by text or package name"),
+                ("esc/backspace", "Clear the filter"),
+                ("a", "Toggle auto-apply for problems with only one edit"),
+                (
+                    "v",
+                    "Toggle merging problems from different package versions",
+                ),
+            ]);
         }
         Some(Mode::SelectEdit) => {
             title = "Help for select-edit";
-            keys.extend(
-                [
-                    ("space/enter/f", "Apply this edit"),
-                    ("d", "Jump to usage details (API/unsafe only)"),
-                    ("up", "Select previous edit"),
-                    ("down", "Select next edit"),
-                    ("esc", "Return to problem list"),
-                ]
-                .into_iter(),
-            );
+            keys.extend([
+                ("space/enter/f", "Apply this edit"),
+                ("A", "Apply this edit to all similar problems"),
+                ("d", "Jump to usage details (API/unsafe only)"),
+                ("up", "Select previous edit"),
+                ("down", "Select next edit"),
+                ("esc/backspace", "Return to problem list"),
+            ]);
         }
         Some(Mode::SelectUsage) => {
             title = "Help for select-usage";
-            keys.extend(
-                [
-                    ("up", "Select previous usage"),
-                    ("down", "Select next usage"),
-                    ("f", "Jump to edits for the current problem"),
-                    ("d/esc", "Return to problem list"),
-                ]
-                .into_iter(),
-            );
+            keys.extend([
+                ("up", "Select previous usage"),
+                ("down", "Select next usage"),
+                ("f", "Jump to edits for the current problem"),
+                ("n", "Toggle showing full symbol names"),
+                ("d/esc/backspace", "Return to problem list"),
+            ]);
         }
         _ => {}
     }
-    keys.extend(
-        [
-            ("p", "Toggle display of package details"),
-            ("q", "Quit"),
-            ("h/?", "Show mode-specific help"),
-        ]
-        .into_iter(),
-    );
+    keys.extend([
+        ("p", "Toggle display of package details"),
+        ("q", "Quit"),
+        ("h/?", "Show mode-specific help"),
+    ]);
     let left_col_width = 5;
     let width = keys.iter().map(|(_, text)| text.len()).max().unwrap_or(0) + left_col_width + 3;
     let height = keys.len() + 2;
@@ -646,6 +961,19 @@ fn render_auto_accept(f: &mut Frame<CrosstermBackend<Stdout>>) {
     ]);
 }
 
+fn render_auto_accept_restored(f: &mut Frame<CrosstermBackend<Stdout>>) {
+    render_message(
+        f,
+        Some("Auto-accept"),
+        &[
+            "Auto-accept is on, since it was on when you last used the UI in this workspace.",
+            "Edits are applied automatically for problems that only have a single edit.",
+            "",
+            "Press 'a' when viewing problems to turn it off. Press any key to continue.",
+        ],
+    );
+}
+
 fn render_message<S: AsRef<str>>(
     f: &mut Frame<CrosstermBackend<Stdout>>,
     title: Option<&str>,
@@ -677,23 +1005,80 @@ fn active_block() -> Block<'static> {
         .border_style(Style::default().fg(Color::Yellow))
 }
 
+/// Returns the deduplicated problems whose description or package name contains `filter`.
+fn visible_problems<'a>(
+    pstore: &'a ProblemStore,
+    filter: &'a str,
+) -> impl Iterator<Item = (ProblemStoreIndex, &'a Problem)> + 'a {
+    pstore
+        .deduplicated_into_iter()
+        .filter(move |(_, problem)| problem_matches_filter(problem, filter))
+}
+
+fn problem_matches_filter(problem: &Problem, filter: &str) -> bool {
+    filter.is_empty()
+        || problem.to_string().contains(filter)
+        || problem
+            .pkg_id()
+            .is_some_and(|pkg_id| pkg_id.name().contains(filter))
+}
+
 fn edits_for_problem(
     pstore_lock: &MutexGuard<ProblemStore>,
+    filter: &str,
     problem_index: usize,
 ) -> Vec<Box<dyn Edit>> {
-    let Some((_, problem)) = pstore_lock.deduplicated_into_iter().nth(problem_index) else {
+    let Some((_, problem)) = visible_problems(pstore_lock, filter).nth(problem_index) else {
         return Vec::new();
     };
     config_editor::fixes_for_problem(problem)
 }
 
+/// Returns the edit at `edit_index` for the problem at `problem_index` and the equivalent edit for
+/// each other problem that is similar to it. Entries are in problem store order.
+fn similar_problem_edits(
+    pstore_lock: &MutexGuard<ProblemStore>,
+    filter: &str,
+    problem_index: usize,
+    edit_index: usize,
+) -> Vec<(ProblemStoreIndex, Box<dyn Edit>)> {
+    let Some((_, selected)) = visible_problems(pstore_lock, filter).nth(problem_index) else {
+        return Vec::new();
+    };
+    let Some(selected_edit) = config_editor::fixes_for_problem(selected)
+        .into_iter()
+        .nth(edit_index)
+    else {
+        return Vec::new();
+    };
+    pstore_lock
+        .deduplicated_into_iter()
+        .filter(|(_, problem)| problem.is_similar_to(selected))
+        .filter_map(|(index, problem)| {
+            equivalent_edit(selected_edit.as_ref(), problem).map(|edit| (index, edit))
+        })
+        .collect()
+}
+
+/// Returns the edit for `problem` that does the same thing as `selected_edit`, but for whichever
+/// package `problem` is for. Similar problems may offer different edits, or the same edits in a
+/// different order, so we can't go by position. Instead we look for an edit of the same kind with
+/// the same arguments.
+fn equivalent_edit(selected_edit: &dyn Edit, problem: &Problem) -> Option<Box<dyn Edit>> {
+    let args = selected_edit.package_independent_args();
+    config_editor::fixes_for_problem(problem)
+        .into_iter()
+        .find(|edit| edit.kind() == selected_edit.kind() && edit.package_independent_args() == args)
+}
+
 fn usages_for_problem(
     pstore_lock: &MutexGuard<ProblemStore>,
+    filter: &str,
     problem_index: usize,
     crate_index: &CrateIndex,
 ) -> Vec<Box<dyn DisplayUsage>> {
     let mut usages_out: Vec<Box<dyn DisplayUsage>> = Vec::new();
-    match pstore_lock.deduplicated_into_iter().nth(problem_index) {
+    match visible_problems(pstore_lock, filter).nth(problem_index) {
         Some((_, Problem::DisallowedApiUsage(usages))) => {
             for usages in usages.usages.values() {
                 for usage in usages {
@@ -731,8 +1116,14 @@ trait DisplayUsage {
         None
     }
 
-    /// A single line that we display in the list of usages.
-    fn list_display(&self) -> String;
+    /// The functions through which the usage is reached, nearest first.
+    fn backtrace(&self) -> Vec<String> {
+        Vec::new()
+    }
+
+    /// A single line that we display in the list of usages. Symbol names longer than `max_len`
+    /// characters are truncated.
+    fn list_display(&self, max_len: Option<usize>) -> String;
 }
 
 impl DisplayUsage for ApiUsage {
@@ -746,8 +1137,23 @@ impl DisplayUsage for ApiUsage {
             .map(|debug_data| format!("{debug_data:#?}"))
     }
 
-    fn list_display(&self) -> String {
-        format!("{} -> {}", self.from, self.to_source)
+    fn backtrace(&self) -> Vec<String> {
+        self.backtrace
+            .iter()
+            .map(|caller| caller.to_string())
+            .collect()
+    }
+
+    fn list_display(&self, max_len: Option<usize>) -> String {
+        let mut display = format!(
+            "{} -> {}",
+            display_truncated(&self.from, max_len),
+            display_truncated(&self.to_source, max_len)
+        );
+        if let Some(env_var) = &self.env_var {
+            display.push_str(&format!(" ({env_var})"));
+        }
+        display
     }
 }
 
@@ -756,7 +1162,7 @@ impl DisplayUsage for UnsafeLocation {
         &self.source_location
     }
 
-    fn list_display(&self) -> String {
+    fn list_display(&self, _max_len: Option<usize>) -> String {
         // In the list, we'd prefer to display source filenames relative to the package root where
         // possible. We already know the crate name and all the usage locations for a crate will
         // generally be under the package root. For any that aren't, we fall back to using the full
@@ -766,6 +1172,9 @@ impl DisplayUsage for UnsafeLocation {
             .as_ref()
             .and_then(|pkg_dir| self.source_location.filename().strip_prefix(pkg_dir).ok())
             .unwrap_or_else(|| self.source_location.filename());
+        if self.source_location.is_unknown() {
+            return format!("{}:?", filename.display());
+        }
         format!("{}:{}", filename.display(), self.source_location.line())
     }
 }
@@ -798,3 +1207,322 @@ fn split_vertial(area: Rect, percentages: &[u16]) -> Rc<[Rect]> {
         .constraints(constraints)
         .split(area)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crate_index::testing::build_script_id;
+    use crate::events::AppEvent;
+    use crate::problem::DisallowedBuildInstruction;
+    use crate::problem::Problem::UnusedPackageConfig;
+    use crate::problem::ProblemList;
+    use std::sync::mpsc::channel;
+    use std::sync::mpsc::Receiver;
+    use std::thread::JoinHandle;
+
+    fn add_problems(
+        problem_store: &ProblemStoreRef,
+        event_recv: &Receiver<AppEvent>,
+        crate_names: &[&str],
+    ) -> JoinHandle<()> {
+        let mut problems = ProblemList::default();
+        for name in crate_names {
+            problems.push(UnusedPackageConfig((*name).into()));
+        }
+        add_problem_list(problem_store, event_recv, problems)
+    }
+
+    /// Adds `problems` to the store from another thread, since adding problems blocks until they're
+    /// resolved.
+    fn add_problem_list(
+        problem_store: &ProblemStoreRef,
+        event_recv: &Receiver<AppEvent>,
+        problems: ProblemList,
+    ) -> JoinHandle<()> {
+        let join_handle = std::thread::spawn({
+            let mut problem_store = problem_store.clone();
+            move || {
+                problem_store.fix_problems(problems);
+            }
+        });
+        assert_eq!(event_recv.recv(), Ok(AppEvent::ProblemsAdded));
+        join_handle
+    }
+
+    fn new_test_ui(problem_store: &ProblemStoreRef) -> ProblemsUi {
+        ProblemsUi::new(
+            problem_store.clone(),
+            Arc::new(CrateIndex::default()),
+            PathBuf::new(),
+            Vec::new(),
+            200,
+        )
+    }
+
+    /// Resolves all problems in the store, allowing the threads that added them to finish.
+    fn resolve_all(problem_store: &ProblemStoreRef) {
+        let mut pstore = problem_store.lock();
+        loop {
+            let maybe_index = pstore
+                .iterate_with_duplicates()
+                .next()
+                .map(|(index, _)| index);
+            let Some(index) = maybe_index else {
+                break;
+            };
+            pstore.resolve(index);
+        }
+    }
+
+    fn selected_problem(ui: &ProblemsUi) -> Problem {
+        visible_problems(&ui.problem_store.lock(), &ui.filter)
+            .nth(ui.problem_index)
+            .unwrap()
+            .1
+            .clone()
+    }
+
+    #[test]
+    fn selection_preserved_when_problems_change() {
+        let (event_send, event_recv) = channel();
+        let problem_store = crate::problem_store::create(event_send);
+        let mut ui = new_test_ui(&problem_store);
+        let mut join_handles = vec![add_problems(&problem_store, &event_recv, &["crab1"])];
+        ui.problems_added().unwrap();
+        join_handles.push(add_problems(
+            &problem_store,
+            &event_recv,
+            &["crab2", "crab3"],
+        ));
+        ui.problems_added().unwrap();
+
+        ui.handle_key(KeyCode::Down.into()).unwrap();
+        ui.handle_key(KeyCode::Down.into()).unwrap();
+        assert_eq!(selected_problem(&ui), UnusedPackageConfig("crab3".into()));
+
+        // Resolve the first problem and add another. The selected problem is now at a different
+        // index, but it should still be selected.
+        let first = problem_store
+            .lock()
+            .iterate_with_duplicates()
+            .next()
+            .unwrap()
+            .0;
+        problem_store.lock().resolve(first);
+        join_handles.push(add_problems(&problem_store, &event_recv, &["crab4"]));
+        ui.problems_added().unwrap();
+        assert_eq!(ui.problem_index, 1);
+        assert_eq!(selected_problem(&ui), UnusedPackageConfig("crab3".into()));
+
+        resolve_all(&problem_store);
+        for join_handle in join_handles {
+            join_handle.join().unwrap();
+        }
+    }
+
+    #[test]
+    fn similar_edits_matched_by_kind_and_args() {
+        let (event_send, event_recv) = channel();
+        let problem_store = crate::problem_store::create(event_send);
+        let mut problems = ProblemList::default();
+        for (name, instruction) in [
+            ("crab1", "cargo:rustc-link-lib=foo"),
+            ("crab2", "cargo:rustc-env=FOO=1"),
+            ("crab3", "cargo:rustc-link-lib=bar"),
+            // A package whose name starts with the name of the selected problem's package.
+            ("crab10", "cargo:rustc-link-lib=baz"),
+        ] {
+            problems.push(Problem::DisallowedBuildInstruction(
+                DisallowedBuildInstruction {
+                    build_script_id: build_script_id(name),
+                    instruction: instruction.to_owned(),
+                    emitted: Vec::new(),
+                },
+            ));
+        }
+        let join_handle = add_problem_list(&problem_store, &event_recv, problems);
+
+        // The third edit of the first problem allows `cargo:rustc-link-*`. The second problem offers
+        // a different edit in that position, which isn't equivalent.
+        let titles: Vec<String> = similar_problem_edits(&problem_store.lock(), "", 0, 2)
+            .iter()
+            .map(|(_, edit)| edit.title())
+            .collect();
+        assert_eq!(
+            titles,
+            vec![
+                "Allow build script for `crab1.build` to emit instruction `cargo:rustc-link-*`",
+                "Allow build script for `crab3.build` to emit instruction `cargo:rustc-link-*`",
+                "Allow build script for `crab10.build` to emit instruction `cargo:rustc-link-*`",
+            ]
+        );
+
+        resolve_all(&problem_store);
+        join_handle.join().unwrap();
+    }
+
+    #[test]
+    fn auto_accept_skips_never_auto_grant() {
+        use crate::crate_index::testing::pkg_id;
+        use crate::crate_index::CrateSel;
+        use crate::problem::ShellInvocation;
+        use crate::proxy::rpc::UnsafeUsage;
+
+        let dir = tempfile::tempdir().unwrap();
+        let config_path = dir.path().join("cackle.toml");
+        std::fs::write(
+            &config_path,
+            "[common]\nversion = 1\nnever_auto_grant = [\"shell\"]\n",
+        )
+        .unwrap();
+        let (event_send, event_recv) = channel();
+        let problem_store = crate::problem_store::create(event_send);
+        let mut ui = new_test_ui(&problem_store);
+        ui.config_path = config_path.clone();
+        let mut problems = ProblemList::default();
+        problems.push(Problem::DisallowedUnsafe(UnsafeUsage {
+            crate_sel: CrateSel::Primary(pkg_id("crab1")),
+            locations: vec![],
+        }));
+        problems.push(Problem::ShellInvocation(ShellInvocation {
+            crate_sel: CrateSel::Primary(pkg_id("crab2")),
+            shell: "sh".to_owned(),
+            location: SourceLocation::new(Path::new("src/lib.rs"), 1, None),
+        }));
+        let join_handle = add_problem_list(&problem_store, &event_recv, problems);
+        ui.problems_added().unwrap();
+        ui.handle_key(KeyCode::Char('a').into()).unwrap();
+        ui.handle_key(KeyCode::Enter.into()).unwrap();
+
+        let config = std::fs::read_to_string(&config_path).unwrap();
+        assert!(config.contains("allow_unsafe = true"));
+        assert!(!config.contains("allow_shell"));
+        assert!(matches!(
+            selected_problem(&ui),
+            Problem::ShellInvocation(..)
+        ));
+        assert_eq!(ui.modes.last(), Some(&Mode::ShowSkippedAutoEdits));
+        assert_eq!(
+            ui.skipped_auto_edits,
+            vec!["Allow package `crab2` to run a shell (grants `shell`)".to_owned()]
+        );
+
+        resolve_all(&problem_store);
+        join_handle.join().unwrap();
+    }
+
+    #[test]
+    fn auto_accept_toggle() {
+        let (event_send, _event_recv) = channel();
+        let problem_store = crate::problem_store::create(event_send);
+        let mut ui = new_test_ui(&problem_store);
+        ui.restore_accept_single_enabled();
+        assert!(ui.accept_single_enabled());
+        assert_eq!(ui.modes.last(), Some(&Mode::ShowAutoAcceptRestored));
+        // Any key dismisses the notice without changing the setting.
+        ui.handle_key(KeyCode::Char('a').into()).unwrap();
+        assert_eq!(ui.modes.last(), Some(&Mode::SelectProblem));
+        assert!(ui.accept_single_enabled());
+        assert!(!ui.take_auto_accept_toggled());
+
+        ui.handle_key(KeyCode::Char('a').into()).unwrap();
+        assert!(!ui.accept_single_enabled());
+        assert!(ui.take_auto_accept_toggled());
+        assert!(!ui.take_auto_accept_toggled());
+    }
+
+    #[test]
+    fn filter_problems() {
+        let (event_send, event_recv) = channel();
+        let problem_store = crate::problem_store::create(event_send);
+        let mut ui = new_test_ui(&problem_store);
+        let join_handle = add_problems(&problem_store, &event_recv, &["crab1", "crab2", "crab3"]);
+        ui.problems_added().unwrap();
+        ui.handle_key(KeyCode::Down.into()).unwrap();
+        assert_eq!(selected_problem(&ui), UnusedPackageConfig("crab2".into()));
+
+        ui.handle_key(KeyCode::Char('/').into()).unwrap();
+        for c in "crab3".chars() {
+            ui.handle_key(KeyCode::Char(c).into()).unwrap();
+        }
+        assert_eq!(ui.num_visible_problems(), 1);
+        assert_eq!(selected_problem(&ui), UnusedPackageConfig("crab3".into()));
+        // Keys that would otherwise be commands are part of the filter while it's being edited.
+        assert_eq!(ui.modes.last(), Some(&Mode::EditFilter));
+        ui.handle_key(KeyCode::Enter.into()).unwrap();
+        assert_eq!(ui.modes.last(), Some(&Mode::SelectProblem));
+        ui.handle_key(KeyCode::Down.into()).unwrap();
+        assert_eq!(selected_problem(&ui), UnusedPackageConfig("crab3".into()));
+
+        // Escape clears the filter, leaving the same problem selected.
+        ui.handle_key(KeyCode::Esc.into()).unwrap();
+        assert_eq!(ui.num_visible_problems(), 3);
+        assert_eq!(ui.problem_index, 2);
+        assert!(!ui.quit_requested());
+
+        resolve_all(&problem_store);
+        join_handle.join().unwrap();
+    }
+
+    #[test]
+    fn breadcrumb_follows_modes() {
+        let (event_send, event_recv) = channel();
+        let problem_store = crate::problem_store::create(event_send);
+        let mut ui = new_test_ui(&problem_store);
+        let join_handle = add_problems(&problem_store, &event_recv, &["crab1"]);
+        ui.problems_added().unwrap();
+        assert_eq!(ui.breadcrumb(), "Problems");
+
+        ui.handle_key(KeyCode::Char('f').into()).unwrap();
+        ui.handle_key(KeyCode::Char('h').into()).unwrap();
+        assert_eq!(
+            ui.breadcrumb(),
+            "Problems > Edits for Config supplied for package `crab1` not in dependency tree > Help"
+        );
+
+        ui.handle_key(KeyCode::Backspace.into()).unwrap();
+        ui.handle_key(KeyCode::Backspace.into()).unwrap();
+        assert_eq!(ui.breadcrumb(), "Problems");
+        // Backing out of the top-level mode doesn't quit.
+        ui.handle_key(KeyCode::Backspace.into()).unwrap();
+        assert!(!ui.quit_requested());
+
+        resolve_all(&problem_store);
+        join_handle.join().unwrap();
+    }
+
+    #[test]
+    fn open_source_location() {
+        use crate::crate_index::testing::pkg_id;
+        use crate::crate_index::CrateSel;
+        use crate::proxy::rpc::UnsafeUsage;
+
+        let (event_send, event_recv) = channel();
+        let problem_store = crate::problem_store::create(event_send);
+        let mut ui = new_test_ui(&problem_store);
+        let location = SourceLocation::new(Path::new("src/lib.rs"), 10, Some(5));
+        let mut problems = ProblemList::default();
+        problems.push(Problem::DisallowedUnsafe(UnsafeUsage {
+            crate_sel: CrateSel::Primary(pkg_id("crab1")),
+            locations: vec![
+                SourceLocation::new(Path::new("src/lib.rs"), 20, None),
+                location.clone(),
+            ],
+        }));
+        problems.push(UnusedPackageConfig("crab2".into()));
+        let join_handle = add_problem_list(&problem_store, &event_recv, problems);
+        ui.problems_added().unwrap();
+
+        ui.handle_key(KeyCode::Char('e').into()).unwrap();
+        assert_eq!(ui.take_source_to_open(), Some(location));
+        assert_eq!(ui.take_source_to_open(), None);
+
+        // A problem without a source location reports an error rather than silently doing nothing.
+        ui.handle_key(KeyCode::Down.into()).unwrap();
+        assert!(ui.handle_key(KeyCode::Char('e').into()).is_err());
+        assert_eq!(ui.take_source_to_open(), None);
+
+        resolve_all(&problem_store);
+        join_handle.join().unwrap();
+    }
+}
@@ -0,0 +1,234 @@
+//! JSON output of problems for consumption by other tools, e.g. CI dashboards. We serialize via
+//! dedicated types rather than deriving `Serialize` on `Problem`, so that the output schema stays
+//! stable even as our internal representation changes.
+
+use crate::location::SourceLocation;
+use crate::problem::Problem;
+use crate::problem::Severity;
+use crate::ui::summary::Summary;
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+/// The top-level JSON document that we print.
+#[derive(Serialize, Debug)]
+pub(super) struct JsonOutput {
+    problems: Vec<JsonProblem>,
+    summary: Summary,
+    unused_config: JsonUnusedConfig,
+}
+
+/// Config that isn't needed and could be removed. This duplicates information in `problems`, but
+/// in a form that tools can act on without parsing messages.
+#[derive(Serialize, Debug, Default)]
+struct JsonUnusedConfig {
+    /// Packages that have config, but aren't in the dependency tree.
+    unknown_packages: Vec<String>,
+    /// For each package, the permissions that it's allowed, but didn't use.
+    unused_permissions: BTreeMap<String, Vec<String>>,
+}
+
+#[derive(Serialize, Debug)]
+struct JsonProblem {
+    /// The kind of problem, e.g. "DisallowedApiUsage".
+    kind: &'static str,
+    severity: &'static str,
+    /// A human-readable description of the problem.
+    message: String,
+    package: Option<JsonPackage>,
+    /// Permissions (APIs) that the problem relates to.
+    permissions: Vec<String>,
+    locations: Vec<SourceLocation>,
+}
+
+#[derive(Serialize, Debug)]
+struct JsonPackage {
+    name: String,
+    version: String,
+}
+
+impl JsonOutput {
+    pub(super) fn new(reported: &[(Problem, Severity)]) -> Self {
+        Self {
+            problems: reported
+                .iter()
+                .map(|(problem, severity)| JsonProblem::new(problem, *severity))
+                .collect(),
+            summary: Summary::new(reported),
+            unused_config: JsonUnusedConfig::new(reported),
+        }
+    }
+}
+
+impl JsonUnusedConfig {
+    fn new(reported: &[(Problem, Severity)]) -> Self {
+        let mut unused_config = Self::default();
+        for (problem, _) in reported {
+            match problem {
+                Problem::UnusedPackageConfig(crate_name) => {
+                    unused_config.unknown_packages.push(crate_name.to_string());
+                }
+                Problem::UnusedAllowApi(unused) => {
+                    let permissions = unused_config
+                        .unused_permissions
+                        .entry(unused.crate_name.to_string())
+                        .or_default();
+                    permissions.extend(unused.permissions.iter().map(|api| api.to_string()));
+                    permissions.sort();
+                    permissions.dedup();
+                }
+                _ => {}
+            }
+        }
+        unused_config.unknown_packages.sort();
+        unused_config.unknown_packages.dedup();
+        unused_config
+    }
+}
+
+impl JsonProblem {
+    fn new(problem: &Problem, severity: Severity) -> Self {
+        Self {
+            kind: kind(problem),
+            severity: match severity {
+                Severity::Warning => "warning",
+                Severity::Error => "error",
+            },
+            message: problem.to_string(),
+            package: problem.pkg_id().map(|pkg_id| JsonPackage {
+                name: pkg_id.name().to_owned(),
+                version: pkg_id.version().to_string(),
+            }),
+            permissions: permissions(problem),
+            locations: problem.source_locations().into_iter().cloned().collect(),
+        }
+    }
+}
+
+/// Returns the name of the kind of problem. This is also used as the rule ID for SARIF output.
+pub(crate) fn kind(problem: &Problem) -> &'static str {
+    match problem {
+        Problem::Message(..) => "Message",
+        Problem::MissingConfiguration(..) => "MissingConfiguration",
+        Problem::UsesBuildScript(..) => "UsesBuildScript",
+        Problem::DisallowedUnsafe(..) => "DisallowedUnsafe",
+        Problem::IsProcMacro(..) => "IsProcMacro",
+        Problem::DisallowedApiUsage(..) => "DisallowedApiUsage",
+        Problem::BuildScriptFailed(..) => "BuildScriptFailed",
+        Problem::DisallowedBuildInstruction(..) => "DisallowedBuildInstruction",
+        Problem::UnusedPackageConfig(..) => "UnusedPackageConfig",
+        Problem::UnusedAllowApi(..) => "UnusedAllowApi",
+        Problem::UnusedAllowSymbols(..) => "UnusedAllowSymbols",
+        Problem::UnusedSuppression(..) => "UnusedSuppression",
+        Problem::UnusedExclude(..) => "UnusedExclude",
+        Problem::SelectSandbox => "SelectSandbox",
+        Problem::ImportStdApi(..) => "ImportStdApi",
+        Problem::AvailableApi(..) => "AvailableApi",
+        Problem::PossibleExportedApi(..) => "PossibleExportedApi",
+        Problem::UndecidedPackages(..) => "UndecidedPackages",
+        Problem::IncludedAndExcluded(..) => "IncludedAndExcluded",
+        Problem::ShellInvocation(..) => "ShellInvocation",
+        Problem::DisallowedLicense(..) => "DisallowedLicense",
+        Problem::YankedVersion(..) => "YankedVersion",
+    }
+}
+
+pub(crate) fn permissions(problem: &Problem) -> Vec<String> {
+    match problem {
+        Problem::DisallowedApiUsage(usages) => {
+            usages.usages.keys().map(|api| api.to_string()).collect()
+        }
+        Problem::UnusedAllowApi(unused) => unused
+            .permissions
+            .iter()
+            .map(|api| api.to_string())
+            .collect(),
+        Problem::UnusedSuppression(unused) => vec![unused.suppression.permission.to_string()],
+        Problem::ImportStdApi(api) => vec![api.to_string()],
+        Problem::AvailableApi(available) => vec![available.api.to_string()],
+        Problem::PossibleExportedApi(possible) => vec![possible.api.to_string()],
+        Problem::IncludedAndExcluded(info) => vec![info.api.to_string()],
+        Problem::UnusedExclude(info) => vec![info.api.to_string()],
+        _ => Vec::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crate_index::testing::pkg_id;
+    use crate::crate_index::CrateSel;
+    use crate::problem::testing::api_usage;
+    use crate::problem::testing::api_usages;
+    use std::collections::BTreeMap;
+    use std::path::Path;
+
+    #[test]
+    fn api_usage_json() {
+        let usage = api_usage(
+            "crab1::connect",
+            "std::net::TcpStream::connect",
+            SourceLocation::new(Path::new("src/lib.rs"), 10, Some(5)),
+        );
+        let usages = api_usages(
+            CrateSel::Primary(pkg_id("crab1")),
+            BTreeMap::from([("net".into(), vec![usage])]),
+        );
+        let output = JsonOutput::new(&[
+            (Problem::DisallowedApiUsage(usages), Severity::Error),
+            (
+                Problem::UnusedPackageConfig("crab2".into()),
+                Severity::Warning,
+            ),
+            (
+                Problem::UnusedAllowApi(crate::problem::UnusedAllowApi {
+                    crate_name: "crab3".into(),
+                    permissions: vec!["process".into(), "fs".into()],
+                }),
+                Severity::Error,
+            ),
+        ]);
+        let json: serde_json::Value = serde_json::to_value(&output).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({
+                "problems": [
+                    {
+                        "kind": "DisallowedApiUsage",
+                        "severity": "error",
+                        "message": "`crab1` uses API `net`",
+                        "package": {"name": "crab1", "version": "0.0.0"},
+                        "permissions": ["net"],
+                        "locations": [{"filename": "src/lib.rs", "line": 10, "column": 5}],
+                    },
+                    {
+                        "kind": "UnusedPackageConfig",
+                        "severity": "warning",
+                        "message": "Config supplied for package `crab2` not in dependency tree",
+                        "package": null,
+                        "permissions": [],
+                        "locations": [],
+                    },
+                    {
+                        "kind": "UnusedAllowApi",
+                        "severity": "error",
+                        "message": "`pkg.crab3` allows APIs that aren't used",
+                        "package": null,
+                        "permissions": ["process", "fs"],
+                        "locations": [],
+                    },
+                ],
+                "summary": {
+                    "errors": 2,
+                    "warnings": 1,
+                    "permissions": {"net": 1},
+                    "top_crates": [{"crate": "crab1", "problems": 1}],
+                    "disallowed_apis": 1,
+                },
+                "unused_config": {
+                    "unknown_packages": ["crab2"],
+                    "unused_permissions": {"crab3": ["fs", "process"]},
+                },
+            })
+        );
+    }
+}
@@ -1,27 +1,164 @@
 //! A user-interface that never prompts. This is used when non-interactive mode is selected.
 
+use crate::crate_index::CrateIndex;
 use crate::events::AppEvent;
+use crate::history::HistoryFile;
+use crate::problem::Problem;
 use crate::problem::Severity;
 use crate::problem_store::ProblemStoreRef;
+use crate::ui::json::JsonOutput;
+use crate::ui::sarif::SarifLog;
+use crate::ui::summary::Summary;
+use crate::ui::OutputFormat;
 use crate::Args;
 use anyhow::Result;
 use colored::Colorize;
+use std::path::PathBuf;
 use std::sync::mpsc::Receiver;
 use std::sync::mpsc::Sender;
 use std::sync::Arc;
 
 pub(crate) struct NullUi {
     args: Arc<Args>,
+    config_path: PathBuf,
+    /// Where we record which problems have been reported, for `--show-age`.
+    history_path: PathBuf,
+    #[cfg_attr(not(feature = "ui"), allow(dead_code))]
+    crate_index: Arc<CrateIndex>,
     abort_sender: Sender<()>,
+    /// Titles of fixes applied due to `--fix`.
+    applied_fixes: Vec<String>,
+    /// Fixes that `--fix` didn't apply because they'd grant a permission listed in
+    /// `never_auto_grant` or edit a table that an override file replaces.
+    skipped_fixes: Vec<String>,
+    /// With `--diff-config`, what the config file would contain after the fixes that we've shown so
+    /// far were applied.
+    #[cfg_attr(not(feature = "ui"), allow(dead_code))]
+    diffed_config: Option<String>,
 }
 
 impl NullUi {
-    pub(crate) fn new(args: &Arc<Args>, abort_sender: Sender<()>) -> Self {
+    pub(crate) fn new(
+        args: &Arc<Args>,
+        config_path: PathBuf,
+        history_path: PathBuf,
+        crate_index: Arc<CrateIndex>,
+        abort_sender: Sender<()>,
+    ) -> Self {
         Self {
             args: args.clone(),
+            config_path,
+            history_path,
+            crate_index,
             abort_sender,
+            applied_fixes: Vec::new(),
+            skipped_fixes: Vec::new(),
+            diffed_config: None,
         }
     }
+
+    /// Applies the fix for each problem that has only a single fix and resolves those problems.
+    #[cfg(feature = "ui")]
+    fn apply_single_fixes(
+        &mut self,
+        pstore: &mut crate::problem_store::ProblemStore,
+    ) -> Result<()> {
+        use crate::config_editor;
+
+        let policy = config_editor::AutoFixPolicy::load(
+            &self.config_path,
+            self.args.config_overrides(),
+            &self.crate_index,
+        )?;
+        let mut editor = config_editor::ConfigEditor::from_file(&self.config_path)?;
+        let num_previously_applied = self.applied_fixes.len();
+        while let Some((index, edit)) =
+            config_editor::first_single_fix(pstore, &editor, &policy, &mut self.skipped_fixes)
+        {
+            edit.apply(&mut editor)?;
+            pstore.resolve(index);
+            self.applied_fixes.push(edit.title());
+        }
+        if self.applied_fixes.len() > num_previously_applied {
+            crate::fs::write_atomic(&self.config_path, &editor.to_toml())?;
+        }
+        Ok(())
+    }
+
+    /// Returns a diff of the change that the fix for `problem` would make to the config file, if it
+    /// has only a single fix. The diff is relative to the config with all previously shown fixes
+    /// applied.
+    #[cfg(feature = "ui")]
+    fn config_diff(&mut self, problem: &Problem) -> Result<String> {
+        use crate::config_editor;
+
+        let policy = config_editor::AutoFixPolicy::load(
+            &self.config_path,
+            self.args.config_overrides(),
+            &self.crate_index,
+        )?;
+        let original = match self.diffed_config.take() {
+            Some(config) => config,
+            None => std::fs::read_to_string(&self.config_path).unwrap_or_default(),
+        };
+        let mut editor = config_editor::ConfigEditor::from_toml_string(&original)?;
+        let Some(edit) =
+            config_editor::single_fix(problem, &editor, &policy, &mut self.skipped_fixes)
+        else {
+            self.diffed_config = Some(original);
+            return Ok(String::new());
+        };
+        edit.apply(&mut editor)?;
+        let updated = editor.to_toml();
+        let diff = format!(
+            "  Fix: {}\n{}",
+            edit.title(),
+            super::diff::unified_diff(&self.config_path.display().to_string(), &original, &updated)
+        );
+        self.diffed_config = Some(updated);
+        Ok(diff)
+    }
+
+    /// Prints which fixes `--fix` applied and which it skipped.
+    fn print_fix_summary(&self) {
+        if self.args.output_format.is_document() {
+            return;
+        }
+        if !self.applied_fixes.is_empty() {
+            println!("Applied fixes to `{}`:", self.config_path.display());
+            for title in &self.applied_fixes {
+                println!("  {title}");
+            }
+        }
+        if !self.skipped_fixes.is_empty() {
+            println!("Skipped fixes that can't be applied automatically:");
+            for description in &self.skipped_fixes {
+                println!("  {description}");
+            }
+        }
+    }
+
+    /// Prints `problem`. `age`, if supplied, says how long the problem has been around.
+    fn print_problem(&mut self, label: &str, problem: &Problem, age: Option<String>) -> Result<()> {
+        let label = match age {
+            Some(age) => format!("{label} ({age})"),
+            None => label.to_owned(),
+        };
+        println!(
+            "{label} {problem:#.max_len$}",
+            max_len = self.args.max_symbol_length
+        );
+        #[cfg(feature = "ui")]
+        if self.args.show_fixes {
+            let original = std::fs::read_to_string(&self.config_path).unwrap_or_default();
+            print!("{}", fixes_text(&original, problem));
+        }
+        #[cfg(feature = "ui")]
+        if self.args.diff_config {
+            print!("{}", self.config_diff(problem)?);
+        }
+        Ok(())
+    }
 }
 
 impl super::UserInterface for NullUi {
@@ -30,45 +167,81 @@ impl super::UserInterface for NullUi {
         problem_store: ProblemStoreRef,
         event_receiver: Receiver<AppEvent>,
     ) -> Result<()> {
+        let mut history = self
+            .args
+            .show_age
+            .then(|| HistoryFile::load(&self.history_path));
+        // Problems that we've reported, so that we can print them as a single document or summarise
+        // them at the end.
+        let mut reported = Vec::new();
+        let mut check_failed = false;
         while let Ok(event) = event_receiver.recv() {
             match event {
-                AppEvent::Shutdown => return Ok(()),
+                AppEvent::Shutdown => break,
+                AppEvent::CheckFailed => check_failed = true,
+                AppEvent::WaitingForChanges(..) | AppEvent::Rescan => {}
                 AppEvent::ProblemsAdded => {
                     let mut pstore = problem_store.lock();
+                    #[cfg(feature = "ui")]
+                    if self.args.fix {
+                        self.apply_single_fixes(&mut pstore)?;
+                    }
                     pstore.group_by_crate();
                     let mut has_errors = false;
                     for (_, problem) in pstore.deduplicated_into_iter() {
                         let mut severity = problem.severity();
                         if matches!(self.args.command, crate::Command::Cargo(..))
                             && severity == Severity::Warning
+                            && !matches!(problem, Problem::DisallowedApiUsage(..))
                         {
                             // When running `cackle cargo x`, not everything will be analysed, so
-                            // unused warnings are expected. As such, we supress all warnings.
+                            // unused warnings are expected. As such, we supress all warnings other
+                            // than usages of APIs that the config says should only warn.
                             continue;
                         }
-                        if self.args.fail_on_warnings {
+                        if self.args.fail_on_warnings || self.args.fix_enabled() {
+                            // With `--fix`, any problems that remain weren't able to be fixed
+                            // automatically, so need attention.
                             severity = Severity::Error
                         };
-                        match severity {
-                            Severity::Warning => {
-                                println!("{} {problem:#}", "WARNING:".yellow())
+                        if self.args.deny_unused_config && problem.is_unused_config() {
+                            severity = Severity::Error
+                        }
+                        let age = history.as_mut().map(|history| {
+                            let previous = history.problem_seen(&problem.stable_id());
+                            history.describe_age(previous)
+                        });
+                        if severity == Severity::Error && !has_errors {
+                            has_errors = true;
+                            // Kill cargo process then wait a bit for any terminal output to settle
+                            // before we start reporting errors.
+                            let _ = self.abort_sender.send(());
+                            std::thread::sleep(std::time::Duration::from_millis(20));
+                            if !self.args.output_format.is_document() {
+                                println!();
                             }
-                            Severity::Error => {
-                                if !has_errors {
-                                    has_errors = true;
-                                    // Kill cargo process then wait a bit for any terminal output to
-                                    // settle before we start reporting errors.
-                                    let _ = self.abort_sender.send(());
-                                    std::thread::sleep(std::time::Duration::from_millis(20));
-                                    println!();
-                                }
-                                println!("{} {problem:#}", "ERROR:".red())
+                        }
+                        match self.args.output_format {
+                            OutputFormat::Human => {
+                                let label = match severity {
+                                    Severity::Warning => "WARNING:".yellow(),
+                                    Severity::Error => "ERROR:".red(),
+                                };
+                                self.print_problem(&label.to_string(), problem, age)?
+                            }
+                            OutputFormat::GithubActions => {
+                                print_github_actions_commands(problem, severity)
                             }
+                            OutputFormat::Json | OutputFormat::Sarif => {}
                         }
+                        reported.push((problem.clone(), severity));
                     }
                     if has_errors {
                         pstore.abort();
                     } else {
+                        if !reported.is_empty() {
+                            pstore.has_warnings = true;
+                        }
                         loop {
                             let maybe_index = pstore
                                 .iterate_with_duplicates()
@@ -84,16 +257,179 @@ impl super::UserInterface for NullUi {
                 }
             }
         }
+        self.print_fix_summary();
+        // If the check didn't finish, the problems we have are incomplete. We don't want a tool that
+        // consumes the document to mistake that for a clean result, so we print no document at all.
+        if check_failed && self.args.output_format.is_document() {
+            return Ok(());
+        }
+        match self.args.output_format {
+            OutputFormat::Json => {
+                println!(
+                    "{}",
+                    serde_json::to_string_pretty(&JsonOutput::new(&reported))?
+                );
+            }
+            OutputFormat::Sarif => {
+                let log = SarifLog::new(&reported, &self.config_path);
+                println!("{}", serde_json::to_string_pretty(&log)?);
+            }
+            OutputFormat::Human => {
+                if !reported.is_empty() {
+                    println!();
+                    print!("{}", Summary::new(&reported));
+                }
+            }
+            OutputFormat::GithubActions => {}
+        }
+        if let Some(history) = &history {
+            history.save()?;
+        }
         Ok(())
     }
 }
 
+/// Returns text describing each of the available fixes for `problem` together with the changes
+/// that it would make to the config file, whose current contents are `original`.
+#[cfg(feature = "ui")]
+fn fixes_text(original: &str, problem: &Problem) -> String {
+    use crate::config_editor::ConfigEditor;
+    use std::fmt::Write;
+
+    let mut out = String::new();
+    for (index, fix) in crate::config_editor::fixes_for_problem(problem)
+        .iter()
+        .enumerate()
+    {
+        let _ = writeln!(out, "  Fix {}: {}", index + 1, fix.title());
+        let updated = ConfigEditor::from_toml_string(original)
+            .and_then(|mut editor| {
+                fix.apply(&mut editor)?;
+                Ok(editor.to_toml())
+            })
+            .unwrap_or_else(|error| {
+                let _ = writeln!(out, "    {error:#}");
+                original.to_owned()
+            });
+        // For each changed line, we show the header of the table that contains it, so that the
+        // change can be copied into the right place.
+        let mut section = None;
+        for diff in diff::lines(original, &updated) {
+            let (prefix, line) = match diff {
+                diff::Result::Both(line, _) => {
+                    if line.starts_with('[') {
+                        section = Some(line);
+                    }
+                    continue;
+                }
+                diff::Result::Left(line) => ('-', line),
+                diff::Result::Right(line) => ('+', line),
+            };
+            if let Some(section) = section.take() {
+                let _ = writeln!(out, "     {section}");
+            }
+            let _ = writeln!(out, "    {prefix}{line}");
+        }
+    }
+    out
+}
+
+fn print_github_actions_commands(problem: &Problem, severity: Severity) {
+    for command in github_actions_commands(problem, severity) {
+        println!("{command}");
+    }
+}
+
+/// Returns GitHub Actions workflow commands that report `problem`. One command is produced for each
+/// source location of the problem, or a single command without a location if it has none. See
+/// https://docs.github.com/en/actions/using-workflows/workflow-commands-for-github-actions
+fn github_actions_commands(problem: &Problem, severity: Severity) -> Vec<String> {
+    let level = match severity {
+        Severity::Warning => "warning",
+        Severity::Error => "error",
+    };
+    let message = escape_github_data(&problem.to_string());
+    let locations = problem.source_locations();
+    if locations.is_empty() {
+        return vec![format!("::{level}::{message}")];
+    }
+    locations
+        .into_iter()
+        .map(|location| {
+            let mut properties = format!(
+                "file={},line={}",
+                escape_github_property(&location.filename().display().to_string()),
+                location.line()
+            );
+            if let Some(column) = location.column() {
+                properties.push_str(&format!(",col={column}"));
+            }
+            format!("::{level} {properties}::{message}")
+        })
+        .collect()
+}
+
+fn escape_github_data(value: &str) -> String {
+    value
+        .replace('%', "%25")
+        .replace('\r', "%0D")
+        .replace('\n', "%0A")
+}
+
+fn escape_github_property(value: &str) -> String {
+    escape_github_data(value)
+        .replace(':', "%3A")
+        .replace(',', "%2C")
+}
+
 #[test]
-fn test_null_ui_with_warning() {
+fn test_github_actions_commands() {
+    use crate::location::SourceLocation;
     use crate::problem::Problem::UnusedPackageConfig;
+    use crate::proxy::rpc::UnsafeUsage;
+    use std::path::Path;
 
+    assert_eq!(
+        github_actions_commands(&UnusedPackageConfig("crab1".into()), Severity::Warning),
+        vec!["::warning::Config supplied for package `crab1` not in dependency tree"]
+    );
+    assert_eq!(
+        github_actions_commands(&Problem::new("100% bad\nreally"), Severity::Error),
+        vec!["::error::100%25 bad%0Areally"]
+    );
+    let unsafe_usage = Problem::DisallowedUnsafe(UnsafeUsage {
+        crate_sel: crate::crate_index::CrateSel::Primary(crate::crate_index::testing::pkg_id(
+            "crab1",
+        )),
+        locations: vec![
+            SourceLocation::new(Path::new("src/lib.rs"), 10, Some(5)),
+            SourceLocation::new(Path::new("src/a,b.rs"), 2, None),
+        ],
+    });
+    assert_eq!(
+        github_actions_commands(&unsafe_usage, Severity::Error),
+        vec![
+            "::error file=src/a%2Cb.rs,line=2::`crab1` uses unsafe",
+            "::error file=src/lib.rs,line=10,col=5::`crab1` uses unsafe",
+        ]
+    );
+}
+
+#[cfg(test)]
+fn null_ui(args: Args, config_path: PathBuf) -> NullUi {
     let (abort_sender, _abort_recv) = std::sync::mpsc::channel();
-    let mut ui = NullUi::new(&Arc::new(Args::default()), abort_sender);
+    NullUi::new(
+        &Arc::new(args),
+        config_path,
+        PathBuf::new(),
+        Arc::default(),
+        abort_sender,
+    )
+}
+
+/// Runs `ui` until it has reported `problems`, returning the outcome.
+#[cfg(test)]
+fn run_null_ui(mut ui: NullUi, problems: crate::problem::ProblemList) -> crate::outcome::Outcome {
     let (event_send, event_recv) = std::sync::mpsc::channel();
     let mut problem_store = crate::problem_store::create(event_send.clone());
     let join_handle = std::thread::spawn({
@@ -102,11 +438,132 @@ fn test_null_ui_with_warning() {
             crate::ui::UserInterface::run(&mut ui, problem_store, event_recv).unwrap();
         }
     });
-    let mut problems = crate::problem::ProblemList::default();
-    problems.push(UnusedPackageConfig("crab1".into()));
-    problems.push(UnusedPackageConfig("crab2".into()));
     let outcome = problem_store.fix_problems(problems);
-    assert_eq!(outcome, crate::outcome::Outcome::Continue);
     event_send.send(AppEvent::Shutdown).unwrap();
     join_handle.join().unwrap();
+    outcome
+}
+
+#[cfg(all(test, feature = "ui"))]
+fn unsafe_problem(crate_name: &str) -> Problem {
+    Problem::DisallowedUnsafe(crate::proxy::rpc::UnsafeUsage {
+        crate_sel: crate::crate_index::CrateSel::Primary(crate::crate_index::testing::pkg_id(
+            crate_name,
+        )),
+        locations: vec![],
+    })
+}
+
+#[test]
+fn test_null_ui_with_warning() {
+    use crate::problem::Problem::UnusedPackageConfig;
+
+    let ui = null_ui(Args::default(), PathBuf::new());
+    let mut problems = crate::problem::ProblemList::default();
+    problems.push(UnusedPackageConfig("crab1".into()));
+    problems.push(UnusedPackageConfig("crab2".into()));
+    assert_eq!(run_null_ui(ui, problems), crate::outcome::Outcome::Continue);
+}
+
+#[cfg(feature = "ui")]
+#[test]
+fn test_null_ui_fix() {
+    let dir = tempfile::tempdir().unwrap();
+    let config_path = dir.path().join("cackle.toml");
+    std::fs::write(&config_path, "[common]\nversion = 1\n").unwrap();
+    let args = Args {
+        fix: true,
+        ..Args::default()
+    };
+    let ui = null_ui(args, config_path.clone());
+    let mut problems = crate::problem::ProblemList::default();
+    problems.push(unsafe_problem("crab1"));
+    // Selecting a sandbox has several possible fixes, so can't be fixed automatically.
+    problems.push(Problem::SelectSandbox);
+    assert_eq!(run_null_ui(ui, problems), crate::outcome::Outcome::GiveUp);
+    assert_eq!(
+        std::fs::read_to_string(&config_path).unwrap(),
+        "[common]\nversion = 1\n\n[pkg.crab1]\nallow_unsafe = true\n"
+    );
+}
+
+#[cfg(feature = "ui")]
+#[test]
+fn test_null_ui_diff_config() {
+    let dir = tempfile::tempdir().unwrap();
+    let config_path = dir.path().join("cackle.toml");
+    std::fs::write(&config_path, "[common]\nversion = 1\n").unwrap();
+    let args = || Args {
+        diff_config: true,
+        ..Args::default()
+    };
+    let ui = null_ui(args(), config_path.clone());
+    let mut problems = crate::problem::ProblemList::default();
+    problems.push(unsafe_problem("crab1"));
+    // Problems that would change the config should cause the check to fail, but the config should
+    // be left as-is.
+    assert_eq!(run_null_ui(ui, problems), crate::outcome::Outcome::GiveUp);
+    assert_eq!(
+        std::fs::read_to_string(&config_path).unwrap(),
+        "[common]\nversion = 1\n"
+    );
+
+    // Each diff builds on the fixes shown before it.
+    let mut ui = null_ui(args(), config_path.clone());
+    let path = config_path.display();
+    assert_eq!(
+        ui.config_diff(&unsafe_problem("crab1")).unwrap(),
+        format!(
+            "  Fix: Allow package `crab1` to use unsafe code\n\
+             --- {path}\n\
+             +++ {path}\n\
+             @@ -1,3 +1,6 @@\n \
+             [common]\n \
+             version = 1\n\
+             +\n\
+             +[pkg.crab1]\n\
+             +allow_unsafe = true\n \n"
+        )
+    );
+    assert_eq!(
+        ui.config_diff(&unsafe_problem("crab2")).unwrap(),
+        format!(
+            "  Fix: Allow package `crab2` to use unsafe code\n\
+             --- {path}\n\
+             +++ {path}\n\
+             @@ -4,3 +4,6 @@\n \
+             [pkg.crab1]\n \
+             allow_unsafe = true\n\
+             +\n\
+             +[pkg.crab2]\n\
+             +allow_unsafe = true\n \n"
+        )
+    );
+    assert_eq!(ui.config_diff(&Problem::SelectSandbox).unwrap(), "");
+}
+
+#[cfg(feature = "ui")]
+#[test]
+fn test_fixes_text() {
+    use crate::proxy::rpc::UnsafeUsage;
+
+    let unsafe_usage = Problem::DisallowedUnsafe(UnsafeUsage {
+        crate_sel: crate::crate_index::CrateSel::Primary(crate::crate_index::testing::pkg_id(
+            "crab1",
+        )),
+        locations: vec![],
+    });
+    let original = indoc::indoc! {r#"
+        [common]
+        version = 1
+
+        [pkg.crab1]
+        allow_apis = [
+            "fs",
+        ]
+    "#};
+    assert_eq!(
+        fixes_text(original, &unsafe_usage),
+        "  Fix 1: Allow package `crab1` to use unsafe code\n     [pkg.crab1]\n    +allow_unsafe = true\n"
+    );
 }
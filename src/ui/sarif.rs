@@ -0,0 +1,375 @@
+//! Output of problems as a SARIF 2.1.0 log. GitHub code scanning accepts this format and shows
+//! results as annotations on pull requests. See
+//! https://docs.oasis-open.org/sarif/sarif/v2.1.0/sarif-v2.1.0.html
+
+use crate::location::SourceLocation;
+use crate::problem::Problem;
+use crate::problem::Severity;
+use crate::proxy::rpc::CACKLE_VERSION;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::path::Path;
+
+const SCHEMA: &str = "https://json.schemastore.org/sarif-2.1.0.json";
+
+/// The rule ID used for unsafe code. Other API usages use the name of the API as the rule ID.
+const UNSAFE_RULE_ID: &str = "unsafe";
+
+#[derive(Serialize, Debug)]
+pub(super) struct SarifLog {
+    #[serde(rename = "$schema")]
+    schema: &'static str,
+    version: &'static str,
+    runs: Vec<Run>,
+}
+
+#[derive(Serialize, Debug)]
+struct Run {
+    tool: Tool,
+    results: Vec<SarifResult>,
+}
+
+#[derive(Serialize, Debug)]
+struct Tool {
+    driver: Driver,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct Driver {
+    name: &'static str,
+    version: &'static str,
+    information_uri: &'static str,
+    rules: Vec<Rule>,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct Rule {
+    id: String,
+    short_description: Message,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    help: Option<Message>,
+}
+
+#[derive(Serialize, Debug)]
+struct Message {
+    text: String,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct SarifResult {
+    rule_id: String,
+    level: &'static str,
+    message: Message,
+    locations: Vec<Location>,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct Location {
+    physical_location: PhysicalLocation,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct PhysicalLocation {
+    artifact_location: ArtifactLocation,
+    region: Region,
+}
+
+#[derive(Serialize, Debug)]
+struct ArtifactLocation {
+    uri: String,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct Region {
+    start_line: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    start_column: Option<u32>,
+}
+
+/// Accumulates rules and results while we build the log.
+struct Builder<'a> {
+    base_dir: &'a Path,
+    /// Where results that can't be attributed to a line of code are reported, since GitHub drops
+    /// results that don't have a location. This is the config file, since that's what the user
+    /// would edit to resolve them.
+    config_path: &'a Path,
+    rules: BTreeMap<String, Rule>,
+    results: Vec<SarifResult>,
+}
+
+impl SarifLog {
+    /// Builds a log containing `reported`. Paths within the directory containing `config_path`,
+    /// which should be the root of the repository, are made relative to it, since that's what
+    /// GitHub needs in order to annotate the corresponding files.
+    pub(super) fn new(reported: &[(Problem, Severity)], config_path: &Path) -> Self {
+        let mut builder = Builder {
+            base_dir: config_path.parent().unwrap_or(Path::new("")),
+            config_path,
+            rules: BTreeMap::new(),
+            results: Vec::new(),
+        };
+        for (problem, severity) in reported {
+            builder.add_problem(problem, *severity);
+        }
+        Self {
+            schema: SCHEMA,
+            version: "2.1.0",
+            runs: vec![Run {
+                tool: Tool {
+                    driver: Driver {
+                        name: "cackle",
+                        version: CACKLE_VERSION,
+                        information_uri: env!("CARGO_PKG_REPOSITORY"),
+                        rules: builder.rules.into_values().collect(),
+                    },
+                },
+                results: builder.results,
+            }],
+        }
+    }
+}
+
+impl<'a> Builder<'a> {
+    fn add_problem(&mut self, problem: &Problem, severity: Severity) {
+        match problem {
+            Problem::DisallowedApiUsage(info) => {
+                for (api, usages) in &info.usages {
+                    self.add_rule(
+                        api.to_string(),
+                        format!("Use of API `{api}`"),
+                        info.descriptions.get(api).cloned(),
+                    );
+                    let mut locations: Vec<&SourceLocation> =
+                        usages.iter().map(|usage| &usage.source_location).collect();
+                    locations.sort();
+                    locations.dedup();
+                    let message = format!("`{}` uses API `{api}`", info.crate_sel);
+                    self.add_results(api.as_ref(), severity, &message, &locations);
+                }
+            }
+            Problem::DisallowedUnsafe(..) => {
+                self.add_rule(
+                    UNSAFE_RULE_ID.to_owned(),
+                    "Use of unsafe code".to_owned(),
+                    None,
+                );
+                self.add_results(
+                    UNSAFE_RULE_ID,
+                    severity,
+                    &problem.to_string(),
+                    &problem.source_locations(),
+                );
+            }
+            _ => {
+                // Other problems can't generally be attributed to a line of code, so we report
+                // them under a rule for their kind and with whatever locations they have.
+                let kind = super::json::kind(problem);
+                self.add_rule(kind.to_owned(), kind.to_owned(), None);
+                let mut locations: Vec<Location> = problem
+                    .source_locations()
+                    .into_iter()
+                    .map(|location| self.location(location))
+                    .collect();
+                if locations.is_empty() {
+                    locations.push(self.config_location());
+                }
+                self.results.push(SarifResult {
+                    rule_id: kind.to_owned(),
+                    level: level(severity),
+                    message: Message {
+                        text: problem.to_string(),
+                    },
+                    locations,
+                });
+            }
+        }
+    }
+
+    fn add_rule(&mut self, id: String, short_description: String, help: Option<String>) {
+        self.rules.entry(id.clone()).or_insert_with(|| Rule {
+            id,
+            short_description: Message {
+                text: short_description,
+            },
+            help: help.map(|text| Message { text }),
+        });
+    }
+
+    /// Adds a result for each of `locations`, since GitHub only annotates the first location of
+    /// each result. If there are no locations, a single result located in the config is added.
+    fn add_results(
+        &mut self,
+        rule_id: &str,
+        severity: Severity,
+        message: &str,
+        locations: &[&SourceLocation],
+    ) {
+        let result = |locations| SarifResult {
+            rule_id: rule_id.to_owned(),
+            level: level(severity),
+            message: Message {
+                text: message.to_owned(),
+            },
+            locations,
+        };
+        if locations.is_empty() {
+            self.results.push(result(vec![self.config_location()]));
+        }
+        for location in locations {
+            let location = self.location(location);
+            self.results.push(result(vec![location]));
+        }
+    }
+
+    fn location(&self, location: &SourceLocation) -> Location {
+        self.file_location(location.filename(), location.line(), location.column())
+    }
+
+    /// Returns the location of the start of the config file.
+    fn config_location(&self) -> Location {
+        self.file_location(self.config_path, 1, None)
+    }
+
+    fn file_location(
+        &self,
+        filename: &Path,
+        start_line: u32,
+        start_column: Option<u32>,
+    ) -> Location {
+        let uri = match filename.strip_prefix(self.base_dir) {
+            Ok(relative) if filename.is_absolute() => relative.display().to_string(),
+            _ if filename.is_absolute() => format!("file://{}", filename.display()),
+            _ => filename.display().to_string(),
+        };
+        Location {
+            physical_location: PhysicalLocation {
+                artifact_location: ArtifactLocation { uri },
+                region: Region {
+                    start_line,
+                    start_column,
+                },
+            },
+        }
+    }
+}
+
+fn level(severity: Severity) -> &'static str {
+    match severity {
+        Severity::Warning => "warning",
+        Severity::Error => "error",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::checker::ApiUsage;
+    use crate::crate_index::testing::pkg_id;
+    use crate::crate_index::CrateSel;
+    use crate::problem::testing::api_usage;
+    use crate::problem::testing::api_usages;
+    use crate::proxy::rpc::UnsafeUsage;
+
+    fn usage(filename: &str, line: u32) -> ApiUsage {
+        api_usage(
+            "crab1::read",
+            "std::fs::read",
+            SourceLocation::new(Path::new(filename), line, Some(5)),
+        )
+    }
+
+    #[test]
+    fn sarif_log() {
+        let mut usages = api_usages(CrateSel::Primary(pkg_id("crab1")), BTreeMap::new());
+        usages.usages.insert(
+            "fs".into(),
+            vec![
+                usage("/repo/crab1/src/lib.rs", 10),
+                usage("/home/.cargo/dep/src/lib.rs", 3),
+            ],
+        );
+        usages
+            .descriptions
+            .insert("fs".into(), "Filesystem access".to_owned());
+        let unsafe_usage = Problem::DisallowedUnsafe(UnsafeUsage {
+            crate_sel: CrateSel::Primary(pkg_id("crab2")),
+            locations: vec![],
+        });
+        let log = SarifLog::new(
+            &[
+                (Problem::DisallowedApiUsage(usages), Severity::Error),
+                (unsafe_usage, Severity::Warning),
+                (Problem::IsProcMacro(pkg_id("crab3")), Severity::Error),
+            ],
+            Path::new("/repo/cackle.toml"),
+        );
+        let json = serde_json::to_value(log).unwrap();
+        let run = &json["runs"][0];
+        assert_eq!(json["version"], "2.1.0");
+        assert_eq!(
+            run["tool"]["driver"]["rules"],
+            serde_json::json!([
+                {
+                    "id": "IsProcMacro",
+                    "shortDescription": {"text": "IsProcMacro"},
+                },
+                {
+                    "id": "fs",
+                    "shortDescription": {"text": "Use of API `fs`"},
+                    "help": {"text": "Filesystem access"},
+                },
+                {
+                    "id": "unsafe",
+                    "shortDescription": {"text": "Use of unsafe code"},
+                },
+            ])
+        );
+        assert_eq!(
+            run["results"],
+            serde_json::json!([
+                {
+                    "ruleId": "fs",
+                    "level": "error",
+                    "message": {"text": "`crab1` uses API `fs`"},
+                    "locations": [{"physicalLocation": {
+                        "artifactLocation": {"uri": "file:///home/.cargo/dep/src/lib.rs"},
+                        "region": {"startLine": 3, "startColumn": 5},
+                    }}],
+                },
+                {
+                    "ruleId": "fs",
+                    "level": "error",
+                    "message": {"text": "`crab1` uses API `fs`"},
+                    "locations": [{"physicalLocation": {
+                        "artifactLocation": {"uri": "crab1/src/lib.rs"},
+                        "region": {"startLine": 10, "startColumn": 5},
+                    }}],
+                },
+                {
+                    "ruleId": "unsafe",
+                    "level": "warning",
+                    "message": {"text": "`crab2` uses unsafe"},
+                    "locations": [{"physicalLocation": {
+                        "artifactLocation": {"uri": "cackle.toml"},
+                        "region": {"startLine": 1},
+                    }}],
+                },
+                {
+                    "ruleId": "IsProcMacro",
+                    "level": "error",
+                    "message": {"text": "`crab3` is a proc macro"},
+                    "locations": [{"physicalLocation": {
+                        "artifactLocation": {"uri": "cackle.toml"},
+                        "region": {"startLine": 1},
+                    }}],
+                },
+            ])
+        );
+    }
+}
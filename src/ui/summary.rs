@@ -0,0 +1,180 @@
+//! A summary of the problems reported by a non-interactive run, printed once the check finishes so
+//! that the overall state can be seen without reading through every problem.
+
+use crate::problem::Problem;
+use crate::problem::ProblemList;
+use crate::problem::Severity;
+use fxhash::FxHashMap;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::collections::BTreeSet;
+use std::fmt::Display;
+
+/// The maximum number of crates listed under `top_crates`.
+const MAX_TOP_CRATES: usize = 5;
+
+#[derive(Serialize, Debug, PartialEq, Eq)]
+pub(super) struct Summary {
+    errors: usize,
+    warnings: usize,
+    /// For each permission, the number of crates that use it without being allowed to.
+    permissions: BTreeMap<String, usize>,
+    /// The crates with the most problems, most first.
+    top_crates: Vec<CrateCount>,
+    /// The number of distinct APIs that are used without permission.
+    disallowed_apis: usize,
+}
+
+#[derive(Serialize, Debug, PartialEq, Eq)]
+struct CrateCount {
+    #[serde(rename = "crate")]
+    crate_name: String,
+    problems: usize,
+}
+
+impl Summary {
+    pub(super) fn new(reported: &[(Problem, Severity)]) -> Self {
+        let count_severity = |wanted| {
+            reported
+                .iter()
+                .filter(|(_, severity)| *severity == wanted)
+                .count()
+        };
+
+        let mut disallowed = ProblemList::default();
+        let mut problems_by_crate: FxHashMap<&str, usize> = FxHashMap::default();
+        for (problem, _) in reported {
+            if let Problem::DisallowedApiUsage(..) = problem {
+                disallowed.push(problem.clone());
+            }
+            if let Some(pkg_id) = problem.pkg_id() {
+                *problems_by_crate.entry(pkg_id.name()).or_default() += 1;
+            }
+        }
+
+        // Different versions of a crate may each have been reported, but count as one crate here.
+        let mut permissions: BTreeMap<String, usize> = BTreeMap::new();
+        for problem in &disallowed.grouped_by_type_and_crate_ignoring_version() {
+            if let Problem::DisallowedApiUsage(usages) = problem {
+                for api in usages.usages.keys() {
+                    *permissions.entry(api.to_string()).or_default() += 1;
+                }
+            }
+        }
+
+        let mut top_crates: Vec<CrateCount> = problems_by_crate
+            .into_iter()
+            .map(|(crate_name, problems)| CrateCount {
+                crate_name: crate_name.to_owned(),
+                problems,
+            })
+            .collect();
+        top_crates.sort_by(|a, b| {
+            b.problems
+                .cmp(&a.problems)
+                .then_with(|| a.crate_name.cmp(&b.crate_name))
+        });
+        top_crates.truncate(MAX_TOP_CRATES);
+
+        let disallowed_apis = reported
+            .iter()
+            .filter_map(|(problem, _)| match problem {
+                Problem::DisallowedApiUsage(usages) => Some(usages),
+                _ => None,
+            })
+            .flat_map(|usages| usages.usages.values().flatten())
+            .map(|usage| usage.to_name.to_string())
+            .collect::<BTreeSet<_>>()
+            .len();
+
+        Self {
+            errors: count_severity(Severity::Error),
+            warnings: count_severity(Severity::Warning),
+            permissions,
+            top_crates,
+            disallowed_apis,
+        }
+    }
+}
+
+impl Display for Summary {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "Summary:")?;
+        writeln!(f, "  Errors: {}", self.errors)?;
+        writeln!(f, "  Warnings: {}", self.warnings)?;
+        if !self.permissions.is_empty() {
+            writeln!(f, "  Crates using disallowed permissions:")?;
+            for (permission, crates) in &self.permissions {
+                writeln!(f, "    {permission}: {crates}")?;
+            }
+            writeln!(
+                f,
+                "  Distinct disallowed APIs used: {}",
+                self.disallowed_apis
+            )?;
+        }
+        if !self.top_crates.is_empty() {
+            writeln!(f, "  Crates with the most problems:")?;
+            for entry in &self.top_crates {
+                writeln!(f, "    {}: {}", entry.crate_name, entry.problems)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::PermissionName;
+    use crate::crate_index::testing::pkg_id;
+    use crate::crate_index::CrateSel;
+    use crate::location::SourceLocation;
+    use crate::problem::testing::api_usages;
+    use std::collections::BTreeMap;
+    use std::path::Path;
+
+    fn api_usage(crate_name: &'static str, permission: &'static str, api: &'static str) -> Problem {
+        let usage = crate::problem::testing::api_usage(
+            "foo",
+            api,
+            SourceLocation::new(Path::new("src/lib.rs"), 10, None),
+        );
+        Problem::DisallowedApiUsage(api_usages(
+            CrateSel::Primary(pkg_id(crate_name)),
+            BTreeMap::from([(PermissionName::from(permission), vec![usage])]),
+        ))
+    }
+
+    #[test]
+    fn summary_counts() {
+        let summary = Summary::new(&[
+            (
+                api_usage("crab1", "net", "std::net::TcpStream::connect"),
+                Severity::Error,
+            ),
+            (api_usage("crab1", "fs", "std::fs::read"), Severity::Error),
+            (
+                api_usage("crab2", "net", "std::net::TcpStream::connect"),
+                Severity::Error,
+            ),
+            (
+                Problem::UnusedPackageConfig("crab3".into()),
+                Severity::Warning,
+            ),
+        ]);
+        assert_eq!(
+            serde_json::to_value(&summary).unwrap(),
+            serde_json::json!({
+                "errors": 3,
+                "warnings": 1,
+                "permissions": {"fs": 1, "net": 2},
+                "top_crates": [
+                    {"crate": "crab1", "problems": 2},
+                    {"crate": "crab2", "problems": 1},
+                ],
+                "disallowed_apis": 2,
+            })
+        );
+    }
+}
@@ -1,11 +1,17 @@
 //! This module tokenises Rust code and looks for the unsafe keyword. This is done as an additional
 //! layer of defence in addition to use of the -Funsafe-code flag when compiling crates, since that
 //! flag unfortunately doesn't completely prevent use of unsafe.
+//!
+//! Inline assembly is also reported. `asm!` has to be inside an unsafe block, but we report the
+//! location of the assembly itself, since that's the part that needs reviewing. `global_asm!`
+//! doesn't need the unsafe keyword at all.
 
 use crate::location::SourceLocation;
 use anyhow::Context;
 use anyhow::Result;
+use std::ffi::OsStr;
 use std::path::Path;
+use std::path::PathBuf;
 
 /// Returns the locations of all unsafe usages found in `path`.
 pub(crate) fn scan_path(path: &Path) -> Result<Vec<SourceLocation>> {
@@ -19,13 +25,38 @@ pub(crate) fn scan_path(path: &Path) -> Result<Vec<SourceLocation>> {
     Ok(scan_string(source, path))
 }
 
+/// Returns whether `path` contains code from one of `modules`, which are paths like `mycrate::ffi`,
+/// where the first segment is the crate name. `crate_root` is the crate's root source file (e.g.
+/// `src/lib.rs`). Modules are located by following the conventions that rustc uses to find the
+/// files for non-inline modules. Inline modules (`mod ffi { ... }`) and modules loaded via
+/// `#[path]` can't be identified.
+pub(crate) fn is_in_modules(path: &Path, crate_root: &Path, modules: &[String]) -> bool {
+    let Some(root_dir) = crate_root.parent() else {
+        return false;
+    };
+    modules.iter().any(|module| {
+        let module_dir: PathBuf = root_dir
+            .iter()
+            .chain(module.split("::").skip(1).map(OsStr::new))
+            .collect();
+        path == module_dir.with_extension("rs") || path.starts_with(&module_dir)
+    })
+}
+
+/// Macros that emit assembly. These are matched by their last path segment, so `asm!` and
+/// `core::arch::asm!` are both found.
+const ASM_MACROS: &[&str] = &["asm", "global_asm", "naked_asm"];
+
 fn scan_string(source: &str, path: &Path) -> Vec<SourceLocation> {
     let mut offset = 0;
     let mut locations = Vec::new();
-    for token in rustc_ap_rustc_lexer::tokenize(source) {
+    let tokens: Vec<_> = rustc_ap_rustc_lexer::tokenize(source).collect();
+    for (index, token) in tokens.iter().enumerate() {
         let new_offset = offset + token.len;
         let token_text = &source[offset..new_offset];
-        if token_text == "unsafe" {
+        if token_text == "unsafe"
+            || (ASM_MACROS.contains(&token_text) && is_macro_invocation(&tokens[index + 1..]))
+        {
             let column = source[..new_offset]
                 .lines()
                 .last()
@@ -39,8 +70,26 @@ fn scan_string(source: &str, path: &Path) -> Vec<SourceLocation> {
     locations
 }
 
+/// Returns whether `following`, the tokens after an identifier, are `!` then an opening delimiter,
+/// ignoring whitespace and comments. The delimiter is needed to tell `asm!(...)` from `asm != x`.
+fn is_macro_invocation(following: &[rustc_ap_rustc_lexer::Token]) -> bool {
+    use rustc_ap_rustc_lexer::TokenKind;
+    let mut kinds = following.iter().map(|token| &token.kind).filter(|kind| {
+        !matches!(
+            kind,
+            TokenKind::Whitespace | TokenKind::LineComment { .. } | TokenKind::BlockComment { .. }
+        )
+    });
+    matches!(kinds.next(), Some(TokenKind::Bang))
+        && matches!(
+            kinds.next(),
+            Some(TokenKind::OpenParen | TokenKind::OpenBracket | TokenKind::OpenBrace)
+        )
+}
+
 #[cfg(test)]
 mod tests {
+    use crate::unsafe_checker::is_in_modules;
     use crate::unsafe_checker::scan_path;
     use crate::unsafe_checker::scan_string;
     use std::ops::Not;
@@ -74,6 +123,35 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_scan_asm() {
+        assert_eq!(
+            unsafe_line_col(r#"core::arch::global_asm!("nop");"#),
+            Some((1, 13))
+        );
+        assert_eq!(
+            unsafe_line_col(r#"global_asm /* x */ !("nop");"#),
+            Some((1, 1))
+        );
+        assert_eq!(unsafe_line_col("fn asm() {}"), None);
+        assert_eq!(unsafe_line_col("let asm = 1; asm != 2"), None);
+    }
+
+    #[test]
+    fn test_is_in_modules() {
+        let root = Path::new("/ws/foo/src/lib.rs");
+        let modules = ["foo::ffi".to_owned(), "foo::sys::linux".to_owned()];
+        let in_modules = |path: &str| is_in_modules(Path::new(path), root, &modules);
+        assert!(in_modules("/ws/foo/src/ffi.rs"));
+        assert!(in_modules("/ws/foo/src/ffi/mod.rs"));
+        assert!(in_modules("/ws/foo/src/ffi/bindings.rs"));
+        assert!(in_modules("/ws/foo/src/sys/linux.rs"));
+        assert!(!in_modules("/ws/foo/src/lib.rs"));
+        assert!(!in_modules("/ws/foo/src/sys.rs"));
+        assert!(!in_modules("/ws/foo/src/sys/windows.rs"));
+        assert!(!in_modules("/ws/foo/src/ffi_helpers.rs"));
+    }
+
     #[track_caller]
     fn has_unsafe_in_file(path: &str) -> bool {
         let root = std::env::var("CARGO_MANIFEST_DIR").expect("CARGO_MANIFEST_DIR should be set");
@@ -89,4 +167,18 @@ mod tests {
         assert!(has_unsafe_in_file("test_crates/crab3/src/lib.rs"));
         assert!(has_unsafe_in_file("test_crates/crab-bin/src/main.rs"));
     }
+
+    #[test]
+    fn test_asm_location_in_test_crate() {
+        let root = std::env::var("CARGO_MANIFEST_DIR").expect("CARGO_MANIFEST_DIR should be set");
+        let path = Path::new(&root).join("test_crates/crab17/src/lib.rs");
+        let source = std::fs::read_to_string(&path).unwrap();
+        let asm_line = source
+            .lines()
+            .position(|line| line.contains("asm!("))
+            .unwrap() as u32
+            + 1;
+        let locations = scan_path(&path).unwrap();
+        assert!(locations.iter().any(|location| location.line() == asm_line));
+    }
 }
@@ -0,0 +1,169 @@
+//! Support for `cackle ui --watch`. Once a check finishes, we wait for the configuration, a cargo
+//! manifest or lock file, or the sources of a workspace member to change, then check again.
+
+use crate::crate_index::CrateIndex;
+use crate::outcome::Outcome;
+use anyhow::Context;
+use anyhow::Result;
+use notify::RecursiveMode;
+use notify::Watcher as _;
+use std::path::Path;
+use std::path::PathBuf;
+use std::sync::mpsc::Receiver;
+use std::sync::mpsc::RecvTimeoutError;
+use std::time::Duration;
+
+/// How long there needs to be no changes before we check again. Editors and tools like `cargo fmt`
+/// often write several files in quick succession and we'd like to only check once.
+const DEBOUNCE: Duration = Duration::from_millis(300);
+
+/// How often we look to see if the user has quit while we're waiting for a change.
+const ABORT_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+pub(crate) struct Watcher {
+    /// Watching stops when this is dropped.
+    _watcher: notify::RecommendedWatcher,
+    change_recv: Receiver<PathBuf>,
+    config_path: PathBuf,
+}
+
+impl Watcher {
+    pub(crate) fn new(
+        config_path: &Path,
+        crate_index: &CrateIndex,
+        target_dir: &Path,
+    ) -> Result<Self> {
+        let config_path = config_path
+            .canonicalize()
+            .unwrap_or_else(|_| config_path.to_owned());
+        let (change_send, change_recv) = std::sync::mpsc::channel();
+        let mut watcher =
+            notify::recommended_watcher(move |result: notify::Result<notify::Event>| {
+                let Ok(event) = result else {
+                    return;
+                };
+                if event.kind.is_access() {
+                    return;
+                }
+                for path in event.paths {
+                    let _ = change_send.send(path);
+                }
+            })
+            .context("Failed to create file watcher")?;
+        // We watch the directory containing the config rather than the config itself, since many
+        // editors save by writing a new file and renaming it over the old one.
+        if let Some(config_dir) = config_path.parent() {
+            watch(&mut watcher, config_dir, RecursiveMode::NonRecursive)?;
+        }
+        // The workspace root contains `Cargo.lock`. It may or may not also be a package.
+        if let Some(workspace_dir) = crate_index.manifest_path.parent() {
+            watch(&mut watcher, workspace_dir, RecursiveMode::NonRecursive)?;
+        }
+        for pkg_id in &crate_index.roots {
+            let Some(pkg_dir) = crate_index.pkg_dir(pkg_id) else {
+                continue;
+            };
+            // The package directory often contains the target directory, which changes constantly
+            // while we build, so we only watch subdirectories recursively.
+            watch(&mut watcher, pkg_dir, RecursiveMode::NonRecursive)?;
+            let entries = pkg_dir
+                .read_dir()
+                .with_context(|| format!("Failed to read directory `{}`", pkg_dir.display()))?;
+            for entry in entries {
+                let path = entry?.path();
+                let is_hidden = path
+                    .file_name()
+                    .is_some_and(|name| name.to_string_lossy().starts_with('.'));
+                if path.is_dir() && path != target_dir && !is_hidden {
+                    watch(&mut watcher, &path, RecursiveMode::Recursive)?;
+                }
+            }
+        }
+        Ok(Self {
+            _watcher: watcher,
+            change_recv,
+            config_path,
+        })
+    }
+
+    /// Blocks until a relevant file changes, then returns `Outcome::Continue`. Returns
+    /// `Outcome::GiveUp` if the user quits, as indicated by something arriving on `abort_recv`.
+    pub(crate) fn wait_for_change(&self, abort_recv: &Receiver<()>) -> Outcome {
+        // Changes to the config made while the check was running were picked up by that check.
+        // Most likely they're the fixes that the user selected. Changes to sources may not have
+        // been, so if there were any, we check again straight away.
+        let mut changed = self
+            .change_recv
+            .try_iter()
+            .any(|path| is_relevant(&path, &self.config_path) && path != self.config_path);
+        while !changed {
+            if abort_recv.try_recv().is_ok() {
+                return Outcome::GiveUp;
+            }
+            match self.change_recv.recv_timeout(ABORT_POLL_INTERVAL) {
+                Ok(path) => changed = is_relevant(&path, &self.config_path),
+                Err(RecvTimeoutError::Timeout) => {}
+                Err(RecvTimeoutError::Disconnected) => return Outcome::GiveUp,
+            }
+        }
+        while self.change_recv.recv_timeout(DEBOUNCE).is_ok() {}
+        Outcome::Continue
+    }
+}
+
+/// Returns whether a change to `path` might change the outcome of a check. Changes to manifests and
+/// lock files can change which dependencies we have.
+fn is_relevant(path: &Path, config_path: &Path) -> bool {
+    path == config_path
+        || path.extension().is_some_and(|ext| ext == "rs")
+        || path
+            .file_name()
+            .is_some_and(|name| name == "Cargo.toml" || name == "Cargo.lock")
+}
+
+fn watch(watcher: &mut notify::RecommendedWatcher, path: &Path, mode: RecursiveMode) -> Result<()> {
+    watcher
+        .watch(path, mode)
+        .with_context(|| format!("Failed to watch `{}`", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn relevant_paths() {
+        let config_path = Path::new("/ws/cackle.toml");
+        assert!(is_relevant(config_path, config_path));
+        assert!(is_relevant(Path::new("/ws/src/lib.rs"), config_path));
+        assert!(is_relevant(Path::new("/ws/Cargo.toml"), config_path));
+        assert!(is_relevant(Path::new("/ws/Cargo.lock"), config_path));
+        assert!(!is_relevant(Path::new("/ws/other.toml"), config_path));
+        assert!(!is_relevant(Path::new("/ws/README.md"), config_path));
+    }
+
+    #[test]
+    fn lock_file_change() {
+        let dir = tempfile::tempdir().unwrap();
+        let dir = dir.path().canonicalize().unwrap();
+        let mut crate_index = CrateIndex::default();
+        crate_index.manifest_path = dir.join("Cargo.toml");
+        let watcher =
+            Watcher::new(&dir.join("cackle.toml"), &crate_index, &dir.join("target")).unwrap();
+        let (abort_send, abort_recv) = std::sync::mpsc::channel();
+
+        std::fs::write(dir.join("notes.txt"), "").unwrap();
+        std::fs::write(dir.join("Cargo.lock"), "").unwrap();
+        // In case we never see the change, give up rather than waiting forever.
+        let timeout_abort_send = abort_send.clone();
+        std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_secs(20));
+            let _ = timeout_abort_send.send(());
+        });
+        assert_eq!(watcher.wait_for_change(&abort_recv), Outcome::Continue);
+
+        std::fs::write(dir.join("notes.txt"), "more").unwrap();
+        abort_send.send(()).unwrap();
+        assert_eq!(watcher.wait_for_change(&abort_recv), Outcome::GiveUp);
+    }
+}
@@ -0,0 +1,141 @@
+//! Determines whether package versions have been yanked by looking in cargo's local cache of the
+//! crates.io index. We never access the network, so if cargo hasn't cached the index entry for a
+//! package, we can't tell and assume that it hasn't been yanked.
+
+use cargo_metadata::semver::Version;
+use std::path::Path;
+use std::path::PathBuf;
+
+pub(crate) struct RegistryCache {
+    /// Cache directories for each copy of the crates.io index that cargo has, e.g. one for the git
+    /// index and one for the sparse index.
+    cache_dirs: Vec<PathBuf>,
+}
+
+impl RegistryCache {
+    /// Locates the cache using `CARGO_HOME`, or `~/.cargo` if that isn't set.
+    pub(crate) fn find() -> Self {
+        match crate::fs::cargo_home() {
+            Some(cargo_home) => Self::from_cargo_home(&cargo_home),
+            None => Self {
+                cache_dirs: Vec::new(),
+            },
+        }
+    }
+
+    pub(crate) fn from_cargo_home(cargo_home: &Path) -> Self {
+        let mut cache_dirs = Vec::new();
+        if let Ok(entries) = cargo_home.join("registry").join("index").read_dir() {
+            for entry in entries.flatten() {
+                let name = entry.file_name();
+                let name = name.to_string_lossy();
+                if name.starts_with("index.crates.io-") || name.starts_with("github.com-") {
+                    cache_dirs.push(entry.path().join(".cache"));
+                }
+            }
+        }
+        cache_dirs.sort();
+        Self { cache_dirs }
+    }
+
+    /// Returns whether the cache says that `version` of `name` has been yanked.
+    pub(crate) fn is_yanked(&self, name: &str, version: &Version) -> bool {
+        let relative_path = cache_relative_path(name);
+        let version = version.to_string();
+        self.cache_dirs.iter().any(|dir| {
+            std::fs::read(dir.join(&relative_path))
+                .is_ok_and(|bytes| is_yanked_in_cache_file(&bytes, &version))
+        })
+    }
+}
+
+/// Returns the path of the index entry for package `name`, relative to the index root. This follows
+/// the layout of the crates.io index, which nests entries by the first few characters of the name.
+fn cache_relative_path(name: &str) -> PathBuf {
+    let name = name.to_lowercase();
+    let mut path = PathBuf::new();
+    match name.len() {
+        0 => {}
+        1 => path.push("1"),
+        2 => path.push("2"),
+        3 => {
+            path.push("3");
+            path.push(&name[..1]);
+        }
+        _ => {
+            path.push(&name[..2]);
+            path.push(&name[2..4]);
+        }
+    }
+    path.push(name);
+    path
+}
+
+/// Returns whether `bytes`, the contents of a cached index entry, records `version` as yanked. The
+/// cache consists of a header followed by version strings and JSON records, all separated by nul
+/// bytes. Rather than depend on the details of the header, which vary between cargo versions, we
+/// look at every record that parses as JSON.
+fn is_yanked_in_cache_file(bytes: &[u8], version: &str) -> bool {
+    bytes
+        .split(|b| *b == 0)
+        .filter(|record| record.first() == Some(&b'{'))
+        .filter_map(|record| serde_json::from_slice::<serde_json::Value>(record).ok())
+        .any(|record| {
+            record["vers"].as_str() == Some(version) && record["yanked"].as_bool() == Some(true)
+        })
+}
+
+#[cfg(test)]
+pub(crate) mod testing {
+    use super::cache_relative_path;
+    use std::path::Path;
+
+    /// Writes an entry for package `name` into a crates.io index cache under `cargo_home`. Each of
+    /// `versions` is a version number and whether it has been yanked.
+    pub(crate) fn write_cache_entry(cargo_home: &Path, name: &str, versions: &[(&str, bool)]) {
+        let path = cargo_home
+            .join("registry/index/index.crates.io-6f17d22bba15001f/.cache")
+            .join(cache_relative_path(name));
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        let mut contents = b"\x03\x02\x00\x00\x00etag\x00".to_vec();
+        for (version, yanked) in versions {
+            contents.extend_from_slice(version.as_bytes());
+            contents.push(0);
+            contents.extend_from_slice(
+                format!(r#"{{"name":"{name}","vers":"{version}","yanked":{yanked}}}"#).as_bytes(),
+            );
+            contents.push(0);
+        }
+        std::fs::write(path, contents).unwrap();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn relative_paths() {
+        assert_eq!(cache_relative_path("a"), Path::new("1/a"));
+        assert_eq!(cache_relative_path("ab"), Path::new("2/ab"));
+        assert_eq!(cache_relative_path("abc"), Path::new("3/a/abc"));
+        assert_eq!(cache_relative_path("Serde"), Path::new("se/rd/serde"));
+    }
+
+    #[test]
+    fn yanked_from_cache() {
+        let cargo_home = tempfile::tempdir().unwrap();
+        testing::write_cache_entry(
+            cargo_home.path(),
+            "foobar",
+            &[("1.0.0", false), ("1.0.1", true)],
+        );
+
+        let cache = RegistryCache::from_cargo_home(cargo_home.path());
+        assert!(!cache.is_yanked("foobar", &Version::new(1, 0, 0)));
+        assert!(cache.is_yanked("foobar", &Version::new(1, 0, 1)));
+        // Packages and versions that aren't in the cache are assumed not to be yanked.
+        assert!(!cache.is_yanked("foobar", &Version::new(2, 0, 0)));
+        assert!(!cache.is_yanked("other", &Version::new(1, 0, 1)));
+    }
+}
@@ -2,6 +2,7 @@ use pmacro1::baz;
 use pmacro1::FooBar;
 
 pmacro1::create_write_to_file!();
+pmacro2::greeting_fn!();
 
 pub trait FooBar {
     fn foo_bar() -> u32;
@@ -25,10 +26,31 @@ fn main() {
     crab7::do_something();
     crab8::print_defaults();
     crab3::run_process();
+    assert!(crab9::run_in_shell("true"));
+    let mut register = 0;
+    crab10::write_register(&mut register, &crab10::doubled(&[1, 2]));
+    assert_eq!(register, 4);
+    println!("{}", (crab11::READ_FILE)("Cargo.toml").is_ok());
+    println!("{:?}", (crab11::env_reader())("HOME"));
+    println!("{}", crab12::link("Cargo.toml", "/tmp/crab12-link").is_ok());
+    assert_eq!(crab13::read_secret(), 42);
+    assert_eq!(crab16::sum(&[1, 2]), 3);
+    assert_eq!(crab17::add_one(41), 42);
+    assert_eq!(crab18::parse_number("42"), crab18::first(&[42]));
+    assert!(crab19::block_on(crab19::read_config("Cargo.toml")).is_some());
+    assert!(crab20::run_via_c("true"));
+    // We don't actually want to bind a socket. This is just so that crab-bin instantiates crab14's
+    // generic functions.
+    if std::env::args().count() > 100 {
+        println!("{}", crab14::can_bind("127.0.0.1:0"));
+        drop(crab14::bind("127.0.0.1:0"));
+        println!("{}", crab15::can_connect("127.0.0.1:1"));
+    }
     res1::print_something();
     assert_eq!(crab2::res_b(), 42);
     assert_eq!(Foo::foo_bar(), 42);
     assert_eq!(function_with_custom_attr(), 40);
+    assert_eq!(greeting(), "Hello from pmacro2");
     // Note, the following call exits
     crab2::stuff::do_stuff();
 }
@@ -0,0 +1,19 @@
+//! A `no_std` crate, such as might be used in embedded firmware. It uses APIs from `core` and
+//! `alloc` that are restricted via API definitions with `core::` and `alloc::` paths.
+
+#![no_std]
+
+extern crate alloc;
+
+use alloc::vec::Vec;
+
+/// Writes each of `values` to `register`, as a driver for a memory-mapped peripheral might.
+pub fn write_register(register: &mut u32, values: &[u32]) {
+    for value in values {
+        unsafe { core::ptr::write_volatile(register, *value) };
+    }
+}
+
+pub fn doubled(values: &[u32]) -> Vec<u32> {
+    values.iter().map(|value| value * 2).collect()
+}
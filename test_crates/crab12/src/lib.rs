@@ -0,0 +1,5 @@
+//! Uses a platform-specific API from `std::os`.
+
+pub fn link(original: &str, link: &str) -> std::io::Result<()> {
+    std::os::unix::fs::symlink(original, link)
+}
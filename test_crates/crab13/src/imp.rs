@@ -0,0 +1,6 @@
+pub mod hidden {
+    #[inline(never)]
+    pub fn read_secret() -> u32 {
+        42
+    }
+}
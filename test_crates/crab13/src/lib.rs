@@ -0,0 +1,11 @@
+//! Exposes a function only via a chain of re-exports. The function is defined at
+//! `crab13::imp::hidden::read_secret`, which is the name that shows up in symbols, but cackle.toml
+//! restricts it using the public path `crab13::read_secret`.
+
+mod imp;
+
+pub mod api {
+    pub use crate::imp::hidden::*;
+}
+
+pub use api::read_secret;
@@ -0,0 +1,11 @@
+//! Uses the network only from generic functions. When these are monomorphized in crab-bin, crab-bin
+//! ends up with references to things like `core::ptr::drop_in_place<std::net::tcp::TcpListener>`,
+//! which shouldn't count as crab-bin using the net API.
+
+pub fn bind<A: std::net::ToSocketAddrs>(addr: A) -> std::net::TcpListener {
+    std::net::TcpListener::bind(addr).unwrap()
+}
+
+pub fn can_bind<A: std::net::ToSocketAddrs>(addr: A) -> bool {
+    std::net::TcpListener::bind(addr).is_ok()
+}
@@ -0,0 +1,9 @@
+//! Includes "vendored" code that uses the network. The vendored code is excluded from analysis via
+//! `ignore_paths`, so crab15 doesn't need permission to use the network.
+
+#[path = "../vendor/bindings.rs"]
+mod bindings;
+
+pub fn can_connect(addr: &str) -> bool {
+    bindings::connect(addr)
+}
@@ -0,0 +1,5 @@
+mod raw;
+
+pub(crate) fn sum(values: &[u32]) -> u32 {
+    unsafe { raw::sum(values.as_ptr(), values.len()) }
+}
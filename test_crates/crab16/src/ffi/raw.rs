@@ -0,0 +1,5 @@
+/// # Safety
+/// `values` must point to `len` initialised values.
+pub(crate) unsafe fn sum(values: *const u32, len: usize) -> u32 {
+    std::slice::from_raw_parts(values, len).iter().sum()
+}
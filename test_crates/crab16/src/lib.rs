@@ -0,0 +1,8 @@
+//! Unsafe code in this crate is confined to the `ffi` module, which is the only place that
+//! cackle.toml permits it.
+
+mod ffi;
+
+pub fn sum(values: &[u32]) -> u32 {
+    ffi::sum(values)
+}
@@ -0,0 +1,18 @@
+//! Uses inline assembly from a function that gets inlined into its caller in another crate. The
+//! unsafe usage should be attributed to the `asm!` here, not to the caller.
+
+#[inline(always)]
+pub fn add_one(value: u64) -> u64 {
+    #[cfg(target_arch = "x86_64")]
+    {
+        let mut result = value;
+        unsafe {
+            std::arch::asm!("add {0}, 1", inout(reg) result);
+        }
+        result
+    }
+    #[cfg(not(target_arch = "x86_64"))]
+    {
+        value + 1
+    }
+}
@@ -0,0 +1,10 @@
+//! Panics if given bad input. Checked for panics by the integration test using
+//! `cackle-no-panic.toml`, which denies this crate the optional `panic` API.
+
+pub fn parse_number(text: &str) -> u32 {
+    text.parse().unwrap()
+}
+
+pub fn first(values: &[u32]) -> u32 {
+    *values.first().expect("values must not be empty")
+}
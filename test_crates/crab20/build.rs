@@ -0,0 +1,38 @@
+use std::path::PathBuf;
+use std::process::Command;
+
+fn main() {
+    let base_dir = PathBuf::from(std::env::var("CARGO_MANIFEST_DIR").unwrap());
+    let out_dir = PathBuf::from(std::env::var("OUT_DIR").unwrap());
+    let object_file = out_dir.join("shim.o");
+    // We need debug info so that references from the C code can be attributed to this package.
+    run(Command::new("cc")
+        .arg("-c")
+        .arg("-g")
+        .arg("-fPIC")
+        .arg(base_dir.join("shim.c"))
+        .arg("-o")
+        .arg(&object_file));
+    run(Command::new("ar")
+        .arg("r")
+        .arg(out_dir.join("libcrab20shim.a"))
+        .arg(&object_file));
+    // Unlike crab2, we let the static library be bundled into our rlib, so that its object file is
+    // checked along with our Rust code.
+    println!("cargo:rustc-link-search={}", out_dir.display());
+    println!("cargo:rustc-link-lib=static=crab20shim");
+    println!("cargo:rerun-if-changed=shim.c");
+}
+
+fn run(cmd: &mut Command) {
+    match cmd.status() {
+        Ok(status) => {
+            if status.code() != Some(0) {
+                panic!("Command exited with non-zero status while running:\n{cmd:?}");
+            }
+        }
+        Err(_) => {
+            panic!("Failed to run {cmd:?}");
+        }
+    }
+}
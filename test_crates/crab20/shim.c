@@ -0,0 +1,7 @@
+#include <stdlib.h>
+
+// Runs a command via the C library, bypassing `std::process`, so that the only way to spot it is by
+// the reference to `system`.
+int crab20_run(const char* command) {
+    return system(command);
+}
@@ -0,0 +1,13 @@
+use std::ffi::CString;
+use std::os::raw::c_char;
+use std::os::raw::c_int;
+
+extern "C" {
+    fn crab20_run(command: *const c_char) -> c_int;
+}
+
+/// Runs `command` via a C shim that calls `system`. Returns whether it succeeded.
+pub fn run_via_c(command: &str) -> bool {
+    let command = CString::new(command).unwrap();
+    unsafe { crab20_run(command.as_ptr()) == 0 }
+}
@@ -0,0 +1,9 @@
+/// Runs a command via a shell. This should be reported separately from using the process API.
+pub fn run_in_shell(command: &str) -> bool {
+    std::process::Command::new("sh")
+        .arg("-c")
+        .arg(command)
+        .status()
+        .map(|status| status.success())
+        .unwrap_or(false)
+}
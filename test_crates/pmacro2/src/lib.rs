@@ -0,0 +1,14 @@
+extern crate proc_macro;
+use proc_macro::TokenStream;
+use std::path::Path;
+
+/// Expands to a function that returns the contents of greeting.txt. The file is read while the
+/// macro is being expanded, so the proc macro itself uses the filesystem at compile time.
+#[proc_macro]
+pub fn greeting_fn(_item: TokenStream) -> TokenStream {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("greeting.txt");
+    let greeting = std::fs::read_to_string(path).unwrap();
+    format!("fn greeting() -> &'static str {{ {:?} }}", greeting.trim())
+        .parse()
+        .unwrap()
+}
@@ -0,0 +1,7 @@
+//! A Rust dynamic library. Unlike a `cdylib`, this exports mangled Rust symbols, so we can only tell
+//! that its public functions are used by looking at what the library exports.
+
+/// Nothing in this crate calls this function, but it's exported, so should count as used.
+pub fn connect() {
+    std::net::TcpStream::connect("127.0.0.1:9876").unwrap();
+}
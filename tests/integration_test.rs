@@ -3,30 +3,192 @@ use anyhow::Result;
 use std::path::Path;
 use std::path::PathBuf;
 use std::process::Command;
+use std::process::Output;
 
 #[test]
 fn integration_test() -> Result<()> {
-    let mut command = Command::new(cackle_exe());
-    // Remove cargo and rust-releated environment variables. In particular we want to remove
-    // variables that cargo sets, but which won't always be set. For example CARGO_PKG_NAME is set
-    // by cargo when it invokes rustc, but only when it's compiling a package, not when it queries
-    // rustc for version information. If we allow such variables to pass through, then our code that
-    // proxies rustc can appear to work from the test, but only because the test itself was run from
-    // cargo.
-    for (var, _) in std::env::vars() {
-        if var.starts_with("CARGO") || var.starts_with("RUST") {
-            command.env_remove(var);
-        }
-    }
-    let status = command
+    let status = cackle_command()
         .arg("--fail-on-warnings")
         .arg("--save-requests")
-        .arg("--path")
-        .arg(crate_root().join("test_crates"))
         .arg("check")
         .status()
         .with_context(|| format!("Failed to invoke `{}`", cackle_exe().display()))?;
     assert!(status.success());
+    check_panics_detected()?;
+    check_warning_severity()?;
+    check_async_usage_attribution()?;
+    check_ffi_usage_detected()?;
+    check_asm_detected()?;
+    check_reachable_code_only()?;
+    check_inspect_binary()?;
+    Ok(())
+}
+
+/// Checks again with a config that denies crab18 the `panic` API and makes sure that its unwraps
+/// are reported at the right places. This reuses the build directory of `integration_test`, so
+/// needs to run after it rather than as a separate test.
+fn check_panics_detected() -> Result<()> {
+    let output = check_test_crates(&["cackle-no-panic.toml"], &["--output-format", "json"])?;
+    // Errors were found.
+    assert_eq!(output.status.code(), Some(1));
+    let problem = json_problem_for(&output, "crab18")?;
+    assert_eq!(problem["permissions"], serde_json::json!(["panic"]));
+    let mut lines: Vec<(String, u64)> = problem["locations"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|location| {
+            (
+                location["filename"].as_str().unwrap().to_owned(),
+                location["line"].as_u64().unwrap(),
+            )
+        })
+        .collect();
+    lines.sort();
+    lines.dedup();
+    assert!(lines
+        .iter()
+        .all(|(filename, _)| filename.ends_with("crab18/src/lib.rs")));
+    let lines: Vec<u64> = lines.into_iter().map(|(_, line)| line).collect();
+    assert_eq!(lines, vec![5, 9]);
+    Ok(())
+}
+
+/// Checks again with crab18 still denied the `panic` API, but with `panic` reporting usages as
+/// warnings. The check should finish, reporting the warnings, rather than fail, unless
+/// `--fail-on-warnings` is given. Like `check_panics_detected`, this reuses the build directory of
+/// `integration_test`.
+fn check_warning_severity() -> Result<()> {
+    let configs = ["cackle-no-panic.toml", "cackle-warn-panic.toml"];
+    let output = check_test_crates(&configs, &["--output-format", "json"])?;
+    // Only warnings were found.
+    assert_eq!(output.status.code(), Some(2));
+    let problem = json_problem_for(&output, "crab18")?;
+    assert_eq!(problem["severity"], "warning");
+    assert_eq!(problem["permissions"], serde_json::json!(["panic"]));
+
+    let output = check_test_crates(&configs, &["--fail-on-warnings"])?;
+    assert_eq!(output.status.code(), Some(1));
+    Ok(())
+}
+
+/// Checks again with a config that denies crab19 the `fs` API, which it uses from within an async
+/// function. The usage should be attributed to that function, not to the state machine that the
+/// compiler generates for it. Like `check_panics_detected`, this reuses the build directory of
+/// `integration_test`.
+fn check_async_usage_attribution() -> Result<()> {
+    let output = check_test_crates(&["cackle-async.toml"], &[])?;
+    assert_eq!(output.status.code(), Some(1));
+    let stdout = std::str::from_utf8(&output.stdout).unwrap();
+    if !stdout
+        .lines()
+        .any(|line| line.trim() == "crab19::read_config")
+        || stdout.contains("{async_fn")
+    {
+        panic!("Usage not attributed to `crab19::read_config`:\n{stdout}");
+    }
+    Ok(())
+}
+
+/// Checks again with an extra config file that denies crab20 the `ffi` API. crab20 calls `system`
+/// from C code that it compiles, which should be reported as a usage of `ffi` by crab20. Like
+/// `check_panics_detected`, this reuses the build directory of `integration_test`.
+fn check_ffi_usage_detected() -> Result<()> {
+    let output = check_test_crates(
+        &["cackle.toml", "cackle-no-ffi.toml"],
+        &["--output-format", "json"],
+    )?;
+    assert_eq!(output.status.code(), Some(1));
+    let problem = json_problem_for(&output, "crab20")?;
+    assert_eq!(problem["permissions"], serde_json::json!(["ffi"]));
+    let locations = problem["locations"].as_array().unwrap();
+    assert!(locations.iter().any(|location| location["filename"]
+        .as_str()
+        .is_some_and(|filename| filename.ends_with("crab20/shim.c"))));
+    Ok(())
+}
+
+/// Checks again with an extra config file that denies crab17 unsafe code. crab17's only unsafe code
+/// is inline assembly, which should be reported at the `asm!` rather than at the unsafe block that
+/// contains it. Like `check_panics_detected`, this reuses the build directory of `integration_test`.
+fn check_asm_detected() -> Result<()> {
+    // crab17 only uses inline assembly on x86_64.
+    if !cfg!(target_arch = "x86_64") {
+        return Ok(());
+    }
+    let output = check_test_crates(
+        &["cackle.toml", "cackle-no-asm.toml"],
+        &["--output-format", "json"],
+    )?;
+    assert_eq!(output.status.code(), Some(1));
+    let problem = json_problem_for(&output, "crab17")?;
+    assert_eq!(problem["kind"], "DisallowedUnsafe");
+    let locations = problem["locations"].as_array().unwrap();
+    assert!(locations.iter().any(|location| location["filename"]
+        .as_str()
+        .is_some_and(|filename| filename.ends_with("crab17/src/lib.rs"))
+        && location["line"] == 10));
+    Ok(())
+}
+
+/// Checks again with `reachable_code_only` set, which requires demangling the symbols in the real
+/// binaries in order to follow references between them. Everything that's allowed is still used
+/// from reachable code, including `crab1_entry`, which is only reachable because shared1 exports
+/// it, so there are no warnings about unused permissions. Reachable unwraps in crab18 must still be
+/// reported. Like `check_panics_detected`, this reuses the build directory of `integration_test`.
+fn check_reachable_code_only() -> Result<()> {
+    let output = check_test_crates(
+        &["cackle.toml", "cackle-reachable.toml"],
+        &["--fail-on-warnings"],
+    )?;
+    if !output.status.success() {
+        panic!(
+            "Check with reachable_code_only failed:\n{}",
+            String::from_utf8_lossy(&output.stdout)
+        );
+    }
+
+    let output = check_test_crates(
+        &["cackle-no-panic.toml", "cackle-reachable.toml"],
+        &["--output-format", "json"],
+    )?;
+    assert_eq!(output.status.code(), Some(1));
+    let problem = json_problem_for(&output, "crab18")?;
+    assert_eq!(problem["permissions"], serde_json::json!(["panic"]));
+    let mut lines: Vec<u64> = problem["locations"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|location| location["line"].as_u64().unwrap())
+        .collect();
+    lines.sort();
+    lines.dedup();
+    assert_eq!(lines, vec![5, 9]);
+    Ok(())
+}
+
+/// Checks the binary that `integration_test` built with `cackle inspect`. Everything it finds was
+/// also found when building, so is allowed by the config. Config for packages that the inspection
+/// doesn't see mustn't be reported as unused, so `--fail-on-warnings` still succeeds.
+fn check_inspect_binary() -> Result<()> {
+    let bin_path = crate_root()
+        .join("test_crates")
+        .join("target")
+        .join("cackle")
+        .join("crab-bin");
+    let output = cackle_command()
+        .arg("--fail-on-warnings")
+        .arg("inspect")
+        .arg(&bin_path)
+        .output()
+        .with_context(|| format!("Failed to invoke `{}`", cackle_exe().display()))?;
+    if !output.status.success() {
+        panic!(
+            "`cackle inspect {}` failed:\n{}",
+            bin_path.display(),
+            String::from_utf8_lossy(&output.stdout)
+        );
+    }
     Ok(())
 }
 
@@ -45,16 +207,69 @@ fn invalid_config() -> Result<()> {
         .arg("check")
         .output()
         .with_context(|| format!("Failed to invoke `{}`", cackle_exe().display()))?;
-    assert!(!output.status.success());
+    // The check couldn't be completed.
+    assert_eq!(output.status.code(), Some(101));
     let stdout = std::str::from_utf8(&output.stdout).unwrap();
     let stderr = std::str::from_utf8(&output.stderr).unwrap();
-    if !stdout.contains("invalid_key") {
+    // Errors go to stderr, so that they can't corrupt JSON or SARIF output on stdout.
+    if !stderr.contains("invalid_key") {
         println!("=== stdout ===\n{stdout}\n=== stderr ===\n{stderr}");
         panic!("Error doesn't mention invalid_key");
     }
     Ok(())
 }
 
+/// Returns a command that runs cackle on the test crates.
+fn cackle_command() -> Command {
+    let mut command = Command::new(cackle_exe());
+    // Remove cargo and rust-releated environment variables. In particular we want to remove
+    // variables that cargo sets, but which won't always be set. For example CARGO_PKG_NAME is set
+    // by cargo when it invokes rustc, but only when it's compiling a package, not when it queries
+    // rustc for version information. If we allow such variables to pass through, then our code that
+    // proxies rustc can appear to work from the test, but only because the test itself was run from
+    // cargo.
+    for (var, _) in std::env::vars() {
+        if var.starts_with("CARGO") || var.starts_with("RUST") {
+            command.env_remove(var);
+        }
+    }
+    command.arg("--path").arg(crate_root().join("test_crates"));
+    command
+}
+
+/// Checks the test crates with `configs`, which are config files in `test_crates` that are layered
+/// in order, passing `args` before the `check` subcommand.
+fn check_test_crates(configs: &[&str], args: &[&str]) -> Result<Output> {
+    let test_crates = crate_root().join("test_crates");
+    let mut command = cackle_command();
+    for config in configs {
+        command.arg("--config").arg(test_crates.join(config));
+    }
+    command
+        .args(args)
+        .arg("check")
+        .output()
+        .with_context(|| format!("Failed to invoke `{}`", cackle_exe().display()))
+}
+
+/// Parses the JSON that cackle printed to `output` and returns the first problem reported for
+/// `pkg_name`.
+fn json_problem_for(output: &Output, pkg_name: &str) -> Result<serde_json::Value> {
+    let stdout = std::str::from_utf8(&output.stdout)?;
+    let json_start = stdout.find('{').unwrap_or(0);
+    let json: serde_json::Value = serde_json::from_str(&stdout[json_start..])
+        .with_context(|| format!("Invalid JSON output:\n{stdout}"))?;
+    json["problems"]
+        .as_array()
+        .and_then(|problems| {
+            problems
+                .iter()
+                .find(|problem| problem["package"]["name"] == pkg_name)
+        })
+        .cloned()
+        .with_context(|| format!("No problem reported for {pkg_name}:\n{stdout}"))
+}
+
 fn create_cargo_dir(dir: &Path) {
     Command::new("cargo")
         .arg("new")